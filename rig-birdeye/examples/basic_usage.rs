@@ -1,5 +1,4 @@
-use rig_birdeye::{TokenSearchAction, TokenSortBy, SortType};
-use rig_core::AgentBuilder;
+use rig_birdeye::{BirdeyeProvider, SortType, TokenSearchParams, TokenSortBy};
 use std::env;
 
 #[tokio::main]
@@ -8,28 +7,23 @@ async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
     // Load API key from environment
-    let api_key = env::var("BIRDEYE_API_KEY")
-        .expect("BIRDEYE_API_KEY must be set");
+    let api_key = env::var("BIRDEYE_API_KEY").expect("BIRDEYE_API_KEY must be set");
 
-    // Create agent with Birdeye plugin
-    let agent = AgentBuilder::new()
-        .with_secret("BIRDEYE_API_KEY", api_key)
-        .build()
-        .await?;
+    let provider = BirdeyeProvider::new(&api_key);
 
     // Search for top SOL tokens by volume
-    let action = TokenSearchAction {
-        keyword: "SOL".to_string(),
-        sort_by: Some(TokenSortBy::Volume24h),
-        sort_type: Some(SortType::Desc),
-        limit: Some(5),
-    };
+    let params = TokenSearchParams::new("SOL")
+        .with_sort(TokenSortBy::Volume, SortType::Descending)
+        .with_limit(5);
 
     println!("\nSearching for top SOL tokens by volume...");
-    let tokens = agent.execute(action).await?;
+    let tokens = provider.search_tokens(params).await?;
 
     println!("\nTop 5 SOL tokens by 24h volume:");
-    println!("{:<20} {:<10} {:<15} {:<15}", "Name", "Symbol", "Price ($)", "24h Volume ($)");
+    println!(
+        "{:<20} {:<10} {:<15} {:<15}",
+        "Name", "Symbol", "Price ($)", "24h Volume ($)"
+    );
     println!("{}", "-".repeat(60));
 
     for token in tokens {
@@ -37,10 +31,10 @@ async fn main() -> anyhow::Result<()> {
             "{:<20} {:<10} {:<15.2} {:<15.2}",
             token.name,
             token.symbol,
-            token.price,
-            token.volume_24h
+            token.price.unwrap_or_default(),
+            token.volume_24h.unwrap_or_default()
         );
     }
 
     Ok(())
-} 
\ No newline at end of file
+}
@@ -1,13 +1,13 @@
-use rig_birdeye::providers::{WebSocketProvider, MarketUpdate, TradeUpdate};
+use dotenv::dotenv;
+use rig_birdeye::providers::WebSocketProvider;
 use std::env;
 use tokio::time::Duration;
-use dotenv::dotenv;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables from .env file
     dotenv().ok();
-    
+
     // Get API key from environment variable
     let api_key = env::var("BIRDEYE_API_KEY").expect("BIRDEYE_API_KEY not found in .env file");
 
@@ -17,12 +17,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Subscribe to market updates
     let mut market_rx = ws_provider.subscribe_market_updates();
     tokio::spawn(async move {
-        while let Ok(update) = market_rx.recv().await {
+        while let Some(update) = market_rx.recv().await {
             println!("Market Update:");
             println!("  Token: {}", update.address);
             println!("  Price: ${:.4}", update.price);
-            println!("  24h Volume: ${:.2}", update.volume_24h);
-            println!("  24h Change: {:.2}%", update.price_change_24h);
+            println!("  Volume this minute: {:.2}", update.volume);
             println!();
         }
     });
@@ -30,7 +29,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Subscribe to trade updates
     let mut trade_rx = ws_provider.subscribe_trade_updates();
     tokio::spawn(async move {
-        while let Ok(update) = trade_rx.recv().await {
+        while let Some(update) = trade_rx.recv().await {
             println!("Trade Update:");
             println!("  Token: {}", update.address);
             println!("  Price: ${:.4}", update.price);
@@ -40,6 +39,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    // Report disconnections and lagging receivers
+    let mut events = ws_provider.events();
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv().await {
+            println!("Stream event: {:?}", event);
+        }
+    });
+
     // List of token addresses to monitor
     let tokens = vec![
         // Example token addresses (replace with actual addresses)
@@ -1,19 +1,29 @@
-use serde::{Deserialize, Serialize};
-use rig_core::plugin::{Action, ActionError};
 use crate::{
     providers::birdeye::BirdeyeProvider,
     types::{
-        api::{TokenSearchParams, TokenInfo, WalletPortfolio},
+        api::{SortType, TokenInfo, TokenSearchParams, TokenSortBy, WalletPortfolio},
         error::BirdeyeError,
+        shared::{Address, Chain},
     },
 };
+use rig_core::plugin::Action;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Search tokens by name, symbol or address, on Solana unless another chain is given
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct TokenSearchAction {
+    /// Name, symbol or address of the token
     pub keyword: String,
-    pub sort_by: Option<String>,
-    pub sort_type: Option<String>,
+    /// Chain to search, the chain of the provider by default
+    #[serde(default)]
+    pub chain: Option<Chain>,
+    /// Field to sort the results by, 24h volume by default
+    pub sort_by: Option<TokenSortBy>,
+    /// Sort order, descending by default
+    pub sort_type: Option<SortType>,
     pub offset: Option<u32>,
+    /// Maximum number of results, at most 20
     pub limit: Option<u32>,
 }
 
@@ -26,19 +36,28 @@ impl Action for TokenSearchAction {
     async fn execute(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
         let params = TokenSearchParams {
             keyword: self.keyword,
-            sort_by: self.sort_by.map(|s| s.parse().unwrap()),
-            sort_type: self.sort_type.map(|s| s.parse().unwrap()),
+            sort_by: self.sort_by,
+            sort_type: self.sort_type,
             offset: self.offset,
             limit: self.limit,
         };
 
-        context.search_tokens(params).await
+        match self.chain {
+            Some(chain) => context.with_chain(chain).search_tokens(params).await,
+            None => context.search_tokens(params).await,
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Get the token holdings of a wallet and their value in USD
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct WalletSearchAction {
+    /// Address of the wallet
     pub address: String,
+    /// Chain of the wallet, detected from the address by default. Required for the EVM chains
+    /// other than Ethereum.
+    #[serde(default)]
+    pub chain: Option<Chain>,
 }
 
 impl Action for WalletSearchAction {
@@ -48,25 +67,38 @@ impl Action for WalletSearchAction {
     type Context = BirdeyeProvider;
 
     async fn execute(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
-        context.search_wallet(&self.address).await
+        let chain = match self.chain {
+            Some(chain) => chain,
+            None => Address::detect_chain(&self.address).ok_or_else(|| {
+                BirdeyeError::InvalidParameters(format!("Unknown address format: {}", self.address))
+            })?,
+        };
+        context
+            .with_chain(chain)
+            .get_wallet_portfolio(&self.address)
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::mock::{MockHttpClient, MockResponse};
+    use serde_json::json;
 
     #[tokio::test]
     async fn test_token_search_action() -> Result<(), BirdeyeError> {
-        let provider = BirdeyeProvider::new("test_key");
-        
-        let action = TokenSearchAction {
-            keyword: "SOL".to_string(),
-            sort_by: None,
-            sort_type: None,
-            offset: None,
-            limit: Some(10),
-        };
+        let provider = BirdeyeProvider::builder("test_key")
+            .transport(MockHttpClient::new())
+            .build();
+
+        let action: TokenSearchAction = serde_json::from_value(json!({
+            "keyword": "SOL",
+            "sort_by": "market_cap",
+            "sort_type": "desc",
+            "limit": 10
+        }))?;
+        assert_eq!(action.sort_by, Some(TokenSortBy::MarketCap));
 
         let result = action.execute(&provider).await?;
         assert!(!result.is_empty());
@@ -75,14 +107,35 @@ mod tests {
 
     #[tokio::test]
     async fn test_wallet_search_action() -> Result<(), BirdeyeError> {
-        let provider = BirdeyeProvider::new("test_key");
-        
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/v1/wallet/token_list",
+            MockResponse::data(json!({
+                "wallet": "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK",
+                "totalUsd": 0.0,
+                "items": []
+            })),
+        )
+        .await;
+        let provider = BirdeyeProvider::builder("test_key").transport(mock).build();
+
         let action = WalletSearchAction {
             address: "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK".to_string(),
+            chain: None,
         };
 
         let result = action.execute(&provider).await?;
         assert!(result.total_usd >= 0.0);
+
+        // A Solana wallet is not one on Base
+        let action: WalletSearchAction = serde_json::from_value(json!({
+            "address": "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK",
+            "chain": "base"
+        }))?;
+        assert!(matches!(
+            action.execute(&provider).await,
+            Err(BirdeyeError::InvalidParameters(_))
+        ));
         Ok(())
     }
 }
@@ -0,0 +1,467 @@
+//! Order book and pool math: spreads, depth, fills walking an order book and swaps in
+//! constant-product pools.
+use serde::{Deserialize, Serialize};
+
+/// Order book of a token. Bids are sorted by decreasing price and asks by increasing price,
+/// sizes are in tokens.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarketDepth {
+    pub bids: Vec<OrderBookEntry>,
+    pub asks: Vec<OrderBookEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OrderBookEntry {
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Summary of an order book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepthAnalysis {
+    pub total_bid_liquidity: f64,
+    pub total_ask_liquidity: f64,
+    /// Value of the bids over the value of the asks, None without asks
+    pub bid_ask_ratio: Option<f64>,
+    /// Spread in % of the mid price, None when a side of the book is empty
+    pub spread: Option<f64>,
+    /// Depth at each of the [DEPTH_LEVELS]
+    pub depth: Vec<DepthLevel>,
+    /// From 0 (no usable book) to 1, see [calculate_depth_quality]
+    pub depth_quality: f64,
+}
+
+/// How the impact of an order was estimated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImpactSource {
+    OrderBook,
+    /// Constant-product pool, for tokens without an order book
+    Amm,
+}
+
+/// Side of an order: buys fill against the asks, sells against the bids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Distances from the mid price at which the depth is measured, in %
+pub const DEPTH_LEVELS: [f64; 3] = [1.0, 2.0, 5.0];
+/// Depth within 2% of the mid price scoring 0 and 1 in the depth quality, in USD
+const MIN_DEPTH_USD: f64 = 1e4;
+const MAX_DEPTH_USD: f64 = 1e7;
+/// Spread scoring 0 in the depth quality, in %
+const MAX_SPREAD: f64 = 2.0;
+/// Price levels within 5% of the mid price for a full score in the depth quality
+const FULL_LEVELS: usize = 20;
+
+/// Value of the book within a distance of the mid price
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DepthLevel {
+    /// Distance from the mid price, in %
+    pub percent: f64,
+    /// Value in USD of the bids down to the mid price - `percent`
+    pub bid_usd: f64,
+    /// Value in USD of the asks up to the mid price + `percent`
+    pub ask_usd: f64,
+}
+
+/// Fill of an order, walking an order book or swapping in a pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Execution {
+    /// Average price of the fills (VWAP)
+    pub average_price: f64,
+    /// Price of the last fill, or of the pool after the swap
+    pub last_price: f64,
+    pub filled_tokens: f64,
+    /// Distance of the average price from the reference price against the order, in %
+    pub slippage: f64,
+    /// Distance of the last price from the reference price against the order, in %
+    pub price_impact: f64,
+}
+
+impl Execution {
+    fn new(
+        side: Side,
+        average_price: f64,
+        last_price: f64,
+        filled_tokens: f64,
+        reference_price: f64,
+    ) -> Self {
+        Self {
+            average_price,
+            last_price,
+            filled_tokens,
+            slippage: adverse_move(side, average_price, reference_price),
+            price_impact: adverse_move(side, last_price, reference_price),
+        }
+    }
+}
+
+/// How much worse than the reference price the price is for the order, in %
+fn adverse_move(side: Side, price: f64, reference_price: f64) -> f64 {
+    let change = (price / reference_price - 1.0) * 100.0;
+    match side {
+        Side::Buy => change,
+        Side::Sell => -change,
+    }
+}
+
+impl MarketDepth {
+    /// Levels an order of the side fills against, best price first
+    fn levels(&self, side: Side) -> Vec<OrderBookEntry> {
+        let entries = match side {
+            Side::Buy => &self.asks,
+            Side::Sell => &self.bids,
+        };
+        let mut levels: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.price > 0.0 && entry.size > 0.0)
+            .copied()
+            .collect();
+        match side {
+            Side::Buy => levels.sort_by(|a, b| a.price.total_cmp(&b.price)),
+            Side::Sell => levels.sort_by(|a, b| b.price.total_cmp(&a.price)),
+        }
+        levels
+    }
+
+    pub fn best_bid(&self) -> Option<f64> {
+        self.levels(Side::Sell).first().map(|entry| entry.price)
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.levels(Side::Buy).first().map(|entry| entry.price)
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.best_bid()? + self.best_ask()?) / 2.0)
+    }
+
+    /// Spread between the best ask and the best bid, in % of the mid price
+    pub fn spread(&self) -> Option<f64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        Some((ask - bid) / ((ask + bid) / 2.0) * 100.0)
+    }
+
+    pub fn analyze(&self) -> DepthAnalysis {
+        let value = |entries: &[OrderBookEntry]| -> f64 {
+            entries.iter().map(|entry| entry.price * entry.size).sum()
+        };
+        let (total_bid_liquidity, total_ask_liquidity) = (value(&self.bids), value(&self.asks));
+        DepthAnalysis {
+            total_bid_liquidity,
+            total_ask_liquidity,
+            bid_ask_ratio: (total_ask_liquidity > 0.0)
+                .then(|| total_bid_liquidity / total_ask_liquidity),
+            spread: self.spread(),
+            depth: DEPTH_LEVELS
+                .iter()
+                .filter_map(|percent| self.depth(*percent))
+                .collect(),
+            depth_quality: calculate_depth_quality(self),
+        }
+    }
+
+    /// Value of the book within `percent` of the mid price
+    pub fn depth(&self, percent: f64) -> Option<DepthLevel> {
+        let mid = self.mid_price()?;
+        let value = |side: Side, within: &dyn Fn(f64) -> bool| -> f64 {
+            self.levels(side)
+                .iter()
+                .filter(|entry| within(entry.price))
+                .map(|entry| entry.price * entry.size)
+                .sum()
+        };
+        Some(DepthLevel {
+            percent,
+            bid_usd: value(Side::Sell, &|price| price >= mid * (1.0 - percent / 100.0)),
+            ask_usd: value(Side::Buy, &|price| price <= mid * (1.0 + percent / 100.0)),
+        })
+    }
+}
+
+/// Constant-product pool (x * y = k) of a token against USD
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmmPool {
+    /// Tokens in the pool
+    pub base_reserve: f64,
+    /// Value in USD of the other side of the pool
+    pub quote_reserve: f64,
+    /// Fee taken on the input of the swaps, e.g. 0.0025 for 0.25%
+    pub fee: f64,
+}
+
+impl AmmPool {
+    /// Pool holding `liquidity_usd` evenly split between both sides at `price`
+    pub fn from_liquidity(liquidity_usd: f64, price: f64, fee: f64) -> Self {
+        Self {
+            base_reserve: liquidity_usd / 2.0 / price,
+            quote_reserve: liquidity_usd / 2.0,
+            fee,
+        }
+    }
+
+    pub fn price(&self) -> f64 {
+        self.quote_reserve / self.base_reserve
+    }
+
+    /// Swaps `size_tokens` out of the pool for a buy, or into the pool for a sell. None if
+    /// the pool does not hold enough tokens for the buy.
+    pub fn execute(&self, side: Side, size_tokens: f64) -> Option<Execution> {
+        let (base, quote) = (self.base_reserve, self.quote_reserve);
+        if size_tokens <= 0.0 || base <= 0.0 || quote <= 0.0 {
+            return None;
+        }
+        let (average_price, last_price) = match side {
+            Side::Buy => {
+                if size_tokens >= base {
+                    return None;
+                }
+                let quote_in = quote * size_tokens / (base - size_tokens);
+                let last_price = (quote + quote_in) / (base - size_tokens);
+                (quote_in / (1.0 - self.fee) / size_tokens, last_price)
+            }
+            Side::Sell => {
+                let base_in = size_tokens * (1.0 - self.fee);
+                let quote_out = quote * base_in / (base + base_in);
+                (
+                    quote_out / size_tokens,
+                    (quote - quote_out) / (base + base_in),
+                )
+            }
+        };
+        Some(Execution::new(
+            side,
+            average_price,
+            last_price,
+            size_tokens,
+            self.price(),
+        ))
+    }
+}
+
+/// Scores the order book from 0 to 1: the spread (30%), the value within 2% of the mid price
+/// on a log scale from $10k to $10M (40%), the balance between the bids and the asks within
+/// 2% (20%) and the number of price levels within 5% (10%). A book missing a side scores 0.
+pub fn calculate_depth_quality(depth: &MarketDepth) -> f64 {
+    let (Some(mid), Some(spread), Some(near)) =
+        (depth.mid_price(), depth.spread(), depth.depth(2.0))
+    else {
+        return 0.0;
+    };
+
+    let spread_score = 1.0 - (spread / MAX_SPREAD).min(1.0);
+    let near_usd = near.bid_usd + near.ask_usd;
+    let size_score = ((near_usd.max(MIN_DEPTH_USD).log10() - MIN_DEPTH_USD.log10())
+        / (MAX_DEPTH_USD.log10() - MIN_DEPTH_USD.log10()))
+    .min(1.0);
+    let balance_score = if near_usd > 0.0 {
+        near.bid_usd.min(near.ask_usd) / near.bid_usd.max(near.ask_usd)
+    } else {
+        0.0
+    };
+    let levels = [Side::Buy, Side::Sell]
+        .iter()
+        .flat_map(|side| depth.levels(*side))
+        .filter(|entry| (entry.price / mid - 1.0).abs() <= 0.05)
+        .count();
+    let levels_score = (levels as f64 / FULL_LEVELS as f64).min(1.0);
+
+    0.3 * spread_score + 0.4 * size_score + 0.2 * balance_score + 0.1 * levels_score
+}
+
+/// Walks the order book to fill `size_tokens`, from the best price. The fill is partial when
+/// the book is too thin, and None when the side of the order is empty.
+pub fn calculate_price_impact(
+    depth: &MarketDepth,
+    side: Side,
+    size_tokens: f64,
+    reference_price: f64,
+) -> Option<Execution> {
+    if size_tokens <= 0.0 {
+        return None;
+    }
+    let mut filled_tokens = 0.0;
+    let mut cost = 0.0;
+    let mut last_price = None;
+    for level in depth.levels(side) {
+        let size = level.size.min(size_tokens - filled_tokens);
+        filled_tokens += size;
+        cost += size * level.price;
+        last_price = Some(level.price);
+        if filled_tokens >= size_tokens {
+            break;
+        }
+    }
+    Some(Execution::new(
+        side,
+        cost / filled_tokens,
+        last_price?,
+        filled_tokens,
+        reference_price,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(levels: &[(f64, f64)]) -> Vec<OrderBookEntry> {
+        levels
+            .iter()
+            .map(|&(price, size)| OrderBookEntry { price, size })
+            .collect()
+    }
+
+    /// Book around $100 with a 2% spread
+    fn book() -> MarketDepth {
+        MarketDepth {
+            bids: entries(&[(99.0, 10.0), (98.0, 20.0), (96.0, 50.0), (90.0, 100.0)]),
+            asks: entries(&[(101.0, 10.0), (102.0, 20.0), (104.0, 50.0), (110.0, 100.0)]),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_spread_and_depth() {
+        let depth = book();
+        assert_eq!(depth.best_bid(), Some(99.0));
+        assert_eq!(depth.best_ask(), Some(101.0));
+        assert_eq!(depth.mid_price(), Some(100.0));
+        assert_close(depth.spread().unwrap(), 2.0);
+
+        let levels: Vec<_> = DEPTH_LEVELS
+            .iter()
+            .map(|percent| {
+                let level = depth.depth(*percent).unwrap();
+                (level.bid_usd, level.ask_usd)
+            })
+            .collect();
+        assert_eq!(
+            levels,
+            [(990.0, 1010.0), (2950.0, 3050.0), (7750.0, 8250.0)]
+        );
+
+        let analysis = depth.analyze();
+        assert_eq!(analysis.total_bid_liquidity, 16750.0);
+        assert_eq!(analysis.total_ask_liquidity, 19250.0);
+        assert_eq!(analysis.bid_ask_ratio, Some(16750.0 / 19250.0));
+        assert_eq!(analysis.depth.len(), DEPTH_LEVELS.len());
+
+        assert_eq!(MarketDepth::default().spread(), None);
+        assert!(MarketDepth::default().depth(1.0).is_none());
+        assert_eq!(MarketDepth::default().analyze().bid_ask_ratio, None);
+    }
+
+    #[test]
+    fn test_order_book_walk() {
+        let depth = book();
+
+        // 10 at 101 and 15 at 102
+        let buy = calculate_price_impact(&depth, Side::Buy, 25.0, 100.0).unwrap();
+        assert_close(buy.average_price, 101.6);
+        assert_eq!(buy.last_price, 102.0);
+        assert_eq!(buy.filled_tokens, 25.0);
+        assert_close(buy.slippage, 1.6);
+        assert_close(buy.price_impact, 2.0);
+
+        // 10 at 99 and 15 at 98
+        let sell = calculate_price_impact(&depth, Side::Sell, 25.0, 100.0).unwrap();
+        assert_close(sell.average_price, 98.4);
+        assert_close(sell.slippage, 1.6);
+        assert_close(sell.price_impact, 2.0);
+
+        // Larger than the book
+        let buy = calculate_price_impact(&depth, Side::Buy, 500.0, 100.0).unwrap();
+        assert_eq!(buy.filled_tokens, 180.0);
+        assert_eq!(buy.last_price, 110.0);
+
+        // The order of the entries does not matter
+        let mut shuffled = book();
+        shuffled.asks.reverse();
+        shuffled.bids.swap(0, 2);
+        assert_eq!(
+            calculate_price_impact(&shuffled, Side::Buy, 25.0, 100.0),
+            calculate_price_impact(&depth, Side::Buy, 25.0, 100.0)
+        );
+
+        assert!(calculate_price_impact(&MarketDepth::default(), Side::Buy, 1.0, 100.0).is_none());
+        assert!(calculate_price_impact(&depth, Side::Sell, 0.0, 100.0).is_none());
+    }
+
+    #[test]
+    fn test_depth_quality() {
+        // Spread and size score 0: balance of 2950 / 3050 and 6 levels out of 20
+        assert_close(
+            calculate_depth_quality(&book()),
+            0.2 * 2950.0 / 3050.0 + 0.1 * 0.3,
+        );
+
+        // 0.02% spread, $20M within 2% on 40 levels
+        let levels = |start: f64, step: f64| -> Vec<OrderBookEntry> {
+            (0..20)
+                .map(|level| OrderBookEntry {
+                    price: start + step * level as f64,
+                    size: 10_000.0,
+                })
+                .collect()
+        };
+        let deep = MarketDepth {
+            bids: levels(99.99, -0.05),
+            asks: levels(100.01, 0.05),
+        };
+        let near = deep.depth(2.0).unwrap();
+        let balance = near.bid_usd / near.ask_usd;
+        assert_close(
+            calculate_depth_quality(&deep),
+            0.3 * 0.99 + 0.4 + 0.2 * balance + 0.1,
+        );
+
+        let one_sided = MarketDepth {
+            bids: vec![],
+            asks: book().asks,
+        };
+        assert_eq!(calculate_depth_quality(&one_sided), 0.0);
+        assert_eq!(calculate_depth_quality(&MarketDepth::default()), 0.0);
+    }
+
+    #[test]
+    fn test_amm_pool() {
+        // 10,000 tokens against $1M
+        let pool = AmmPool::from_liquidity(2_000_000.0, 100.0, 0.0);
+        assert_eq!(pool.price(), 100.0);
+
+        let buy = pool.execute(Side::Buy, 100.0).unwrap();
+        assert_close(buy.average_price, 1e6 / 9900.0);
+        assert_close(buy.slippage, 100.0 / 99.0);
+        assert_close(buy.last_price, 1e10 / (9900.0 * 9900.0));
+
+        let sell = pool.execute(Side::Sell, 100.0).unwrap();
+        assert_close(sell.average_price, 1e6 / 10100.0);
+        assert_close(sell.slippage, 100.0 / 101.0);
+        assert_close(
+            sell.price_impact,
+            (1.0 - 1e4 * 1e4 / (10100.0 * 10100.0)) * 100.0,
+        );
+
+        // The fee makes the swaps worse
+        let with_fee = AmmPool { fee: 0.003, ..pool };
+        assert!(with_fee.execute(Side::Buy, 100.0).unwrap().slippage > buy.slippage);
+        assert!(with_fee.execute(Side::Sell, 100.0).unwrap().slippage > sell.slippage);
+
+        // The pool cannot give more tokens than it has
+        assert!(pool.execute(Side::Buy, 10_000.0).is_none());
+    }
+}
@@ -1,430 +1,1137 @@
-use std::time::Duration;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use crate::types::{
-    api::{TokenSearchParams, WalletPortfolio, TokenOverview, LiquidityAnalysis, MarketImpact, PricePoint},
-    error::BirdeyeError,
-    TimeInterval,
-};
 use super::{
+    cache::ResponseCache,
+    pagination::{paginate, Page, PageCursor, StreamOptions},
     rate_limiter::RateLimiter,
-    pagination::{PaginationParams, PaginatedResponse, PaginatedIterator},
-    cache::CachedClient,
+    transport::{HttpRequest, HttpTransport, Transport},
 };
 use crate::{
-    providers::{
-        cache::TokenCache,
-        pagination::{PaginatedRequest},
-    },
+    analysis::{AmmPool, ImpactSource, Side},
     types::{
-        api::{TokenInfo},
+        api::{
+            LiquidityAnalysis, Market, MarketImpact, PriceHistoryParams, PricePoint, SortType,
+            TokenHolder, TokenInfo, TokenListItem, TokenListParams, TokenOverview,
+            TokenSearchParams, TokenSecurity, TokenSortBy, TokenTrade, WalletPortfolio,
+        },
+        error::BirdeyeError,
+        shared::{Address, Chain},
+        TimeInterval,
     },
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use serde::de::DeserializeOwned;
-
-// Default cache TTL of 1 minute for frequently accessed data
-const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
-const DEFAULT_RATE_LIMIT: f64 = 10.0;
-const DEFAULT_CACHE_DURATION: Duration = Duration::from_secs(60);
+use futures_util::Stream;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{sync::Arc, time::Duration};
 
 // Birdeye API allows 10 requests per second
 const RATE_LIMIT_CAPACITY: f64 = 10.0;
 const RATE_LIMIT_REFILL_RATE: f64 = 10.0; // tokens per second
 
-const API_BASE_URL: &str = "https://public-api.birdeye.so";
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 const MAX_RETRIES: u32 = 3;
 const RETRY_DELAY: Duration = Duration::from_millis(1000);
 
+// Largest page of the search, token list, trades, holders and markets endpoints
+const MAX_SEARCH_LIMIT: u32 = 20;
+const MAX_TOKEN_LIST_LIMIT: u32 = 50;
+const MAX_TRADES_LIMIT: u32 = 50;
+const MAX_HOLDERS_LIMIT: u32 = 100;
+const MARKETS_LIMIT: u32 = 20;
+// Candles fetched when the price history has no start
+const DEFAULT_HISTORY_POINTS: i64 = 100;
+// Liquidity in USD scored 0 and 1 by the depth quality
+const MIN_SCORED_LIQUIDITY: f64 = 1e4;
+const MAX_SCORED_LIQUIDITY: f64 = 1e7;
+
+/// Client of the Birdeye API on one chain, sharing one rate limiter and cache between its
+/// clones
 #[derive(Clone)]
 pub struct BirdeyeProvider {
-    client: Client,
+    transport: Arc<dyn Transport>,
     api_key: String,
+    chain: Chain,
     rate_limiter: RateLimiter,
-    token_cache: Arc<Mutex<TokenCache>>,
+    cache: Option<ResponseCache>,
+    max_retries: u32,
+    retry_delay: Duration,
 }
 
-pub struct CachedBirdeyeProvider {
-    inner: CachedClient<BirdeyeProvider>,
+/// Builder of a [BirdeyeProvider] talking to another server or through another transport
+pub struct BirdeyeProviderBuilder {
+    api_key: String,
+    transport: Option<Arc<dyn Transport>>,
+    base_url: Option<String>,
+    chain: Chain,
+    cache: Option<ResponseCache>,
+    rate_limit: (f64, f64),
+    max_retries: u32,
+    retry_delay: Duration,
 }
 
-impl CachedBirdeyeProvider {
+impl BirdeyeProviderBuilder {
     pub fn new(api_key: &str) -> Self {
-        let provider = BirdeyeProvider::new(api_key.to_string());
         Self {
-            inner: CachedClient::new(provider, DEFAULT_CACHE_TTL),
+            api_key: api_key.to_string(),
+            transport: None,
+            base_url: None,
+            chain: Chain::default(),
+            cache: None,
+            rate_limit: (RATE_LIMIT_CAPACITY, RATE_LIMIT_REFILL_RATE),
+            max_retries: MAX_RETRIES,
+            retry_delay: RETRY_DELAY,
         }
     }
 
-    pub fn with_cache_ttl(api_key: &str, cache_ttl: Duration) -> Self {
-        let provider = BirdeyeProvider::new(api_key.to_string());
-        Self {
-            inner: CachedClient::new(provider, cache_ttl),
-        }
+    /// Sends the requests over HTTP to `base_url` instead of the Birdeye API
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.to_string());
+        self
     }
 
-    pub async fn get_token_overview(&self, address: &str) -> Result<TokenOverview, BirdeyeError> {
-        self.inner
-            .get_token_overview(address, |provider, addr| async move {
-                provider.get_token_overview(addr).await
-            })
-            .await
+    /// Sends the requests through `transport`. Overrides the base URL.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
     }
-}
 
-impl BirdeyeProvider {
-    pub fn new(api_key: String) -> Self {
-        Self::new_with_config(
-            api_key,
-            DEFAULT_RATE_LIMIT,
-            DEFAULT_CACHE_DURATION,
-        )
+    /// Chain of the requests, Solana by default
+    pub fn chain(mut self, chain: Chain) -> Self {
+        self.chain = chain;
+        self
     }
 
-    pub fn new_with_config(
-        api_key: String,
-        rate_limit: f64,
-        cache_duration: Duration,
-    ) -> Self {
-        let client = Client::builder()
-            .timeout(DEFAULT_TIMEOUT)
-            .build()
-            .expect("Failed to create HTTP client");
+    /// Caches the responses, none are by default
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
 
-        Self {
-            client,
-            api_key,
-            rate_limiter: RateLimiter::new(rate_limit, rate_limit),
-            token_cache: Arc::new(Mutex::new(TokenCache::new(cache_duration))),
+    /// Burst of requests allowed and requests per second after it
+    pub fn rate_limit(mut self, capacity: f64, refill_rate: f64) -> Self {
+        self.rate_limit = (capacity, refill_rate);
+        self
+    }
+
+    /// Retries of the requests that are rate limited or fail on the server
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Delay before the first retry, growing linearly with the next ones
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    pub fn build(self) -> BirdeyeProvider {
+        let transport = self.transport.unwrap_or_else(|| match &self.base_url {
+            Some(base_url) => Arc::new(HttpTransport::new(base_url)),
+            None => Arc::new(HttpTransport::default()),
+        });
+
+        BirdeyeProvider {
+            transport,
+            api_key: self.api_key,
+            chain: self.chain,
+            cache: self.cache,
+            rate_limiter: RateLimiter::new(self.rate_limit.0, self.rate_limit.1),
+            max_retries: self.max_retries,
+            retry_delay: self.retry_delay,
         }
     }
+}
+
+/// Envelope of every Birdeye response
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    #[serde(default)]
+    success: bool,
+    data: Option<T>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Items<T> {
+    items: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct TokenList {
+    tokens: Vec<TokenListItem>,
+}
+
+#[derive(Deserialize)]
+struct SearchGroup {
+    #[serde(rename = "type")]
+    kind: String,
+    // Markets and tokens are shaped differently, only the tokens are parsed
+    #[serde(default)]
+    result: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Candle {
+    #[serde(rename = "unixTime")]
+    unix_time: i64,
+    #[serde(rename = "c")]
+    close: f64,
+    #[serde(rename = "v", default)]
+    volume: f64,
+}
+
+impl BirdeyeProvider {
+    pub fn new(api_key: &str) -> Self {
+        BirdeyeProviderBuilder::new(api_key).build()
+    }
+
+    pub fn builder(api_key: &str) -> BirdeyeProviderBuilder {
+        BirdeyeProviderBuilder::new(api_key)
+    }
 
     /// Create a new BirdeyeProvider with custom rate limiting parameters
     pub fn with_rate_limit(api_key: &str, capacity: f64, refill_rate: f64) -> Self {
-        let client = Client::builder()
-            .timeout(DEFAULT_TIMEOUT)
+        BirdeyeProviderBuilder::new(api_key)
+            .rate_limit(capacity, refill_rate)
             .build()
-            .expect("Failed to create HTTP client");
+    }
+
+    pub fn chain(&self) -> Chain {
+        self.chain
+    }
 
+    /// The same provider on another chain, sharing the rate limiter
+    pub fn with_chain(&self, chain: Chain) -> Self {
         Self {
-            client,
-            api_key: api_key.to_string(),
-            rate_limiter: RateLimiter::new(capacity, refill_rate),
-            token_cache: Arc::new(Mutex::new(TokenCache::new(DEFAULT_CACHE_TTL))),
+            chain,
+            ..self.clone()
         }
     }
 
-    async fn request<T: DeserializeOwned>(&self, endpoint: &str, params: &[(&str, String)]) -> Result<T, BirdeyeError> {
-        let url = format!("{}{}", API_BASE_URL, endpoint);
-        
+    /// Checks that the address is one of the chain of the provider
+    fn validate(&self, address: &str) -> Result<(), BirdeyeError> {
+        Address::validate(address, self.chain)
+    }
+
+    /// The cache of the responses, if any
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.cache.as_ref()
+    }
+
+    /// The `data` of the response of `endpoint`, from the cache if there is one
+    async fn request<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, BirdeyeError> {
+        let data = match &self.cache {
+            Some(cache) => {
+                let key = ResponseCache::key(self.chain, endpoint, params);
+                let provider = self.clone();
+                let fetched_endpoint = endpoint.to_string();
+                let params: Vec<_> = params
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect();
+                let fetch = async move {
+                    let params: Vec<_> = params
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.clone()))
+                        .collect();
+                    provider.fetch(&fetched_endpoint, &params).await
+                };
+                cache.get_or_fetch(endpoint, key, fetch).await?
+            }
+            None => Arc::new(self.fetch(endpoint, params).await?),
+        };
+        Ok(T::deserialize(&*data)?)
+    }
+
+    /// Requests `endpoint` and returns the `data` of the response. Rate limited and server
+    /// errors are retried, other errors are returned as they are.
+    async fn fetch(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<serde_json::Value, BirdeyeError> {
         let mut retries = 0;
         loop {
             // Wait for rate limit token
             self.rate_limiter.acquire().await;
-            
-            let response = self.client
-                .get(&url)
-                .header("X-API-KEY", &self.api_key)
-                .query(params)
-                .send()
-                .await
-                .map_err(BirdeyeError::RequestError)?;
-
-            if !response.status().is_success() {
-                if retries >= MAX_RETRIES {
-                    return Err(BirdeyeError::InvalidResponse(format!(
-                        "HTTP {} - {}",
-                        response.status(),
-                        response.text().await.unwrap_or_default()
-                    )));
-                }
+
+            let response = self
+                .transport
+                .get(HttpRequest {
+                    endpoint,
+                    query: params,
+                    api_key: &self.api_key,
+                    chain: self.chain,
+                })
+                .await?;
+            let status = StatusCode::from_u16(response.status)
+                .map_err(|err| BirdeyeError::InvalidResponse(err.to_string()))?;
+            let body = response.body;
+
+            let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            if retryable && retries < self.max_retries {
                 retries += 1;
-                tokio::time::sleep(RETRY_DELAY).await;
+                tracing::debug!(
+                    "Birdeye {} returned {}, retry {}",
+                    endpoint,
+                    status,
+                    retries
+                );
+                tokio::time::sleep(self.retry_delay * retries).await;
                 continue;
             }
 
-            return response
-                .json()
-                .await
-                .map_err(BirdeyeError::SerializationError);
+            match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    return Err(BirdeyeError::InvalidApiKey)
+                }
+                StatusCode::TOO_MANY_REQUESTS => return Err(BirdeyeError::RateLimitExceeded),
+                status if !status.is_success() => {
+                    let message = serde_json::from_str::<ApiResponse<serde_json::Value>>(&body)
+                        .ok()
+                        .and_then(|response| response.message)
+                        .unwrap_or(body);
+                    return Err(BirdeyeError::ApiError {
+                        status_code: status.as_u16(),
+                        message,
+                    });
+                }
+                _ => {}
+            }
+
+            let response: ApiResponse<serde_json::Value> = serde_json::from_str(&body)?;
+            return match response.data {
+                Some(data) if response.success => Ok(data),
+                _ => Err(BirdeyeError::ApiError {
+                    status_code: status.as_u16(),
+                    message: response
+                        .message
+                        .unwrap_or_else(|| format!("Unsuccessful response from {}", endpoint)),
+                }),
+            };
         }
     }
 
-    pub async fn search_tokens(&self, params: TokenSearchParams) -> Result<Vec<TokenInfo>, BirdeyeError> {
-        let mut query_params = vec![
-            ("query", params.query.clone()),
+    /// Tokens matching the keyword, by 24h volume unless sorted otherwise
+    pub async fn search_tokens(
+        &self,
+        params: TokenSearchParams,
+    ) -> Result<Vec<TokenInfo>, BirdeyeError> {
+        let query_params = vec![
+            ("keyword", params.keyword.clone()),
+            ("target", "token".to_string()),
+            ("chain", self.chain.to_string()),
+            (
+                "sort_by",
+                params.sort_by.unwrap_or(TokenSortBy::Volume).to_string(),
+            ),
+            (
+                "sort_type",
+                params.sort_type.unwrap_or(SortType::Descending).to_string(),
+            ),
+            ("offset", params.offset.unwrap_or(0).to_string()),
+            (
+                "limit",
+                params.limit.unwrap_or(10).min(MAX_SEARCH_LIMIT).to_string(),
+            ),
         ];
 
-        if let Some(limit) = params.limit {
-            query_params.push(("limit", limit.to_string()));
-        }
+        let groups: Items<SearchGroup> = self.request("/defi/v3/search", &query_params).await?;
+        groups
+            .items
+            .into_iter()
+            .filter(|group| group.kind == "token")
+            .flat_map(|group| group.result)
+            .map(|token| serde_json::from_value(token).map_err(BirdeyeError::SerializationError))
+            .collect()
+    }
 
-        if let Some(offset) = params.offset {
-            query_params.push(("offset", offset.to_string()));
+    /// All the tokens matching the keyword, from the offset of the params
+    pub fn search_tokens_stream(
+        &self,
+        params: TokenSearchParams,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<TokenInfo, BirdeyeError>> + Send + 'static {
+        let client = self.clone();
+        let start = PageCursor::Offset(params.offset.unwrap_or(0));
+        let fetch = move |cursor, limit| {
+            let client = client.clone();
+            let mut params = params.clone();
+            async move {
+                let offset = offset_of(cursor)?;
+                params.offset = Some(offset);
+                params.limit = Some(limit);
+                Ok(Page::at_offset(
+                    client.search_tokens(params).await?,
+                    offset,
+                    limit,
+                ))
+            }
+        };
+
+        paginate(fetch, start, capped(options, MAX_SEARCH_LIMIT))
+    }
+
+    /// The most traded token with the symbol (case insensitive), if any
+    pub async fn find_token(&self, symbol: &str) -> Result<Option<TokenInfo>, BirdeyeError> {
+        let params = TokenSearchParams::new(symbol)
+            .with_sort(TokenSortBy::Volume, SortType::Descending)
+            .with_limit(MAX_SEARCH_LIMIT);
+        Ok(self
+            .search_tokens(params)
+            .await?
+            .into_iter()
+            .find(|token| token.symbol.eq_ignore_ascii_case(symbol)))
+    }
+
+    /// Page of the list of all the tokens, e.g. sorted by 24h change for the top movers
+    pub async fn get_token_list(
+        &self,
+        params: TokenListParams,
+    ) -> Result<Vec<TokenListItem>, BirdeyeError> {
+        let mut query_params = vec![
+            ("sort_by", params.sort_by.to_string()),
+            ("sort_type", params.sort_type.to_string()),
+            ("offset", params.offset.unwrap_or(0).to_string()),
+            (
+                "limit",
+                params
+                    .limit
+                    .unwrap_or(MAX_TOKEN_LIST_LIMIT)
+                    .min(MAX_TOKEN_LIST_LIMIT)
+                    .to_string(),
+            ),
+        ];
+        if let Some(min_liquidity) = params.min_liquidity {
+            query_params.push(("min_liquidity", min_liquidity.to_string()));
         }
 
-        self.request("/v1/token/search", &query_params).await
+        let list: TokenList = self.request("/defi/tokenlist", &query_params).await?;
+        Ok(list.tokens)
     }
 
-    pub fn search_tokens_iter(
+    /// All the tokens of the list, from the offset of the params
+    pub fn token_list_stream(
         &self,
-        params: TokenSearchParams,
-    ) -> PaginatedIterator<TokenInfo, impl PaginatedRequest<TokenInfo>> {
+        params: TokenListParams,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<TokenListItem, BirdeyeError>> + Send + 'static {
         let client = self.clone();
-        let request = move |offset: u32, limit: u32| {
+        let start = PageCursor::Offset(params.offset.unwrap_or(0));
+        let fetch = move |cursor, limit| {
+            let client = client.clone();
             let mut params = params.clone();
-            params.offset = Some(offset);
-            params.limit = Some(limit);
-            Box::pin(async move { client.search_tokens(params).await })
+            async move {
+                let offset = offset_of(cursor)?;
+                params.offset = Some(offset);
+                params.limit = Some(limit);
+                Ok(Page::at_offset(
+                    client.get_token_list(params).await?,
+                    offset,
+                    limit,
+                ))
+            }
         };
 
-        PaginatedIterator::new(request, params.limit.unwrap_or(10))
+        paginate(fetch, start, capped(options, MAX_TOKEN_LIST_LIMIT))
+    }
+
+    /// The latest swaps of the token before `before_time`, or before now
+    pub async fn get_token_trades(
+        &self,
+        address: &str,
+        before_time: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<TokenTrade>, BirdeyeError> {
+        self.validate(address)?;
+        let mut query_params = vec![
+            ("address", address.to_string()),
+            ("tx_type", "swap".to_string()),
+            ("limit", limit.min(MAX_TRADES_LIMIT).to_string()),
+        ];
+        if let Some(before_time) = before_time {
+            query_params.push(("before_time", before_time.to_string()));
+        }
+
+        let trades: Items<TokenTrade> = self
+            .request("/defi/txs/token/seek_by_time", &query_params)
+            .await?;
+        Ok(trades.items)
+    }
+
+    /// The swaps of the token before `before_time`, or before now, the latest first. Each page
+    /// continues before the time of the last trade of the previous one, so trades of that very
+    /// second left out of the page are skipped.
+    pub fn trades_stream(
+        &self,
+        address: &str,
+        before_time: Option<i64>,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<TokenTrade, BirdeyeError>> + Send + 'static {
+        let client = self.clone();
+        let address = address.to_string();
+        let before_time = before_time.unwrap_or_else(|| chrono::Utc::now().timestamp() + 1);
+        let fetch = move |cursor, limit| {
+            let client = client.clone();
+            let address = address.clone();
+            async move {
+                let before_time = match cursor {
+                    PageCursor::Cursor(time) => time.parse().map_err(|_| {
+                        BirdeyeError::InvalidParameters(format!("Invalid trade cursor {}", time))
+                    })?,
+                    PageCursor::Offset(_) => before_time,
+                };
+                let items = client
+                    .get_token_trades(&address, Some(before_time), limit)
+                    .await?;
+                let next = match items.last() {
+                    Some(last) if items.len() as u32 >= limit => {
+                        Some(PageCursor::Cursor(last.block_unix_time.to_string()))
+                    }
+                    _ => None,
+                };
+                Ok(Page { items, next })
+            }
+        };
+
+        paginate(
+            fetch,
+            PageCursor::Cursor(before_time.to_string()),
+            capped(options, MAX_TRADES_LIMIT),
+        )
+    }
+
+    /// Page of the holders of the token, the largest first
+    pub async fn get_token_holders(
+        &self,
+        address: &str,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<TokenHolder>, BirdeyeError> {
+        self.validate(address)?;
+        let query_params = vec![
+            ("address", address.to_string()),
+            ("offset", offset.to_string()),
+            ("limit", limit.min(MAX_HOLDERS_LIMIT).to_string()),
+        ];
+        let holders: Items<TokenHolder> =
+            self.request("/defi/v3/token/holder", &query_params).await?;
+        Ok(holders.items)
+    }
+
+    /// All the holders of the token, the largest first
+    pub fn holders_stream(
+        &self,
+        address: &str,
+        options: StreamOptions,
+    ) -> impl Stream<Item = Result<TokenHolder, BirdeyeError>> + Send + 'static {
+        let client = self.clone();
+        let address = address.to_string();
+        let fetch = move |cursor, limit| {
+            let client = client.clone();
+            let address = address.clone();
+            async move {
+                let offset = offset_of(cursor)?;
+                let holders = client.get_token_holders(&address, offset, limit).await?;
+                Ok(Page::at_offset(holders, offset, limit))
+            }
+        };
+
+        paginate(
+            fetch,
+            PageCursor::Offset(0),
+            capped(options, MAX_HOLDERS_LIMIT),
+        )
     }
 
     pub async fn get_token_overview(&self, address: &str) -> Result<TokenOverview, BirdeyeError> {
+        self.validate(address)?;
         let query_params = vec![("address", address.to_string())];
-        self.request("/v1/token/overview", &query_params).await
+        self.request("/defi/token_overview", &query_params).await
     }
 
-    pub async fn analyze_liquidity(&self, address: &str) -> Result<LiquidityAnalysis, BirdeyeError> {
+    pub async fn get_token_security(&self, address: &str) -> Result<TokenSecurity, BirdeyeError> {
+        self.validate(address)?;
         let query_params = vec![("address", address.to_string())];
-        self.request("/v1/token/liquidity", &query_params).await
+        self.request("/defi/token_security", &query_params).await
     }
 
-    pub async fn get_market_impact(&self, address: &str, size_usd: f64) -> Result<MarketImpact, BirdeyeError> {
+    /// The last 100 candles of the token at the interval
+    pub async fn get_price_history(
+        &self,
+        address: &str,
+        interval: TimeInterval,
+    ) -> Result<Vec<PricePoint>, BirdeyeError> {
+        self.get_price_history_range(PriceHistoryParams::new(address, interval))
+            .await
+    }
+
+    pub async fn get_price_history_range(
+        &self,
+        params: PriceHistoryParams,
+    ) -> Result<Vec<PricePoint>, BirdeyeError> {
+        self.validate(&params.address)?;
+        let time_to = params
+            .time_to
+            .unwrap_or_else(|| chrono::Utc::now().timestamp());
+        let time_from = params
+            .time_from
+            .unwrap_or(time_to - params.interval.seconds() * DEFAULT_HISTORY_POINTS);
+        if time_from >= time_to {
+            return Err(BirdeyeError::InvalidParameters(format!(
+                "time_from {} is not before time_to {}",
+                time_from, time_to
+            )));
+        }
+
         let query_params = vec![
-            ("address", address.to_string()),
-            ("size", size_usd.to_string()),
+            ("address", params.address.clone()),
+            ("type", params.interval.api_type().to_string()),
+            ("time_from", time_from.to_string()),
+            ("time_to", time_to.to_string()),
         ];
-        self.request("/v1/token/impact", &query_params).await
+        let candles: Items<Candle> = self.request("/defi/ohlcv", &query_params).await?;
+        Ok(candles
+            .items
+            .into_iter()
+            .map(|candle| PricePoint {
+                timestamp: candle.unix_time,
+                price: candle.close,
+                volume: candle.volume,
+            })
+            .collect())
     }
 
-    pub async fn get_price_history(&self, address: &str, interval: TimeInterval) -> Result<Vec<PricePoint>, BirdeyeError> {
+    pub async fn get_wallet_portfolio(
+        &self,
+        wallet_address: &str,
+    ) -> Result<WalletPortfolio, BirdeyeError> {
+        self.validate(wallet_address)?;
+        let query_params = vec![("wallet", wallet_address.to_string())];
+        self.request("/v1/wallet/token_list", &query_params).await
+    }
+
+    /// The deepest markets of the token
+    pub async fn get_markets(&self, address: &str) -> Result<Vec<Market>, BirdeyeError> {
+        self.validate(address)?;
         let query_params = vec![
             ("address", address.to_string()),
-            ("interval", interval.to_string()),
+            ("sort_by", "liquidity".to_string()),
+            ("sort_type", "desc".to_string()),
+            ("offset", "0".to_string()),
+            ("limit", MARKETS_LIMIT.to_string()),
         ];
-        self.request("/v1/token/price_history", &query_params).await
+        let markets: Items<Market> = self.request("/defi/v2/markets", &query_params).await?;
+        Ok(markets.items)
     }
 
-    pub async fn get_wallet_portfolio(&self, wallet_address: &str) -> Result<WalletPortfolio, BirdeyeError> {
-        let query_params = vec![("wallet", wallet_address.to_string())];
-        self.request("/v1/wallet/tokens", &query_params).await
+    pub async fn analyze_liquidity(
+        &self,
+        address: &str,
+    ) -> Result<LiquidityAnalysis, BirdeyeError> {
+        Ok(liquidity_analysis(&self.get_markets(address).await?))
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TokenInfo {
-    pub address: String,
-    pub symbol: String,
-    pub name: String,
-    pub decimals: u8,
-    pub price_usd: f64,
-    pub volume_24h: f64,
-    pub market_cap: f64,
-}
-
-impl Clone for BirdeyeProvider {
-    fn clone(&self) -> Self {
-        Self {
-            client: self.client.clone(),
-            api_key: self.api_key.clone(),
-            rate_limiter: RateLimiter::new(RATE_LIMIT_CAPACITY, RATE_LIMIT_REFILL_RATE),
-            token_cache: Arc::new(Mutex::new(TokenCache::new(DEFAULT_CACHE_TTL))),
+    /// Estimated execution of an order of `size_usd` of the token across its markets, fees
+    /// excluded
+    pub async fn get_market_impact(
+        &self,
+        address: &str,
+        side: Side,
+        size_usd: f64,
+    ) -> Result<MarketImpact, BirdeyeError> {
+        if size_usd <= 0.0 {
+            return Err(BirdeyeError::InvalidParameters(format!(
+                "Trade size must be positive, got {}",
+                size_usd
+            )));
         }
+        let overview = self.get_token_overview(address).await?;
+        let markets = self.get_markets(address).await?;
+        let liquidity: f64 = markets.iter().map(|market| market.liquidity).sum();
+        market_impact(overview.price, liquidity, side, size_usd)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TokenOverview {
-    pub price: f64,
-    pub volume_24h: f64,
-    pub price_change_24h: f64,
-    pub liquidity: f64,
-    pub holders: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LiquidityAnalysis {
-    pub total_bid_liquidity: f64,
-    pub total_ask_liquidity: f64,
-    pub bid_ask_ratio: f64,
-    pub depth_quality: f64,
+/// The options with pages no larger than the endpoint allows
+fn capped(options: StreamOptions, max_page_size: u32) -> StreamOptions {
+    StreamOptions {
+        page_size: options.page_size.min(max_page_size),
+        ..options
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MarketImpact {
-    pub price_impact: f64,
-    pub executed_price: f64,
-    pub size_usd: f64,
-    pub size_tokens: f64,
+fn offset_of(cursor: PageCursor) -> Result<u32, BirdeyeError> {
+    match cursor {
+        PageCursor::Offset(offset) => Ok(offset),
+        PageCursor::Cursor(cursor) => Err(BirdeyeError::InvalidParameters(format!(
+            "Offset expected, got the cursor {}",
+            cursor
+        ))),
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PricePoint {
-    pub timestamp: i64,
-    pub price: f64,
-    pub volume: f64,
-}
+fn liquidity_analysis(markets: &[Market]) -> LiquidityAnalysis {
+    let total_liquidity: f64 = markets.iter().map(|market| market.liquidity).sum();
+    let volume_24h = markets.iter().map(|market| market.volume_24h).sum();
+    let top_market_share = if total_liquidity > 0.0 {
+        markets
+            .iter()
+            .map(|market| market.liquidity)
+            .fold(0.0, f64::max)
+            / total_liquidity
+    } else {
+        0.0
+    };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum TimeInterval {
-    FiveMinutes,
-    FifteenMinutes,
-    OneHour,
-    FourHours,
-    OneDay,
-    OneWeek,
-    OneMonth,
-}
+    // Mostly the size of the liquidity on a log scale, a bit how spread it is across markets
+    let size = ((total_liquidity.max(1.0).log10() - MIN_SCORED_LIQUIDITY.log10())
+        / (MAX_SCORED_LIQUIDITY.log10() - MIN_SCORED_LIQUIDITY.log10()))
+    .clamp(0.0, 1.0);
+    let spread = if total_liquidity > 0.0 {
+        1.0 - top_market_share
+    } else {
+        0.0
+    };
 
-impl ToString for TimeInterval {
-    fn to_string(&self) -> String {
-        match self {
-            TimeInterval::FiveMinutes => "5m".to_string(),
-            TimeInterval::FifteenMinutes => "15m".to_string(),
-            TimeInterval::OneHour => "1h".to_string(),
-            TimeInterval::FourHours => "4h".to_string(),
-            TimeInterval::OneDay => "1d".to_string(),
-            TimeInterval::OneWeek => "1w".to_string(),
-            TimeInterval::OneMonth => "1M".to_string(),
-        }
+    LiquidityAnalysis {
+        total_liquidity,
+        volume_24h,
+        markets: markets.len(),
+        top_market_share,
+        depth_quality: 0.8 * size + 0.2 * spread,
     }
 }
 
-impl std::str::FromStr for TimeInterval {
-    type Err = BirdeyeError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "5m" => Ok(TimeInterval::FiveMinutes),
-            "15m" => Ok(TimeInterval::FifteenMinutes),
-            "1h" => Ok(TimeInterval::OneHour),
-            "4h" => Ok(TimeInterval::FourHours),
-            "1d" => Ok(TimeInterval::OneDay),
-            "1w" => Ok(TimeInterval::OneWeek),
-            "1m" => Ok(TimeInterval::OneMonth),
-            _ => Err(BirdeyeError::InvalidParameters(format!(
-                "Invalid time interval: {}. Valid intervals are: 5m, 15m, 1h, 4h, 1d, 1w, 1M",
-                s
-            ))),
-        }
+/// Impact of an order on constant product pools holding `liquidity` USD in total. Splitting
+/// the order across the pools in proportion to their reserves costs the same as trading on a
+/// single pool with all of them.
+fn market_impact(
+    price: f64,
+    liquidity: f64,
+    side: Side,
+    size_usd: f64,
+) -> Result<MarketImpact, BirdeyeError> {
+    if price <= 0.0 || liquidity <= 0.0 {
+        return Err(BirdeyeError::InvalidResponse(
+            "The token has no priced liquidity".to_string(),
+        ));
     }
+
+    let size_tokens = size_usd / price;
+    let execution = AmmPool::from_liquidity(liquidity, price, 0.0)
+        .execute(side, size_tokens)
+        .ok_or_else(|| {
+            BirdeyeError::InvalidParameters(format!(
+                "Buying {} tokens is more than the markets hold",
+                size_tokens
+            ))
+        })?;
+    Ok(MarketImpact::new(
+        side,
+        size_usd,
+        size_tokens,
+        execution,
+        ImpactSource::Amm,
+    ))
 }
 
 #[cfg(test)]
-mod time_interval_tests {
+mod tests {
     use super::*;
+    use crate::{
+        providers::mock::{MockHttpClient, MockResponse},
+        types::api::TokenListSortBy,
+    };
+    use futures_util::TryStreamExt;
+    use serde_json::json;
 
-    #[test]
-    fn test_time_interval_to_string() {
-        assert_eq!(TimeInterval::FiveMinutes.to_string(), "5m");
-        assert_eq!(TimeInterval::FifteenMinutes.to_string(), "15m");
-        assert_eq!(TimeInterval::OneHour.to_string(), "1h");
-        assert_eq!(TimeInterval::FourHours.to_string(), "4h");
-        assert_eq!(TimeInterval::OneDay.to_string(), "1d");
-        assert_eq!(TimeInterval::OneWeek.to_string(), "1w");
-        assert_eq!(TimeInterval::OneMonth.to_string(), "1M");
-    }
-
-    #[test]
-    fn test_time_interval_from_str() {
-        assert_eq!("5m".parse::<TimeInterval>().unwrap(), TimeInterval::FiveMinutes);
-        assert_eq!("15m".parse::<TimeInterval>().unwrap(), TimeInterval::FifteenMinutes);
-        assert_eq!("1h".parse::<TimeInterval>().unwrap(), TimeInterval::OneHour);
-        assert_eq!("4h".parse::<TimeInterval>().unwrap(), TimeInterval::FourHours);
-        assert_eq!("1d".parse::<TimeInterval>().unwrap(), TimeInterval::OneDay);
-        assert_eq!("1w".parse::<TimeInterval>().unwrap(), TimeInterval::OneWeek);
-        assert_eq!("1M".parse::<TimeInterval>().unwrap(), TimeInterval::OneMonth);
-    }
-
-    #[test]
-    fn test_invalid_time_interval() {
-        assert!(matches!(
-            "invalid".parse::<TimeInterval>(),
-            Err(BirdeyeError::InvalidParameters(_))
-        ));
+    fn mock_provider(mock: MockHttpClient) -> BirdeyeProvider {
+        BirdeyeProvider::builder("test_key").transport(mock).build()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    const SOL: &str = "So11111111111111111111111111111111111111112";
+    const WALLET: &str = "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK";
+    const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
 
     #[tokio::test]
     async fn test_search_tokens() -> Result<(), BirdeyeError> {
-        let provider = CachedBirdeyeProvider::new("test_key");
-        let params = TokenSearchParams {
-            keyword: "SOL".to_string(),
-            sort_by: None,
-            sort_type: None,
-            offset: None,
-            limit: Some(10),
-        };
+        let provider = mock_provider(MockHttpClient::new());
+        let params = TokenSearchParams::new("SOL").with_limit(10);
 
-        let response = provider.search_tokens(params).await?;
-        assert!(!response.is_empty());
-        assert!(response.len() == 10);
+        let tokens = provider.search_tokens(params).await?;
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].symbol, "SOL");
+        assert_eq!(tokens[0].volume_24h, Some(1000000.0));
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_search_tokens_iter() -> Result<(), BirdeyeError> {
-        let provider = BirdeyeProvider::new("test_key");
-        let params = TokenSearchParams {
-            keyword: "SOL".to_string(),
-            sort_by: None,
-            sort_type: None,
-            offset: None,
-            limit: Some(10),
-        };
+    async fn test_search_tokens_stream() -> Result<(), BirdeyeError> {
+        let provider = mock_provider(MockHttpClient::new());
+        let params = TokenSearchParams::new("SOL");
 
-        let mut total_tokens = 0;
-        let mut iterator = provider.search_tokens_iter(params);
-        
-        while let Some(result) = iterator.next_page().await {
-            let tokens = result?;
-            total_tokens += tokens.len();
-            if total_tokens >= 20 {
-                break;
-            }
-        }
+        // The mock returns a single token, a short page ends the stream
+        let tokens: Vec<_> = provider
+            .search_tokens_stream(params, StreamOptions::new(10))
+            .try_collect()
+            .await?;
+        assert_eq!(tokens.len(), 1);
+        Ok(())
+    }
 
-        assert!(total_tokens > 0);
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_rate_limit() -> Result<(), BirdeyeError> {
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/defi/tokenlist",
+            MockResponse::data(json!({
+                "tokens": [
+                    { "address": "token1", "symbol": "A", "liquidity": 1000.0 },
+                    { "address": "token2", "symbol": "B", "liquidity": 1000.0 }
+                ]
+            })),
+        )
+        .await;
+        let provider = BirdeyeProvider::builder("test_key")
+            .transport(mock)
+            .rate_limit(1.0, 1.0)
+            .build();
+
+        // Every page is full, the cap ends the stream after 3 of them, 1 per second
+        let start = tokio::time::Instant::now();
+        let options = StreamOptions::new(2).with_prefetch(2).with_max_items(6);
+        let params = TokenListParams::new(TokenListSortBy::Volume, SortType::Descending);
+        let tokens: Vec<_> = provider
+            .token_list_stream(params, options)
+            .try_collect()
+            .await?;
+        assert_eq!(tokens.len(), 6);
+        assert!(start.elapsed() >= Duration::from_secs(2));
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_get_token_overview() -> Result<(), BirdeyeError> {
-        let provider = CachedBirdeyeProvider::new("test_key");
-        let overview = provider.get_token_overview("mock_address").await?;
-        assert!(overview.price > 0.0);
+    async fn test_find_token() -> Result<(), BirdeyeError> {
+        let provider = mock_provider(MockHttpClient::new());
+        let token = provider.find_token("sol").await?.expect("SOL is found");
+        assert_eq!(token.address, SOL);
+        // Tokens merely containing the keyword are not matches
+        assert!(provider.find_token("SO").await?.is_none());
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_analyze_liquidity() -> Result<(), BirdeyeError> {
-        let provider = CachedBirdeyeProvider::new("test_key");
-        let liquidity = provider.analyze_liquidity("mock_address").await?;
-        assert!(liquidity.bid_ask_ratio > 0.0);
+    async fn test_get_token_list() -> Result<(), BirdeyeError> {
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/defi/tokenlist",
+            MockResponse::data(json!({
+                "updateUnixTime": 1729256400,
+                "tokens": [
+                    { "address": "mover1", "symbol": "WIF", "name": "dogwifhat", "price": 2.5, "v24hChangePercent": 42.0, "v24hUSD": 300000000.0, "liquidity": 20000000.0, "mc": 2500000000.0 },
+                    { "address": "mover2", "price": 0.01, "v24hChangePercent": 30.0, "v24hUSD": 5000.0, "liquidity": 100000.0 }
+                ],
+                "total": 2
+            })),
+        ).await;
+        let provider = mock_provider(mock);
+
+        let params = TokenListParams::new(TokenListSortBy::Change, SortType::Descending)
+            .with_limit(100)
+            .with_min_liquidity(50000.0);
+        let tokens = provider.get_token_list(params).await?;
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].symbol, "WIF");
+        assert_eq!(tokens[0].price_change_24h, 42.0);
+        assert_eq!(tokens[0].market_cap, Some(2500000000.0));
+        assert_eq!(tokens[1].symbol, "");
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_get_market_impact() -> Result<(), BirdeyeError> {
-        let provider = CachedBirdeyeProvider::new("test_key");
-        let impact = provider.get_market_impact("mock_address", 1000.0).await?;
-        assert!(impact.price_impact > 0.0);
+    async fn test_get_token_overview() -> Result<(), BirdeyeError> {
+        let provider = mock_provider(MockHttpClient::new());
+        let overview = provider.get_token_overview(SOL).await?;
+        assert_eq!(overview.symbol, "SOL");
+        assert_eq!(overview.price, 100.0);
+        assert_eq!(overview.price_change_24h, 5.0);
+        assert_eq!(overview.holders, Some(1000000));
         Ok(())
     }
 
     #[tokio::test]
     async fn test_get_price_history() -> Result<(), BirdeyeError> {
-        let provider = CachedBirdeyeProvider::new("test_key");
-        let history = provider.get_price_history("mock_address", TimeInterval::OneHour).await?;
-        assert!(!history.is_empty());
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/defi/ohlcv",
+            MockResponse::data(json!({
+                "items": [
+                    { "o": 99.0, "h": 101.0, "l": 98.0, "c": 100.0, "v": 5000.0, "unixTime": 1700000000, "type": "1H" },
+                    { "o": 100.0, "h": 103.0, "l": 99.0, "c": 102.5, "v": 7000.0, "unixTime": 1700003600, "type": "1H" }
+                ]
+            })),
+        ).await;
+        let provider = mock_provider(mock);
+
+        let history = provider
+            .get_price_history(SOL, TimeInterval::OneHour)
+            .await?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].timestamp, 1700003600);
+        assert_eq!(history[1].price, 102.5);
+        assert_eq!(history[1].volume, 7000.0);
+
+        let mut params = PriceHistoryParams::new(SOL, TimeInterval::OneHour);
+        params.time_from = Some(1700003600);
+        params.time_to = Some(1700000000);
+        assert!(matches!(
+            provider.get_price_history_range(params).await,
+            Err(BirdeyeError::InvalidParameters(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_wallet_portfolio() -> Result<(), BirdeyeError> {
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/v1/wallet/token_list",
+            MockResponse::data(json!({
+                "wallet": "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK",
+                "totalUsd": 1500.0,
+                "items": [{
+                    "address": SOL,
+                    "decimals": 9,
+                    "balance": 15000000000u64,
+                    "uiAmount": 15.0,
+                    "chainId": "solana",
+                    "name": "Wrapped SOL",
+                    "symbol": "SOL",
+                    "priceUsd": 100.0,
+                    "valueUsd": 1500.0
+                }]
+            })),
+        )
+        .await;
+        let provider = mock_provider(mock);
+
+        let portfolio = provider
+            .get_wallet_portfolio("DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK")
+            .await?;
+        assert_eq!(portfolio.total_usd, 1500.0);
+        assert_eq!(portfolio.items[0].amount, 15.0);
+        assert_eq!(portfolio.items[0].value_usd, Some(1500.0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_token_security() -> Result<(), BirdeyeError> {
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/defi/token_security",
+            MockResponse::data(json!({
+                "creatorAddress": null,
+                "ownerAddress": null,
+                "totalSupply": 1000000000.0,
+                "mutableMetadata": true,
+                "freezeable": null,
+                "freezeAuthority": null,
+                "transferFeeEnable": null,
+                "isToken2022": false,
+                "top10HolderPercent": 0.32
+            })),
+        )
+        .await;
+        let provider = mock_provider(mock);
+
+        let security = provider.get_token_security(SOL).await?;
+        assert_eq!(security.mutable_metadata, Some(true));
+        assert_eq!(security.is_token2022, Some(false));
+        assert_eq!(security.freezeable, None);
+        assert_eq!(security.top10_holder_percent, Some(0.32));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_liquidity_and_market_impact() -> Result<(), BirdeyeError> {
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/defi/v2/markets",
+            MockResponse::data(json!({
+                "items": [
+                    { "address": "pool1", "name": "SOL-USDC", "source": "Raydium", "liquidity": 300000.0, "price": 100.0, "volume24h": 900000.0 },
+                    { "address": "pool2", "name": "SOL-USDT", "source": "Orca", "liquidity": 100000.0, "price": 100.0, "volume24h": 100000.0 }
+                ],
+                "total": 2
+            })),
+        ).await;
+        let provider = mock_provider(mock);
+
+        let liquidity = provider.analyze_liquidity(SOL).await?;
+        assert_eq!(liquidity.total_liquidity, 400000.0);
+        assert_eq!(liquidity.volume_24h, 1000000.0);
+        assert_eq!(liquidity.markets, 2);
+        assert_eq!(liquidity.top_market_share, 0.75);
+        assert!(liquidity.depth_quality > 0.4 && liquidity.depth_quality < 0.6);
+
+        // Buying $2,000 (20 SOL) out of 2,000 SOL against $200,000
+        let impact = provider.get_market_impact(SOL, Side::Buy, 2000.0).await?;
+        assert_eq!(impact.size_tokens, 20.0);
+        assert_eq!(impact.source, ImpactSource::Amm);
+        assert!((impact.executed_price - 200000.0 / 1980.0).abs() < 1e-9);
+        assert!((impact.slippage - 100.0 / 99.0).abs() < 1e-9);
+        assert!((impact.price_impact - ((2000.0f64 / 1980.0).powi(2) - 1.0) * 100.0).abs() < 1e-9);
+
+        // The pools cannot sell more tokens than they hold
+        assert!(matches!(
+            provider.get_market_impact(SOL, Side::Buy, 200000.0).await,
+            Err(BirdeyeError::InvalidParameters(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let mock = MockHttpClient::new();
+        mock.set_response(
+            "/defi/token_overview",
+            MockResponse {
+                status: 401,
+                body: json!({"success": false, "message": "Unauthorized"}),
+                delay: Duration::ZERO,
+            },
+        )
+        .await;
+        mock.set_response(
+            "/defi/token_security",
+            MockResponse {
+                status: 200,
+                body: json!({"success": false, "message": "Token not found"}),
+                delay: Duration::ZERO,
+            },
+        )
+        .await;
+        mock.set_response(
+            "/v1/wallet/token_list",
+            MockResponse {
+                status: 400,
+                body: json!({"success": false, "message": "Invalid wallet"}),
+                delay: Duration::ZERO,
+            },
+        )
+        .await;
+        let provider = mock_provider(mock);
+
+        assert!(matches!(
+            provider.get_token_overview(SOL).await,
+            Err(BirdeyeError::InvalidApiKey)
+        ));
+        match provider.get_token_security(SOL).await {
+            Err(BirdeyeError::ApiError {
+                status_code: 200,
+                message,
+            }) => assert_eq!(message, "Token not found"),
+            other => panic!("Expected an API error, got {:?}", other),
+        }
+        match provider.get_wallet_portfolio(WALLET).await {
+            Err(BirdeyeError::ApiError {
+                status_code: 400,
+                message,
+            }) => assert_eq!(message, "Invalid wallet"),
+            other => panic!("Expected an API error, got {:?}", other),
+        }
+        // Malformed addresses are not sent
+        assert!(matches!(
+            provider.get_wallet_portfolio("nope").await,
+            Err(BirdeyeError::InvalidParameters(_))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries() {
+        let mock = MockHttpClient::new();
+        mock.simulate_rate_limit("/defi/token_overview").await;
+        mock.simulate_network_error("/defi/v3/search").await;
+        let provider = mock_provider(mock);
+
+        assert!(matches!(
+            provider.get_token_overview(SOL).await,
+            Err(BirdeyeError::RateLimitExceeded)
+        ));
+        assert!(matches!(
+            provider.search_tokens(TokenSearchParams::new("SOL")).await,
+            Err(BirdeyeError::ApiError {
+                status_code: 500,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chains() -> Result<(), BirdeyeError> {
+        let provider = mock_provider(MockHttpClient::new());
+        assert_eq!(provider.chain(), Chain::Solana);
+
+        let base = provider.with_chain(Chain::Base);
+        assert_eq!(base.chain(), Chain::Base);
+        assert_eq!(base.get_token_overview(WETH).await?.symbol, "SOL");
+        assert!(matches!(
+            base.get_token_overview(SOL).await,
+            Err(BirdeyeError::InvalidParameters(_))
+        ));
+        assert!(provider.get_token_security(WETH).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_provider() -> Result<(), BirdeyeError> {
+        let mock = Arc::new(MockHttpClient::new());
+        let provider = BirdeyeProvider::builder("test_key")
+            .transport(mock.clone())
+            .max_retries(0)
+            .cache(ResponseCache::default())
+            .build();
+        let first = provider.get_token_overview(SOL).await?;
+
+        // The second overview comes from the cache even though the API now fails
+        mock.simulate_network_error("/defi/token_overview").await;
+        let second = provider.get_token_overview(SOL).await?;
+        assert_eq!(first.price, second.price);
+
+        // The same address on another chain is another token
+        mock.set_response(
+            "/defi/token_overview",
+            MockResponse::data(json!({ "address": WETH, "decimals": 18, "price": 2500.0 })),
+        )
+        .await;
+        let ethereum = provider.with_chain(Chain::Ethereum);
+        assert_eq!(ethereum.get_token_overview(WETH).await?.price, 2500.0);
+        mock.simulate_network_error("/defi/token_overview").await;
+        assert_eq!(ethereum.get_token_overview(WETH).await?.price, 2500.0);
+        assert!(provider
+            .with_chain(Chain::Base)
+            .get_token_overview(WETH)
+            .await
+            .is_err());
+
+        // Every endpoint is cached, whatever its response type
+        provider
+            .search_tokens(TokenSearchParams::new("SOL"))
+            .await?;
+        mock.simulate_network_error("/defi/v3/search").await;
+        assert_eq!(
+            provider
+                .search_tokens(TokenSearchParams::new("SOL"))
+                .await?[0]
+                .symbol,
+            "SOL"
+        );
+        let stats = provider.response_cache().unwrap().stats();
+        assert_eq!((stats.hits, stats.misses), (3, 4));
         Ok(())
     }
 }
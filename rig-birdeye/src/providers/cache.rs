@@ -1,102 +1,695 @@
+use crate::types::{error::BirdeyeError, shared::Chain};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use serde_json::Value;
 use std::{
-    collections::HashMap,
-    sync::Arc,
-    time::{Duration, Instant},
-};
-use tokio::sync::RwLock;
-use crate::types::{
-    api::TokenOverview,
-    error::BirdeyeError,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
+use tokio::time::Instant;
 
-pub struct TokenCache {
-    cache: HashMap<String, (TokenOverview, Instant)>,
-    ttl: Duration,
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_STALE_WHILE_REVALIDATE: Duration = Duration::from_secs(300);
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+// Endpoints changing more or less often than the default TTL
+const ENDPOINT_TTLS: &[(&str, Duration)] = &[
+    ("/defi/token_security", Duration::from_secs(3600)),
+    ("/defi/v3/search", Duration::from_secs(300)),
+    ("/defi/v2/markets", Duration::from_secs(300)),
+    ("/v1/wallet/token_list", Duration::from_secs(30)),
+    ("/defi/txs/token/seek_by_time", Duration::ZERO),
+];
+
+/// TTLs and size of a [ResponseCache]
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// TTL of the endpoints without one of their own
+    pub default_ttl: Duration,
+    /// TTLs by endpoint, e.g. `/defi/token_security`. A zero TTL leaves the endpoint uncached.
+    pub ttls: HashMap<String, Duration>,
+    /// How long after expiring a response is still served while it is fetched again
+    pub stale_while_revalidate: Duration,
+    /// Responses kept in memory, the least recently used being evicted first
+    pub max_entries: usize,
 }
 
-impl TokenCache {
-    pub fn new(ttl: Duration) -> Self {
+impl Default for CacheConfig {
+    fn default() -> Self {
         Self {
-            cache: HashMap::new(),
-            ttl,
+            default_ttl: DEFAULT_TTL,
+            ttls: ENDPOINT_TTLS
+                .iter()
+                .map(|(endpoint, ttl)| (endpoint.to_string(), *ttl))
+                .collect(),
+            stale_while_revalidate: DEFAULT_STALE_WHILE_REVALIDATE,
+            max_entries: DEFAULT_MAX_ENTRIES,
         }
     }
+}
 
-    pub fn get(&self, key: &str) -> Option<&TokenOverview> {
-        self.cache.get(key).and_then(|(value, timestamp)| {
-            if timestamp.elapsed() < self.ttl {
-                Some(value)
-            } else {
-                None
-            }
-        })
+impl CacheConfig {
+    pub fn with_ttl(mut self, endpoint: &str, ttl: Duration) -> Self {
+        self.ttls.insert(endpoint.to_string(), ttl);
+        self
+    }
+
+    pub fn with_default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    pub fn with_stale_while_revalidate(mut self, duration: Duration) -> Self {
+        self.stale_while_revalidate = duration;
+        self
     }
 
-    pub fn insert(&mut self, key: String, value: TokenOverview) {
-        self.cache.insert(key, (value, Instant::now()));
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
     }
 
-    pub fn clear_expired(&mut self) {
-        self.cache.retain(|_, (_, timestamp)| timestamp.elapsed() < self.ttl);
+    pub fn ttl(&self, endpoint: &str) -> Duration {
+        self.ttls.get(endpoint).copied().unwrap_or(self.default_ttl)
     }
 }
 
-pub struct CachedClient<T> {
-    inner: T,
+/// Response of the cache as persisted by a [CacheStore]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub value: Value,
+    /// Unix timestamp of the request, in seconds
+    pub fetched_at: i64,
+}
+
+/// Storage outliving the process, read when a response is not in memory and written with
+/// every new response. Called from blocking threads.
+pub trait CacheStore: Send + Sync {
+    fn load(&self, key: &str) -> Result<Option<StoredResponse>, BirdeyeError>;
+    fn save(&self, key: &str, response: &StoredResponse) -> Result<(), BirdeyeError>;
+    fn clear(&self) -> Result<(), BirdeyeError>;
+}
+
+/// Counters of a [ResponseCache]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Expired responses served while fetching them again
+    pub stale_hits: u64,
+    pub misses: u64,
+    /// Requests answered by an identical request in flight
+    pub coalesced: u64,
+    pub evictions: u64,
+}
+
+type SharedFetch = Shared<BoxFuture<'static, Result<Arc<Value>, Arc<BirdeyeError>>>>;
+
+struct Entry {
+    value: Arc<Value>,
+    fetched_at: Instant,
     ttl: Duration,
+    /// Position in the LRU order
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<String, Entry>,
+    /// Keys by last use, the least recent first
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+enum Lookup {
+    Fresh(Arc<Value>),
+    Stale(Arc<Value>),
+    Missing,
+}
+
+impl Entries {
+    fn lookup(&mut self, key: &str, stale_while_revalidate: Duration) -> Lookup {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return Lookup::Missing;
+        };
+        let age = entry.fetched_at.elapsed();
+        if age >= entry.ttl + stale_while_revalidate {
+            self.remove(key);
+            return Lookup::Missing;
+        }
+
+        self.tick += 1;
+        self.lru.remove(&entry.tick);
+        self.lru.insert(self.tick, key.to_string());
+        entry.tick = self.tick;
+        if age < entry.ttl {
+            Lookup::Fresh(entry.value.clone())
+        } else {
+            Lookup::Stale(entry.value.clone())
+        }
+    }
+
+    /// Inserts the entry and returns the number of entries evicted to make room for it
+    fn insert(
+        &mut self,
+        key: String,
+        value: Arc<Value>,
+        fetched_at: Instant,
+        ttl: Duration,
+        max_entries: usize,
+    ) -> u64 {
+        self.remove(&key);
+        self.tick += 1;
+        self.lru.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                fetched_at,
+                ttl,
+                tick: self.tick,
+            },
+        );
+
+        let mut evicted = 0;
+        while self.entries.len() > max_entries {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+            evicted += 1;
+        }
+        evicted
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.tick);
+        }
+    }
+}
+
+struct Inner {
+    config: CacheConfig,
+    entries: Mutex<Entries>,
+    in_flight: Mutex<HashMap<String, SharedFetch>>,
+    store: Option<Arc<dyn CacheStore>>,
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// Cache of the `data` of the Birdeye responses, by chain, endpoint and query, shared between
+/// its clones. Identical requests made while one is in flight wait for its response, and
+/// expired responses are served for a while longer as they are fetched again in the
+/// background.
+#[derive(Clone)]
+pub struct ResponseCache {
+    inner: Arc<Inner>,
 }
 
-impl<T> CachedClient<T> {
-    pub fn new(inner: T, ttl: Duration) -> Self {
-        Self { inner, ttl }
+impl ResponseCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                entries: Mutex::new(Entries::default()),
+                in_flight: Mutex::new(HashMap::new()),
+                store: None,
+                hits: AtomicU64::new(0),
+                stale_hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                coalesced: AtomicU64::new(0),
+                evictions: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Persists the responses in the store, e.g. the `SqliteCacheStore` of the `sqlite` feature
+    pub fn with_store(config: CacheConfig, store: impl CacheStore + 'static) -> Self {
+        let mut cache = Self::new(config);
+        Arc::get_mut(&mut cache.inner)
+            .expect("The cache is not shared yet")
+            .store = Some(Arc::new(store));
+        cache
     }
 
-    pub async fn get_token_overview<F, Fut>(
+    pub fn config(&self) -> &CacheConfig {
+        &self.inner.config
+    }
+
+    /// Key of the response of the endpoint on the chain, whatever the order of the query
+    pub fn key(chain: Chain, endpoint: &str, query: &[(&str, String)]) -> String {
+        let mut query: Vec<_> = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        query.sort();
+        format!("{}:{}?{}", chain, endpoint, query.join("&"))
+    }
+
+    /// The cached response of the endpoint at `key`, or else the one of `fetch`
+    pub async fn get_or_fetch<F>(
         &self,
-        key: &str,
+        endpoint: &str,
+        key: String,
         fetch: F,
-    ) -> Result<crate::types::api::TokenOverview, crate::types::error::BirdeyeError>
+    ) -> Result<Arc<Value>, BirdeyeError>
     where
-        F: FnOnce(&T, &str) -> Fut,
-        Fut: std::future::Future<Output = Result<crate::types::api::TokenOverview, crate::types::error::BirdeyeError>>,
+        F: Future<Output = Result<Value, BirdeyeError>> + Send + 'static,
     {
-        let cache_key = format!("token_overview:{}", key);
-        
-        // Try to get from cache first
-        if let Some(cached) = self.get_cached(&cache_key).await {
-            return serde_json::from_str(&cached)
-                .map_err(crate::types::error::BirdeyeError::SerializationError);
-        }
-
-        // If not in cache, fetch fresh data
-        let result = fetch(&self.inner, key).await?;
-        
-        // Cache the result
-        let serialized = serde_json::to_string(&result)
-            .map_err(crate::types::error::BirdeyeError::SerializationError)?;
-        self.set_cached(&cache_key, serialized).await;
-
-        Ok(result)
-    }
-
-    async fn get_cached(&self, key: &str) -> Option<String> {
-        let cache = self.cache.read().await;
-        if let Some(entry) = cache.get(key) {
-            if entry.expires_at > Instant::now() {
-                return Some(entry.value.clone());
+        let ttl = self.inner.config.ttl(endpoint);
+        if ttl.is_zero() {
+            return fetch.await.map(Arc::new);
+        }
+
+        match self.lookup(&key).await {
+            Lookup::Fresh(value) => {
+                self.inner.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(value)
+            }
+            Lookup::Stale(value) => {
+                self.inner.stale_hits.fetch_add(1, Ordering::Relaxed);
+                let revalidate = self.fetch(key, ttl, fetch);
+                tokio::spawn(async move {
+                    if let Err(err) = revalidate.await {
+                        tracing::debug!("Failed to revalidate a cached Birdeye response: {}", err);
+                    }
+                });
+                Ok(value)
+            }
+            Lookup::Missing => {
+                self.inner.misses.fetch_add(1, Ordering::Relaxed);
+                self.fetch(key, ttl, fetch)
+                    .await
+                    .map_err(|err| Arc::try_unwrap(err).unwrap_or_else(|err| err.shared_copy()))
             }
         }
-        None
     }
 
-    async fn set_cached(&self, key: &str, value: String) {
-        let mut cache = self.cache.write().await;
-        cache.insert(
-            key.to_string(),
-            CacheEntry {
-                value,
-                expires_at: Instant::now() + self.ttl,
+    /// Looks the key up in memory, then in the store
+    async fn lookup(&self, key: &str) -> Lookup {
+        let stale_while_revalidate = self.inner.config.stale_while_revalidate;
+        let lookup = self
+            .inner
+            .entries
+            .lock()
+            .unwrap()
+            .lookup(key, stale_while_revalidate);
+        if !matches!(lookup, Lookup::Missing) {
+            return lookup;
+        }
+        let Some(store) = self.inner.store.clone() else {
+            return lookup;
+        };
+
+        let stored_key = key.to_string();
+        let stored = match tokio::task::spawn_blocking(move || store.load(&stored_key)).await {
+            Ok(Ok(Some(stored))) => stored,
+            Ok(Ok(None)) => return Lookup::Missing,
+            Ok(Err(err)) => {
+                tracing::warn!("Failed to load a cached Birdeye response: {}", err);
+                return Lookup::Missing;
+            }
+            Err(err) => {
+                tracing::warn!("Failed to load a cached Birdeye response: {}", err);
+                return Lookup::Missing;
+            }
+        };
+
+        // Responses older than the process are dated back from now
+        let age = Duration::from_secs((unix_now() - stored.fetched_at).max(0) as u64);
+        let Some(fetched_at) = Instant::now().checked_sub(age) else {
+            return Lookup::Missing;
+        };
+        let endpoint = endpoint_of(key);
+        let ttl = self.inner.config.ttl(endpoint);
+        self.insert(key.to_string(), Arc::new(stored.value), fetched_at, ttl);
+        self.inner
+            .entries
+            .lock()
+            .unwrap()
+            .lookup(key, stale_while_revalidate)
+    }
+
+    /// Fetches the response, or joins the identical fetch in flight
+    fn fetch<F>(&self, key: String, ttl: Duration, fetch: F) -> SharedFetch
+    where
+        F: Future<Output = Result<Value, BirdeyeError>> + Send + 'static,
+    {
+        let mut in_flight = self.inner.in_flight.lock().unwrap();
+        if let Some(shared) = in_flight.get(&key) {
+            self.inner.coalesced.fetch_add(1, Ordering::Relaxed);
+            return shared.clone();
+        }
+
+        let cache = self.clone();
+        let fetch_key = key.clone();
+        let shared = async move {
+            let result = fetch.await;
+            cache.inner.in_flight.lock().unwrap().remove(&fetch_key);
+            let value = Arc::new(result.map_err(Arc::new)?);
+            cache.insert(fetch_key.clone(), value.clone(), Instant::now(), ttl);
+            cache.persist(fetch_key, &value).await;
+            Ok(value)
+        }
+        .boxed()
+        .shared();
+        in_flight.insert(key, shared.clone());
+        shared
+    }
+
+    fn insert(&self, key: String, value: Arc<Value>, fetched_at: Instant, ttl: Duration) {
+        let max_entries = self.inner.config.max_entries;
+        let evicted =
+            self.inner
+                .entries
+                .lock()
+                .unwrap()
+                .insert(key, value, fetched_at, ttl, max_entries);
+        self.inner.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    async fn persist(&self, key: String, value: &Value) {
+        let Some(store) = self.inner.store.clone() else {
+            return;
+        };
+        let response = StoredResponse {
+            value: value.clone(),
+            fetched_at: unix_now(),
+        };
+        match tokio::task::spawn_blocking(move || store.save(&key, &response)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => tracing::warn!("Failed to persist a Birdeye response: {}", err),
+            Err(err) => tracing::warn!("Failed to persist a Birdeye response: {}", err),
+        }
+    }
+
+    /// Responses in memory
+    pub fn len(&self) -> usize {
+        self.inner.entries.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets all the responses, in memory and in the store
+    pub async fn clear(&self) -> Result<(), BirdeyeError> {
+        *self.inner.entries.lock().unwrap() = Entries::default();
+        if let Some(store) = self.inner.store.clone() {
+            tokio::task::spawn_blocking(move || store.clear())
+                .await
+                .map_err(|err| BirdeyeError::CacheError(err.to_string()))??;
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            stale_hits: self.inner.stale_hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            coalesced: self.inner.coalesced.load(Ordering::Relaxed),
+            evictions: self.inner.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(CacheConfig::default())
+    }
+}
+
+/// Endpoint of a key made by [ResponseCache::key]
+fn endpoint_of(key: &str) -> &str {
+    let path = key.split_once(':').map_or(key, |(_, path)| path);
+    path.split_once('?').map_or(path, |(endpoint, _)| endpoint)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::AtomicU32;
+
+    const OVERVIEW: &str = "/defi/token_overview";
+
+    fn key(address: &str) -> String {
+        ResponseCache::key(Chain::Solana, OVERVIEW, &[("address", address.to_string())])
+    }
+
+    /// Fetch answering the number of fetches so far, after a delay
+    fn counting_fetch(
+        count: &Arc<AtomicU32>,
+    ) -> impl Future<Output = Result<Value, BirdeyeError>> + Send + 'static {
+        let count = count.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(json!(count.fetch_add(1, Ordering::SeqCst) + 1))
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let query = [("address", "SOL".to_string()), ("type", "1H".to_string())];
+        let reversed = [query[1].clone(), query[0].clone()];
+        assert_eq!(
+            ResponseCache::key(Chain::Solana, "/defi/ohlcv", &query),
+            ResponseCache::key(Chain::Solana, "/defi/ohlcv", &reversed)
+        );
+        let key = ResponseCache::key(Chain::Base, "/defi/ohlcv", &query);
+        assert_eq!(key, "base:/defi/ohlcv?address=SOL&type=1H");
+        assert_eq!(endpoint_of(&key), "/defi/ohlcv");
+        assert_eq!(
+            CacheConfig::default().ttl("/defi/token_security"),
+            Duration::from_secs(3600)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ttl_and_stale_while_revalidate() -> Result<(), BirdeyeError> {
+        let cache = ResponseCache::new(
+            CacheConfig::default()
+                .with_ttl(OVERVIEW, Duration::from_secs(10))
+                .with_stale_while_revalidate(Duration::from_secs(20)),
+        );
+        let count = Arc::new(AtomicU32::new(0));
+
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+                .await?,
+            json!(1)
+        );
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+                .await?,
+            json!(1)
+        );
+
+        // Expired, the stale response is served while the next one is fetched
+        tokio::time::advance(Duration::from_secs(15)).await;
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+                .await?,
+            json!(1)
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+                .await?,
+            json!(2)
+        );
+
+        // Too old to be served at all
+        tokio::time::advance(Duration::from_secs(31)).await;
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+                .await?,
+            json!(3)
+        );
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                stale_hits: 1,
+                misses: 2,
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_uncached_endpoint() -> Result<(), BirdeyeError> {
+        let cache = ResponseCache::new(CacheConfig::default().with_ttl(OVERVIEW, Duration::ZERO));
+        let count = Arc::new(AtomicU32::new(0));
+        cache
+            .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+            .await?;
+        cache
+            .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+            .await?;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert!(cache.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_coalescing() -> Result<(), BirdeyeError> {
+        let cache = ResponseCache::default();
+        let count = Arc::new(AtomicU32::new(0));
+
+        let (first, second, other) = tokio::join!(
+            cache.get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count)),
+            cache.get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count)),
+            cache.get_or_fetch(OVERVIEW, key("b"), counting_fetch(&count)),
+        );
+        assert_eq!(first?, second?);
+        other?;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.stats().coalesced, 1);
+
+        // Both callers get the error, and it is not cached
+        let failing = || async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Err(BirdeyeError::ApiError {
+                status_code: 500,
+                message: "Down".to_string(),
+            })
+        };
+        let (first, second) = tokio::join!(
+            cache.get_or_fetch(OVERVIEW, key("c"), failing()),
+            cache.get_or_fetch(OVERVIEW, key("c"), failing()),
+        );
+        for result in [first, second] {
+            assert!(matches!(
+                result,
+                Err(BirdeyeError::ApiError {
+                    status_code: 500,
+                    ..
+                })
+            ));
+        }
+        assert_eq!(cache.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lru_eviction() -> Result<(), BirdeyeError> {
+        let cache = ResponseCache::new(CacheConfig::default().with_max_entries(2));
+        let count = Arc::new(AtomicU32::new(0));
+
+        cache
+            .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+            .await?;
+        cache
+            .get_or_fetch(OVERVIEW, key("b"), counting_fetch(&count))
+            .await?;
+        // a is used again, so b is the least recently used when c comes in
+        cache
+            .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+            .await?;
+        cache
+            .get_or_fetch(OVERVIEW, key("c"), counting_fetch(&count))
+            .await?;
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats().evictions, 1);
+
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+                .await?,
+            json!(1)
+        );
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("b"), counting_fetch(&count))
+                .await?,
+            json!(4)
+        );
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct MemoryStore {
+        responses: Mutex<HashMap<String, StoredResponse>>,
+    }
+
+    impl CacheStore for Arc<MemoryStore> {
+        fn load(&self, key: &str) -> Result<Option<StoredResponse>, BirdeyeError> {
+            Ok(self.responses.lock().unwrap().get(key).cloned())
+        }
+
+        fn save(&self, key: &str, response: &StoredResponse) -> Result<(), BirdeyeError> {
+            self.responses
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), response.clone());
+            Ok(())
+        }
+
+        fn clear(&self) -> Result<(), BirdeyeError> {
+            self.responses.lock().unwrap().clear();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store() -> Result<(), BirdeyeError> {
+        let store = Arc::new(MemoryStore::default());
+        let count = Arc::new(AtomicU32::new(0));
+        let cache = ResponseCache::with_store(CacheConfig::default(), store.clone());
+        cache
+            .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+            .await?;
+        assert_eq!(store.load(&key("a"))?.unwrap().value, json!(1));
+
+        // Another process finds the response in the store, unless it is too old
+        let cache = ResponseCache::with_store(CacheConfig::default(), store.clone());
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("a"), counting_fetch(&count))
+                .await?,
+            json!(1)
+        );
+        assert_eq!(cache.stats().hits, 1);
+
+        store.save(
+            &key("b"),
+            &StoredResponse {
+                value: json!("old"),
+                fetched_at: unix_now() - 3600,
             },
+        )?;
+        assert_eq!(
+            *cache
+                .get_or_fetch(OVERVIEW, key("b"), counting_fetch(&count))
+                .await?,
+            json!(2)
         );
+
+        cache.clear().await?;
+        assert!(cache.is_empty());
+        assert_eq!(store.load(&key("a"))?, None);
+        Ok(())
     }
 }
@@ -1,10 +1,9 @@
+use super::transport::{HttpRequest, HttpResponse, Transport};
+use crate::types::error::BirdeyeError;
+use async_trait::async_trait;
+use serde_json::json;
 use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::sync::RwLock;
-use serde_json::json;
-use crate::types::{
-    api::{TokenInfo, TokenOverview, WalletPortfolio, LiquidityAnalysis, MarketImpact, PricePoint},
-    error::BirdeyeError,
-};
 
 /// Mock response data for testing
 #[derive(Clone)]
@@ -14,7 +13,19 @@ pub struct MockResponse {
     pub delay: Duration,
 }
 
-/// Mock HTTP client for testing
+impl MockResponse {
+    /// Successful response wrapping `data` the way the Birdeye API does
+    pub fn data(data: serde_json::Value) -> Self {
+        Self {
+            status: 200,
+            body: json!({ "success": true, "data": data }),
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+/// Mock HTTP client for testing, answering each endpoint with a canned response whatever the
+/// query
 pub struct MockHttpClient {
     responses: Arc<RwLock<HashMap<String, MockResponse>>>,
 }
@@ -22,50 +33,45 @@ pub struct MockHttpClient {
 impl MockHttpClient {
     pub fn new() -> Self {
         let mut responses = HashMap::new();
-        
+
         // Add default mock responses
         responses.insert(
-            "/token/search".to_string(),
+            "/defi/v3/search".to_string(),
             MockResponse {
-                status: 200,
-                body: json!({
-                    "data": [
-                        {
+                delay: Duration::from_millis(100),
+                ..MockResponse::data(json!({
+                    "items": [{
+                        "type": "token",
+                        "result": [{
                             "address": "So11111111111111111111111111111111111111112",
                             "symbol": "SOL",
                             "name": "Wrapped SOL",
                             "decimals": 9,
-                            "price_usd": 100.0,
-                            "volume_24h": 1000000.0,
-                            "market_cap": 10000000000.0
-                        }
-                    ],
-                    "pagination": {
-                        "offset": 0,
-                        "limit": 10,
-                        "total": 1
-                    }
-                }),
-                delay: Duration::from_millis(100),
+                            "price": 100.0,
+                            "volume_24h_usd": 1000000.0,
+                            "market_cap": 10000000000.0,
+                            "liquidity": 500000.0
+                        }]
+                    }]
+                }))
             },
         );
 
         responses.insert(
-            "/token/overview".to_string(),
+            "/defi/token_overview".to_string(),
             MockResponse {
-                status: 200,
-                body: json!({
+                delay: Duration::from_millis(50),
+                ..MockResponse::data(json!({
                     "address": "So11111111111111111111111111111111111111112",
                     "decimals": 9,
                     "symbol": "SOL",
                     "name": "Wrapped SOL",
                     "price": 100.0,
-                    "volume_24h": 1000000.0,
-                    "price_change_24h": 5.0,
+                    "v24hUSD": 1000000.0,
+                    "priceChange24hPercent": 5.0,
                     "liquidity": 500000.0,
-                    "holders": 1000000
-                }),
-                delay: Duration::from_millis(50),
+                    "holder": 1000000
+                }))
             },
         );
 
@@ -86,10 +92,11 @@ impl MockHttpClient {
             endpoint,
             MockResponse {
                 status: 429,
-                body: json!({"error": "Rate limit exceeded"}),
+                body: json!({"success": false, "message": "Rate limit exceeded"}),
                 delay: Duration::from_millis(50),
             },
-        ).await;
+        )
+        .await;
     }
 
     /// Simulate a network error
@@ -98,83 +105,38 @@ impl MockHttpClient {
             endpoint,
             MockResponse {
                 status: 500,
-                body: json!({"error": "Internal server error"}),
+                body: json!({"success": false, "message": "Internal server error"}),
                 delay: Duration::from_millis(50),
             },
-        ).await;
+        )
+        .await;
     }
 
-    /// Get mock response for an endpoint
+    /// Get mock response for an endpoint, after its delay
     pub async fn get(&self, endpoint: &str) -> Option<MockResponse> {
-        let responses = self.responses.read().await;
-        responses.get(endpoint).cloned()
-    }
-}
-
-/// Create test data for various response types
-pub fn create_test_token_info() -> TokenInfo {
-    TokenInfo {
-        address: "So11111111111111111111111111111111111111112".to_string(),
-        symbol: "SOL".to_string(),
-        name: "Wrapped SOL".to_string(),
-        decimals: 9,
-        price_usd: 100.0,
-        volume_24h: 1000000.0,
-        market_cap: 10000000000.0,
+        let response = self.responses.read().await.get(endpoint).cloned()?;
+        tokio::time::sleep(response.delay).await;
+        Some(response)
     }
 }
 
-pub fn create_test_token_overview() -> TokenOverview {
-    TokenOverview {
-        price: 100.0,
-        volume_24h: 1000000.0,
-        price_change_24h: 5.0,
-        liquidity: 500000.0,
-        holders: 1000000,
+#[async_trait]
+impl Transport for MockHttpClient {
+    async fn get(&self, request: HttpRequest<'_>) -> Result<HttpResponse, BirdeyeError> {
+        let response = MockHttpClient::get(self, request.endpoint)
+            .await
+            .ok_or_else(|| BirdeyeError::ApiError {
+                status_code: 404,
+                message: format!("No mock response for {}", request.endpoint),
+            })?;
+
+        Ok(HttpResponse {
+            status: response.status,
+            body: response.body.to_string(),
+        })
     }
 }
 
-pub fn create_test_wallet_portfolio() -> WalletPortfolio {
-    WalletPortfolio {
-        wallet: "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK".to_string(),
-        total_usd: 1000000.0,
-        items: vec![],
-    }
-}
-
-pub fn create_test_liquidity_analysis() -> LiquidityAnalysis {
-    LiquidityAnalysis {
-        total_bid_liquidity: 250000.0,
-        total_ask_liquidity: 250000.0,
-        bid_ask_ratio: 1.0,
-        depth_quality: 0.8,
-    }
-}
-
-pub fn create_test_market_impact() -> MarketImpact {
-    MarketImpact {
-        price_impact: 0.01,
-        executed_price: 101.0,
-        size_usd: 10000.0,
-        size_tokens: 100.0,
-    }
-}
-
-pub fn create_test_price_history() -> Vec<PricePoint> {
-    vec![
-        PricePoint {
-            timestamp: chrono::Utc::now().timestamp(),
-            price: 100.0,
-            volume: 100000.0,
-        },
-        PricePoint {
-            timestamp: chrono::Utc::now().timestamp() - 3600,
-            price: 99.0,
-            volume: 95000.0,
-        },
-    ]
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,15 +145,15 @@ mod tests {
     #[tokio::test]
     async fn test_mock_client_basic() {
         let client = MockHttpClient::new();
-        let response = client.get("/token/search").await.unwrap();
+        let response = client.get("/defi/v3/search").await.unwrap();
         assert_eq!(response.status, 200);
     }
 
     #[tokio::test]
     async fn test_mock_client_rate_limit() {
         let client = MockHttpClient::new();
-        client.simulate_rate_limit("/token/search").await;
-        let response = client.get("/token/search").await.unwrap();
+        client.simulate_rate_limit("/defi/v3/search").await;
+        let response = client.get("/defi/v3/search").await.unwrap();
         assert_eq!(response.status, 429);
     }
 
@@ -199,22 +161,24 @@ mod tests {
     async fn test_mock_client_delay() {
         let client = MockHttpClient::new();
         let start = Instant::now();
-        let response = client.get("/token/search").await.unwrap();
+        let response = client.get("/defi/v3/search").await.unwrap();
         assert!(start.elapsed() >= response.delay);
     }
 
     #[tokio::test]
     async fn test_mock_client_custom_response() {
         let client = MockHttpClient::new();
-        client.set_response(
-            "/custom",
-            MockResponse {
-                status: 200,
-                body: json!({"test": true}),
-                delay: Duration::from_millis(0),
-            },
-        ).await;
-        
+        client
+            .set_response(
+                "/custom",
+                MockResponse {
+                    status: 200,
+                    body: json!({"test": true}),
+                    delay: Duration::from_millis(0),
+                },
+            )
+            .await;
+
         let response = client.get("/custom").await.unwrap();
         assert_eq!(response.status, 200);
         assert_eq!(response.body, json!({"test": true}));
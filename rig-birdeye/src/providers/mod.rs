@@ -1,11 +1,22 @@
 pub mod birdeye;
-pub mod websocket;
-pub mod rate_limiter;
-pub mod pagination;
 pub mod cache;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod pagination;
+pub mod rate_limiter;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_cache;
+pub mod transport;
+pub mod websocket;
 
-pub use birdeye::{BirdeyeProvider, CachedBirdeyeProvider, TokenInfo, TokenOverview, LiquidityAnalysis, MarketImpact, PricePoint, TimeInterval};
-pub use websocket::{WebSocketProvider, MarketUpdate, TradeUpdate, TradeSide};
-pub use pagination::{PaginationParams, PaginatedResponse, PaginatedIterator};
+pub use birdeye::{BirdeyeProvider, BirdeyeProviderBuilder};
+pub use cache::{CacheConfig, CacheStats, CacheStore, ResponseCache, StoredResponse};
+pub use pagination::{paginate, Page, PageCursor, PaginationParams, StreamOptions};
 pub use rate_limiter::RateLimiter;
-pub use cache::CachedClient;
+#[cfg(feature = "sqlite")]
+pub use sqlite_cache::SqliteCacheStore;
+pub use transport::{HttpRequest, HttpResponse, HttpTransport, Transport};
+pub use websocket::{
+    CandleUpdate, MarketUpdate, NewPairUpdate, StreamEvent, Subscription, TradeSide, TradeUpdate,
+    UpdateReceiver, WebSocketConfig, WebSocketProvider,
+};
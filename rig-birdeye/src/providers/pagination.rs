@@ -1,6 +1,10 @@
-use std::future::Future;
-use std::pin::Pin;
 use crate::types::error::BirdeyeError;
+use futures_util::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use std::future::Future;
+use tokio::sync::mpsc;
 
 #[derive(Debug, Clone)]
 pub struct PaginationParams {
@@ -23,60 +27,281 @@ impl Default for PaginationParams {
     }
 }
 
-pub trait PaginatedRequest<T>: Send + Sync {
-    type Future: Future<Output = Result<Vec<T>, BirdeyeError>> + Send;
-    fn request(&self, offset: u32, limit: u32) -> Self::Future;
+/// Position of a page: an offset, or a cursor given by the previous page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageCursor {
+    Offset(u32),
+    Cursor(String),
 }
 
-pub struct PaginatedIterator<T, R>
-where
-    R: PaginatedRequest<T>,
-{
-    request: R,
-    current_offset: u32,
-    page_size: u32,
-    has_more: bool,
+/// Items of a page and the position of the next one, if any
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<PageCursor>,
 }
 
-impl<T, R> PaginatedIterator<T, R>
-where
-    R: PaginatedRequest<T>,
-{
-    pub fn new(request: R, page_size: u32) -> Self {
+impl<T> Page<T> {
+    /// Page of an endpoint paginated by offset, the last one when it is short
+    pub fn at_offset(items: Vec<T>, offset: u32, limit: u32) -> Self {
+        let next =
+            (items.len() as u32 >= limit).then(|| PageCursor::Offset(offset + items.len() as u32));
+        Self { items, next }
+    }
+}
+
+/// How a paginated endpoint is streamed
+#[derive(Debug, Clone, Copy)]
+pub struct StreamOptions {
+    /// Items requested per page, capped by the largest page of the endpoint
+    pub page_size: u32,
+    /// Pages fetched ahead of the consumer, in the background. None are by default.
+    pub prefetch: usize,
+    /// Items after which the stream ends, and no more pages are requested
+    pub max_items: Option<usize>,
+}
+
+/// Largest pages of the endpoint, all fetched on demand
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self::new(u32::MAX)
+    }
+}
+
+impl StreamOptions {
+    pub fn new(page_size: u32) -> Self {
         Self {
-            request,
-            current_offset: 0,
             page_size,
-            has_more: true,
+            prefetch: 0,
+            max_items: None,
         }
     }
 
-    pub async fn next_page(&mut self) -> Option<Result<Vec<T>, BirdeyeError>> {
-        if !self.has_more {
+    pub fn with_prefetch(mut self, pages: usize) -> Self {
+        self.prefetch = pages;
+        self
+    }
+
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+}
+
+/// Items of the pages of `fetch` from `start`, requesting each page with its position and
+/// size. The stream ends after an empty page, the last page or the first error. Pages are
+/// requested one at a time even when prefetched, so they queue behind the rate limiter of
+/// the provider like any other request.
+pub fn paginate<T, F, Fut>(
+    fetch: F,
+    start: PageCursor,
+    options: StreamOptions,
+) -> impl Stream<Item = Result<T, BirdeyeError>> + Send + 'static
+where
+    T: Send + 'static,
+    F: Fn(PageCursor, u32) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Page<T>, BirdeyeError>> + Send + 'static,
+{
+    let max_items = options.max_items.unwrap_or(usize::MAX);
+    let pages = pages(fetch, start, options.page_size.max(1), max_items);
+    let pages = if options.prefetch == 0 {
+        pages
+    } else {
+        prefetched(pages, options.prefetch)
+    };
+
+    pages
+        .flat_map(|page| {
+            let items: Vec<_> = match page {
+                Ok(items) => items.into_iter().map(Ok).collect(),
+                Err(err) => vec![Err(err)],
+            };
+            stream::iter(items)
+        })
+        .take(max_items)
+}
+
+struct PageState<F> {
+    fetch: F,
+    next: Option<PageCursor>,
+    fetched: usize,
+}
+
+/// Pages of `fetch`, the last one asking for no more than the items left
+fn pages<T, F, Fut>(
+    fetch: F,
+    start: PageCursor,
+    page_size: u32,
+    max_items: usize,
+) -> BoxStream<'static, Result<Vec<T>, BirdeyeError>>
+where
+    T: Send + 'static,
+    F: Fn(PageCursor, u32) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Page<T>, BirdeyeError>> + Send + 'static,
+{
+    let state = PageState {
+        fetch,
+        next: Some(start),
+        fetched: 0,
+    };
+    stream::unfold(state, move |mut state| async move {
+        let cursor = state.next.take()?;
+        let left = max_items.saturating_sub(state.fetched);
+        if left == 0 {
             return None;
         }
 
-        let result = self.request.request(self.current_offset, self.page_size).await;
-        match result {
-            Ok(items) => {
-                self.has_more = items.len() as u32 == self.page_size;
-                self.current_offset += items.len() as u32;
-                Some(Ok(items))
+        let limit = page_size.min(u32::try_from(left).unwrap_or(u32::MAX));
+        match (state.fetch)(cursor, limit).await {
+            Ok(page) => {
+                state.fetched += page.items.len();
+                state.next = if page.items.is_empty() {
+                    None
+                } else {
+                    page.next
+                };
+                Some((Ok(page.items), state))
             }
-            Err(e) => {
-                self.has_more = false;
-                Some(Err(e))
+            Err(err) => Some((Err(err), state)),
+        }
+    })
+    .boxed()
+}
+
+/// Fetches the pages in the background, up to `prefetch` of them ahead of the consumer.
+/// Dropping the stream stops the fetching.
+fn prefetched<T: Send + 'static>(
+    mut pages: BoxStream<'static, Result<Vec<T>, BirdeyeError>>,
+    prefetch: usize,
+) -> BoxStream<'static, Result<Vec<T>, BirdeyeError>> {
+    let (sender, receiver) = mpsc::channel(prefetch);
+    tokio::spawn(async move {
+        while let Some(page) = pages.next().await {
+            if sender.send(page).await.is_err() {
+                break;
             }
         }
-    }
+    });
+
+    stream::unfold(receiver, |mut receiver| async move {
+        let page = receiver.recv().await?;
+        Some((page, receiver))
+    })
+    .boxed()
+}
 
-    pub async fn collect_all(mut self) -> Result<Vec<T>, BirdeyeError> {
-        let mut all_items = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
-        while let Some(result) = self.next_page().await {
-            all_items.extend(result?);
+    /// Endpoint of 25 numbers paginated by offset, recording its requests
+    fn numbers(
+        requests: &Arc<Mutex<Vec<(PageCursor, u32)>>>,
+    ) -> impl Fn(
+        PageCursor,
+        u32,
+    ) -> futures_util::future::BoxFuture<'static, Result<Page<u32>, BirdeyeError>> {
+        let requests = requests.clone();
+        move |cursor, limit| {
+            requests.lock().unwrap().push((cursor.clone(), limit));
+            Box::pin(async move {
+                let PageCursor::Offset(offset) = cursor else {
+                    return Err(BirdeyeError::InvalidParameters("Not an offset".to_string()));
+                };
+                let items = (offset..25).take(limit as usize).collect();
+                Ok(Page::at_offset(items, offset, limit))
+            })
         }
+    }
+
+    #[tokio::test]
+    async fn test_offset_pagination() -> Result<(), BirdeyeError> {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let items: Vec<_> = paginate(
+            numbers(&requests),
+            PageCursor::Offset(0),
+            StreamOptions::new(10),
+        )
+        .try_collect()
+        .await?;
+        assert_eq!(items, (0..25).collect::<Vec<_>>());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        // Full last page, the next one is empty
+        requests.lock().unwrap().clear();
+        let items: Vec<_> = paginate(
+            numbers(&requests),
+            PageCursor::Offset(5),
+            StreamOptions::new(10),
+        )
+        .try_collect()
+        .await?;
+        assert_eq!(items.len(), 20);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_items() -> Result<(), BirdeyeError> {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let options = StreamOptions::new(10).with_max_items(13);
+        let items: Vec<_> = paginate(numbers(&requests), PageCursor::Offset(0), options)
+            .try_collect()
+            .await?;
+        assert_eq!(items.len(), 13);
+        // The last page only asks for the items left
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [(PageCursor::Offset(0), 10), (PageCursor::Offset(10), 3)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pagination_and_errors() {
+        // Pages chained by cursor, the third one failing
+        let fetch = |cursor: PageCursor, _limit: u32| async move {
+            match cursor {
+                PageCursor::Cursor(cursor) if cursor == "b" => Ok(Page {
+                    items: vec!["b1", "b2"],
+                    next: Some(PageCursor::Cursor("c".to_string())),
+                }),
+                PageCursor::Cursor(cursor) if cursor == "c" => Err(BirdeyeError::RateLimitExceeded),
+                _ => Ok(Page {
+                    items: vec!["a1"],
+                    next: Some(PageCursor::Cursor("b".to_string())),
+                }),
+            }
+        };
+
+        let results: Vec<_> = paginate(fetch, PageCursor::Offset(0), StreamOptions::new(2))
+            .collect()
+            .await;
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[2].as_ref().unwrap(), &"b2");
+        assert!(matches!(results[3], Err(BirdeyeError::RateLimitExceeded)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prefetch() -> Result<(), BirdeyeError> {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let options = StreamOptions::new(5).with_prefetch(2);
+        let mut stream = Box::pin(paginate(numbers(&requests), PageCursor::Offset(0), options));
+
+        assert_eq!(stream.try_next().await?, Some(0));
+        // While the first page is consumed, the next ones are fetched
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(requests.lock().unwrap().len() >= 3);
 
-        Ok(all_items)
+        // Dropping the stream stops the fetching
+        drop(stream);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(requests.lock().unwrap().len() < 6);
+        Ok(())
     }
 }
@@ -0,0 +1,118 @@
+use super::cache::{CacheStore, StoredResponse};
+use crate::types::error::BirdeyeError;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{path::Path, sync::Mutex};
+
+/// [CacheStore] keeping the responses in a SQLite database, so they survive restarts
+pub struct SqliteCacheStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteCacheStore {
+    /// Opens the database at `path`, creating it if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BirdeyeError> {
+        Self::with_connection(Connection::open(path).map_err(cache_error)?)
+    }
+
+    /// Database living as long as the store
+    pub fn in_memory() -> Result<Self, BirdeyeError> {
+        Self::with_connection(Connection::open_in_memory().map_err(cache_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, BirdeyeError> {
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS birdeye_cache (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL,
+                    fetched_at INTEGER NOT NULL
+                )",
+                [],
+            )
+            .map_err(cache_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl CacheStore for SqliteCacheStore {
+    fn load(&self, key: &str) -> Result<Option<StoredResponse>, BirdeyeError> {
+        let row = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT value, fetched_at FROM birdeye_cache WHERE key = ?1",
+                params![key],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .optional()
+            .map_err(cache_error)?;
+
+        row.map(|(value, fetched_at)| {
+            Ok(StoredResponse {
+                value: serde_json::from_str(&value)?,
+                fetched_at,
+            })
+        })
+        .transpose()
+    }
+
+    fn save(&self, key: &str, response: &StoredResponse) -> Result<(), BirdeyeError> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO birdeye_cache (key, value, fetched_at) VALUES (?1, ?2, ?3)",
+                params![key, response.value.to_string(), response.fetched_at],
+            )
+            .map_err(cache_error)?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), BirdeyeError> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM birdeye_cache", [])
+            .map_err(cache_error)?;
+        Ok(())
+    }
+}
+
+fn cache_error(err: rusqlite::Error) -> BirdeyeError {
+    BirdeyeError::CacheError(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sqlite_store() -> Result<(), BirdeyeError> {
+        let path = std::env::temp_dir().join(format!("birdeye_cache_{}.db", std::process::id()));
+        let response = StoredResponse {
+            value: json!({ "symbol": "SOL", "price": 150.8216 }),
+            fetched_at: 1729256400,
+        };
+
+        let store = SqliteCacheStore::open(&path)?;
+        assert_eq!(store.load("solana:/defi/token_overview?address=SOL")?, None);
+        store.save("solana:/defi/token_overview?address=SOL", &response)?;
+        drop(store);
+
+        // The response outlives the store
+        let store = SqliteCacheStore::open(&path)?;
+        assert_eq!(
+            store.load("solana:/defi/token_overview?address=SOL")?,
+            Some(response)
+        );
+        store.clear()?;
+        assert_eq!(store.load("solana:/defi/token_overview?address=SOL")?, None);
+
+        std::fs::remove_file(&path).ok();
+        Ok(())
+    }
+}
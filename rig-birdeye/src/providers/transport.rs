@@ -0,0 +1,91 @@
+use crate::types::{error::BirdeyeError, shared::Chain};
+use async_trait::async_trait;
+use reqwest::Client;
+use std::{sync::Arc, time::Duration};
+
+pub const API_BASE_URL: &str = "https://public-api.birdeye.so";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// GET request to an endpoint of the API
+#[derive(Debug, Clone)]
+pub struct HttpRequest<'a> {
+    /// Path of the endpoint, e.g. `/defi/token_overview`
+    pub endpoint: &'a str,
+    pub query: &'a [(&'a str, String)],
+    pub api_key: &'a str,
+    /// Chain of the request, sent as the `x-chain` header
+    pub chain: Chain,
+}
+
+/// Raw response of the API, parsed and retried by the provider
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// How the provider reaches the Birdeye API. The default sends the requests over HTTP, other
+/// transports can route them through a proxy or answer them without a network.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn get(&self, request: HttpRequest<'_>) -> Result<HttpResponse, BirdeyeError>;
+}
+
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    async fn get(&self, request: HttpRequest<'_>) -> Result<HttpResponse, BirdeyeError> {
+        (**self).get(request).await
+    }
+}
+
+/// Transport sending the requests to the Birdeye API, or to another server at `base_url`
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: Client,
+    base_url: String,
+}
+
+impl HttpTransport {
+    pub fn new(base_url: &str) -> Self {
+        let client = Client::builder()
+            .timeout(DEFAULT_TIMEOUT)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new(API_BASE_URL)
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn get(&self, request: HttpRequest<'_>) -> Result<HttpResponse, BirdeyeError> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, request.endpoint))
+            .header("X-API-KEY", request.api_key)
+            .header("accept", "application/json")
+            .header("x-chain", request.chain.to_string())
+            .query(request.query)
+            .send()
+            .await?;
+        let status = response.status().as_u16();
+
+        Ok(HttpResponse {
+            status,
+            body: response.text().await?,
+        })
+    }
+}
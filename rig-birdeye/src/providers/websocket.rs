@@ -1,18 +1,48 @@
+use crate::types::{error::BirdeyeError, shared::Chain, TimeInterval};
 use futures_util::{SinkExt, StreamExt};
-use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
 use serde::{Deserialize, Serialize};
-use crate::types::error::BirdeyeError;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    net::TcpStream,
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+    time::Instant,
+};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
 
+// Followed by the chain
 const WEBSOCKET_URL: &str = "wss://public-api.birdeye.so/socket";
+// Birdeye only accepts connections from its origin and with its protocol
+const ORIGIN: &str = "ws://public-api.birdeye.so";
+const PROTOCOL: &str = "echo-protocol";
+// A connection up for this long is healthy again, the next reconnect starts from the
+// initial backoff
+const STABLE_CONNECTION: Duration = Duration::from_secs(30);
+// Chart type of the price updates
+const PRICE_CHART_TYPE: &str = "1m";
 
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Price of a token, sent every time its current 1 minute candle changes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MarketUpdate {
     pub address: String,
     pub price: f64,
-    pub volume_24h: f64,
-    pub price_change_24h: f64,
+    /// Volume of the current minute
+    pub volume: f64,
     pub timestamp: i64,
 }
 
@@ -21,124 +51,956 @@ pub struct MarketUpdate {
 pub struct TradeUpdate {
     pub address: String,
     pub price: f64,
+    /// Value of the trade in USD
     pub size: f64,
     pub side: TradeSide,
     pub timestamp: i64,
+    pub tx_hash: String,
+    pub owner: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TradeSide {
     Buy,
     Sell,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Current candle of a token at an interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct SubscribeMessage {
-    action: String,
-    token: String,
-    api_key: String,
+pub struct CandleUpdate {
+    pub address: String,
+    pub interval: TimeInterval,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub timestamp: i64,
 }
 
-#[derive(Debug)]
-pub struct WebSocketProvider {
+/// Pool created on a DEX
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewPairUpdate {
+    pub address: String,
+    pub name: String,
+    pub source: String,
+    pub base: PairToken,
+    pub quote: PairToken,
+    pub tx_hash: String,
+    pub block_time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairToken {
+    pub address: String,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub decimals: Option<u8>,
+}
+
+/// Feed of the stream. The subscriptions are kept by the provider and restored when it
+/// reconnects.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Subscription {
+    /// [MarketUpdate]s of the token
+    Price { address: String },
+    /// [CandleUpdate]s of the token at the interval
+    Ohlcv {
+        address: String,
+        interval: TimeInterval,
+    },
+    /// [TradeUpdate]s of the token
+    Txs { address: String },
+    /// [NewPairUpdate]s of all the tokens
+    NewPairs,
+}
+
+/// Subscription message of Birdeye covering subscriptions of this kind
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Channel {
+    Price,
+    Txs,
+    NewPairs,
+}
+
+const CHANNELS: [Channel; 3] = [Channel::Price, Channel::Txs, Channel::NewPairs];
+
+impl Subscription {
+    fn channel(&self) -> Channel {
+        match self {
+            Subscription::Price { .. } | Subscription::Ohlcv { .. } => Channel::Price,
+            Subscription::Txs { .. } => Channel::Txs,
+            Subscription::NewPairs => Channel::NewPairs,
+        }
+    }
+}
+
+/// Change of the state of the stream, for monitoring
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    Connected,
+    Disconnected {
+        reason: String,
+    },
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    /// A receiver fell behind and skipped updates
+    Lagged {
+        stream: &'static str,
+        skipped: u64,
+    },
+    /// A message of the server could not be parsed
+    InvalidMessage {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct WebSocketConfig {
+    pub url: String,
+    /// Delay before the first reconnect, doubling with every failed attempt
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// How often the connection is pinged
+    pub ping_interval: Duration,
+    /// How long after the last ping the server may stay silent before the connection is
+    /// considered dead
+    pub pong_timeout: Duration,
+    /// Updates buffered for slow receivers before they lag
+    pub channel_capacity: usize,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self::for_chain(Chain::default())
+    }
+}
+
+impl WebSocketConfig {
+    /// Default configuration streaming the chain
+    pub fn for_chain(chain: Chain) -> Self {
+        Self {
+            url: format!("{}/{}", WEBSOCKET_URL, chain),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            ping_interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+            channel_capacity: 1000,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Receiver of the updates of a stream. Skipped updates are logged, counted and sent as
+/// [StreamEvent::Lagged] rather than ending the stream.
+pub struct UpdateReceiver<T> {
+    receiver: broadcast::Receiver<T>,
+    stream: &'static str,
+    lagged: Arc<AtomicU64>,
+    events: broadcast::Sender<StreamEvent>,
+}
+
+impl<T: Clone> UpdateReceiver<T> {
+    /// The next update, or None once the provider is dropped
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(update) => return Some(update),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        "{} receiver lagged, skipped {} updates",
+                        self.stream,
+                        skipped
+                    );
+                    self.lagged.fetch_add(skipped, Ordering::Relaxed);
+                    let _ = self.events.send(StreamEvent::Lagged {
+                        stream: self.stream,
+                        skipped,
+                    });
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+enum Command {
+    /// Sends the subscription message of the channel again
+    Update(Channel),
+}
+
+struct Inner {
     api_key: String,
+    config: WebSocketConfig,
+    subscriptions: Mutex<BTreeSet<Subscription>>,
+    commands: Mutex<Option<mpsc::UnboundedSender<Command>>>,
     market_sender: broadcast::Sender<MarketUpdate>,
     trade_sender: broadcast::Sender<TradeUpdate>,
+    candle_sender: broadcast::Sender<CandleUpdate>,
+    pair_sender: broadcast::Sender<NewPairUpdate>,
+    event_sender: broadcast::Sender<StreamEvent>,
+    lagged: Arc<AtomicU64>,
+}
+
+/// Stream of the Birdeye WebSocket API. Once started, it reconnects with backoff whenever the
+/// connection fails or stops answering pings, and subscribes again to all its subscriptions.
+pub struct WebSocketProvider {
+    inner: Arc<Inner>,
+    task: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl WebSocketProvider {
     pub fn new(api_key: &str) -> Self {
-        let (market_sender, _) = broadcast::channel(1000);
-        let (trade_sender, _) = broadcast::channel(1000);
-        
+        Self::with_config(api_key, WebSocketConfig::default())
+    }
+
+    pub fn with_config(api_key: &str, config: WebSocketConfig) -> Self {
+        let capacity = config.channel_capacity;
         Self {
-            api_key: api_key.to_string(),
-            market_sender,
-            trade_sender,
+            inner: Arc::new(Inner {
+                api_key: api_key.to_string(),
+                config,
+                subscriptions: Mutex::new(BTreeSet::new()),
+                commands: Mutex::new(None),
+                market_sender: broadcast::channel(capacity).0,
+                trade_sender: broadcast::channel(capacity).0,
+                candle_sender: broadcast::channel(capacity).0,
+                pair_sender: broadcast::channel(capacity).0,
+                event_sender: broadcast::channel(capacity).0,
+                lagged: Arc::new(AtomicU64::new(0)),
+            }),
+            task: Mutex::new(None),
+        }
+    }
+
+    pub fn subscribe_market_updates(&self) -> UpdateReceiver<MarketUpdate> {
+        self.receiver(&self.inner.market_sender, "market")
+    }
+
+    pub fn subscribe_trade_updates(&self) -> UpdateReceiver<TradeUpdate> {
+        self.receiver(&self.inner.trade_sender, "trade")
+    }
+
+    pub fn subscribe_candle_updates(&self) -> UpdateReceiver<CandleUpdate> {
+        self.receiver(&self.inner.candle_sender, "candle")
+    }
+
+    pub fn subscribe_new_pairs(&self) -> UpdateReceiver<NewPairUpdate> {
+        self.receiver(&self.inner.pair_sender, "new pair")
+    }
+
+    /// Connections, disconnections and other events of the stream
+    pub fn events(&self) -> broadcast::Receiver<StreamEvent> {
+        self.inner.event_sender.subscribe()
+    }
+
+    /// Updates skipped by lagging receivers so far
+    pub fn lagged(&self) -> u64 {
+        self.inner.lagged.load(Ordering::Relaxed)
+    }
+
+    fn receiver<T>(
+        &self,
+        sender: &broadcast::Sender<T>,
+        stream: &'static str,
+    ) -> UpdateReceiver<T> {
+        UpdateReceiver {
+            receiver: sender.subscribe(),
+            stream,
+            lagged: self.inner.lagged.clone(),
+            events: self.inner.event_sender.clone(),
         }
     }
 
-    pub fn subscribe_market_updates(&self) -> broadcast::Receiver<MarketUpdate> {
-        self.market_sender.subscribe()
+    /// Adds the subscription, sent right away when connected. Returns false if it already
+    /// existed.
+    pub fn subscribe(&self, subscription: Subscription) -> bool {
+        let channel = subscription.channel();
+        let added = self
+            .inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .insert(subscription);
+        if added {
+            self.inner.update(channel);
+        }
+        added
     }
 
-    pub fn subscribe_trade_updates(&self) -> broadcast::Receiver<TradeUpdate> {
-        self.trade_sender.subscribe()
+    /// Removes the subscription. Returns false if it did not exist.
+    pub fn unsubscribe(&self, subscription: &Subscription) -> bool {
+        let removed = self
+            .inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .remove(subscription);
+        if removed {
+            self.inner.update(subscription.channel());
+        }
+        removed
+    }
+
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Starts streaming in the background. Does nothing if already started.
+    pub fn start(&self) {
+        let mut task = self.task.lock().unwrap();
+        if task.as_ref().is_some_and(|task| !task.is_finished()) {
+            return;
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.inner.commands.lock().unwrap() = Some(sender);
+        *task = Some(tokio::spawn(self.inner.clone().run(receiver)));
     }
 
+    /// Stops streaming. The subscriptions are kept for the next start.
+    pub fn stop(&self) {
+        self.inner.commands.lock().unwrap().take();
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+
+    /// Subscribes to the prices and trades of the tokens and starts streaming
     pub async fn connect_and_stream(&self, tokens: Vec<String>) -> Result<(), BirdeyeError> {
-        let url = format!("{}?apiKey={}", WEBSOCKET_URL, self.api_key);
-        let (ws_stream, _) = connect_async(&url)
-            .await
-            .map_err(|e| BirdeyeError::WebSocketError(e.to_string()))?;
-        
-        let (mut write, mut read) = ws_stream.split();
-
-        // Subscribe to tokens
-        for token in tokens {
-            let subscribe_msg = SubscribeMessage {
-                action: "subscribe".to_string(),
-                token,
-                api_key: self.api_key.clone(),
-            };
-            
-            let msg = serde_json::to_string(&subscribe_msg)
-                .map_err(|e| BirdeyeError::SerializationError(e))?;
-            
-            write.send(Message::Text(msg))
-                .await
-                .map_err(|e| BirdeyeError::WebSocketError(e.to_string()))?;
+        if tokens.is_empty() {
+            return Err(BirdeyeError::InvalidParameters(
+                "No token to stream".to_string(),
+            ));
+        }
+        for address in tokens {
+            self.subscribe(Subscription::Price {
+                address: address.clone(),
+            });
+            self.subscribe(Subscription::Txs { address });
         }
+        self.start();
+        Ok(())
+    }
+}
 
-        let market_sender = self.market_sender.clone();
-        let trade_sender = self.trade_sender.clone();
+impl Drop for WebSocketProvider {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
 
-        // Spawn message handling task
-        tokio::spawn(async move {
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        if let Ok(market_update) = serde_json::from_str::<MarketUpdate>(&text) {
-                            let _ = market_sender.send(market_update);
-                        } else if let Ok(trade_update) = serde_json::from_str::<TradeUpdate>(&text) {
-                            let _ = trade_sender.send(trade_update);
+impl Inner {
+    fn emit(&self, event: StreamEvent) {
+        let _ = self.event_sender.send(event);
+    }
+
+    fn update(&self, channel: Channel) {
+        if let Some(commands) = self.commands.lock().unwrap().as_ref() {
+            let _ = commands.send(Command::Update(channel));
+        }
+    }
+
+    async fn run(self: Arc<Self>, mut commands: mpsc::UnboundedReceiver<Command>) {
+        let mut attempt = 0;
+        loop {
+            match self.connect().await {
+                Ok(socket) => {
+                    let connected_at = Instant::now();
+                    tracing::info!("Connected to the Birdeye WebSocket");
+                    self.emit(StreamEvent::Connected);
+
+                    let reason = self.stream(socket, &mut commands).await;
+                    tracing::warn!("Birdeye WebSocket disconnected: {}", reason);
+                    self.emit(StreamEvent::Disconnected { reason });
+                    if connected_at.elapsed() >= STABLE_CONNECTION {
+                        attempt = 0;
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!("Failed to connect to the Birdeye WebSocket: {}", err);
+                    self.emit(StreamEvent::Disconnected {
+                        reason: err.to_string(),
+                    });
+                }
+            }
+
+            attempt += 1;
+            let delay = self.config.backoff(attempt);
+            self.emit(StreamEvent::Reconnecting { attempt, delay });
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn connect(&self) -> Result<Socket, BirdeyeError> {
+        let url = format!("{}?x-api-key={}", self.config.url, self.api_key);
+        let mut request = url
+            .into_client_request()
+            .map_err(|err| BirdeyeError::WebSocketError(err.to_string()))?;
+        request
+            .headers_mut()
+            .insert("Origin", HeaderValue::from_static(ORIGIN));
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(PROTOCOL));
+
+        let (socket, _) = connect_async(request)
+            .await
+            .map_err(|err| BirdeyeError::WebSocketError(err.to_string()))?;
+        Ok(socket)
+    }
+
+    /// Streams until the connection fails, and returns why
+    async fn stream(
+        &self,
+        socket: Socket,
+        commands: &mut mpsc::UnboundedReceiver<Command>,
+    ) -> String {
+        let (mut write, mut read) = socket.split();
+
+        // All the subscriptions are sent on connect, updates queued until now are stale
+        while commands.try_recv().is_ok() {}
+        for channel in CHANNELS {
+            if let Some(message) = self.subscription_message(channel, true) {
+                if let Err(err) = write.send(Message::Text(message.to_string())).await {
+                    return err.to_string();
+                }
+            }
+        }
+
+        let mut ping = tokio::time::interval(self.config.ping_interval);
+        ping.tick().await;
+        let mut last_seen = Instant::now();
+        loop {
+            tokio::select! {
+                message = read.next() => {
+                    last_seen = Instant::now();
+                    match message {
+                        Some(Ok(Message::Text(text))) => self.handle(&text),
+                        Some(Ok(Message::Binary(data))) => match String::from_utf8(data) {
+                            Ok(text) => self.handle(&text),
+                            Err(err) => self.invalid_message(err.to_string()),
+                        },
+                        Some(Ok(Message::Ping(data))) => {
+                            if let Err(err) = write.send(Message::Pong(data)).await {
+                                return err.to_string();
+                            }
+                        }
+                        Some(Ok(Message::Close(frame))) => {
+                            return match frame {
+                                Some(frame) => format!("closed by the server: {} {}", frame.code, frame.reason),
+                                None => "closed by the server".to_string(),
+                            };
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(err)) => return err.to_string(),
+                        None => return "connection closed".to_string(),
+                    }
+                }
+                command = commands.recv() => match command {
+                    Some(Command::Update(channel)) => {
+                        if let Some(message) = self.subscription_message(channel, false) {
+                            if let Err(err) = write.send(Message::Text(message.to_string())).await {
+                                return err.to_string();
+                            }
                         }
                     }
-                    Ok(Message::Close(_)) => break,
-                    Err(e) => {
-                        eprintln!("WebSocket error: {}", e);
-                        break;
+                    None => return "stopped".to_string(),
+                },
+                _ = ping.tick() => {
+                    if last_seen.elapsed() > self.config.ping_interval + self.config.pong_timeout {
+                        return "heartbeat timed out".to_string();
+                    }
+                    if let Err(err) = write.send(Message::Ping(Vec::new())).await {
+                        return err.to_string();
                     }
-                    _ => {}
                 }
             }
-        });
+        }
+    }
 
+    /// Message subscribing to all the subscriptions of the channel, or unsubscribing from the
+    /// channel when there are none. Nothing is sent for an empty channel on connect.
+    fn subscription_message(&self, channel: Channel, on_connect: bool) -> Option<Value> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let subscriptions: Vec<_> = subscriptions
+            .iter()
+            .filter(|subscription| subscription.channel() == channel)
+            .collect();
+        if subscriptions.is_empty() {
+            if on_connect {
+                return None;
+            }
+            let kind = match channel {
+                Channel::Price => "UNSUBSCRIBE_PRICE",
+                Channel::Txs => "UNSUBSCRIBE_TXS",
+                Channel::NewPairs => "UNSUBSCRIBE_NEW_PAIR",
+            };
+            return Some(json!({ "type": kind }));
+        }
+
+        Some(match channel {
+            Channel::Price => {
+                let query = subscriptions
+                    .iter()
+                    .filter_map(|subscription| match subscription {
+                        Subscription::Price { address } => Some((address, PRICE_CHART_TYPE)),
+                        Subscription::Ohlcv { address, interval } => {
+                            Some((address, interval.api_type()))
+                        }
+                        _ => None,
+                    })
+                    .map(|(address, chart_type)| {
+                        format!(
+                            "(address = {} AND chartType = {} AND currency = usd)",
+                            address, chart_type
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" OR ");
+                json!({
+                    "type": "SUBSCRIBE_PRICE",
+                    "data": { "queryType": "complex", "query": query }
+                })
+            }
+            Channel::Txs => {
+                let query = subscriptions
+                    .iter()
+                    .filter_map(|subscription| match subscription {
+                        Subscription::Txs { address } => Some(format!("address = {}", address)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" OR ");
+                json!({
+                    "type": "SUBSCRIBE_TXS",
+                    "data": { "queryType": "complex", "query": query }
+                })
+            }
+            Channel::NewPairs => json!({ "type": "SUBSCRIBE_NEW_PAIR" }),
+        })
+    }
+
+    fn invalid_message(&self, error: String) {
+        tracing::warn!("Invalid message from the Birdeye WebSocket: {}", error);
+        self.emit(StreamEvent::InvalidMessage { error });
+    }
+
+    fn handle(&self, text: &str) {
+        if let Err(error) = self.dispatch(text) {
+            self.invalid_message(format!("{}: {}", error, text));
+        }
+    }
+
+    fn dispatch(&self, text: &str) -> Result<(), serde_json::Error> {
+        #[derive(Deserialize)]
+        struct Envelope {
+            #[serde(rename = "type")]
+            kind: String,
+            #[serde(default)]
+            data: Value,
+        }
+
+        let envelope: Envelope = serde_json::from_str(text)?;
+        match envelope.kind.as_str() {
+            "PRICE_DATA" => self.price(serde_json::from_value(envelope.data)?),
+            "TXS_DATA" => self.trade(serde_json::from_value(envelope.data)?),
+            "NEW_PAIR_DATA" => {
+                let pair: NewPairUpdate = serde_json::from_value(envelope.data)?;
+                let _ = self.pair_sender.send(pair);
+            }
+            kind => tracing::debug!("Birdeye WebSocket message {}: {}", kind, envelope.data),
+        }
         Ok(())
     }
+
+    fn price(&self, candle: PriceData) {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let address = candle.address.clone();
+        if candle.chart_type == PRICE_CHART_TYPE
+            && subscriptions.contains(&Subscription::Price {
+                address: address.clone(),
+            })
+        {
+            let _ = self.market_sender.send(MarketUpdate {
+                address: address.clone(),
+                price: candle.close,
+                volume: candle.volume,
+                timestamp: candle.unix_time,
+            });
+        }
+
+        let interval = subscriptions
+            .iter()
+            .find_map(|subscription| match subscription {
+                Subscription::Ohlcv {
+                    address: subscribed,
+                    interval,
+                } if *subscribed == address && interval.api_type() == candle.chart_type => {
+                    Some(*interval)
+                }
+                _ => None,
+            });
+        if let Some(interval) = interval {
+            let _ = self.candle_sender.send(CandleUpdate {
+                address,
+                interval,
+                open: candle.open,
+                high: candle.high,
+                low: candle.low,
+                close: candle.close,
+                volume: candle.volume,
+                timestamp: candle.unix_time,
+            });
+        }
+    }
+
+    fn trade(&self, tx: TxData) {
+        // The price is the one of the side of the swap that is the token
+        let price = [&tx.from, &tx.to]
+            .into_iter()
+            .find(|side| side.address == tx.token_address)
+            .and_then(|side| side.price.or(side.nearest_price))
+            .unwrap_or_default();
+
+        let _ = self.trade_sender.send(TradeUpdate {
+            address: tx.token_address,
+            price,
+            size: tx.volume_usd,
+            side: tx.side,
+            timestamp: tx.block_unix_time,
+            tx_hash: tx.tx_hash,
+            owner: tx.owner,
+        });
+    }
+}
+
+#[derive(Deserialize)]
+struct PriceData {
+    address: String,
+    #[serde(rename = "type")]
+    chart_type: String,
+    #[serde(rename = "o")]
+    open: f64,
+    #[serde(rename = "h")]
+    high: f64,
+    #[serde(rename = "l")]
+    low: f64,
+    #[serde(rename = "c")]
+    close: f64,
+    #[serde(rename = "v", default)]
+    volume: f64,
+    #[serde(rename = "unixTime")]
+    unix_time: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TxData {
+    token_address: String,
+    side: TradeSide,
+    #[serde(rename = "volumeUSD", default)]
+    volume_usd: f64,
+    block_unix_time: i64,
+    tx_hash: String,
+    #[serde(default)]
+    owner: String,
+    from: SwapSide,
+    to: SwapSide,
 }
 
-// Example usage:
-//
-// let ws_provider = WebSocketProvider::new("your-api-key");
-// 
-// // Subscribe to market updates
-// let mut market_rx = ws_provider.subscribe_market_updates();
-// tokio::spawn(async move {
-//     while let Ok(update) = market_rx.recv().await {
-//         println!("Market update: {:?}", update);
-//     }
-// });
-//
-// // Subscribe to trade updates
-// let mut trade_rx = ws_provider.subscribe_trade_updates();
-// tokio::spawn(async move {
-//     while let Ok(update) = trade_rx.recv().await {
-//         println!("Trade update: {:?}", update);
-//     }
-// });
-//
-// // Connect and start streaming
-// ws_provider.connect_and_stream(vec!["token-address-1".to_string(), "token-address-2".to_string()]).await?;
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwapSide {
+    address: String,
+    #[serde(default)]
+    price: Option<f64>,
+    #[serde(default)]
+    nearest_price: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{
+        accept_hdr_async,
+        tungstenite::handshake::server::{Request, Response},
+    };
+
+    const SOL: &str = "So11111111111111111111111111111111111111112";
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+
+    fn config(url: String) -> WebSocketConfig {
+        WebSocketConfig {
+            url,
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(100),
+            ping_interval: Duration::from_millis(100),
+            pong_timeout: Duration::from_millis(100),
+            channel_capacity: 16,
+        }
+    }
+
+    /// Local server handing the accepted connections to the test
+    #[allow(clippy::result_large_err)] // The handshake callback's error type is tungstenite's
+    async fn server() -> (String, mpsc::UnboundedReceiver<WebSocketStream<TcpStream>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/socket/solana", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let socket =
+                    accept_hdr_async(stream, |request: &Request, mut response: Response| {
+                        assert_eq!(request.headers()["Origin"], ORIGIN);
+                        assert!(request
+                            .uri()
+                            .query()
+                            .unwrap()
+                            .contains("x-api-key=test_key"));
+                        response
+                            .headers_mut()
+                            .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(PROTOCOL));
+                        Ok(response)
+                    })
+                    .await
+                    .unwrap();
+                let _ = sender.send(socket);
+            }
+        });
+        (url, receiver)
+    }
+
+    async fn next_text(socket: &mut WebSocketStream<TcpStream>) -> Value {
+        loop {
+            match socket.next().await.unwrap().unwrap() {
+                Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                _ => continue,
+            }
+        }
+    }
+
+    async fn next_event(events: &mut broadcast::Receiver<StreamEvent>) -> StreamEvent {
+        tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = WebSocketConfig::default();
+        assert_eq!(config.backoff(1), Duration::from_secs(1));
+        assert_eq!(config.backoff(3), Duration::from_secs(4));
+        assert_eq!(config.backoff(7), Duration::from_secs(60));
+        assert_eq!(config.backoff(100), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_messages() {
+        let provider = WebSocketProvider::new("test_key");
+        provider.subscribe(Subscription::Price {
+            address: SOL.to_string(),
+        });
+        provider.subscribe(Subscription::Ohlcv {
+            address: BONK.to_string(),
+            interval: TimeInterval::FiveMinutes,
+        });
+        assert!(!provider.subscribe(Subscription::Price {
+            address: SOL.to_string()
+        }));
+
+        assert_eq!(
+            provider.inner.subscription_message(Channel::Price, true),
+            Some(json!({
+                "type": "SUBSCRIBE_PRICE",
+                "data": {
+                    "queryType": "complex",
+                    "query": format!(
+                        "(address = {} AND chartType = 1m AND currency = usd) OR (address = {} AND chartType = 5m AND currency = usd)",
+                        SOL, BONK
+                    )
+                }
+            }))
+        );
+        assert_eq!(
+            provider.inner.subscription_message(Channel::Txs, true),
+            None
+        );
+        assert_eq!(
+            provider.inner.subscription_message(Channel::Txs, false),
+            Some(json!({ "type": "UNSUBSCRIBE_TXS" }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_resubscribe() {
+        let (url, mut connections) = server().await;
+        let provider = WebSocketProvider::with_config("test_key", config(url));
+        let mut events = provider.events();
+        let mut markets = provider.subscribe_market_updates();
+        let mut trades = provider.subscribe_trade_updates();
+        let mut candles = provider.subscribe_candle_updates();
+        provider
+            .connect_and_stream(vec![SOL.to_string()])
+            .await
+            .unwrap();
+        provider.subscribe(Subscription::Ohlcv {
+            address: SOL.to_string(),
+            interval: TimeInterval::OneHour,
+        });
+
+        let mut socket = connections.recv().await.unwrap();
+        assert_eq!(next_event(&mut events).await, StreamEvent::Connected);
+        assert_eq!(next_text(&mut socket).await["type"], "SUBSCRIBE_PRICE");
+        assert_eq!(
+            next_text(&mut socket).await,
+            json!({ "type": "SUBSCRIBE_TXS", "data": { "queryType": "complex", "query": format!("address = {}", SOL) } })
+        );
+
+        socket.send(Message::Text(json!({
+            "type": "PRICE_DATA",
+            "data": { "o": 150.1, "h": 151.0, "l": 150.0, "c": 150.8, "v": 1200.5, "eventType": "ohlcv", "type": "1m", "unixTime": 1729256400, "symbol": "SOL", "address": SOL }
+        }).to_string())).await.unwrap();
+        socket.send(Message::Text(json!({
+            "type": "PRICE_DATA",
+            "data": { "o": 149.0, "h": 152.0, "l": 148.5, "c": 150.8, "v": 80000.0, "eventType": "ohlcv", "type": "1H", "unixTime": 1729253800, "symbol": "SOL", "address": SOL }
+        }).to_string())).await.unwrap();
+        socket.send(Message::Text(json!({
+            "type": "TXS_DATA",
+            "data": {
+                "blockUnixTime": 1729256401, "owner": "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1", "source": "raydium",
+                "txHash": "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T", "side": "sell", "tokenAddress": SOL, "volumeUSD": 3016.2,
+                "from": { "address": SOL, "symbol": "SOL", "decimals": 9, "uiAmount": 20.0, "price": 150.81, "nearestPrice": 150.8 },
+                "to": { "address": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "symbol": "USDC", "decimals": 6, "uiAmount": 3016.2, "price": null, "nearestPrice": 1.0 }
+            }
+        }).to_string())).await.unwrap();
+        socket
+            .send(Message::Text("not json".to_string()))
+            .await
+            .unwrap();
+
+        let market = markets.recv().await.unwrap();
+        assert_eq!(
+            (market.price, market.volume, market.timestamp),
+            (150.8, 1200.5, 1729256400)
+        );
+        let candle = candles.recv().await.unwrap();
+        assert_eq!(
+            (candle.interval, candle.high, candle.volume),
+            (TimeInterval::OneHour, 152.0, 80000.0)
+        );
+        let trade = trades.recv().await.unwrap();
+        assert_eq!(
+            (trade.side, trade.price, trade.size),
+            (TradeSide::Sell, 150.81, 3016.2)
+        );
+        assert!(matches!(
+            next_event(&mut events).await,
+            StreamEvent::InvalidMessage { .. }
+        ));
+
+        // The server goes away, the provider reconnects and subscribes again
+        drop(socket);
+        assert!(matches!(
+            next_event(&mut events).await,
+            StreamEvent::Disconnected { .. }
+        ));
+        assert!(matches!(
+            next_event(&mut events).await,
+            StreamEvent::Reconnecting { attempt: 1, .. }
+        ));
+        let mut socket = connections.recv().await.unwrap();
+        assert_eq!(next_event(&mut events).await, StreamEvent::Connected);
+        let price = next_text(&mut socket).await;
+        assert!(price["data"]["query"]
+            .as_str()
+            .unwrap()
+            .contains("chartType = 1H"));
+        assert_eq!(next_text(&mut socket).await["type"], "SUBSCRIBE_TXS");
+
+        // Changes are sent while connected
+        provider.unsubscribe(&Subscription::Txs {
+            address: SOL.to_string(),
+        });
+        assert_eq!(
+            next_text(&mut socket).await,
+            json!({ "type": "UNSUBSCRIBE_TXS" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_timeout() {
+        let (url, mut connections) = server().await;
+        let provider = WebSocketProvider::with_config("test_key", config(url));
+        let mut events = provider.events();
+        provider.subscribe(Subscription::NewPairs);
+        provider.start();
+
+        // The server never reads, so the pings go unanswered
+        let _silent = connections.recv().await.unwrap();
+        assert_eq!(next_event(&mut events).await, StreamEvent::Connected);
+        assert_eq!(
+            next_event(&mut events).await,
+            StreamEvent::Disconnected {
+                reason: "heartbeat timed out".to_string()
+            }
+        );
+        assert!(connections.recv().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_lagged_receiver() {
+        let provider = WebSocketProvider::with_config("test_key", config(String::new()));
+        provider.subscribe(Subscription::NewPairs);
+        let mut pairs = provider.subscribe_new_pairs();
+        let mut events = provider.events();
+
+        let pair = json!({
+            "type": "NEW_PAIR_DATA",
+            "data": {
+                "address": "CXmRVmXZpHzvzY2k1nZrYnhtyPjgAUuWbcYF1xq6Aai5", "name": "WIF-SOL", "source": "raydium",
+                "base": { "address": "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm", "name": "dogwifhat", "symbol": "WIF", "decimals": 6 },
+                "quote": { "address": SOL, "name": "Wrapped SOL", "symbol": "SOL", "decimals": 9 },
+                "txHash": "2CoDhR5mXz1KDFoGJuw1Yxd3R7Ab3WmrhhvbvGFg34BUiyYKh2wS7BeTPr5Rfb7zBBwzXLEmyq2cfaMJK3QcGsZ8",
+                "blockTime": 1729256400
+            }
+        })
+        .to_string();
+        for _ in 0..20 {
+            provider.inner.handle(&pair);
+        }
+
+        // 16 updates fit in the channel, the receiver skips the 4 oldest and goes on
+        assert_eq!(
+            pairs.recv().await.unwrap().base.symbol.as_deref(),
+            Some("WIF")
+        );
+        assert_eq!(provider.lagged(), 4);
+        assert_eq!(
+            next_event(&mut events).await,
+            StreamEvent::Lagged {
+                stream: "new pair",
+                skipped: 4
+            }
+        );
+    }
+}
@@ -1,22 +1,30 @@
-use serde::{Deserialize, Serialize};
 use super::TimeInterval;
+use crate::{
+    analysis::{Execution, ImpactSource, Side},
+    providers::pagination::PaginationParams,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::fmt;
-use crate::providers::pagination::PaginationParams;
 
 // Token Search Types
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TokenSearchParams {
-    pub query: String,
-    pub limit: Option<u32>,
+    pub keyword: String,
+    pub sort_by: Option<TokenSortBy>,
+    pub sort_type: Option<SortType>,
     pub offset: Option<u32>,
+    pub limit: Option<u32>,
 }
 
 impl TokenSearchParams {
-    pub fn new(keyword: String) -> Self {
+    pub fn new(keyword: impl Into<String>) -> Self {
         Self {
-            query: keyword,
-            limit: None,
+            keyword: keyword.into(),
+            sort_by: None,
+            sort_type: None,
             offset: None,
+            limit: None,
         }
     }
 
@@ -27,7 +35,8 @@ impl TokenSearchParams {
     }
 
     pub fn with_pagination(mut self, pagination: PaginationParams) -> Self {
-        self.pagination = Some(pagination);
+        self.offset = pagination.offset;
+        self.limit = pagination.limit;
         self
     }
 
@@ -45,32 +54,30 @@ impl TokenSearchParams {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum TokenSortBy {
-    #[serde(rename = "price")]
     Price,
-    #[serde(rename = "volume")]
     Volume,
-    #[serde(rename = "liquidity")]
     Liquidity,
-    #[serde(rename = "price_change")]
     PriceChange,
+    MarketCap,
 }
 
+// Values of the `sort_by` parameter of the Birdeye search endpoint
 impl fmt::Display for TokenSortBy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TokenSortBy::Price => write!(f, "price"),
-            TokenSortBy::Volume => write!(f, "volume"),
+            TokenSortBy::Volume => write!(f, "volume_24h_usd"),
             TokenSortBy::Liquidity => write!(f, "liquidity"),
-            TokenSortBy::PriceChange => write!(f, "price_change"),
+            TokenSortBy::PriceChange => write!(f, "price_change_24h_percent"),
+            TokenSortBy::MarketCap => write!(f, "marketcap"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum SortType {
     #[serde(rename = "asc")]
     Ascending,
@@ -87,36 +94,106 @@ impl fmt::Display for SortType {
     }
 }
 
-// Token Market Data Types
+// Token List Types
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenListParams {
+    pub sort_by: TokenListSortBy,
+    pub sort_type: SortType,
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
+    /// Leaves out the tokens with less liquidity in USD
+    pub min_liquidity: Option<f64>,
+}
+
+impl TokenListParams {
+    pub fn new(sort_by: TokenListSortBy, sort_type: SortType) -> Self {
+        Self {
+            sort_by,
+            sort_type,
+            offset: None,
+            limit: None,
+            min_liquidity: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_min_liquidity(mut self, min_liquidity: f64) -> Self {
+        self.min_liquidity = Some(min_liquidity);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenListSortBy {
+    Volume,
+    Change,
+    Liquidity,
+    MarketCap,
+}
+
+// Values of the `sort_by` parameter of the Birdeye token list endpoint
+impl fmt::Display for TokenListSortBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenListSortBy::Volume => write!(f, "v24hUSD"),
+            TokenListSortBy::Change => write!(f, "v24hChangePercent"),
+            TokenListSortBy::Liquidity => write!(f, "liquidity"),
+            TokenListSortBy::MarketCap => write!(f, "mc"),
+        }
+    }
+}
+
+/// Token of the token list
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TokenMarketData {
+pub struct TokenListItem {
     pub address: String,
+    #[serde(default)]
     pub symbol: String,
+    #[serde(default)]
     pub name: String,
-    pub decimals: u8,
+    #[serde(default)]
     pub price: f64,
+    /// 24h change in %
+    #[serde(rename = "v24hChangePercent", default)]
     pub price_change_24h: f64,
+    #[serde(rename = "v24hUSD", default)]
     pub volume_24h: f64,
+    #[serde(default)]
+    pub liquidity: f64,
+    #[serde(rename = "mc", default)]
     pub market_cap: Option<f64>,
-    pub fully_diluted_valuation: Option<f64>,
-    pub circulating_supply: Option<f64>,
-    pub total_supply: Option<f64>,
 }
 
 // Wallet Portfolio Types
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct WalletPortfolio {
-    pub wallet_address: String,
-    pub total_value_usd: f64,
-    pub tokens: Vec<TokenBalance>,
+    pub wallet: String,
+    pub total_usd: f64,
+    pub items: Vec<TokenBalance>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct TokenBalance {
-    pub token_address: String,
-    pub symbol: String,
+    pub address: String,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub decimals: u8,
+    /// Balance in tokens, adjusted for the decimals
+    #[serde(rename = "uiAmount")]
     pub amount: f64,
-    pub value_usd: f64,
+    #[serde(default)]
+    pub price_usd: Option<f64>,
+    #[serde(default)]
+    pub value_usd: Option<f64>,
 }
 
 // Price History Types
@@ -124,64 +201,120 @@ pub struct TokenBalance {
 pub struct PriceHistoryParams {
     pub address: String,
     pub interval: TimeInterval,
+    /// Unix time of the first candle, defaults to 100 intervals before `time_to`
     pub time_from: Option<i64>,
+    /// Unix time of the last candle, defaults to now
     pub time_to: Option<i64>,
 }
 
+impl PriceHistoryParams {
+    pub fn new(address: impl Into<String>, interval: TimeInterval) -> Self {
+        Self {
+            address: address.into(),
+            interval,
+            time_from: None,
+            time_to: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PriceHistoryPoint {
+pub struct PricePoint {
     pub timestamp: i64,
+    /// Close price of the interval
     pub price: f64,
     pub volume: f64,
 }
 
 // Token Security Types
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TokenSecurity {
-    pub address: String,
-    pub total_supply: f64,
-    pub mintable: bool,
-    pub proxied: bool,
-    pub owner_address: String,
-    pub creator_address: String,
-    pub security_checks: SecurityChecks,
+    #[serde(default)]
+    pub creator_address: Option<String>,
+    #[serde(default)]
+    pub owner_address: Option<String>,
+    #[serde(default)]
+    pub creation_time: Option<i64>,
+    #[serde(default)]
+    pub total_supply: Option<f64>,
+    #[serde(default)]
+    pub mutable_metadata: Option<bool>,
+    #[serde(default)]
+    pub freezeable: Option<bool>,
+    #[serde(default)]
+    pub freeze_authority: Option<String>,
+    #[serde(default)]
+    pub transfer_fee_enable: Option<bool>,
+    #[serde(default)]
+    pub non_transferable: Option<bool>,
+    #[serde(default)]
+    pub is_token2022: Option<bool>,
+    #[serde(default)]
+    pub is_true_token: Option<bool>,
+    /// Share of the supply held by the 10 largest holders, from 0 to 1
+    #[serde(default)]
+    pub top10_holder_percent: Option<f64>,
 }
 
+// Trade Types
+/// Swap of a token
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SecurityChecks {
-    pub honeypot: bool,
-    pub trading_cooldown: bool,
-    pub transfer_pausable: bool,
-    pub is_blacklisted: bool,
-    pub is_whitelisted: bool,
-    pub is_proxy: bool,
-    pub is_mintable: bool,
-    pub can_take_back_ownership: bool,
-    pub hidden_owner: bool,
-    pub anti_whale_modifiable: bool,
-    pub is_anti_whale: bool,
-    pub trading_pausable: bool,
-    pub can_be_blacklisted: bool,
-    pub is_true_token: bool,
-    pub is_airdrop_scam: bool,
-    pub slippage_modifiable: bool,
-    pub is_honeypot: bool,
-    pub transfer_pausable_time: bool,
-    pub is_wrapped: bool,
+#[serde(rename_all = "camelCase")]
+pub struct TokenTrade {
+    pub tx_hash: String,
+    pub block_unix_time: i64,
+    /// DEX of the swap
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub owner: String,
+    /// Whether the token was bought or sold
+    pub side: Side,
+    /// Price of the token in USD
+    #[serde(default)]
+    pub token_price: Option<f64>,
+    #[serde(default)]
+    pub pool_id: Option<String>,
+}
+
+// Holder Types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenHolder {
+    pub owner: String,
+    #[serde(default)]
+    pub token_account: String,
+    /// Balance in tokens, adjusted for the decimals
+    #[serde(rename = "ui_amount")]
+    pub amount: f64,
 }
 
 // Token Overview Types
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct TokenOverview {
     pub address: String,
+    #[serde(default)]
     pub symbol: String,
+    #[serde(default)]
     pub name: String,
     pub decimals: u8,
     pub price: f64,
+    #[serde(default)]
+    pub liquidity: f64,
+    #[serde(rename = "priceChange24hPercent", default)]
+    pub price_change_24h: f64,
+    #[serde(rename = "v24hUSD", default)]
     pub volume_24h: f64,
-    pub market_cap: f64,
-    pub fully_diluted_market_cap: Option<f64>,
-    pub total_supply: f64,
+    #[serde(rename = "trade24h", default)]
+    pub trades_24h: u64,
+    #[serde(rename = "marketCap", alias = "mc", default)]
+    pub market_cap: Option<f64>,
+    #[serde(rename = "holder", default)]
+    pub holders: Option<u64>,
+    #[serde(rename = "supply", default)]
+    pub total_supply: Option<f64>,
+    #[serde(default)]
     pub circulating_supply: Option<f64>,
 }
 
@@ -191,31 +324,85 @@ pub struct TokenInfo {
     pub symbol: String,
     pub name: String,
     pub decimals: u8,
+    #[serde(default)]
     pub price: Option<f64>,
+    #[serde(rename = "volume_24h_usd", default)]
     pub volume_24h: Option<f64>,
+    #[serde(default)]
+    pub market_cap: Option<f64>,
+    #[serde(default)]
+    pub liquidity: Option<f64>,
+}
+
+// Liquidity Types
+
+/// Pool or order book of the token on a DEX
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Market {
+    pub address: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub source: String,
+    /// Value locked in the market in USD
+    #[serde(default)]
+    pub liquidity: f64,
+    #[serde(default)]
+    pub price: Option<f64>,
+    #[serde(rename = "volume24h", default)]
+    pub volume_24h: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LiquidityAnalysis {
-    pub total_bid_liquidity: f64,
-    pub total_ask_liquidity: f64,
-    pub bid_ask_ratio: f64,
+    /// Liquidity of all the markets of the token in USD
+    pub total_liquidity: f64,
+    pub volume_24h: f64,
+    pub markets: usize,
+    /// Share of the liquidity in the deepest market, from 0 to 1
+    pub top_market_share: f64,
+    /// Score of the depth of the markets, from 0 (illiquid) to 1
     pub depth_quality: f64,
 }
 
+/// Estimated execution of an order
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketImpact {
+    pub side: Side,
+    /// Distance of the last fill from the current price against the order, in %
     pub price_impact: f64,
+    /// Average price of the fills (VWAP)
     pub executed_price: f64,
+    /// Distance of the average price from the current price against the order, in %
+    pub slippage: f64,
     pub size_usd: f64,
+    /// Size of the order at the current price
     pub size_tokens: f64,
+    /// Tokens filled, less than `size_tokens` when the order book is too thin
+    pub filled_tokens: f64,
+    pub source: ImpactSource,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PricePoint {
-    pub timestamp: i64,
-    pub price: f64,
-    pub volume: f64,
+impl MarketImpact {
+    pub fn new(
+        side: Side,
+        size_usd: f64,
+        size_tokens: f64,
+        execution: Execution,
+        source: ImpactSource,
+    ) -> Self {
+        Self {
+            side,
+            price_impact: execution.price_impact,
+            executed_price: execution.average_price,
+            slippage: execution.slippage,
+            size_usd,
+            size_tokens,
+            filled_tokens: execution.filled_tokens,
+            source,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
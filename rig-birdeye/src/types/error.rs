@@ -8,6 +8,15 @@ pub enum BirdeyeError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("Birdeye API error {status_code}: {message}")]
+    ApiError { status_code: u16, message: String },
+
+    #[error("Invalid Birdeye API key")]
+    InvalidApiKey,
+
+    #[error("Invalid parameters: {0}")]
+    InvalidParameters(String),
+
     #[error("WebSocket error: {0}")]
     WebSocketError(String),
 
@@ -21,8 +30,31 @@ pub enum BirdeyeError {
     CacheError(String),
 }
 
-impl From<BirdeyeError> for rig_core::plugin::ActionError {
-    fn from(error: BirdeyeError) -> Self {
-        rig_core::plugin::ActionError::new(error.to_string())
+impl BirdeyeError {
+    /// Copy of the error for each of the callers sharing a request. The errors of reqwest and
+    /// serde_json cannot be cloned and are copied as invalid responses.
+    pub(crate) fn shared_copy(&self) -> Self {
+        match self {
+            BirdeyeError::ApiError {
+                status_code,
+                message,
+            } => BirdeyeError::ApiError {
+                status_code: *status_code,
+                message: message.clone(),
+            },
+            BirdeyeError::InvalidApiKey => BirdeyeError::InvalidApiKey,
+            BirdeyeError::InvalidParameters(message) => {
+                BirdeyeError::InvalidParameters(message.clone())
+            }
+            BirdeyeError::WebSocketError(message) => BirdeyeError::WebSocketError(message.clone()),
+            BirdeyeError::RateLimitExceeded => BirdeyeError::RateLimitExceeded,
+            BirdeyeError::InvalidResponse(message) => {
+                BirdeyeError::InvalidResponse(message.clone())
+            }
+            BirdeyeError::CacheError(message) => BirdeyeError::CacheError(message.clone()),
+            BirdeyeError::RequestError(_) | BirdeyeError::SerializationError(_) => {
+                BirdeyeError::InvalidResponse(self.to_string())
+            }
+        }
     }
 }
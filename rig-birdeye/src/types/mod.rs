@@ -1,11 +1,12 @@
 pub mod api;
 pub mod error;
+pub mod shared;
 
-use std::fmt;
-use serde::{Deserialize, Serialize};
 use crate::types::error::BirdeyeError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum TimeInterval {
     #[serde(rename = "5m")]
     FiveMinutes,
@@ -23,6 +24,34 @@ pub enum TimeInterval {
     OneMonth,
 }
 
+impl TimeInterval {
+    /// Name of the interval in the `type` parameter of the Birdeye OHLCV endpoint
+    pub fn api_type(&self) -> &'static str {
+        match self {
+            TimeInterval::FiveMinutes => "5m",
+            TimeInterval::FifteenMinutes => "15m",
+            TimeInterval::OneHour => "1H",
+            TimeInterval::FourHours => "4H",
+            TimeInterval::OneDay => "1D",
+            TimeInterval::OneWeek => "1W",
+            TimeInterval::OneMonth => "1M",
+        }
+    }
+
+    /// Length of the interval in seconds, a month being 30 days
+    pub fn seconds(&self) -> i64 {
+        match self {
+            TimeInterval::FiveMinutes => 5 * 60,
+            TimeInterval::FifteenMinutes => 15 * 60,
+            TimeInterval::OneHour => 60 * 60,
+            TimeInterval::FourHours => 4 * 60 * 60,
+            TimeInterval::OneDay => 24 * 60 * 60,
+            TimeInterval::OneWeek => 7 * 24 * 60 * 60,
+            TimeInterval::OneMonth => 30 * 24 * 60 * 60,
+        }
+    }
+}
+
 impl fmt::Display for TimeInterval {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -49,10 +78,58 @@ impl std::str::FromStr for TimeInterval {
             "1d" => Ok(TimeInterval::OneDay),
             "1w" => Ok(TimeInterval::OneWeek),
             "1m" => Ok(TimeInterval::OneMonth),
-            _ => Err(BirdeyeError::InvalidResponse(format!(
+            _ => Err(BirdeyeError::InvalidParameters(format!(
                 "Invalid time interval: {}. Valid intervals are: 5m, 15m, 1h, 4h, 1d, 1w, 1M",
                 s
             ))),
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_interval_to_string() {
+        assert_eq!(TimeInterval::FiveMinutes.to_string(), "5m");
+        assert_eq!(TimeInterval::FifteenMinutes.to_string(), "15m");
+        assert_eq!(TimeInterval::OneHour.to_string(), "1h");
+        assert_eq!(TimeInterval::FourHours.to_string(), "4h");
+        assert_eq!(TimeInterval::OneDay.to_string(), "1d");
+        assert_eq!(TimeInterval::OneWeek.to_string(), "1w");
+        assert_eq!(TimeInterval::OneMonth.to_string(), "1M");
+        assert_eq!(TimeInterval::OneHour.api_type(), "1H");
+    }
+
+    #[test]
+    fn test_time_interval_from_str() {
+        assert_eq!(
+            "5m".parse::<TimeInterval>().unwrap(),
+            TimeInterval::FiveMinutes
+        );
+        assert_eq!(
+            "15m".parse::<TimeInterval>().unwrap(),
+            TimeInterval::FifteenMinutes
+        );
+        assert_eq!("1h".parse::<TimeInterval>().unwrap(), TimeInterval::OneHour);
+        assert_eq!(
+            "4h".parse::<TimeInterval>().unwrap(),
+            TimeInterval::FourHours
+        );
+        assert_eq!("1d".parse::<TimeInterval>().unwrap(), TimeInterval::OneDay);
+        assert_eq!("1w".parse::<TimeInterval>().unwrap(), TimeInterval::OneWeek);
+        assert_eq!(
+            "1M".parse::<TimeInterval>().unwrap(),
+            TimeInterval::OneMonth
+        );
+    }
+
+    #[test]
+    fn test_invalid_time_interval() {
+        assert!(matches!(
+            "invalid".parse::<TimeInterval>(),
+            Err(BirdeyeError::InvalidParameters(_))
+        ));
+    }
+}
@@ -1,8 +1,12 @@
+use crate::types::error::BirdeyeError;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Chain of the requests, sent as the `x-chain` header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Chain {
+    #[default]
     Solana,
     Ethereum,
     Arbitrum,
@@ -15,6 +19,13 @@ pub enum Chain {
     Sui,
 }
 
+impl Chain {
+    /// Whether the addresses of the chain are EVM ones, i.e. 0x and 20 bytes in hex
+    pub fn is_evm(&self) -> bool {
+        !matches!(self, Chain::Solana | Chain::Sui)
+    }
+}
+
 impl std::fmt::Display for Chain {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -32,6 +43,29 @@ impl std::fmt::Display for Chain {
     }
 }
 
+impl std::str::FromStr for Chain {
+    type Err = BirdeyeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "solana" => Ok(Chain::Solana),
+            "ethereum" => Ok(Chain::Ethereum),
+            "arbitrum" => Ok(Chain::Arbitrum),
+            "avalanche" => Ok(Chain::Avalanche),
+            "bsc" => Ok(Chain::Bsc),
+            "optimism" => Ok(Chain::Optimism),
+            "polygon" => Ok(Chain::Polygon),
+            "base" => Ok(Chain::Base),
+            "zksync" => Ok(Chain::Zksync),
+            "sui" => Ok(Chain::Sui),
+            _ => Err(BirdeyeError::InvalidParameters(format!(
+                "Unsupported chain: {}",
+                s
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Address {
     pub address: String,
@@ -50,28 +84,93 @@ pub enum AddressType {
 
 // Helper functions for address validation and chain detection
 impl Address {
-    pub fn detect_chain(address: &str) -> Chain {
-        if address.starts_with("0x") {
-            if address.len() == 66 {
-                Chain::Sui // Sui addresses are 0x + 64 hex chars
-            } else {
-                Chain::Ethereum // Default to Ethereum for other 0x addresses
-            }
-        } else {
-            Chain::Solana // Default to Solana for base58 addresses
+    /// Chain of a well-formed address. EVM chains share their address format, so their
+    /// addresses are detected as Ethereum ones.
+    pub fn detect_chain(address: &str) -> Option<Chain> {
+        match address.strip_prefix("0x") {
+            Some(hex) if is_hex(hex, 32) => Some(Chain::Sui),
+            Some(hex) if is_hex(hex, 20) => Some(Chain::Ethereum),
+            Some(_) => None,
+            None if is_base58(address, 32) => Some(Chain::Solana),
+            None => None,
         }
     }
 
     pub fn is_valid_address(address: &str) -> bool {
-        match Self::detect_chain(address) {
-            Chain::Solana => bs58::decode(address).into_vec().is_ok(),
-            Chain::Sui => address.starts_with("0x") && address.len() == 66 && hex::decode(&address[2..]).is_ok(),
-            _ => address.starts_with("0x") && address.len() == 42 && hex::decode(&address[2..]).is_ok(),
+        Self::detect_chain(address).is_some()
+    }
+
+    /// Checks that the address is well-formed on the chain
+    pub fn validate(address: &str, chain: Chain) -> Result<(), BirdeyeError> {
+        let valid = match Self::detect_chain(address) {
+            Some(Chain::Ethereum) => chain.is_evm(),
+            Some(detected) => detected == chain,
+            None => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(BirdeyeError::InvalidParameters(format!(
+                "{} is not a {} address",
+                address, chain
+            )))
         }
     }
 }
 
-// Constants for API configuration
-pub const API_BASE_URL: &str = "https://public-api.birdeye.so";
-pub const DEFAULT_MAX_RETRIES: u32 = 3;
-pub const RETRY_DELAY_MS: u64 = 2000; 
\ No newline at end of file
+fn is_hex(value: &str, bytes: usize) -> bool {
+    value.len() == 2 * bytes && hex::decode(value).is_ok()
+}
+
+fn is_base58(value: &str, bytes: usize) -> bool {
+    bs58::decode(value)
+        .into_vec()
+        .is_ok_and(|decoded| decoded.len() == bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: &str = "So11111111111111111111111111111111111111112";
+    const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+    const SUI: &str = "0x0000000000000000000000000000000000000000000000000000000000000002";
+
+    #[test]
+    fn test_detect_chain() {
+        assert_eq!(Address::detect_chain(SOL), Some(Chain::Solana));
+        assert_eq!(Address::detect_chain(WETH), Some(Chain::Ethereum));
+        assert_eq!(Address::detect_chain(SUI), Some(Chain::Sui));
+
+        // Too short, not base58 (0 and l) and not hex
+        assert_eq!(Address::detect_chain("So1111"), None);
+        assert_eq!(
+            Address::detect_chain("So0000000000000000000000000000000000000000l"),
+            None
+        );
+        assert_eq!(
+            Address::detect_chain("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Czz"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Address::validate(SOL, Chain::Solana).is_ok());
+        assert!(Address::validate(WETH, Chain::Base).is_ok());
+        assert!(Address::validate(SUI, Chain::Sui).is_ok());
+        assert!(matches!(
+            Address::validate(WETH, Chain::Solana),
+            Err(BirdeyeError::InvalidParameters(_))
+        ));
+        assert!(Address::validate(SOL, Chain::Arbitrum).is_err());
+        assert!(Address::validate(SUI, Chain::Ethereum).is_err());
+    }
+
+    #[test]
+    fn test_chain_from_str() {
+        assert_eq!("Base".parse::<Chain>().unwrap(), Chain::Base);
+        assert_eq!(Chain::Bsc.to_string().parse::<Chain>().unwrap(), Chain::Bsc);
+        assert!("bitcoin".parse::<Chain>().is_err());
+    }
+}
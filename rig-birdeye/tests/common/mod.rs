@@ -0,0 +1,109 @@
+//! Local Birdeye server replaying the recorded responses of tests/fixtures
+#![allow(dead_code)]
+
+use std::{path::PathBuf, time::Duration};
+
+use rig_birdeye::BirdeyeProvider;
+use serde_json::Value;
+use wiremock::{
+    matchers::{header, method, path, query_param},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+/// The only key the server accepts
+pub const API_KEY: &str = "fixture-api-key";
+
+/// Recorded response in tests/fixtures/{name}.json
+pub fn fixture(name: &str) -> Value {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.json", name));
+    let json = std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Failed to read fixture {}: {}", path.display(), err));
+    serde_json::from_str(&json).unwrap_or_else(|err| panic!("Invalid fixture {}: {}", name, err))
+}
+
+pub struct BirdeyeMockServer {
+    server: MockServer,
+}
+
+impl BirdeyeMockServer {
+    /// Server answering 401 to the requests without [API_KEY] and 404 to the endpoints without
+    /// a fixture
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        Mock::given(|request: &Request| {
+            request.headers.get("X-API-KEY").map(|key| key.as_bytes()) != Some(API_KEY.as_bytes())
+        })
+        .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+            "success": false,
+            "message": "Unauthorized"
+        })))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+
+        Self { server }
+    }
+
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Provider sending its requests to the server, retrying quickly
+    pub fn provider(&self) -> BirdeyeProvider {
+        self.provider_with_key(API_KEY)
+    }
+
+    pub fn provider_with_key(&self, api_key: &str) -> BirdeyeProvider {
+        BirdeyeProvider::builder(api_key)
+            .base_url(&self.uri())
+            .retry_delay(Duration::from_millis(10))
+            .build()
+    }
+
+    /// Answers the endpoint with the fixture
+    pub async fn replay(&self, endpoint: &str, fixture_name: &str) {
+        self.replay_query(endpoint, &[], fixture_name).await;
+    }
+
+    /// Answers the requests of the endpoint with the query parameters with the fixture
+    pub async fn replay_query(&self, endpoint: &str, query: &[(&str, &str)], fixture_name: &str) {
+        let mut mock = Mock::given(method("GET"))
+            .and(path(endpoint))
+            .and(header("X-API-KEY", API_KEY));
+        for (name, value) in query {
+            mock = mock.and(query_param(*name, *value));
+        }
+        mock.respond_with(ResponseTemplate::new(200).set_body_json(fixture(fixture_name)))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Fails the next `times` requests of the endpoint with the status, before the fixtures
+    pub async fn fail(&self, endpoint: &str, status: u16, times: u64) {
+        Mock::given(method("GET"))
+            .and(path(endpoint))
+            .respond_with(
+                ResponseTemplate::new(status).set_body_json(serde_json::json!({
+                    "success": false,
+                    "message": format!("Simulated {}", status)
+                })),
+            )
+            .up_to_n_times(times)
+            .with_priority(2)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Requests received by the endpoint
+    pub async fn requests(&self, endpoint: &str) -> Vec<Request> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|request| request.url.path() == endpoint)
+            .collect()
+    }
+}
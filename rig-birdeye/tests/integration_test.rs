@@ -1,82 +1,191 @@
+mod common;
+
+use common::BirdeyeMockServer;
+use futures_util::{StreamExt, TryStreamExt};
 use rig_birdeye::{
-    actions::{TokenSearchAction, WalletSearchAction},
-    providers::birdeye::BirdeyeProvider,
+    providers::StreamOptions,
     types::{
-        api::{TokenSortBy, SortType, TokenSearchParams},
+        api::{SortType, TokenSearchParams, TokenSortBy},
         error::BirdeyeError,
     },
+    BirdeyePlugin, Chain, Side, TimeInterval,
 };
-use std::env;
-
-fn setup() -> BirdeyeProvider {
-    // Load environment variables from .env file
-    dotenv::dotenv().ok();
-    
-    // Initialize logging
-    tracing_subscriber::fmt::init();
-    
-    // Create Birdeye provider
-    let api_key = env::var("BIRDEYE_API_KEY")
-        .expect("BIRDEYE_API_KEY must be set");
-    
-    BirdeyeProvider::new(&api_key)
-}
+use rig_core::plugin::PluginRegistry;
+use serde_json::json;
+
+const SOL: &str = "So11111111111111111111111111111111111111112";
+const WALLET: &str = "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK";
+const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
 
 #[tokio::test]
 async fn test_token_search() -> Result<(), Box<dyn std::error::Error>> {
-    let provider = setup();
-
-    let params = TokenSearchParams {
-        keyword: "SOL".to_string(),
-        sort_by: Some(TokenSortBy::Volume),
-        sort_type: Some(SortType::Descending),
-        offset: None,
-        limit: Some(10),
-    };
+    let server = BirdeyeMockServer::start().await;
+    server.replay("/defi/v3/search", "search_sol_page1").await;
+    let provider = server.provider();
 
+    let params = TokenSearchParams::new("SOL")
+        .with_sort(TokenSortBy::Volume, SortType::Descending)
+        .with_limit(2);
     let tokens = provider.search_tokens(params).await?;
-    assert!(!tokens.is_empty(), "No tokens found");
-    
+    assert_eq!(tokens.len(), 2);
+
     // Validate first token
     let token = &tokens[0];
-    assert!(!token.address.is_empty(), "Token address is empty");
-    assert!(!token.symbol.is_empty(), "Token symbol is empty");
-    assert!(token.price_usd > 0.0, "Token price should be positive");
-    assert!(token.volume_24h > 0.0, "Token volume should be positive");
+    assert_eq!(token.address, SOL);
+    assert_eq!(token.symbol, "SOL");
+    assert_eq!(token.price, Some(150.8216));
+    assert_eq!(token.volume_24h, Some(4197534118.93));
+
+    let requests = server.requests("/defi/v3/search").await;
+    let query: Vec<_> = requests[0].url.query_pairs().into_owned().collect();
+    for param in [
+        ("keyword", "SOL"),
+        ("sort_by", "volume_24h_usd"),
+        ("sort_type", "desc"),
+        ("limit", "2"),
+    ] {
+        assert!(
+            query.contains(&(param.0.to_string(), param.1.to_string())),
+            "Missing {:?}",
+            param
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_search_pagination() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay_query("/defi/v3/search", &[("offset", "0")], "search_sol_page1")
+        .await;
+    server
+        .replay_query("/defi/v3/search", &[("offset", "2")], "search_sol_page2")
+        .await;
+    let provider = server.provider();
+
+    // The second page is short, so it is the last one. Its market results are not tokens.
+    let tokens: Vec<_> = provider
+        .search_tokens_stream(TokenSearchParams::new("SOL"), StreamOptions::new(2))
+        .try_collect()
+        .await?;
+    let symbols: Vec<_> = tokens.iter().map(|token| token.symbol.as_str()).collect();
+    assert_eq!(symbols, ["SOL", "compassSOL", "mSOL"]);
+    assert_eq!(server.requests("/defi/v3/search").await.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_trades_stream() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay_query(
+            "/defi/txs/token/seek_by_time",
+            &[("before_time", "1729256400")],
+            "txs_sol_page1",
+        )
+        .await;
+    server
+        .replay_query(
+            "/defi/txs/token/seek_by_time",
+            &[("before_time", "1729256391")],
+            "txs_sol_page2",
+        )
+        .await;
+    let provider = server.provider();
+
+    // Each page continues before the last trade of the previous one
+    let trades: Vec<_> = provider
+        .trades_stream(SOL, Some(1729256400), StreamOptions::new(2))
+        .try_collect()
+        .await?;
+    let times: Vec<_> = trades.iter().map(|trade| trade.block_unix_time).collect();
+    assert_eq!(times, [1729256398, 1729256391, 1729256380]);
+    assert_eq!(trades[1].side, Side::Sell);
+    assert_eq!(trades[2].pool_id, None);
+
+    // Trades are never cached, the cap stops the paging
+    let trades: Vec<_> = provider
+        .trades_stream(
+            SOL,
+            Some(1729256400),
+            StreamOptions::new(2).with_max_items(1),
+        )
+        .try_collect()
+        .await?;
+    assert_eq!(trades.len(), 1);
+    let requests = server.requests("/defi/txs/token/seek_by_time").await;
+    assert_eq!(requests.len(), 3);
+    assert!(requests[2].url.query().unwrap().contains("limit=1"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_holders_stream() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay_query("/defi/v3/token/holder", &[("offset", "0")], "holders_sol")
+        .await;
+    let provider = server.provider();
+
+    // The first page is full, the second one is not found and ends the stream
+    let results: Vec<_> = provider
+        .holders_stream(SOL, StreamOptions::new(2).with_prefetch(1))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().amount, 1820042.0);
+    assert_eq!(
+        results[1].as_ref().unwrap().owner,
+        "8ZUczUAUSLHbdaVz4hvn6gEjpZQqDzD3o8oJoBFZZ2yL"
+    );
+    assert!(matches!(
+        results[2],
+        Err(BirdeyeError::ApiError {
+            status_code: 404,
+            ..
+        })
+    ));
 
     Ok(())
 }
 
 #[tokio::test]
 async fn test_wallet_search() -> Result<(), Box<dyn std::error::Error>> {
-    let provider = setup();
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay_query(
+            "/v1/wallet/token_list",
+            &[("wallet", WALLET)],
+            "wallet_token_list",
+        )
+        .await;
+    let provider = server.provider();
 
-    // Use a known Solana wallet address for testing
-    let wallet_address = "DYw8jCTfwHNRJhhmFcbXvVDTqWMEVFBX6ZKUmG5CNSKK".to_string();
+    let portfolio = provider.get_wallet_portfolio(WALLET).await?;
+    assert_eq!(portfolio.wallet, WALLET, "Wallet address mismatch");
+    assert_eq!(portfolio.total_usd, 23853.41);
+    assert_eq!(portfolio.items.len(), 3);
+    assert_eq!(portfolio.items[1].symbol.as_deref(), Some("USDC"));
+    assert_eq!(portfolio.items[1].amount, 5679.41);
 
-    let portfolio = provider.search_wallet(&wallet_address).await?;
-    assert_eq!(portfolio.wallet, wallet_address, "Wallet address mismatch");
-    assert!(portfolio.total_usd >= 0.0, "Total USD should be non-negative");
-    assert!(!portfolio.items.is_empty(), "Portfolio should contain tokens");
+    // Unknown tokens have no metadata nor price
+    assert_eq!(portfolio.items[2].symbol, None);
+    assert_eq!(portfolio.items[2].value_usd, None);
 
     Ok(())
 }
 
 #[tokio::test]
 async fn test_invalid_api_key() {
-    let provider = BirdeyeProvider::new("invalid_key");
-
-    let params = TokenSearchParams {
-        keyword: "SOL".to_string(),
-        sort_by: None,
-        sort_type: None,
-        offset: None,
-        limit: None,
-    };
+    let server = BirdeyeMockServer::start().await;
+    server.replay("/defi/v3/search", "search_sol_page1").await;
+    let provider = server.provider_with_key("invalid_key");
 
-    let result = provider.search_tokens(params).await;
-    assert!(result.is_err(), "Expected error with invalid API key");
-    
+    let result = provider.search_tokens(TokenSearchParams::new("SOL")).await;
     match result.unwrap_err() {
         BirdeyeError::InvalidApiKey => (),
         err => panic!("Expected InvalidApiKey error, got: {:?}", err),
@@ -85,17 +194,218 @@ async fn test_invalid_api_key() {
 
 #[tokio::test]
 async fn test_token_overview() -> Result<(), Box<dyn std::error::Error>> {
-    let provider = setup();
-    
-    // Use SOL token address for testing
-    let token_address = "So11111111111111111111111111111111111111112";
-    let overview = provider.get_token_overview(token_address).await?;
-    
-    assert_eq!(overview.address, token_address);
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay_query(
+            "/defi/token_overview",
+            &[("address", SOL)],
+            "token_overview_sol",
+        )
+        .await;
+    let provider = server.provider();
+
+    let overview = provider.get_token_overview(SOL).await?;
+    assert_eq!(overview.address, SOL);
     assert_eq!(overview.symbol, "SOL");
-    assert!(overview.price > 0.0);
-    assert!(overview.volume_24h > 0.0);
+    assert_eq!(overview.price, 150.8216);
+    assert_eq!(overview.price_change_24h, -2.4131);
+    assert_eq!(overview.volume_24h, 4197534118.93);
+    assert_eq!(overview.market_cap, Some(89452938361.06));
+    assert_eq!(overview.holders, Some(1101327));
     assert!(overview.liquidity > 0.0);
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_price_history() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay_query(
+            "/defi/ohlcv",
+            &[("address", SOL), ("type", "1H")],
+            "ohlcv_sol_1h",
+        )
+        .await;
+    let provider = server.provider();
+
+    let history = provider
+        .get_price_history(SOL, TimeInterval::OneHour)
+        .await?;
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].timestamp, 1729245600);
+    assert_eq!(history[2].price, 151.0318);
+    assert_eq!(history[1].volume, 598361.54);
+
+    // 100 hourly candles up to now
+    let request = &server.requests("/defi/ohlcv").await[0];
+    let param = |name: &str| -> i64 {
+        request
+            .url
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.parse().unwrap())
+            .unwrap()
+    };
+    assert_eq!(param("time_to") - param("time_from"), 100 * 3600);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_security() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay("/defi/token_security", "token_security_sol")
+        .await;
+    let provider = server.provider();
+
+    let security = provider.get_token_security(SOL).await?;
+    assert_eq!(security.creator_address, None);
+    assert_eq!(security.freezeable, None);
+    assert_eq!(security.is_token2022, Some(false));
+    assert_eq!(security.top10_holder_percent, Some(0.2056));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_liquidity_and_market_impact() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server.replay("/defi/v2/markets", "markets_sol").await;
+    server
+        .replay("/defi/token_overview", "token_overview_sol")
+        .await;
+    let provider = server.provider();
+
+    let liquidity = provider.analyze_liquidity(SOL).await?;
+    assert_eq!(liquidity.total_liquidity, 32000000.0);
+    assert_eq!(liquidity.markets, 2);
+    assert_eq!(liquidity.top_market_share, 0.75);
+    assert!(liquidity.depth_quality > 0.8);
+
+    // $160,000 against $16M of quote reserve
+    let impact = provider.get_market_impact(SOL, Side::Buy, 160000.0).await?;
+    assert!((impact.slippage - 100.0 / 99.0).abs() < 1e-9);
+    assert!((impact.executed_price - 150.8216 / 0.99).abs() < 1e-9);
+    assert!((impact.price_impact - (1.0 / (0.99f64 * 0.99) - 1.0) * 100.0).abs() < 1e-9);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chain_header() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay("/defi/token_overview", "token_overview_sol")
+        .await;
+    server.replay("/defi/v3/search", "search_sol_page1").await;
+    let provider = server.provider();
+
+    // Solana unless told otherwise
+    provider.get_token_overview(SOL).await?;
+    let base = provider.with_chain(Chain::Base);
+    base.get_token_overview(WETH).await?;
+    base.search_tokens(TokenSearchParams::new("WETH")).await?;
+
+    let chains: Vec<_> = server
+        .requests("/defi/token_overview")
+        .await
+        .iter()
+        .map(|request| {
+            request
+                .headers
+                .get("x-chain")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(chains, ["solana", "base"]);
+    let search = &server.requests("/defi/v3/search").await[0];
+    assert_eq!(search.headers.get("x-chain").unwrap(), "base");
+    assert!(search.url.query().unwrap().contains("chain=base"));
+
+    // Addresses of other chains are rejected before any request
+    let result = base.get_token_security(SOL).await;
+    assert!(matches!(result, Err(BirdeyeError::InvalidParameters(_))));
+    assert!(server.requests("/defi/token_security").await.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retries() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server
+        .replay("/defi/token_overview", "token_overview_sol")
+        .await;
+    server.fail("/defi/token_overview", 429, 1).await;
+    server.fail("/defi/token_overview", 502, 1).await;
+    let provider = server.provider();
+
+    // Rate limited then failing on the server, the third attempt succeeds
+    let overview = provider.get_token_overview(SOL).await?;
+    assert_eq!(overview.symbol, "SOL");
+    assert_eq!(server.requests("/defi/token_overview").await.len(), 3);
+
+    // Retries give up after 3 of them
+    server.fail("/defi/token_security", 503, 10).await;
+    let result = provider.get_token_security(SOL).await;
+    assert!(matches!(
+        result,
+        Err(BirdeyeError::ApiError {
+            status_code: 503,
+            ..
+        })
+    ));
+    assert_eq!(server.requests("/defi/token_security").await.len(), 4);
+
+    // Client errors are not retried
+    let result = provider.get_wallet_portfolio(WALLET).await;
+    assert!(matches!(
+        result,
+        Err(BirdeyeError::ApiError {
+            status_code: 404,
+            ..
+        })
+    ));
+    assert_eq!(server.requests("/v1/wallet/token_list").await.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plugin_actions() -> Result<(), Box<dyn std::error::Error>> {
+    let server = BirdeyeMockServer::start().await;
+    server.replay("/defi/v3/search", "search_sol_page1").await;
+    server
+        .replay("/v1/wallet/token_list", "wallet_token_list")
+        .await;
+
+    let registry = PluginRegistry::new()
+        .context(server.provider())
+        .plugin(&BirdeyePlugin);
+
+    let tokens = registry
+        .call(
+            "birdeye.search_tokens",
+            json!({ "keyword": "SOL", "sort_by": "liquidity" }),
+        )
+        .await?;
+    assert_eq!(tokens[0]["symbol"], "SOL");
+    let requests = server.requests("/defi/v3/search").await;
+    assert!(requests[0]
+        .url
+        .query()
+        .unwrap()
+        .contains("sort_by=liquidity"));
+
+    let portfolio = registry
+        .call("birdeye.search_wallet", json!({ "address": WALLET }))
+        .await?;
+    assert_eq!(portfolio["totalUsd"], 23853.41);
+
+    Ok(())
+}
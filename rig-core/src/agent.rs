@@ -115,8 +115,11 @@ use crate::{
         Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder,
         CompletionResponse, Document, Message, ModelChoice, Prompt, PromptError,
     },
+    context::{ContextAssembler, ContextParts},
+    plugin::{ActionError, PluginRegistry},
+    rerank::{reciprocal_rank_fusion, RerankerDyn},
     tool::{Tool, ToolSet},
-    vector_store::{VectorStoreError, VectorStoreIndexDyn},
+    vector_store::{scoring::SearchOptions, VectorStoreError, VectorStoreIndexDyn},
 };
 
 /// Struct reprensenting an LLM agent. An agent is an LLM model combined with a preamble
@@ -154,10 +157,17 @@ pub struct Agent<M: CompletionModel> {
     max_tokens: Option<u64>,
     /// Additional parameters to be passed to the model
     additional_params: Option<serde_json::Value>,
-    /// List of vector store, with the sample number
-    dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
+    /// List of vector store, with the sample number and search options
+    dynamic_context: Vec<(usize, SearchOptions, Box<dyn VectorStoreIndexDyn>)>,
+    /// Constant `k` used to merge the results of the dynamic context indexes with reciprocal rank
+    /// fusion. If not set, the results are concatenated.
+    rank_fusion: Option<f64>,
+    /// Reranker applied to the dynamic context, with the number of documents to keep
+    reranker: Option<(usize, Box<dyn RerankerDyn>)>,
     /// Dynamic tools
     dynamic_tools: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
+    /// Assembler used to fit the request in the context window of the model
+    context_assembler: Option<ContextAssembler>,
     /// Actual tool implementations
     pub tools: ToolSet,
 }
@@ -169,33 +179,58 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
         chat_history: Vec<Message>,
     ) -> Result<CompletionRequestBuilder<M>, CompletionError> {
         let dynamic_context = stream::iter(self.dynamic_context.iter())
-            .then(|(num_sample, index)| async {
-                Ok::<_, VectorStoreError>(
-                    index
-                        .top_n(prompt, *num_sample)
-                        .await?
-                        .into_iter()
-                        .map(|(_, id, doc)| {
-                            // Pretty print the document if possible for better readability
-                            let text = serde_json::to_string_pretty(&doc)
-                                .unwrap_or_else(|_| doc.to_string());
+            .then(|(num_sample, options, index)| async {
+                let results = index
+                    .top_n(prompt, options.fetch_size(*num_sample))
+                    .await?
+                    .into_iter()
+                    .map(|(score, id, doc)| {
+                        // Pretty print the document if possible for better readability
+                        let text =
+                            serde_json::to_string_pretty(&doc).unwrap_or_else(|_| doc.to_string());
 
+                        (
+                            score,
                             Document {
                                 id,
                                 text,
                                 additional_props: HashMap::new(),
-                            }
-                        })
+                            },
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok::<_, VectorStoreError>(
+                    options
+                        .apply(results, *num_sample)
+                        .into_iter()
+                        .map(|(_, doc)| doc)
                         .collect::<Vec<_>>(),
                 )
             })
-            .try_fold(vec![], |mut acc, docs| async {
-                acc.extend(docs);
-                Ok(acc)
-            })
+            .try_collect::<Vec<_>>()
             .await
             .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
 
+        let dynamic_context = match self.rank_fusion {
+            Some(k) => reciprocal_rank_fusion(dynamic_context, k)
+                .into_iter()
+                .map(|(_, doc)| doc)
+                .collect(),
+            None => dynamic_context.concat(),
+        };
+
+        let dynamic_context = match &self.reranker {
+            Some((top_k, reranker)) => reranker
+                .rerank(prompt, dynamic_context, *top_k)
+                .await
+                .map_err(|e| CompletionError::RequestError(Box::new(e)))?
+                .into_iter()
+                .map(|(_, doc)| doc)
+                .collect(),
+            None => dynamic_context,
+        };
+
         let dynamic_tools = stream::iter(self.dynamic_tools.iter())
             .then(|(num_sample, index)| async {
                 Ok::<_, VectorStoreError>(
@@ -232,13 +267,29 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
             .collect::<Vec<_>>()
             .await;
 
+        let parts = ContextParts {
+            prompt: prompt.to_string(),
+            preamble: Some(self.preamble.clone()),
+            static_context: self.static_context.clone(),
+            dynamic_context,
+            tools: [static_tools, dynamic_tools].concat(),
+            chat_history,
+        };
+
+        let parts = match &self.context_assembler {
+            Some(assembler) => assembler
+                .assemble(parts, self.max_tokens)
+                .map_err(|e| CompletionError::RequestError(Box::new(e)))?,
+            None => parts,
+        };
+
         Ok(self
             .model
             .completion_request(prompt)
-            .preamble(self.preamble.clone())
-            .messages(chat_history)
-            .documents([self.static_context.clone(), dynamic_context].concat())
-            .tools([static_tools.clone(), dynamic_tools].concat())
+            .preamble(parts.preamble.unwrap_or_default())
+            .messages(parts.chat_history)
+            .documents([parts.static_context, parts.dynamic_context].concat())
+            .tools(parts.tools)
             .temperature_opt(self.temperature)
             .max_tokens_opt(self.max_tokens)
             .additional_params_opt(self.additional_params.clone()))
@@ -306,10 +357,17 @@ pub struct AgentBuilder<M: CompletionModel> {
     additional_params: Option<serde_json::Value>,
     /// Maximum number of tokens for the completion
     max_tokens: Option<u64>,
-    /// List of vector store, with the sample number
-    dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
+    /// List of vector store, with the sample number and search options
+    dynamic_context: Vec<(usize, SearchOptions, Box<dyn VectorStoreIndexDyn>)>,
+    /// Constant `k` used to merge the results of the dynamic context indexes with reciprocal rank
+    /// fusion. If not set, the results are concatenated.
+    rank_fusion: Option<f64>,
+    /// Reranker applied to the dynamic context, with the number of documents to keep
+    reranker: Option<(usize, Box<dyn RerankerDyn>)>,
     /// Dynamic tools
     dynamic_tools: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
+    /// Assembler used to fit the request in the context window of the model
+    context_assembler: Option<ContextAssembler>,
     /// Temperature of the model
     temperature: Option<f64>,
     /// Actual tool implementations
@@ -327,7 +385,10 @@ impl<M: CompletionModel> AgentBuilder<M> {
             max_tokens: None,
             additional_params: None,
             dynamic_context: vec![],
+            rank_fusion: None,
+            reranker: None,
             dynamic_tools: vec![],
+            context_assembler: None,
             tools: ToolSet::default(),
         }
     }
@@ -366,15 +427,53 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Add the actions of the plugins of the registry as static tools. Fails if the context of
+    /// an action is missing from the registry.
+    pub fn plugins(self, registry: &PluginRegistry) -> Result<Self, ActionError> {
+        Ok(registry
+            .tools()?
+            .into_iter()
+            .fold(self, |builder, tool| builder.tool(tool)))
+    }
+
     /// Add some dynamic context to the agent. On each prompt, `sample` documents from the
     /// dynamic context will be inserted in the request.
     pub fn dynamic_context(
+        self,
+        sample: usize,
+        dynamic_context: impl VectorStoreIndexDyn + 'static,
+    ) -> Self {
+        self.dynamic_context_with_options(sample, SearchOptions::default(), dynamic_context)
+    }
+
+    /// Same as [AgentBuilder::dynamic_context], but with [SearchOptions] to normalize the scores
+    /// of the index, filter out documents below a minimum score and diversify the sampled
+    /// documents with maximal marginal relevance. Fewer than `sample` documents may be inserted
+    /// if not enough documents pass the minimum score.
+    pub fn dynamic_context_with_options(
         mut self,
         sample: usize,
+        options: SearchOptions,
         dynamic_context: impl VectorStoreIndexDyn + 'static,
     ) -> Self {
         self.dynamic_context
-            .push((sample, Box::new(dynamic_context)));
+            .push((sample, options, Box::new(dynamic_context)));
+        self
+    }
+
+    /// Merge the documents retrieved from the dynamic context indexes using reciprocal rank fusion
+    /// with the constant `k` (see [reciprocal_rank_fusion]), instead of concatenating them.
+    /// Documents returned by several indexes are only included once.
+    pub fn reciprocal_rank_fusion(mut self, k: f64) -> Self {
+        self.rank_fusion = Some(k);
+        self
+    }
+
+    /// Rerank the documents retrieved from the dynamic context on each prompt and keep the `top_k`
+    /// most relevant ones. Combine with a larger `sample` in [AgentBuilder::dynamic_context] to
+    /// over-fetch candidates for the reranker.
+    pub fn reranker(mut self, top_k: usize, reranker: impl RerankerDyn + 'static) -> Self {
+        self.reranker = Some((top_k, Box::new(reranker)));
         self
     }
 
@@ -391,6 +490,14 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Fit the preamble, context documents, tool definitions and chat history of each request in
+    /// the context window of the model using the given [ContextAssembler]
+    /// (e.g.: `ContextAssembler::for_model(&model)`).
+    pub fn context_assembler(mut self, assembler: ContextAssembler) -> Self {
+        self.context_assembler = Some(assembler);
+        self
+    }
+
     /// Set the temperature of the model
     pub fn temperature(mut self, temperature: f64) -> Self {
         self.temperature = Some(temperature);
@@ -420,7 +527,10 @@ impl<M: CompletionModel> AgentBuilder<M> {
             max_tokens: self.max_tokens,
             additional_params: self.additional_params,
             dynamic_context: self.dynamic_context,
+            rank_fusion: self.rank_fusion,
+            reranker: self.reranker,
             dynamic_tools: self.dynamic_tools,
+            context_assembler: self.context_assembler,
             tools: self.tools,
         }
     }
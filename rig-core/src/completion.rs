@@ -62,12 +62,16 @@
 //!
 //! For more information on how to use the completion functionality, refer to the documentation of
 //! the individual traits, structs, and enums defined in this module.
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{json_utils, tool::ToolSetError};
+use crate::{
+    json_utils,
+    tokenizer::{HeuristicTokenizer, Tokenizer},
+    tool::ToolSetError,
+};
 
 // Errors
 #[derive(Debug, Error)]
@@ -243,6 +247,17 @@ pub trait CompletionModel: Clone + Send + Sync {
     fn completion_request(&self, prompt: &str) -> CompletionRequestBuilder<Self> {
         CompletionRequestBuilder::new(self.clone(), prompt.to_string())
     }
+
+    /// Returns the tokenizer used to count the tokens of requests sent to the model.
+    /// Defaults to a [HeuristicTokenizer](crate::tokenizer::HeuristicTokenizer).
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        Arc::new(HeuristicTokenizer::default())
+    }
+
+    /// Returns the context window of the model (in tokens, including the completion), if known.
+    fn context_window(&self) -> Option<usize> {
+        None
+    }
 }
 
 /// Struct representing a general completion request that can be sent to a completion model provider.
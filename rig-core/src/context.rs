@@ -0,0 +1,480 @@
+//! This module provides the [ContextAssembler], which fits the different parts of a completion
+//! request (preamble, static context, dynamic context, tool definitions and chat history) in the
+//! context window of a model.
+//!
+//! Each part of the request is a [ContextSection]. Sections are granted tokens in priority order,
+//! and each section has a [TruncationPolicy] describing what happens when it does not fit in the
+//! remaining budget. The prompt itself is always included in full.
+//!
+//! # Example
+//! ```no_run
+//! use rig::{
+//!     context::{ContextAssembler, ContextSection, TruncationPolicy},
+//!     providers::openai,
+//! };
+//! # use rig::vector_store::in_memory_store::InMemoryVectorStore;
+//!
+//! let openai = openai::Client::from_env();
+//! let model = openai.completion_model(openai::GPT_4O);
+//! # let index = InMemoryVectorStore::<String>::default()
+//! #     .index(openai.embedding_model(openai::TEXT_EMBEDDING_ADA_002));
+//!
+//! let assembler = ContextAssembler::for_model(&model)
+//!     // Keep room for a 2000 tokens answer
+//!     .reserve_output(2000)
+//!     // Keep the most recent chat messages before the retrieved documents
+//!     .priorities([
+//!         ContextSection::Preamble,
+//!         ContextSection::Tools,
+//!         ContextSection::ChatHistory,
+//!         ContextSection::StaticContext,
+//!         ContextSection::DynamicContext,
+//!     ])
+//!     .policy(ContextSection::Preamble, TruncationPolicy::Required);
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .dynamic_context(10, index)
+//!     .context_assembler(assembler)
+//!     .build();
+//! ```
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    completion::{CompletionModel, CompletionRequest, Document, Message, ToolDefinition},
+    tokenizer::{HeuristicTokenizer, Tokenizer},
+};
+
+/// Context window used by [ContextAssembler::for_model] when the context window of the model is
+///  not known.
+pub const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+/// Approximate number of tokens added by providers around each message and document
+///  (role markers, separators, etc.).
+const ITEM_OVERHEAD: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ContextError {
+    /// The prompt alone does not fit in the context window
+    #[error("Prompt requires {required} tokens but only {available} are available")]
+    PromptTooLarge { required: usize, available: usize },
+
+    /// A [TruncationPolicy::Required] section does not fit in the remaining budget
+    #[error("{section:?} requires {required} tokens but only {available} are available")]
+    BudgetExceeded {
+        section: ContextSection,
+        required: usize,
+        available: usize,
+    },
+}
+
+/// The parts of a completion request that the [ContextAssembler] can fit in the budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContextSection {
+    Preamble,
+    StaticContext,
+    DynamicContext,
+    Tools,
+    ChatHistory,
+}
+
+/// What to do with a [ContextSection] that does not entirely fit in the remaining budget.
+///
+/// Items of a section are considered in order, except for the chat history where the most
+///  recent messages are considered first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Keep the items that fit and drop the rest.
+    Drop,
+    /// Keep the items that fit, truncate the text of the first item that does not fit and drop
+    /// the rest. Tool definitions cannot be truncated and are dropped instead.
+    Truncate,
+    /// Fail with [ContextError::BudgetExceeded] if the whole section does not fit.
+    Required,
+}
+
+/// The parts of a completion request, as assembled by an [Agent](crate::agent::Agent) before
+///  being fitted in the budget.
+#[derive(Clone, Debug, Default)]
+pub struct ContextParts {
+    pub prompt: String,
+    pub preamble: Option<String>,
+    pub static_context: Vec<Document>,
+    pub dynamic_context: Vec<Document>,
+    pub tools: Vec<ToolDefinition>,
+    pub chat_history: Vec<Message>,
+}
+
+/// Fits the parts of a completion request in the context window of a model.
+/// See the [module documentation](self) for more information.
+#[derive(Clone)]
+pub struct ContextAssembler {
+    tokenizer: Arc<dyn Tokenizer>,
+    context_window: usize,
+    reserved_output: usize,
+    priorities: Vec<ContextSection>,
+    policies: HashMap<ContextSection, TruncationPolicy>,
+}
+
+impl ContextAssembler {
+    /// Creates a new assembler for a context window of `context_window` tokens, counted with
+    ///  `tokenizer`.
+    ///
+    /// By default, sections are prioritized as follows: preamble, tool definitions, static
+    ///  context, chat history and dynamic context. The preamble and the context documents are
+    ///  truncated while tool definitions and chat messages are dropped.
+    pub fn new(tokenizer: Arc<dyn Tokenizer>, context_window: usize) -> Self {
+        Self {
+            tokenizer,
+            context_window,
+            reserved_output: 0,
+            priorities: vec![
+                ContextSection::Preamble,
+                ContextSection::Tools,
+                ContextSection::StaticContext,
+                ContextSection::ChatHistory,
+                ContextSection::DynamicContext,
+            ],
+            policies: HashMap::from([
+                (ContextSection::Preamble, TruncationPolicy::Truncate),
+                (ContextSection::Tools, TruncationPolicy::Drop),
+                (ContextSection::StaticContext, TruncationPolicy::Truncate),
+                (ContextSection::ChatHistory, TruncationPolicy::Drop),
+                (ContextSection::DynamicContext, TruncationPolicy::Truncate),
+            ]),
+        }
+    }
+
+    /// Creates a new assembler using the tokenizer and context window of `model`. If the context
+    ///  window of the model is not known, [DEFAULT_CONTEXT_WINDOW] is used.
+    pub fn for_model<M: CompletionModel>(model: &M) -> Self {
+        Self::new(
+            model.tokenizer(),
+            model.context_window().unwrap_or(DEFAULT_CONTEXT_WINDOW),
+        )
+    }
+
+    /// Set the number of tokens reserved for the completion. If the request sets `max_tokens`,
+    ///  the largest of the two values is reserved.
+    pub fn reserve_output(mut self, tokens: usize) -> Self {
+        self.reserved_output = tokens;
+        self
+    }
+
+    /// Set the order in which sections are granted tokens. Sections that are not listed are
+    ///  dropped entirely.
+    pub fn priorities(mut self, priorities: impl IntoIterator<Item = ContextSection>) -> Self {
+        self.priorities = priorities.into_iter().collect();
+        self
+    }
+
+    /// Set the truncation policy of a section
+    pub fn policy(mut self, section: ContextSection, policy: TruncationPolicy) -> Self {
+        self.policies.insert(section, policy);
+        self
+    }
+
+    /// Returns the number of tokens of `text`, as counted by the tokenizer of the assembler.
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.tokenizer.count_tokens(text)
+    }
+
+    /// Fits `parts` in the budget. `max_output` is the maximum number of tokens of the completion,
+    ///  if set on the request.
+    pub fn assemble(
+        &self,
+        parts: ContextParts,
+        max_output: Option<u64>,
+    ) -> Result<ContextParts, ContextError> {
+        let reserved = self
+            .reserved_output
+            .max(max_output.unwrap_or_default() as usize);
+        let prompt_tokens = self.count_tokens(&parts.prompt) + ITEM_OVERHEAD;
+        let available = self.context_window.saturating_sub(reserved);
+        let mut budget =
+            available
+                .checked_sub(prompt_tokens)
+                .ok_or(ContextError::PromptTooLarge {
+                    required: prompt_tokens,
+                    available,
+                })?;
+
+        let mut fitted = ContextParts {
+            prompt: parts.prompt,
+            ..Default::default()
+        };
+        let mut preamble = parts.preamble;
+        let mut static_context = Some(parts.static_context);
+        let mut dynamic_context = Some(parts.dynamic_context);
+        let mut tools = Some(parts.tools);
+        let mut chat_history = Some(parts.chat_history);
+
+        for section in &self.priorities {
+            let policy = self
+                .policies
+                .get(section)
+                .copied()
+                .unwrap_or(TruncationPolicy::Drop);
+
+            match section {
+                ContextSection::Preamble => {
+                    if let Some(text) = preamble.take() {
+                        fitted.preamble = self
+                            .fit_items(
+                                *section,
+                                policy,
+                                vec![text],
+                                &mut budget,
+                                String::clone,
+                                truncate,
+                            )?
+                            .pop();
+                    }
+                }
+                ContextSection::StaticContext | ContextSection::DynamicContext => {
+                    let (source, target) = match section {
+                        ContextSection::StaticContext => {
+                            (&mut static_context, &mut fitted.static_context)
+                        }
+                        _ => (&mut dynamic_context, &mut fitted.dynamic_context),
+                    };
+                    if let Some(docs) = source.take() {
+                        *target = self.fit_items(
+                            *section,
+                            policy,
+                            docs,
+                            &mut budget,
+                            |doc| doc.to_string(),
+                            |doc, tokenizer, tokens| Document {
+                                text: tokenizer.truncate(&doc.text, tokens),
+                                ..doc
+                            },
+                        )?;
+                    }
+                }
+                ContextSection::Tools => {
+                    if let Some(defs) = tools.take() {
+                        // Tool definitions cannot be truncated
+                        let policy = match policy {
+                            TruncationPolicy::Truncate => TruncationPolicy::Drop,
+                            policy => policy,
+                        };
+                        fitted.tools = self.fit_items(
+                            *section,
+                            policy,
+                            defs,
+                            &mut budget,
+                            |def| serde_json::to_string(def).unwrap_or_default(),
+                            |def, _, _| def,
+                        )?;
+                    }
+                }
+                ContextSection::ChatHistory => {
+                    if let Some(messages) = chat_history.take() {
+                        // Most recent messages are kept first
+                        let mut kept = self.fit_items(
+                            *section,
+                            policy,
+                            messages.into_iter().rev().collect(),
+                            &mut budget,
+                            |msg| format!("{}: {}", msg.role, msg.content),
+                            |msg, tokenizer, tokens| Message {
+                                content: tokenizer.truncate(&msg.content, tokens),
+                                ..msg
+                            },
+                        )?;
+                        kept.reverse();
+                        fitted.chat_history = kept;
+                    }
+                }
+            }
+        }
+
+        Ok(fitted)
+    }
+
+    /// Fits a [CompletionRequest] in the budget. All the documents of the request are treated
+    ///  as [ContextSection::DynamicContext].
+    pub fn fit(&self, request: CompletionRequest) -> Result<CompletionRequest, ContextError> {
+        let parts = self.assemble(
+            ContextParts {
+                prompt: request.prompt,
+                preamble: request.preamble,
+                static_context: vec![],
+                dynamic_context: request.documents,
+                tools: request.tools,
+                chat_history: request.chat_history,
+            },
+            request.max_tokens,
+        )?;
+
+        Ok(CompletionRequest {
+            prompt: parts.prompt,
+            preamble: parts.preamble,
+            chat_history: parts.chat_history,
+            documents: parts.dynamic_context,
+            tools: parts.tools,
+            ..request
+        })
+    }
+
+    /// Keeps the `items` of a section that fit in `budget` according to `policy`, and subtracts
+    ///  their tokens from the budget.
+    fn fit_items<T>(
+        &self,
+        section: ContextSection,
+        policy: TruncationPolicy,
+        items: Vec<T>,
+        budget: &mut usize,
+        text: impl Fn(&T) -> String,
+        truncate: impl Fn(T, &dyn Tokenizer, usize) -> T,
+    ) -> Result<Vec<T>, ContextError> {
+        let counted = items
+            .into_iter()
+            .map(|item| {
+                let tokens = self.count_tokens(&text(&item)) + ITEM_OVERHEAD;
+                (item, tokens)
+            })
+            .collect::<Vec<_>>();
+
+        if policy == TruncationPolicy::Required {
+            let required = counted.iter().map(|(_, tokens)| tokens).sum::<usize>();
+            if required > *budget {
+                return Err(ContextError::BudgetExceeded {
+                    section,
+                    required,
+                    available: *budget,
+                });
+            }
+        }
+
+        let total = counted.len();
+        let mut kept = vec![];
+        for (item, tokens) in counted {
+            if tokens <= *budget {
+                *budget -= tokens;
+                kept.push(item);
+                continue;
+            }
+
+            if policy == TruncationPolicy::Truncate && *budget > ITEM_OVERHEAD {
+                // The overhead of the item (e.g.: document header) is roughly accounted for by
+                // the item overhead, so the remaining budget goes to the text
+                let item = truncate(item, self.tokenizer.as_ref(), *budget - ITEM_OVERHEAD);
+                *budget = 0;
+                kept.push(item);
+            }
+            break;
+        }
+
+        if kept.len() < total {
+            tracing::info!(target: "rig",
+                "Context budget: kept {} of {} items of {:?}",
+                kept.len(),
+                total,
+                section
+            );
+        }
+
+        Ok(kept)
+    }
+}
+
+impl Default for ContextAssembler {
+    /// Assembler with a [HeuristicTokenizer] and a context window of [DEFAULT_CONTEXT_WINDOW].
+    fn default() -> Self {
+        Self::new(
+            Arc::new(HeuristicTokenizer::default()),
+            DEFAULT_CONTEXT_WINDOW,
+        )
+    }
+}
+
+fn truncate(text: String, tokenizer: &dyn Tokenizer, tokens: usize) -> String {
+    tokenizer.truncate(&text, tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use super::{ContextAssembler, ContextError, ContextParts, ContextSection, TruncationPolicy};
+    use crate::{
+        completion::{Document, Message},
+        tokenizer::HeuristicTokenizer,
+    };
+
+    fn doc(id: &str, text: &str) -> Document {
+        Document {
+            id: id.to_string(),
+            text: text.to_string(),
+            additional_props: HashMap::new(),
+        }
+    }
+
+    fn message(content: &str) -> Message {
+        Message {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    // One token per character makes budgets easy to reason about
+    fn assembler(context_window: usize) -> ContextAssembler {
+        ContextAssembler::new(Arc::new(HeuristicTokenizer::new(1.0)), context_window)
+    }
+
+    #[test]
+    fn test_everything_fits() {
+        let parts = ContextParts {
+            prompt: "hi".to_string(),
+            preamble: Some("be nice".to_string()),
+            dynamic_context: vec![doc("a", "alpha")],
+            chat_history: vec![message("hello")],
+            ..Default::default()
+        };
+
+        let fitted = assembler(1000).assemble(parts, None).unwrap();
+        assert_eq!(fitted.preamble.as_deref(), Some("be nice"));
+        assert_eq!(fitted.dynamic_context.len(), 1);
+        assert_eq!(fitted.chat_history.len(), 1);
+    }
+
+    #[test]
+    fn test_truncates_dynamic_context_and_drops_old_messages() {
+        let parts = ContextParts {
+            prompt: "p".repeat(10),
+            dynamic_context: vec![doc("a", &"a".repeat(100)), doc("b", "b")],
+            chat_history: vec![message(&"old".repeat(20)), message("new")],
+            ..Default::default()
+        };
+
+        // 14 tokens for the prompt, 13 for the last message, 50 left for documents
+        let fitted = assembler(77).assemble(parts, None).unwrap();
+        assert_eq!(fitted.chat_history.len(), 1);
+        assert_eq!(fitted.chat_history[0].content, "new");
+        assert_eq!(fitted.dynamic_context.len(), 1);
+        assert_eq!(fitted.dynamic_context[0].text, "a".repeat(46));
+    }
+
+    #[test]
+    fn test_required_section_and_prompt_too_large() {
+        let parts = ContextParts {
+            prompt: "p".repeat(10),
+            preamble: Some("x".repeat(100)),
+            ..Default::default()
+        };
+
+        let assembler = assembler(64).policy(ContextSection::Preamble, TruncationPolicy::Required);
+        assert!(matches!(
+            assembler.assemble(parts.clone(), None),
+            Err(ContextError::BudgetExceeded {
+                section: ContextSection::Preamble,
+                ..
+            })
+        ));
+        assert!(matches!(
+            assembler.assemble(parts, Some(60)),
+            Err(ContextError::PromptTooLarge { .. })
+        ));
+    }
+}
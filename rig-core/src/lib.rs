@@ -81,13 +81,17 @@
 pub mod agent;
 pub mod cli_chatbot;
 pub mod completion;
+pub mod context;
 pub mod embeddings;
 pub mod extractor;
 pub(crate) mod json_utils;
 pub mod loaders;
 pub mod one_or_many;
 pub mod pipeline;
+pub mod plugin;
 pub mod providers;
+pub mod rerank;
+pub mod tokenizer;
 pub mod tool;
 pub mod vector_store;
 
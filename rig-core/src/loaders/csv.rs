@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use super::document::{
+    documents, Document, DocumentFormat, DocumentLoader, DocumentLoaderError, DocumentMetadata,
+};
+
+/// [DocumentLoader] for CSV files. See [Csv] for how files are parsed.
+pub type CsvFileLoader<'a, T> = DocumentLoader<'a, Csv, T>;
+
+/// CSV [DocumentFormat]. The first record of the file is used as the header and every following
+///  record becomes a [Document] whose text lists the `column: value` pairs of the row, one per
+///  line. The `row` of each document is its 1-based position after the header. A malformed record
+///  fails the whole file.
+///
+/// By default all columns are included in the text. Use [Csv::text_columns] to select a subset
+///  and [Csv::url_column] to read the `url` of each document from a column.
+#[derive(Clone, Debug)]
+pub struct Csv {
+    delimiter: u8,
+    text_columns: Option<Vec<String>>,
+    url_column: Option<String>,
+}
+
+impl Default for Csv {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            text_columns: None,
+            url_column: None,
+        }
+    }
+}
+
+impl Csv {
+    /// Sets the field delimiter (`,` by default).
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Only includes the given columns in the text of each document.
+    pub fn text_columns(mut self, columns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.text_columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Reads the url of each document from the given column.
+    pub fn url_column(mut self, column: &str) -> Self {
+        self.url_column = Some(column.to_string());
+        self
+    }
+}
+
+impl DocumentFormat for Csv {
+    fn parse(&self, path: &Path, contents: &str) -> Result<Vec<Document>, DocumentLoaderError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_reader(contents.as_bytes());
+        let headers = reader.headers()?.clone();
+
+        let column = |name: &str| headers.iter().position(|header| header == name);
+        let text_columns = match &self.text_columns {
+            Some(columns) => columns.iter().filter_map(|name| column(name)).collect(),
+            None => (0..headers.len()).collect::<Vec<_>>(),
+        };
+        let url_column = self.url_column.as_deref().and_then(column);
+
+        let parsed = reader
+            .records()
+            .enumerate()
+            .map(|(i, record)| {
+                let record = record?;
+                let text = text_columns
+                    .iter()
+                    .filter_map(|&i| Some((headers.get(i)?, record.get(i)?.trim())))
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(header, value)| format!("{header}: {value}"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                Ok((
+                    text,
+                    DocumentMetadata {
+                        row: Some(i + 1),
+                        url: url_column
+                            .and_then(|i| record.get(i))
+                            .filter(|url| !url.is_empty())
+                            .map(String::from),
+                        ..Default::default()
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>, DocumentLoaderError>>()?;
+
+        Ok(documents(path, parsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::{Csv, CsvFileLoader};
+
+    #[test]
+    fn test_csv_loader() {
+        let temp = assert_fs::TempDir::new().expect("Failed to create temp dir");
+        temp.child("tokens.csv")
+            .write_str(
+                "symbol,name,website\nBONK,\"Bonk, Inc\",https://bonkcoin.com\nJUP,Jupiter,\n",
+            )
+            .expect("Failed to write to tokens.csv");
+
+        let glob = temp.path().to_string_lossy().to_string() + "/*.csv";
+        let docs = CsvFileLoader::with_glob(&glob)
+            .unwrap()
+            .with_format(
+                Csv::default()
+                    .text_columns(["symbol", "name"])
+                    .url_column("website"),
+            )
+            .load()
+            .ignore_errors()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0].text, "symbol: BONK\nname: Bonk, Inc");
+        assert_eq!(
+            docs[0].metadata.url.as_deref(),
+            Some("https://bonkcoin.com")
+        );
+        assert_eq!(docs[1].metadata.row, Some(2));
+        assert_eq!(docs[1].metadata.url, None);
+    }
+}
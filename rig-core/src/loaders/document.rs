@@ -0,0 +1,313 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::embeddings::{Embed, EmbedError, TextEmbedder};
+
+use super::file::{FileLoader, FileLoaderError};
+
+#[derive(Error, Debug)]
+pub enum DocumentLoaderError {
+    #[error("{0}")]
+    FileLoaderError(#[from] FileLoaderError),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON error on line {line}: {source}")]
+    JsonError {
+        line: usize,
+        source: serde_json::Error,
+    },
+
+    #[error("Missing field `{field}` on line {line}")]
+    MissingField { line: usize, field: String },
+
+    #[cfg(feature = "csv")]
+    #[error("CSV error: {0}")]
+    CsvError(#[from] csv::Error),
+}
+
+// ================================================================
+// Document and DocumentFormat definitions
+// ================================================================
+
+/// Metadata extracted alongside the text of a [Document]. Fields that do not apply to a given
+///  format are left empty (e.g.: `row` is only set for CSV and JSONL documents).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    /// Path of the file the document was loaded from.
+    pub source: PathBuf,
+    /// Headings the document is nested under, from outermost to innermost.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub heading_path: Vec<String>,
+    /// 1-based row (CSV) or line (JSONL) number of the document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Clean text extracted from a file along with its [DocumentMetadata]. A single file can produce
+///  several documents (e.g.: one per markdown section or one per CSV row).
+///
+/// The `id` is derived from the source path and the position of the document in the file (e.g.:
+///  `tokens.csv#12`), so it can be used as the document id when inserting into a vector store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+    pub metadata: DocumentMetadata,
+}
+
+impl Embed for Document {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.text.clone());
+        Ok(())
+    }
+}
+
+/// Trait for file formats that can be parsed into [Document]s by a [DocumentLoader].
+pub trait DocumentFormat {
+    /// Parses the `contents` of the file at `path` into documents.
+    fn parse(&self, path: &Path, contents: &str) -> Result<Vec<Document>, DocumentLoaderError>;
+}
+
+/// Builds the documents of the file at `path` from `(text, metadata)` pairs, assigning ids and
+///  setting the source of each document. Documents with empty text are dropped.
+pub(crate) fn documents(
+    path: &Path,
+    parsed: impl IntoIterator<Item = (String, DocumentMetadata)>,
+) -> Vec<Document> {
+    parsed
+        .into_iter()
+        .filter(|(text, _)| !text.trim().is_empty())
+        .enumerate()
+        .map(|(i, (text, metadata))| Document {
+            id: format!("{}#{i}", path.display()),
+            text,
+            metadata: DocumentMetadata {
+                source: path.to_path_buf(),
+                ..metadata
+            },
+        })
+        .collect()
+}
+
+// ================================================================
+// DocumentLoader definitions and implementations
+// ================================================================
+
+/// [DocumentLoader] is a utility for loading structured text files (markdown, HTML, CSV, JSONL)
+///  from the filesystem using glob patterns or directory paths. It works like the
+///  [FileLoader], but parses the files with a [DocumentFormat] into clean text and metadata.
+///
+/// Each supported format has an alias (e.g.: [MarkdownFileLoader](super::MarkdownFileLoader)),
+///  and formats with options can be configured with [DocumentLoader::with_format].
+///
+/// # Example Usage
+///
+/// ```rust
+/// use rig::loaders::JsonlFileLoader;
+/// use rig::loaders::jsonl::Jsonl;
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     // Load every tweet of the archive as a document, ignoring unreadable files. A malformed
+///     //  line fails its whole file, so the file is skipped.
+///     let tweets = JsonlFileLoader::with_glob("archive/*.jsonl")?
+///         .with_format(Jsonl::default().text_field("full_text"))
+///         .load()
+///         .ignore_errors();
+///
+///     for tweet in tweets {
+///         println!("{:?} {}", tweet.metadata.url, tweet.text);
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct DocumentLoader<'a, F, T> {
+    format: F,
+    iterator: Box<dyn Iterator<Item = T> + 'a>,
+}
+
+impl<'a, F: DocumentFormat + Clone + 'a>
+    DocumentLoader<'a, F, Result<PathBuf, DocumentLoaderError>>
+{
+    /// Replaces the format used to parse the files, e.g. to configure format options.
+    pub fn with_format(self, format: F) -> Self {
+        DocumentLoader {
+            format,
+            iterator: self.iterator,
+        }
+    }
+
+    /// Parses the files within the iterator returned by [DocumentLoader::with_glob] or
+    ///  [DocumentLoader::with_dir] into [Document]s. Errors are reported once per file.
+    ///
+    /// # Example
+    /// Load the sections of the markdown files in "docs/*.md" along with their heading path.
+    ///
+    /// ```rust
+    /// let documents = MarkdownFileLoader::with_glob("docs/*.md")?.load();
+    /// for result in documents {
+    ///     match result {
+    ///         Ok(doc) => println!("{:?} {}", doc.metadata.heading_path, doc.text),
+    ///         Err(e) => eprintln!("Error loading file: {}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn load(self) -> DocumentLoader<'a, F, Result<Document, DocumentLoaderError>> {
+        let format = self.format.clone();
+        DocumentLoader {
+            format: self.format,
+            iterator: Box::new(self.iterator.flat_map(move |res| {
+                match res.and_then(|path| parse(&format, &path)) {
+                    Ok(documents) => documents.into_iter().map(Ok).collect::<Vec<_>>(),
+                    Err(e) => vec![Err(e)],
+                }
+            })),
+        }
+    }
+
+    /// Parses the files within the iterator returned by [DocumentLoader::with_glob] or
+    ///  [DocumentLoader::with_dir] and returns the clean text of each file.
+    ///
+    /// # Example
+    /// Read the text content of the HTML files in "exports/*.html".
+    ///
+    /// ```rust
+    /// let content = HtmlFileLoader::with_glob("exports/*.html")?.read();
+    /// for result in content {
+    ///     match result {
+    ///         Ok(text) => println!("{}", text),
+    ///         Err(e) => eprintln!("Error reading file: {}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn read(self) -> DocumentLoader<'a, F, Result<String, DocumentLoaderError>> {
+        DocumentLoader {
+            format: self.format.clone(),
+            iterator: Box::new(
+                self.read_with_path()
+                    .iterator
+                    .map(|res| res.map(|(_, text)| text)),
+            ),
+        }
+    }
+
+    /// Parses the files within the iterator returned by [DocumentLoader::with_glob] or
+    ///  [DocumentLoader::with_dir] and returns the path along with the clean text of each file.
+    pub fn read_with_path(
+        self,
+    ) -> DocumentLoader<'a, F, Result<(PathBuf, String), DocumentLoaderError>> {
+        let format = self.format.clone();
+        DocumentLoader {
+            format: self.format,
+            iterator: Box::new(self.iterator.map(move |res| {
+                let path = res?;
+                let text = parse(&format, &path)?
+                    .into_iter()
+                    .map(|doc| doc.text)
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                Ok((path, text))
+            })),
+        }
+    }
+}
+
+fn parse<F: DocumentFormat>(format: &F, path: &Path) -> Result<Vec<Document>, DocumentLoaderError> {
+    let contents = std::fs::read_to_string(path)?;
+    format.parse(path, &contents)
+}
+
+impl<'a, F: 'a, T: 'a> DocumentLoader<'a, F, Result<T, DocumentLoaderError>> {
+    /// Ignores errors in the iterator, returning only successful results. This can be used on any
+    ///  [DocumentLoader] state of iterator whose items are results.
+    ///
+    /// Files are parsed as a whole: a file with a malformed line or row is dropped entirely.
+    ///
+    /// # Example
+    /// Load the rows of the CSV files in "lists/*.csv" and ignore errors from unreadable files.
+    ///
+    /// ```rust
+    /// let rows = CsvFileLoader::with_glob("lists/*.csv")?.load().ignore_errors();
+    /// for row in rows {
+    ///     println!("{}", row.text)
+    /// }
+    /// ```
+    pub fn ignore_errors(self) -> DocumentLoader<'a, F, T> {
+        DocumentLoader {
+            format: self.format,
+            iterator: Box::new(self.iterator.filter_map(|res| res.ok())),
+        }
+    }
+}
+
+impl<'a, F: Default> DocumentLoader<'a, F, Result<PathBuf, DocumentLoaderError>> {
+    /// Creates a new [DocumentLoader] using a glob pattern to match files.
+    ///
+    /// # Example
+    /// Create a [DocumentLoader] for all `.md` files that match the glob "docs/*.md".
+    ///
+    /// ```rust
+    /// let loader = MarkdownFileLoader::with_glob("docs/*.md")?;
+    /// ```
+    pub fn with_glob(pattern: &'a str) -> Result<Self, DocumentLoaderError> {
+        Ok(Self::from_file_loader(FileLoader::with_glob(pattern)?))
+    }
+
+    /// Creates a new [DocumentLoader] on all files within a directory.
+    ///
+    /// # Example
+    /// Create a [DocumentLoader] for all files that are in the directory "docs" (ignores subdirectories).
+    ///
+    /// ```rust
+    /// let loader = MarkdownFileLoader::with_dir("docs")?;
+    /// ```
+    pub fn with_dir(directory: &'a str) -> Result<Self, DocumentLoaderError> {
+        Ok(Self::from_file_loader(FileLoader::with_dir(directory)?))
+    }
+
+    fn from_file_loader(loader: FileLoader<'a, Result<PathBuf, FileLoaderError>>) -> Self {
+        DocumentLoader {
+            format: F::default(),
+            iterator: Box::new(
+                loader
+                    .into_iter()
+                    .map(|res| res.map_err(DocumentLoaderError::FileLoaderError)),
+            ),
+        }
+    }
+}
+
+// ================================================================
+// Iterators for DocumentLoader
+// ================================================================
+
+pub struct IntoIter<'a, T> {
+    iterator: Box<dyn Iterator<Item = T> + 'a>,
+}
+
+impl<'a, F, T> IntoIterator for DocumentLoader<'a, F, T> {
+    type Item = T;
+    type IntoIter = IntoIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            iterator: self.iterator,
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iterator.next()
+    }
+}
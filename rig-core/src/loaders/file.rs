@@ -3,6 +3,8 @@ use std::{fs, path::PathBuf};
 use glob::glob;
 use thiserror::Error;
 
+use super::splitter::{Chunk, TextSplitter};
+
 #[derive(Error, Debug)]
 pub enum FileLoaderError {
     #[error("Invalid glob pattern: {0}")]
@@ -208,6 +210,33 @@ impl FileLoader<'_, Result<PathBuf, FileLoaderError>> {
     }
 }
 
+impl<'a> FileLoader<'a, (PathBuf, String)> {
+    /// Splits the contents of every file into [Chunk]s using the given `splitter`. The source of
+    ///  each chunk is the path of the file it was read from.
+    ///
+    /// # Example
+    /// Split markdown notes into chunks of at most 1000 characters and embed them.
+    ///
+    /// ```rust
+    /// let chunks = FileLoader::with_glob("notes/*.md")?
+    ///     .read_with_path()
+    ///     .ignore_errors()
+    ///     .split(RecursiveSplitter::new(1000, 200)?);
+    ///
+    /// let embeddings = EmbeddingsBuilder::new(model)
+    ///     .documents(chunks)?
+    ///     .build()
+    ///     .await?;
+    /// ```
+    pub fn split<S: TextSplitter + 'a>(self, splitter: S) -> FileLoader<'a, Chunk> {
+        FileLoader {
+            iterator: Box::new(self.iterator.flat_map(move |(path, contents)| {
+                splitter.chunks(&path.to_string_lossy(), &contents)
+            })),
+        }
+    }
+}
+
 // ================================================================
 // Iterators for FileLoader
 // ================================================================
@@ -0,0 +1,291 @@
+use std::path::Path;
+
+use super::document::{
+    documents, Document, DocumentFormat, DocumentLoader, DocumentLoaderError, DocumentMetadata,
+};
+
+/// [DocumentLoader] for HTML files. See [Html] for how files are parsed.
+pub type HtmlFileLoader<'a, T> = DocumentLoader<'a, Html, T>;
+
+/// HTML [DocumentFormat]. Every file becomes a single [Document] containing the visible text of
+///  the page, with one line per block element (paragraphs, list items, table rows, etc.).
+///  The contents of `<script>`, `<style>` and `<head>` elements are dropped and HTML entities are
+///  decoded.
+///
+/// The `title` of the document is taken from the `<title>` element and the `url` from the
+///  canonical link (or the `og:url` meta tag) of the page.
+#[derive(Clone, Debug, Default)]
+pub struct Html;
+
+impl DocumentFormat for Html {
+    fn parse(&self, path: &Path, contents: &str) -> Result<Vec<Document>, DocumentLoaderError> {
+        let page = parse_html(contents);
+        Ok(documents(
+            path,
+            [(
+                page.text,
+                DocumentMetadata {
+                    title: page.title,
+                    url: page.url,
+                    ..Default::default()
+                },
+            )],
+        ))
+    }
+}
+
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+const SKIPPED_TAGS: &[&str] = &["head", "noscript", "script", "style", "svg", "template"];
+
+struct Page {
+    text: String,
+    title: Option<String>,
+    url: Option<String>,
+}
+
+struct Tag<'a> {
+    name: String,
+    closing: bool,
+    raw: &'a str,
+}
+
+impl Tag<'_> {
+    fn attr(&self, name: &str) -> Option<String> {
+        let lower = self.raw.to_ascii_lowercase();
+        let mut from = 0;
+        while let Some(found) = lower[from..].find(name) {
+            let start = from + found;
+            from = start + name.len();
+            // Make sure we matched a whole attribute name followed by `=`
+            let preceded = lower[..start].ends_with(|c: char| c.is_whitespace());
+            let rest = lower[from..].trim_start();
+            if !preceded || !rest.starts_with('=') {
+                continue;
+            }
+            let value_start = self.raw.len() - rest.len() + 1;
+            let value = self.raw[value_start..].trim_start();
+            let value = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+                _ => value
+                    .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                    .next()
+                    .unwrap_or_default(),
+            };
+            return Some(decode_entities(value));
+        }
+        None
+    }
+}
+
+/// Parses a tag starting right after its `<`. Returns the tag and the length of its source.
+fn parse_tag(source: &str) -> Option<(Tag<'_>, usize)> {
+    let mut quote = None;
+    let end = source.char_indices().find_map(|(i, c)| match (quote, c) {
+        (None, '"' | '\'') => {
+            quote = Some(c);
+            None
+        }
+        (Some(q), _) if q == c => {
+            quote = None;
+            None
+        }
+        (None, '>') => Some(i),
+        _ => None,
+    })?;
+
+    let raw = &source[..end];
+    let closing = raw.starts_with('/');
+    let name = raw
+        .trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    Some((Tag { name, closing, raw }, end + 1))
+}
+
+fn parse_html(html: &str) -> Page {
+    let mut text = String::new();
+    let mut title = None;
+    let mut url = None;
+    let mut skipping: Option<String> = None;
+    let mut in_title = false;
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        let (before, after) = rest.split_at(start);
+        if in_title {
+            title = Some(decode_entities(before).trim().to_string());
+        } else if skipping.is_none() {
+            text.push_str(before);
+        }
+
+        // Comments and doctype declarations
+        if let Some(comment) = after.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        let Some((tag, len)) = parse_tag(&after[1..]) else {
+            // Not a tag, keep the `<` as text
+            if skipping.is_none() {
+                text.push('<');
+            }
+            rest = &after[1..];
+            continue;
+        };
+        rest = &after[len + 1..];
+
+        match tag.name.as_str() {
+            "title" => in_title = !tag.closing,
+            "link" if tag.attr("rel").is_some_and(|rel| rel == "canonical") => {
+                url = url.or(tag.attr("href"));
+            }
+            "meta" if tag.attr("property").is_some_and(|p| p == "og:url") => {
+                url = url.or(tag.attr("content"));
+            }
+            _ => {}
+        }
+
+        match &skipping {
+            Some(name) if tag.closing && *name == tag.name => skipping = None,
+            Some(_) => {}
+            None if !tag.closing && SKIPPED_TAGS.contains(&tag.name.as_str()) => {
+                skipping = Some(tag.name)
+            }
+            None if BLOCK_TAGS.contains(&tag.name.as_str()) => text.push('\n'),
+            None if tag.name == "td" || tag.name == "th" => text.push(' '),
+            None => {}
+        }
+    }
+    if skipping.is_none() {
+        text.push_str(rest);
+    }
+
+    Page {
+        text: decode_entities(&text)
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        title: title.filter(|t| !t.is_empty()),
+        url,
+    }
+}
+
+/// Decodes named and numeric HTML character references.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end + 1))
+        });
+
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::HtmlFileLoader;
+
+    #[test]
+    fn test_html_loader() {
+        let temp = assert_fs::TempDir::new().expect("Failed to create temp dir");
+        temp.child("thread.html")
+            .write_str(
+                r#"<!DOCTYPE html>
+                <html><head><title>#alpha &amp; calls</title>
+                <link rel="canonical" href="https://discord.com/channels/1/2">
+                <style>p { color: red; }</style></head>
+                <body><!-- export --><div class="msg"><p>JUP looks <b>strong</b></p>
+                <p>Entry &lt; 1.2&#36;</p><script>track()</script></div></body></html>"#,
+            )
+            .expect("Failed to write to thread.html");
+
+        let glob = temp.path().to_string_lossy().to_string() + "/*.html";
+        let docs = HtmlFileLoader::with_glob(&glob)
+            .unwrap()
+            .load()
+            .ignore_errors()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].text, "JUP looks strong\nEntry < 1.2$");
+        assert_eq!(docs[0].metadata.title.as_deref(), Some("#alpha & calls"));
+        assert_eq!(
+            docs[0].metadata.url.as_deref(),
+            Some("https://discord.com/channels/1/2")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::document::{
+    documents, Document, DocumentFormat, DocumentLoader, DocumentLoaderError, DocumentMetadata,
+};
+
+/// [DocumentLoader] for JSON Lines files. See [Jsonl] for how files are parsed.
+pub type JsonlFileLoader<'a, T> = DocumentLoader<'a, Jsonl, T>;
+
+/// JSON Lines [DocumentFormat]. Every non-empty line of the file must be a JSON object and
+///  becomes a [Document] whose text is the value of the `text_field` of the object (`"text"` by
+///  default). The `url` of the document is read from the `url_field` (`"url"` by default) and its
+///  `row` is the line number of the object in the file. A line that is not valid JSON or lacks the
+///  text field fails the whole file.
+///
+/// Fields of nested objects can be selected with a dot-separated path, e.g.:
+///  `Jsonl::default().text_field("legacy.full_text")`.
+#[derive(Clone, Debug)]
+pub struct Jsonl {
+    text_field: String,
+    url_field: String,
+}
+
+impl Default for Jsonl {
+    fn default() -> Self {
+        Self {
+            text_field: "text".to_string(),
+            url_field: "url".to_string(),
+        }
+    }
+}
+
+impl Jsonl {
+    /// Sets the field the text of each document is read from.
+    pub fn text_field(mut self, field: &str) -> Self {
+        self.text_field = field.to_string();
+        self
+    }
+
+    /// Sets the field the url of each document is read from.
+    pub fn url_field(mut self, field: &str) -> Self {
+        self.url_field = field.to_string();
+        self
+    }
+}
+
+impl DocumentFormat for Jsonl {
+    fn parse(&self, path: &Path, contents: &str) -> Result<Vec<Document>, DocumentLoaderError> {
+        let parsed = contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(line, json)| {
+                let value: Value = serde_json::from_str(json)
+                    .map_err(|source| DocumentLoaderError::JsonError { line, source })?;
+
+                let text = field(&value, &self.text_field)
+                    .and_then(as_text)
+                    .ok_or_else(|| DocumentLoaderError::MissingField {
+                        line,
+                        field: self.text_field.clone(),
+                    })?;
+
+                Ok((
+                    text,
+                    DocumentMetadata {
+                        row: Some(line),
+                        url: field(&value, &self.url_field).and_then(as_text),
+                        ..Default::default()
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>, DocumentLoaderError>>()?;
+
+        Ok(documents(path, parsed))
+    }
+}
+
+fn field<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::{Jsonl, JsonlFileLoader};
+    use crate::loaders::document::DocumentLoaderError;
+
+    #[test]
+    fn test_jsonl_loader() {
+        let temp = assert_fs::TempDir::new().expect("Failed to create temp dir");
+        temp.child("tweets.jsonl")
+            .write_str(concat!(
+                r#"{"tweet": {"full_text": "gm"}, "url": "https://x.com/a/status/1"}"#,
+                "\n\n",
+                r#"{"tweet": {"full_text": "wagmi"}}"#,
+                "\n",
+            ))
+            .expect("Failed to write to tweets.jsonl");
+        temp.child("broken.jsonl")
+            .write_str("{\"text\": \"ok\"}\n{not json}")
+            .expect("Failed to write to broken.jsonl");
+
+        let glob = temp.path().to_string_lossy().to_string() + "/tweets.jsonl";
+        let docs = JsonlFileLoader::with_glob(&glob)
+            .unwrap()
+            .with_format(Jsonl::default().text_field("tweet.full_text"))
+            .load()
+            .ignore_errors()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0].text, "gm");
+        assert_eq!(
+            docs[0].metadata.url.as_deref(),
+            Some("https://x.com/a/status/1")
+        );
+        assert_eq!(docs[1].metadata.row, Some(3));
+
+        let glob = temp.path().to_string_lossy().to_string() + "/broken.jsonl";
+        let results = JsonlFileLoader::with_glob(&glob)
+            .unwrap()
+            .load()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            results[..],
+            [Err(DocumentLoaderError::JsonError { line: 2, .. })]
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+use std::path::Path;
+
+use super::{
+    document::{
+        documents, Document, DocumentFormat, DocumentLoader, DocumentLoaderError, DocumentMetadata,
+    },
+    splitter::{markdown_sections, parse_heading},
+};
+
+/// [DocumentLoader] for markdown files. See [Markdown] for how files are parsed.
+pub type MarkdownFileLoader<'a, T> = DocumentLoader<'a, Markdown, T>;
+
+/// Markdown [DocumentFormat]. Every section of the file (as delimited by its headings) becomes a
+///  [Document] whose `heading_path` is the list of headings the section is nested under. The
+///  title of the documents is the first top-level heading of the file.
+///
+/// Markdown syntax (emphasis, links, images, inline code and code fences) is stripped from the
+///  text, keeping only the link and image text.
+#[derive(Clone, Debug, Default)]
+pub struct Markdown;
+
+impl DocumentFormat for Markdown {
+    fn parse(&self, path: &Path, contents: &str) -> Result<Vec<Document>, DocumentLoaderError> {
+        let mut in_code_block = false;
+        let title = contents
+            .lines()
+            .filter(|line| {
+                let fence = line.trim_start();
+                if fence.starts_with("```") || fence.starts_with("~~~") {
+                    in_code_block = !in_code_block;
+                }
+                !in_code_block
+            })
+            .filter_map(parse_heading)
+            .find(|(level, _)| *level == 1)
+            .map(|(_, title)| clean_inline(&title));
+
+        Ok(documents(
+            path,
+            markdown_sections(contents)
+                .into_iter()
+                .map(|(headings, section)| {
+                    (
+                        clean_markdown(&section),
+                        DocumentMetadata {
+                            heading_path: headings.iter().map(|h| clean_inline(h)).collect(),
+                            title: title.clone(),
+                            ..Default::default()
+                        },
+                    )
+                }),
+        ))
+    }
+}
+
+/// Strips markdown syntax from a block of markdown, line by line.
+fn clean_markdown(text: &str) -> String {
+    let mut in_code_block = false;
+    text.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_code_block = !in_code_block;
+                return None;
+            }
+            if in_code_block {
+                return Some(line.to_string());
+            }
+            if let Some((_, title)) = parse_heading(line) {
+                return Some(clean_inline(&title));
+            }
+            Some(clean_inline(trimmed.trim_start_matches('>').trim_start()))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Strips inline markdown syntax: emphasis markers, inline code, links and images.
+fn clean_inline(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '`' => {}
+            // Keep underscores and tildes inside words (e.g.: `token_address`)
+            '_' | '~'
+                if !(out.ends_with(char::is_alphanumeric)
+                    && chars.peek().is_some_and(|c| c.is_alphanumeric())) => {}
+            '!' if chars.peek() == Some(&'[') => {}
+            '[' => {
+                let label = chars.by_ref().take_while(|c| *c != ']').collect::<String>();
+                out.push_str(&clean_inline(&label));
+                // Skip the link target
+                if chars.peek() == Some(&'(') {
+                    chars.by_ref().take_while(|c| *c != ')').for_each(drop);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::{Markdown, MarkdownFileLoader};
+    use crate::loaders::document::DocumentFormat;
+
+    #[test]
+    fn test_markdown_loader() {
+        let temp = assert_fs::TempDir::new().expect("Failed to create temp dir");
+        temp.child("sol.md")
+            .write_str("# Solana\nA **fast** chain.\n## Fees\nSee [the docs](https://solana.com).")
+            .expect("Failed to write to sol.md");
+
+        let glob = temp.path().to_string_lossy().to_string() + "/*.md";
+        let docs = MarkdownFileLoader::with_glob(&glob)
+            .unwrap()
+            .load()
+            .ignore_errors()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0].text, "Solana\nA fast chain.");
+        assert_eq!(docs[1].text, "Fees\nSee the docs.");
+        assert_eq!(docs[1].metadata.heading_path, vec!["Solana", "Fees"]);
+        assert_eq!(docs[1].metadata.title.as_deref(), Some("Solana"));
+        assert!(docs[1].metadata.source.ends_with("sol.md"));
+    }
+
+    #[test]
+    fn test_title_ignores_code_blocks() {
+        let contents = "```sh\n# install the cli\nnpm i -g cli\n```\n# Setup\nRun it.";
+        let docs = Markdown
+            .parse(std::path::Path::new("setup.md"), contents)
+            .expect("Failed to parse setup.md");
+
+        assert_eq!(docs.len(), 2);
+        assert!(docs[0].text.contains("# install the cli"));
+        assert_eq!(docs[1].metadata.heading_path, vec!["Setup"]);
+        assert!(docs
+            .iter()
+            .all(|doc| doc.metadata.title.as_deref() == Some("Setup")));
+    }
+}
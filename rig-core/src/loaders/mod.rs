@@ -8,14 +8,44 @@
 //! files. This loader also provides PDF-specific preprocessing methods for splitting the PDF into pages
 //! and keeping track of the page numbers along with their contents.
 //!
-//! Note: The [PdfFileLoader] requires the `pdf` feature to be enabled in the `Cargo.toml` file.
+//! The [DocumentLoader] also follows the [FileLoader] API, but parses structured text files into
+//! [Document]s made of clean text and [DocumentMetadata] (source path, heading path, row number, url).
+//! The following formats are supported: [MarkdownFileLoader], [HtmlFileLoader], [JsonlFileLoader]
+//! and [CsvFileLoader].
+//!
+//! The [splitter] module provides [TextSplitter] implementations that break loaded documents into
+//! embedding-sized [Chunk]s, e.g.: `FileLoader::with_glob(..)?.read_with_path().ignore_errors().split(..)`.
+//!
+//! Note: The [PdfFileLoader] requires the `pdf` feature and the [CsvFileLoader] requires the `csv`
+//! feature to be enabled in the `Cargo.toml` file.
 
+pub mod document;
 pub mod file;
+pub mod html;
+pub mod jsonl;
+pub mod markdown;
+pub mod splitter;
 
+pub use document::{
+    Document, DocumentFormat, DocumentLoader, DocumentLoaderError, DocumentMetadata,
+};
 pub use file::FileLoader;
+pub use html::HtmlFileLoader;
+pub use jsonl::JsonlFileLoader;
+pub use markdown::MarkdownFileLoader;
+pub use splitter::{
+    CharacterSplitter, Chunk, MarkdownSplitter, RecursiveSplitter, SentenceSplitter, TextSplitter,
+    TokenSplitter,
+};
 
 #[cfg(feature = "pdf")]
 pub mod pdf;
 
 #[cfg(feature = "pdf")]
 pub use pdf::PdfFileLoader;
+
+#[cfg(feature = "csv")]
+pub mod csv;
+
+#[cfg(feature = "csv")]
+pub use csv::CsvFileLoader;
@@ -0,0 +1,577 @@
+//! This module provides text splitters that break loaded documents into embedding-sized chunks.
+//!
+//! All splitters implement the [TextSplitter] trait, which turns a single string into a list of
+//! chunks. The following splitters are provided:
+//! - [CharacterSplitter]: fixed-size character windows with overlap.
+//! - [TokenSplitter]: fixed-size windows of whitespace-delimited tokens with overlap.
+//! - [RecursiveSplitter]: recursively splits on a list of separators (paragraphs, lines,
+//!   sentences, words) until every chunk fits in the configured size.
+//! - [MarkdownSplitter]: splits markdown documents on headings, keeping track of the heading path
+//!   of every chunk.
+//! - [SentenceSplitter]: groups whole sentences into chunks.
+//!
+//! Chunks produced by [TextSplitter::chunks] are [Chunk] values, which implement [Embed] and can
+//! be passed directly to the [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder).
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::embeddings::{Embed, EmbedError, TextEmbedder};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SplitterError {
+    #[error("Chunk size must be greater than zero")]
+    ZeroChunkSize,
+
+    #[error("Chunk overlap ({overlap}) must be smaller than chunk size ({size})")]
+    OverlapTooLarge { size: usize, overlap: usize },
+}
+
+fn validate(size: usize, overlap: usize) -> Result<(), SplitterError> {
+    if size == 0 {
+        return Err(SplitterError::ZeroChunkSize);
+    }
+    if overlap >= size {
+        return Err(SplitterError::OverlapTooLarge { size, overlap });
+    }
+    Ok(())
+}
+
+// ================================================================
+// TextSplitter trait and Chunk type
+// ================================================================
+
+/// Trait for types that split a text into smaller chunks.
+pub trait TextSplitter {
+    /// Splits `text` into a list of chunks. Empty chunks are never returned.
+    fn split_text(&self, text: &str) -> Vec<String>;
+
+    /// Splits `text` into [Chunk]s tagged with the given `source` and their position in the text.
+    fn chunks(&self, source: &str, text: &str) -> Vec<Chunk> {
+        self.split_text(text)
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| Chunk {
+                id: format!("{source}#{index}"),
+                source: source.to_string(),
+                index,
+                text,
+            })
+            .collect()
+    }
+}
+
+/// A piece of a larger document produced by a [TextSplitter].
+///
+/// The `id` is derived from the source and the chunk index (e.g.: `notes/sol.md#3`), so it can
+///  be used as the document id when inserting chunks into a vector store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub id: String,
+    pub source: String,
+    pub index: usize,
+    pub text: String,
+}
+
+impl Embed for Chunk {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.text.clone());
+        Ok(())
+    }
+}
+
+// ================================================================
+// CharacterSplitter
+// ================================================================
+
+/// Splits text into windows of at most `chunk_size` characters, where consecutive windows share
+///  `chunk_overlap` characters.
+#[derive(Clone, Debug)]
+pub struct CharacterSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+}
+
+impl CharacterSplitter {
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Result<Self, SplitterError> {
+        validate(chunk_size, chunk_overlap)?;
+        Ok(Self {
+            chunk_size,
+            chunk_overlap,
+        })
+    }
+}
+
+impl TextSplitter for CharacterSplitter {
+    fn split_text(&self, text: &str) -> Vec<String> {
+        let chars = text.chars().collect::<Vec<_>>();
+        windows(chars.len(), self.chunk_size, self.chunk_overlap)
+            .map(|(start, end)| chars[start..end].iter().collect::<String>())
+            .filter(|chunk| !chunk.trim().is_empty())
+            .collect()
+    }
+}
+
+// ================================================================
+// TokenSplitter
+// ================================================================
+
+/// Splits text into windows of at most `chunk_size` whitespace-delimited tokens, where
+///  consecutive windows share `chunk_overlap` tokens. The original text between the first and
+///  last token of each window is preserved as is.
+#[derive(Clone, Debug)]
+pub struct TokenSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+}
+
+impl TokenSplitter {
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Result<Self, SplitterError> {
+        validate(chunk_size, chunk_overlap)?;
+        Ok(Self {
+            chunk_size,
+            chunk_overlap,
+        })
+    }
+}
+
+impl TextSplitter for TokenSplitter {
+    fn split_text(&self, text: &str) -> Vec<String> {
+        let spans = token_spans(text);
+        windows(spans.len(), self.chunk_size, self.chunk_overlap)
+            .map(|(start, end)| text[spans[start].0..spans[end - 1].1].to_string())
+            .collect()
+    }
+}
+
+/// Returns the byte ranges of the whitespace-delimited tokens of `text`.
+fn token_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Returns the `(start, end)` ranges of overlapping windows over `len` items.
+fn windows(len: usize, size: usize, overlap: usize) -> impl Iterator<Item = (usize, usize)> {
+    let step = size - overlap;
+    (0..len)
+        .step_by(step)
+        .map(move |start| (start, (start + size).min(len)))
+        // Skip windows that are entirely contained in the previous one
+        .take_while(move |(start, _)| *start == 0 || start + overlap < len)
+}
+
+// ================================================================
+// RecursiveSplitter
+// ================================================================
+
+/// Splits text on the first separator that appears in it, and recursively splits any piece that
+///  is still larger than `chunk_size` characters using the next separators. Adjacent small pieces
+///  are then merged back together into chunks of at most `chunk_size` characters, with roughly
+///  `chunk_overlap` characters of overlap between consecutive chunks.
+///
+/// By default, the separators are paragraphs, lines, sentences, words and finally characters.
+#[derive(Clone, Debug)]
+pub struct RecursiveSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+    separators: Vec<String>,
+}
+
+impl RecursiveSplitter {
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Result<Self, SplitterError> {
+        validate(chunk_size, chunk_overlap)?;
+        Ok(Self {
+            chunk_size,
+            chunk_overlap,
+            separators: ["\n\n", "\n", ". ", " ", ""]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        })
+    }
+
+    /// Replaces the default separators. Separators are tried in order and an empty separator
+    ///  splits the text into individual characters.
+    pub fn separators(mut self, separators: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.separators = separators.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn split_recursive(&self, text: &str, separators: &[String]) -> Vec<String> {
+        // Find the first separator that appears in the text
+        let position = separators
+            .iter()
+            .position(|sep| sep.is_empty() || text.contains(sep.as_str()))
+            .unwrap_or(separators.len());
+        let remaining = separators.get(position + 1..).unwrap_or_default();
+
+        let pieces = match separators.get(position) {
+            Some(sep) if !sep.is_empty() => text
+                .split_inclusive(sep.as_str())
+                .map(String::from)
+                .collect::<Vec<_>>(),
+            Some(_) => text.chars().map(String::from).collect(),
+            None => vec![text.to_string()],
+        };
+
+        let mut chunks = vec![];
+        let mut fitting = vec![];
+        for piece in pieces {
+            if piece.chars().count() <= self.chunk_size {
+                fitting.push(piece);
+                continue;
+            }
+
+            chunks.extend(self.merge(std::mem::take(&mut fitting)));
+            if remaining.is_empty() {
+                chunks.push(piece.trim().to_string());
+            } else {
+                chunks.extend(self.split_recursive(&piece, remaining));
+            }
+        }
+        chunks.extend(self.merge(fitting));
+
+        chunks
+    }
+
+    /// Merges small pieces into chunks no larger than `chunk_size`, carrying over trailing
+    ///  pieces of up to `chunk_overlap` characters into the next chunk.
+    fn merge(&self, pieces: Vec<String>) -> Vec<String> {
+        let mut chunks = vec![];
+        let mut current: Vec<(String, usize)> = vec![];
+        let mut total = 0;
+
+        for piece in pieces {
+            let len = piece.chars().count();
+            if total + len > self.chunk_size && !current.is_empty() {
+                chunks.push(current.iter().map(|(s, _)| s.as_str()).collect::<String>());
+
+                // Drop pieces from the front until the remainder fits in the overlap and leaves
+                // room for the new piece.
+                while !current.is_empty()
+                    && (total > self.chunk_overlap || total + len > self.chunk_size)
+                {
+                    total -= current.remove(0).1;
+                }
+            }
+            total += len;
+            current.push((piece, len));
+        }
+
+        if !current.is_empty() {
+            chunks.push(current.iter().map(|(s, _)| s.as_str()).collect::<String>());
+        }
+
+        chunks
+            .into_iter()
+            .map(|chunk| chunk.trim().to_string())
+            .collect()
+    }
+}
+
+impl TextSplitter for RecursiveSplitter {
+    fn split_text(&self, text: &str) -> Vec<String> {
+        self.split_recursive(text, &self.separators)
+            .into_iter()
+            .filter(|chunk| !chunk.is_empty())
+            .collect()
+    }
+}
+
+// ================================================================
+// MarkdownSplitter
+// ================================================================
+
+/// A chunk of a markdown document along with the path of headings it is nested under
+///  (e.g.: `["Tokenomics", "Vesting"]`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarkdownChunk {
+    pub headings: Vec<String>,
+    pub text: String,
+}
+
+/// Splits markdown documents into sections on ATX headings (`#`, `##`, ...). Headings inside
+///  fenced code blocks are ignored. Sections larger than `chunk_size` characters are further
+///  split with a [RecursiveSplitter].
+#[derive(Clone, Debug)]
+pub struct MarkdownSplitter {
+    inner: RecursiveSplitter,
+    chunk_size: usize,
+}
+
+impl MarkdownSplitter {
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Result<Self, SplitterError> {
+        Ok(Self {
+            inner: RecursiveSplitter::new(chunk_size, chunk_overlap)?,
+            chunk_size,
+        })
+    }
+
+    /// Splits `text` into [MarkdownChunk]s, keeping track of the heading path of each chunk.
+    pub fn split_with_headings(&self, text: &str) -> Vec<MarkdownChunk> {
+        markdown_sections(text)
+            .into_iter()
+            .flat_map(|(headings, section)| {
+                let texts = if section.chars().count() <= self.chunk_size {
+                    vec![section.trim().to_string()]
+                } else {
+                    self.inner.split_text(&section)
+                };
+                texts
+                    .into_iter()
+                    .filter(|text| !text.is_empty())
+                    .map(move |text| MarkdownChunk {
+                        headings: headings.clone(),
+                        text,
+                    })
+            })
+            .collect()
+    }
+}
+
+impl TextSplitter for MarkdownSplitter {
+    fn split_text(&self, text: &str) -> Vec<String> {
+        self.split_with_headings(text)
+            .into_iter()
+            .map(|chunk| chunk.text)
+            .collect()
+    }
+}
+
+/// Parses an ATX heading line into its level and title.
+pub(crate) fn parse_heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim().to_string()))
+}
+
+/// Splits a markdown document into `(heading path, section text)` pairs. Each section includes
+///  its own heading line.
+pub(crate) fn markdown_sections(text: &str) -> Vec<(Vec<String>, String)> {
+    let mut sections = vec![];
+    let mut stack: Vec<(usize, String)> = vec![];
+    let mut current = String::new();
+    let mut in_code_block = false;
+
+    for line in text.lines() {
+        let fence = line.trim_start();
+        if fence.starts_with("```") || fence.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+
+        if let Some((level, title)) = parse_heading(line).filter(|_| !in_code_block) {
+            if !current.trim().is_empty() {
+                let path = stack.iter().map(|(_, t)| t.clone()).collect();
+                sections.push((path, std::mem::take(&mut current)));
+            }
+            current.clear();
+            stack.retain(|(l, _)| *l < level);
+            stack.push((level, title));
+        }
+
+        current.push_str(line);
+        current.push('\n');
+    }
+
+    if !current.trim().is_empty() {
+        let path = stack.iter().map(|(_, t)| t.clone()).collect();
+        sections.push((path, current));
+    }
+
+    sections
+}
+
+// ================================================================
+// SentenceSplitter
+// ================================================================
+
+/// Groups whole sentences into chunks of at most `chunk_size` characters. Consecutive chunks
+///  share `sentence_overlap` sentences. A single sentence longer than `chunk_size` becomes its
+///  own chunk.
+#[derive(Clone, Debug)]
+pub struct SentenceSplitter {
+    chunk_size: usize,
+    sentence_overlap: usize,
+}
+
+impl SentenceSplitter {
+    pub fn new(chunk_size: usize) -> Result<Self, SplitterError> {
+        validate(chunk_size, 0)?;
+        Ok(Self {
+            chunk_size,
+            sentence_overlap: 0,
+        })
+    }
+
+    /// Sets the number of sentences shared by consecutive chunks.
+    pub fn sentence_overlap(mut self, sentence_overlap: usize) -> Self {
+        self.sentence_overlap = sentence_overlap;
+        self
+    }
+}
+
+impl TextSplitter for SentenceSplitter {
+    fn split_text(&self, text: &str) -> Vec<String> {
+        let sentences = split_sentences(text);
+
+        let mut chunks = vec![];
+        let mut start = 0;
+        while start < sentences.len() {
+            let mut end = start;
+            let mut len = 0;
+            while end < sentences.len() {
+                let next = sentences[end].chars().count() + usize::from(end > start);
+                if end > start && len + next > self.chunk_size {
+                    break;
+                }
+                len += next;
+                end += 1;
+            }
+
+            chunks.push(sentences[start..end].join(" "));
+            if end == sentences.len() {
+                break;
+            }
+            // Always make progress, even if the overlap covers the whole chunk
+            start = end.saturating_sub(self.sentence_overlap).max(start + 1);
+        }
+
+        chunks
+    }
+}
+
+/// Splits `text` into sentences. A sentence ends with `.`, `!` or `?` followed by whitespace, or
+///  with a blank line.
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = vec![];
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let boundary = match (c, chars.peek()) {
+            ('.' | '!' | '?', Some((_, next))) => next.is_whitespace(),
+            ('\n', Some((_, '\n'))) => true,
+            _ => false,
+        };
+        if boundary {
+            let end = i + c.len_utf8();
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_splitter() {
+        let splitter = CharacterSplitter::new(4, 1).unwrap();
+        assert_eq!(
+            splitter.split_text("abcdefghij"),
+            vec!["abcd", "defg", "ghij"]
+        );
+        assert_eq!(
+            CharacterSplitter::new(4, 4).unwrap_err(),
+            SplitterError::OverlapTooLarge {
+                size: 4,
+                overlap: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_token_splitter() {
+        let splitter = TokenSplitter::new(3, 1).unwrap();
+        assert_eq!(
+            splitter.split_text("one two  three four five"),
+            vec!["one two  three", "three four five"]
+        );
+    }
+
+    #[test]
+    fn test_recursive_splitter() {
+        let text =
+            "First paragraph is here.\n\nSecond paragraph is a bit longer than the first one.";
+        let splitter = RecursiveSplitter::new(30, 0).unwrap();
+        let chunks = splitter.split_text(text);
+
+        assert_eq!(chunks[0], "First paragraph is here.");
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 30));
+        assert_eq!(
+            chunks[1..].join(" "),
+            "Second paragraph is a bit longer than the first one."
+        );
+    }
+
+    #[test]
+    fn test_markdown_splitter() {
+        let text = "# Token\nIntro\n## Supply\nFixed\n```\n# not a heading\n```\n# Team\nAnon";
+        let splitter = MarkdownSplitter::new(100, 0).unwrap();
+        let chunks = splitter.split_with_headings(text);
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.headings.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                vec!["Token".to_string()],
+                vec!["Token".to_string(), "Supply".to_string()],
+                vec!["Team".to_string()],
+            ]
+        );
+        assert!(chunks[1].text.contains("# not a heading"));
+    }
+
+    #[test]
+    fn test_sentence_splitter() {
+        let text = "SOL is up. BONK is down! Is JUP next? Nobody knows.";
+        let splitter = SentenceSplitter::new(30).unwrap().sentence_overlap(1);
+
+        assert_eq!(
+            splitter.split_text(text),
+            vec![
+                "SOL is up. BONK is down!",
+                "BONK is down! Is JUP next?",
+                "Is JUP next? Nobody knows."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunks_have_ids() {
+        let splitter = CharacterSplitter::new(5, 0).unwrap();
+        let chunks = splitter.chunks("notes.md", "hello world");
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].id, "notes.md#1");
+        assert_eq!(chunks[1].text, " worl");
+    }
+}
@@ -0,0 +1,543 @@
+//! Module defining plugins, bundles of actions that agents can use as tools.
+//!
+//! An [Action] is a typed operation: its arguments are the fields of the action type itself,
+//! deserialized from the arguments of the tool call, and it runs against a shared context (e.g.
+//! an API client) to produce a serializable output. The JSON schema of the tool is derived from
+//! the action type with [schemars], and its description defaults to the doc comment of the type.
+//!
+//! A [Plugin] registers its actions with a [PluginRegistrar]. The [PluginRegistry] collects the
+//! actions of several plugins along with their contexts, and turns them into tools that can be
+//! added to an agent with [AgentBuilder::plugins](crate::agent::AgentBuilder::plugins).
+//!
+//! # Example
+//! ```no_run
+//! use rig::{
+//!     plugin::{Action, Plugin, PluginRegistrar, PluginRegistry},
+//!     providers::openai,
+//! };
+//!
+//! struct Prices;
+//!
+//! impl Prices {
+//!     async fn price(&self, symbol: &str) -> Result<f64, std::io::Error> {
+//!         Ok(if symbol == "SOL" { 150.0 } else { 1.0 })
+//!     }
+//! }
+//!
+//! /// Get the price of a token in USD
+//! #[derive(serde::Deserialize, schemars::JsonSchema)]
+//! struct GetPrice {
+//!     /// Symbol of the token
+//!     symbol: String,
+//! }
+//!
+//! impl Action for GetPrice {
+//!     const NAME: &'static str = "get_price";
+//!     type Output = f64;
+//!     type Error = std::io::Error;
+//!     type Context = Prices;
+//!
+//!     async fn execute(self, context: &Prices) -> Result<f64, std::io::Error> {
+//!         context.price(&self.symbol).await
+//!     }
+//! }
+//!
+//! struct PricesPlugin;
+//!
+//! impl Plugin for PricesPlugin {
+//!     fn name(&self) -> &'static str {
+//!         "prices"
+//!     }
+//!
+//!     fn description(&self) -> &'static str {
+//!         "Token prices"
+//!     }
+//!
+//!     fn register(&self, registrar: &mut dyn PluginRegistrar) {
+//!         registrar.register_action::<GetPrice>();
+//!     }
+//! }
+//!
+//! let registry = PluginRegistry::new().context(Prices).plugin(&PricesPlugin);
+//! let agent = openai::Client::from_env()
+//!     .agent(openai::GPT_4O)
+//!     .plugins(&registry)?
+//!     .build();
+//! # Ok::<_, rig::plugin::ActionError>(())
+//! ```
+
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{completion::ToolDefinition, tool::Tool};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ActionError {
+    #[error("Unknown action: {0}")]
+    NotFound(String),
+
+    #[error("Invalid arguments for action {action}: {source}")]
+    InvalidArgs {
+        action: String,
+        source: serde_json::Error,
+    },
+
+    #[error("Action {action} needs a context of type {context}, add it to the registry")]
+    MissingContext { action: String, context: String },
+
+    #[error("Action {action} failed: {source}")]
+    ExecutionError {
+        action: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("Failed to serialize the output of action {action}: {source}")]
+    OutputError {
+        action: String,
+        source: serde_json::Error,
+    },
+}
+
+/// Typed operation of a plugin. The fields of the type are the arguments of the action.
+pub trait Action: for<'a> Deserialize<'a> + JsonSchema + Send + Sync + 'static {
+    /// The name of the action, used as the name of its tool. This name should be unique.
+    const NAME: &'static str;
+
+    /// Description of the action given to the model. Defaults to the doc comment of the type.
+    const DESCRIPTION: &'static str = "";
+
+    /// The output type of the action.
+    type Output: Serialize + Send;
+    /// The error type of the action.
+    type Error: std::error::Error + Send + Sync + 'static;
+    /// The context the action runs against, e.g. an API client. Contexts are shared by all the
+    /// actions that use the same type.
+    type Context: Send + Sync + 'static;
+
+    /// The action execution method.
+    fn execute(
+        self,
+        context: &Self::Context,
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send;
+}
+
+/// Bundle of actions
+pub trait Plugin: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Registers the actions of the plugin
+    fn register(&self, registrar: &mut dyn PluginRegistrar);
+}
+
+/// Collects the actions registered by plugins
+pub trait PluginRegistrar {
+    /// Registers a type-erased action. Plugins should use
+    /// [register_action](#method.register_action) instead.
+    fn register_dyn(&mut self, action: Arc<dyn ActionDyn>);
+}
+
+impl dyn PluginRegistrar + '_ {
+    /// Registers the action `A`
+    pub fn register_action<A: Action>(&mut self) {
+        self.register_dyn(Arc::new(ActionHandle::<A>(PhantomData)));
+    }
+}
+
+/// Wrapper trait to allow for dynamic dispatch of actions
+pub trait ActionDyn: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn definition(&self) -> ToolDefinition;
+
+    /// Type and name of the context of the action
+    fn context_type(&self) -> (TypeId, &'static str);
+
+    /// Executes the action with its arguments as JSON. `context` must be of the context type
+    /// of the action.
+    fn call(
+        &self,
+        context: Arc<dyn Any + Send + Sync>,
+        args: Value,
+    ) -> BoxFuture<'static, Result<Value, ActionError>>;
+}
+
+struct ActionHandle<A>(PhantomData<fn() -> A>);
+
+impl<A: Action> ActionDyn for ActionHandle<A> {
+    fn name(&self) -> &'static str {
+        A::NAME
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        // Subschemas are inlined, as not every provider resolves references
+        let schema = SchemaSettings::draft07()
+            .with(|settings| settings.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<A>();
+
+        let description = match A::DESCRIPTION {
+            "" => schema
+                .schema
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.description.clone())
+                .unwrap_or_default(),
+            description => description.to_string(),
+        };
+
+        let mut parameters = serde_json::to_value(&schema.schema).unwrap_or_default();
+        if let Some(parameters) = parameters.as_object_mut() {
+            // The name and description are already part of the definition
+            parameters.remove("title");
+            parameters.remove("description");
+        }
+
+        ToolDefinition {
+            name: A::NAME.to_string(),
+            description,
+            parameters,
+        }
+    }
+
+    fn context_type(&self) -> (TypeId, &'static str) {
+        (TypeId::of::<A::Context>(), type_name::<A::Context>())
+    }
+
+    fn call(
+        &self,
+        context: Arc<dyn Any + Send + Sync>,
+        args: Value,
+    ) -> BoxFuture<'static, Result<Value, ActionError>> {
+        Box::pin(async move {
+            let action: A =
+                serde_json::from_value(args).map_err(|source| ActionError::InvalidArgs {
+                    action: A::NAME.to_string(),
+                    source,
+                })?;
+            let context = context.downcast_ref::<A::Context>().ok_or_else(|| {
+                ActionError::MissingContext {
+                    action: A::NAME.to_string(),
+                    context: type_name::<A::Context>().to_string(),
+                }
+            })?;
+
+            let output =
+                action
+                    .execute(context)
+                    .await
+                    .map_err(|err| ActionError::ExecutionError {
+                        action: A::NAME.to_string(),
+                        source: Box::new(err),
+                    })?;
+            serde_json::to_value(output).map_err(|source| ActionError::OutputError {
+                action: A::NAME.to_string(),
+                source,
+            })
+        })
+    }
+}
+
+/// Actions of the registered plugins, and the contexts they run against
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: Vec<(&'static str, &'static str)>,
+    actions: Vec<Arc<dyn ActionDyn>>,
+    contexts: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the context of the actions whose context type is `C`, replacing the previous one
+    pub fn context<C: Send + Sync + 'static>(mut self, context: C) -> Self {
+        self.contexts.insert(TypeId::of::<C>(), Arc::new(context));
+        self
+    }
+
+    /// Register the actions of the plugin
+    pub fn plugin(mut self, plugin: &dyn Plugin) -> Self {
+        self.plugins.push((plugin.name(), plugin.description()));
+        plugin.register(&mut self);
+        self
+    }
+
+    /// Names and descriptions of the registered plugins
+    pub fn plugins(&self) -> &[(&'static str, &'static str)] {
+        &self.plugins
+    }
+
+    /// Names of the registered actions
+    pub fn actions(&self) -> Vec<&'static str> {
+        self.actions.iter().map(|action| action.name()).collect()
+    }
+
+    /// Execute the action with the given name and JSON arguments
+    pub async fn call(&self, name: &str, args: Value) -> Result<Value, ActionError> {
+        let action = self
+            .actions
+            .iter()
+            .find(|action| action.name() == name)
+            .ok_or_else(|| ActionError::NotFound(name.to_string()))?;
+        let context = self.context_of(action.as_ref())?;
+        action.call(context, args).await
+    }
+
+    /// Tools of the registered actions. Fails if the context of an action is missing.
+    pub fn tools(&self) -> Result<Vec<ActionTool>, ActionError> {
+        self.actions
+            .iter()
+            .map(|action| {
+                Ok(ActionTool {
+                    action: action.clone(),
+                    context: self.context_of(action.as_ref())?,
+                })
+            })
+            .collect()
+    }
+
+    fn context_of(
+        &self,
+        action: &dyn ActionDyn,
+    ) -> Result<Arc<dyn Any + Send + Sync>, ActionError> {
+        let (type_id, context) = action.context_type();
+        self.contexts
+            .get(&type_id)
+            .cloned()
+            .ok_or_else(|| ActionError::MissingContext {
+                action: action.name().to_string(),
+                context: context.to_string(),
+            })
+    }
+}
+
+impl PluginRegistrar for PluginRegistry {
+    fn register_dyn(&mut self, action: Arc<dyn ActionDyn>) {
+        // Registering an action twice replaces it
+        self.actions
+            .retain(|registered| registered.name() != action.name());
+        self.actions.push(action);
+    }
+}
+
+/// [Tool] running an action against its context
+#[derive(Clone)]
+pub struct ActionTool {
+    action: Arc<dyn ActionDyn>,
+    context: Arc<dyn Any + Send + Sync>,
+}
+
+impl Tool for ActionTool {
+    // Overridden by `name`, every action has its own name
+    const NAME: &'static str = "action";
+
+    type Error = ActionError;
+    type Args = Value;
+    type Output = Value;
+
+    fn name(&self) -> String {
+        self.action.name().to_string()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        self.action.definition()
+    }
+
+    fn call(
+        &self,
+        args: Self::Args,
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send + Sync {
+        SyncFuture(self.action.call(self.context.clone(), args))
+    }
+}
+
+/// Future that is `Sync`, as tool futures must be, whether the future of the action is or not
+struct SyncFuture<F>(F);
+
+// SAFETY: the future is only ever accessed through `Pin<&mut Self>` in `poll`, so a shared
+// reference never gives access to it and it cannot be used from several threads at once.
+unsafe impl<F: Send> Sync for SyncFuture<F> {}
+
+impl<F: Future + Unpin> Future for SyncFuture<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::tool::ToolSet;
+
+    struct Wallets {
+        balance: f64,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Unknown wallet {0}")]
+    struct UnknownWallet(String);
+
+    #[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+    #[serde(rename_all = "lowercase")]
+    enum Currency {
+        Usd,
+        Sol,
+    }
+
+    /// Get the balance of a wallet
+    #[derive(Deserialize, JsonSchema)]
+    struct GetBalance {
+        /// Address of the wallet
+        wallet: String,
+        currency: Option<Currency>,
+    }
+
+    impl Action for GetBalance {
+        const NAME: &'static str = "get_balance";
+        type Output = f64;
+        type Error = UnknownWallet;
+        type Context = Wallets;
+
+        async fn execute(self, context: &Wallets) -> Result<f64, UnknownWallet> {
+            if self.wallet != "alice" {
+                return Err(UnknownWallet(self.wallet));
+            }
+            Ok(match self.currency {
+                Some(Currency::Sol) => context.balance / 100.0,
+                _ => context.balance,
+            })
+        }
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct Ping {}
+
+    impl Action for Ping {
+        const NAME: &'static str = "ping";
+        const DESCRIPTION: &'static str = "Check that the service is up";
+        type Output = String;
+        type Error = UnknownWallet;
+        type Context = String;
+
+        async fn execute(self, context: &String) -> Result<String, UnknownWallet> {
+            Ok(context.clone())
+        }
+    }
+
+    struct WalletPlugin;
+
+    impl Plugin for WalletPlugin {
+        fn name(&self) -> &'static str {
+            "wallets"
+        }
+
+        fn description(&self) -> &'static str {
+            "Wallet balances"
+        }
+
+        fn register(&self, registrar: &mut dyn PluginRegistrar) {
+            registrar.register_action::<GetBalance>();
+            registrar.register_action::<Ping>();
+        }
+    }
+
+    #[test]
+    fn test_definition() {
+        let definition = ActionHandle::<GetBalance>(PhantomData).definition();
+        assert_eq!(definition.name, "get_balance");
+        assert_eq!(definition.description, "Get the balance of a wallet");
+        assert_eq!(definition.parameters["type"], "object");
+        assert_eq!(definition.parameters["required"], json!(["wallet"]));
+        assert_eq!(
+            definition.parameters["properties"]["wallet"]["description"],
+            "Address of the wallet"
+        );
+        // Nested types are inlined
+        assert!(definition.parameters.get("definitions").is_none());
+        assert!(definition.parameters["properties"]["currency"]
+            .to_string()
+            .contains("\"sol\""));
+
+        let definition = ActionHandle::<Ping>(PhantomData).definition();
+        assert_eq!(definition.description, "Check that the service is up");
+    }
+
+    #[tokio::test]
+    async fn test_registry() {
+        let registry = PluginRegistry::new()
+            .context(Wallets { balance: 250.0 })
+            .plugin(&WalletPlugin);
+        assert_eq!(registry.actions(), vec!["get_balance", "ping"]);
+
+        let balance = registry
+            .call(
+                "get_balance",
+                json!({ "wallet": "alice", "currency": "sol" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(balance, json!(2.5));
+
+        assert!(matches!(
+            registry
+                .call("get_balance", json!({ "wallet": "bob" }))
+                .await,
+            Err(ActionError::ExecutionError { .. })
+        ));
+        assert!(matches!(
+            registry.call("get_balance", json!({})).await,
+            Err(ActionError::InvalidArgs { .. })
+        ));
+        assert!(matches!(
+            registry.call("ping", json!({})).await,
+            Err(ActionError::MissingContext { .. })
+        ));
+        assert!(matches!(
+            registry.tools(),
+            Err(ActionError::MissingContext { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tools() {
+        let registry = PluginRegistry::new()
+            .context(Wallets { balance: 250.0 })
+            .context("pong".to_string())
+            .plugin(&WalletPlugin);
+
+        let mut toolset = ToolSet::default();
+        for tool in registry.tools().unwrap() {
+            toolset.add_tool(tool);
+        }
+        assert!(toolset.contains("get_balance"));
+
+        let output = toolset
+            .call("get_balance", json!({ "wallet": "alice" }).to_string())
+            .await
+            .unwrap();
+        assert_eq!(output, "250.0");
+        assert_eq!(
+            toolset.call("ping", "{}".to_string()).await.unwrap(),
+            "\"pong\""
+        );
+    }
+}
@@ -200,6 +200,14 @@ enum ToolChoice {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn tokenizer(&self) -> std::sync::Arc<dyn crate::tokenizer::Tokenizer> {
+        crate::tokenizer::for_model(&self.model)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        crate::tokenizer::context_window(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -15,7 +15,9 @@ use crate::{
     completion::{self, CompletionError},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, Embed,
+    json_utils,
+    rerank::{self, RerankError},
+    Embed,
 };
 
 use schemars::JsonSchema;
@@ -104,6 +106,11 @@ impl Client {
         CompletionModel::new(self.clone(), model)
     }
 
+    /// Create a rerank model with the given name (e.g.: [RERANK_ENGLISH_V3]).
+    pub fn rerank_model(&self, model: &str) -> RerankModel {
+        RerankModel::new(self.clone(), model)
+    }
+
     pub fn agent(&self, model: &str) -> AgentBuilder<CompletionModel> {
         AgentBuilder::new(self.completion_model(model))
     }
@@ -282,6 +289,107 @@ impl EmbeddingModel {
     }
 }
 
+// ================================================================
+// Cohere Rerank API
+// ================================================================
+/// `rerank-v3.5` rerank model
+pub const RERANK_V3_5: &str = "rerank-v3.5";
+/// `rerank-english-v3.0` rerank model
+pub const RERANK_ENGLISH_V3: &str = "rerank-english-v3.0";
+/// `rerank-multilingual-v3.0` rerank model
+pub const RERANK_MULTILINGUAL_V3: &str = "rerank-multilingual-v3.0";
+
+#[derive(Deserialize)]
+pub struct RerankResponse {
+    pub id: String,
+    pub results: Vec<RerankResult>,
+    #[serde(default)]
+    pub meta: Option<Meta>,
+}
+
+#[derive(Deserialize)]
+pub struct RerankResult {
+    pub index: usize,
+    pub relevance_score: f64,
+}
+
+/// Cohere rerank model, used to rerank the dynamic context of an agent
+/// (see [crate::rerank::Reranker]).
+#[derive(Clone)]
+pub struct RerankModel {
+    client: Client,
+    pub model: String,
+}
+
+impl RerankModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+}
+
+impl rerank::Reranker for RerankModel {
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<completion::Document>,
+        top_k: usize,
+    ) -> Result<Vec<(f64, completion::Document)>, RerankError> {
+        if documents.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let response = self
+            .client
+            .post("/v1/rerank")
+            .json(&json!({
+                "model": self.model,
+                "query": query,
+                "documents": documents.iter().map(|doc| &doc.text).collect::<Vec<_>>(),
+                "top_n": top_k,
+            }))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<RerankResponse>>().await? {
+                ApiResponse::Ok(response) => {
+                    if let Some(meta) = response.meta {
+                        tracing::info!(target: "rig",
+                            "Cohere rerank billed units: {}",
+                            meta.billed_units,
+                        );
+                    }
+
+                    let mut documents = documents.into_iter().map(Some).collect::<Vec<_>>();
+                    response
+                        .results
+                        .into_iter()
+                        .map(|result| {
+                            documents
+                                .get_mut(result.index)
+                                .and_then(Option::take)
+                                .map(|doc| (result.relevance_score, doc))
+                                .ok_or_else(|| {
+                                    RerankError::ResponseError(format!(
+                                        "Invalid document index in rerank response: {}",
+                                        result.index
+                                    ))
+                                })
+                        })
+                        .collect()
+                }
+                ApiResponse::Err(error) => Err(RerankError::ProviderError(error.message)),
+            }
+        } else {
+            Err(RerankError::ProviderError(response.text().await?))
+        }
+    }
+}
+
 // ================================================================
 // Cohere Completion API
 // ================================================================
@@ -514,6 +622,14 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn tokenizer(&self) -> std::sync::Arc<dyn crate::tokenizer::Tokenizer> {
+        crate::tokenizer::for_model(&self.model)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        crate::tokenizer::context_window(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
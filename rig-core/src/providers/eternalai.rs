@@ -457,6 +457,14 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn tokenizer(&self) -> std::sync::Arc<dyn crate::tokenizer::Tokenizer> {
+        crate::tokenizer::for_model(&self.model)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        crate::tokenizer::context_window(&self.model)
+    }
+
     async fn completion(
         &self,
         mut completion_request: CompletionRequest,
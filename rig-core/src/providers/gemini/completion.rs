@@ -45,6 +45,14 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = GenerateContentResponse;
 
+    fn tokenizer(&self) -> std::sync::Arc<dyn crate::tokenizer::Tokenizer> {
+        crate::tokenizer::for_model(&self.model)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        crate::tokenizer::context_window(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
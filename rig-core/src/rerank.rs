@@ -0,0 +1,278 @@
+//! This module defines the [Reranker] trait, which reorders documents retrieved from one or more
+//! vector store indexes by their relevance to a query.
+//!
+//! Vector search scores documents by the similarity of their embeddings to the embedding of the
+//! query, which is fast but approximate. A reranker scores each candidate against the query
+//! directly (e.g.: with a cross-encoder or an LLM), so agents typically over-fetch candidates from
+//! their dynamic context indexes, rerank them and keep only the best ones.
+//!
+//! The module provides:
+//! - [LlmReranker]: uses any [CompletionModel] to grade the relevance of each document.
+//! - [reciprocal_rank_fusion]: merges ranked lists from several indexes into a single ranking.
+//!
+//! The Cohere provider also implements [Reranker] with its rerank endpoint
+//! (see [crate::providers::cohere::RerankModel]).
+//!
+//! # Example
+//! ```rust
+//! use rig::{providers::openai, rerank::LlmReranker};
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent("gpt-4o")
+//!     .preamble("You are a trading assistant.")
+//!     // Over-fetch 20 candidates from each index...
+//!     .dynamic_context(20, research_index)
+//!     .dynamic_context(20, trade_memory_index)
+//!     // ...merge them with reciprocal rank fusion...
+//!     .reciprocal_rank_fusion(60.0)
+//!     // ...and keep the 5 most relevant ones
+//!     .reranker(5, LlmReranker::new(openai.completion_model("gpt-4o-mini")))
+//!     .build();
+//! ```
+use std::collections::HashMap;
+
+use futures::{future::BoxFuture, stream, StreamExt, TryStreamExt};
+
+use crate::completion::{CompletionError, CompletionModel, Document, ModelChoice};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RerankError {
+    /// Http error (e.g.: connection error, timeout, etc.)
+    #[error("HttpError: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    /// Json error (e.g.: serialization, deserialization)
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// Error returned by the completion model used to grade documents
+    #[error("CompletionError: {0}")]
+    CompletionError(#[from] CompletionError),
+
+    /// Error parsing the rerank response
+    #[error("ResponseError: {0}")]
+    ResponseError(String),
+
+    /// Error returned by the rerank model provider
+    #[error("ProviderError: {0}")]
+    ProviderError(String),
+}
+
+/// Trait for rerankers that reorder documents by their relevance to a query.
+pub trait Reranker: Send + Sync {
+    /// Scores `documents` against `query` and returns the `top_k` most relevant ones, sorted by
+    ///  decreasing relevance. The result is a list of tuples of the form (score, document).
+    fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<Document>,
+        top_k: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, Document)>, RerankError>> + Send;
+}
+
+pub type RerankResults = Result<Vec<(f64, Document)>, RerankError>;
+
+/// Object-safe version of [Reranker], used to store rerankers in an [Agent](crate::agent::Agent).
+pub trait RerankerDyn: Send + Sync {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<Document>,
+        top_k: usize,
+    ) -> BoxFuture<'a, RerankResults>;
+}
+
+impl<R: Reranker> RerankerDyn for R {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<Document>,
+        top_k: usize,
+    ) -> BoxFuture<'a, RerankResults> {
+        Box::pin(Reranker::rerank(self, query, documents, top_k))
+    }
+}
+
+// ================================================================
+// LLM-based reranker
+// ================================================================
+
+const LLM_RERANKER_PREAMBLE: &str = "\
+You are a relevance grader. You will be given a query and a document.
+Rate how relevant the document is to the query on a scale from 0 (completely irrelevant) to 10 \
+(directly answers the query).
+Respond with the number only.";
+
+/// Reranker that uses a completion model as a cross-encoder: the model is shown the query along
+///  with each candidate document and asked to grade its relevance from 0 to 10. Documents are
+///  graded concurrently (up to `concurrency` requests at a time, 8 by default).
+///
+/// Responses that do not contain a number are treated as a score of 0.
+#[derive(Clone)]
+pub struct LlmReranker<M: CompletionModel> {
+    model: M,
+    preamble: String,
+    concurrency: usize,
+}
+
+impl<M: CompletionModel> LlmReranker<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            preamble: LLM_RERANKER_PREAMBLE.to_string(),
+            concurrency: 8,
+        }
+    }
+
+    /// Replaces the grading instructions sent to the model. The model must still respond with
+    ///  a number.
+    pub fn preamble(mut self, preamble: &str) -> Self {
+        self.preamble = preamble.to_string();
+        self
+    }
+
+    /// Set the maximum number of concurrent grading requests
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    async fn grade(&self, query: &str, document: &Document) -> Result<f64, RerankError> {
+        let response = self
+            .model
+            .completion_request(&format!(
+                "<query>\n{query}\n</query>\n<document>\n{}\n</document>",
+                document.text
+            ))
+            .preamble(self.preamble.clone())
+            .temperature(0.0)
+            .send()
+            .await?;
+
+        Ok(match response.choice {
+            ModelChoice::Message(text) => parse_score(&text).unwrap_or_else(|| {
+                tracing::warn!(target: "rig", "Could not parse relevance score: {}", text);
+                0.0
+            }),
+            ModelChoice::ToolCall(..) => {
+                return Err(RerankError::ResponseError(
+                    "Expected a relevance score, got a tool call".into(),
+                ))
+            }
+        })
+    }
+}
+
+impl<M: CompletionModel> Reranker for LlmReranker<M> {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<Document>,
+        top_k: usize,
+    ) -> Result<Vec<(f64, Document)>, RerankError> {
+        let scored = stream::iter(documents)
+            .map(|document| async move {
+                let score = self.grade(query, &document).await?;
+                Ok::<_, RerankError>((score, document))
+            })
+            .buffered(self.concurrency)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(top_k_by_score(scored, top_k))
+    }
+}
+
+/// Parses the first number found in `text`.
+fn parse_score(text: &str) -> Option<f64> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let number = text[start..]
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .next()?;
+    number.trim_end_matches('.').parse().ok()
+}
+
+/// Sorts `scored` documents by decreasing score and keeps the first `k`. The sort is stable, so
+///  documents with equal scores keep their original order.
+pub(crate) fn top_k_by_score(mut scored: Vec<(f64, Document)>, k: usize) -> Vec<(f64, Document)> {
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    scored.truncate(k);
+    scored
+}
+
+// ================================================================
+// Reciprocal rank fusion
+// ================================================================
+
+/// Default value of the `k` constant of [reciprocal_rank_fusion], as suggested by the original
+///  paper (Cormack et al., 2009).
+pub const DEFAULT_RRF_K: f64 = 60.0;
+
+/// Merges several ranked lists of documents (e.g.: the results of several vector store indexes)
+///  into a single ranking using reciprocal rank fusion. Each document is scored with
+///  `sum(1 / (k + rank))` over the lists it appears in (with 1-based ranks), so documents ranked
+///  highly by several lists come first. Documents are identified by their id.
+///
+/// The result is sorted by decreasing fused score.
+pub fn reciprocal_rank_fusion(
+    rankings: impl IntoIterator<Item = Vec<Document>>,
+    k: f64,
+) -> Vec<(f64, Document)> {
+    let mut fused: Vec<(f64, Document)> = vec![];
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for ranking in rankings {
+        for (rank, document) in ranking.into_iter().enumerate() {
+            let score = 1.0 / (k + rank as f64 + 1.0);
+            match positions.get(&document.id) {
+                Some(&i) => fused[i].0 += score,
+                None => {
+                    positions.insert(document.id.clone(), fused.len());
+                    fused.push((score, document));
+                }
+            }
+        }
+    }
+
+    let len = fused.len();
+    top_k_by_score(fused, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{parse_score, reciprocal_rank_fusion, DEFAULT_RRF_K};
+    use crate::completion::Document;
+
+    fn doc(id: &str) -> Document {
+        Document {
+            id: id.to_string(),
+            text: format!("text of {id}"),
+            additional_props: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let fused = reciprocal_rank_fusion(
+            vec![
+                vec![doc("a"), doc("b"), doc("c")],
+                vec![doc("c"), doc("b"), doc("d")],
+            ],
+            DEFAULT_RRF_K,
+        );
+
+        let ids = fused.iter().map(|(_, d)| d.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["c", "b", "a", "d"]);
+        assert!((fused[1].0 - 2.0 / 62.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_parse_score() {
+        assert_eq!(parse_score("8"), Some(8.0));
+        assert_eq!(parse_score("Score: 7.5."), Some(7.5));
+        assert_eq!(parse_score("irrelevant"), None);
+    }
+}
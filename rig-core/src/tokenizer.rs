@@ -0,0 +1,182 @@
+//! This module defines the [Tokenizer] trait, used to count the tokens of the different parts of
+//! a completion request so that they can be fitted in the context window of a model
+//! (see [crate::context::ContextAssembler]).
+//!
+//! Every [CompletionModel](crate::completion::CompletionModel) exposes the tokenizer and the
+//! context window of its model through [CompletionModel::tokenizer](crate::completion::CompletionModel::tokenizer)
+//! and [CompletionModel::context_window](crate::completion::CompletionModel::context_window).
+//!
+//! OpenAI models are tokenized with their actual BPE encodings when the `tiktoken` feature is
+//! enabled. Other models (and OpenAI models without the `tiktoken` feature) use a
+//! [HeuristicTokenizer] calibrated for the provider, which slightly overestimates the number of
+//! tokens of typical English text.
+use std::sync::Arc;
+
+/// Trait for tokenizers that can count the tokens of a text.
+pub trait Tokenizer: Send + Sync {
+    /// Returns the number of tokens of `text`.
+    fn count_tokens(&self, text: &str) -> usize;
+
+    /// Returns the longest prefix of `text` that has at most `max_tokens` tokens.
+    fn truncate(&self, text: &str, max_tokens: usize) -> String;
+}
+
+/// Tokenizer that estimates the number of tokens from the number of characters of the text.
+#[derive(Clone, Copy, Debug)]
+pub struct HeuristicTokenizer {
+    chars_per_token: f64,
+}
+
+impl Default for HeuristicTokenizer {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl HeuristicTokenizer {
+    pub fn new(chars_per_token: f64) -> Self {
+        Self {
+            chars_per_token: chars_per_token.max(1.0),
+        }
+    }
+}
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+
+    fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        let max_chars = (max_tokens as f64 * self.chars_per_token).floor() as usize;
+        text.chars().take(max_chars).collect()
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+pub use tiktoken::TiktokenTokenizer;
+
+#[cfg(feature = "tiktoken")]
+mod tiktoken {
+    use std::sync::Arc;
+
+    use tiktoken_rs::CoreBPE;
+
+    use super::Tokenizer;
+
+    /// Tokenizer backed by the BPE encodings used by OpenAI models.
+    #[derive(Clone)]
+    pub struct TiktokenTokenizer {
+        bpe: Arc<CoreBPE>,
+    }
+
+    impl TiktokenTokenizer {
+        /// Returns the tokenizer of the given OpenAI model, if known.
+        pub fn for_model(model: &str) -> Option<Self> {
+            tiktoken_rs::get_bpe_from_model(model)
+                .ok()
+                .map(|bpe| Self { bpe: Arc::new(bpe) })
+        }
+    }
+
+    impl Tokenizer for TiktokenTokenizer {
+        fn count_tokens(&self, text: &str) -> usize {
+            self.bpe.encode_with_special_tokens(text).len()
+        }
+
+        fn truncate(&self, text: &str, max_tokens: usize) -> String {
+            let tokens = self.bpe.encode_with_special_tokens(text);
+            if tokens.len() <= max_tokens {
+                return text.to_string();
+            }
+            // Tokens can split multi-byte characters, so drop tokens until the prefix decodes
+            (0..=max_tokens)
+                .rev()
+                .find_map(|n| self.bpe.decode(tokens[..n].to_vec()).ok())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Returns the tokenizer of the given model. See the module documentation for which tokenizer
+///  is used for each provider.
+pub fn for_model(model: &str) -> Arc<dyn Tokenizer> {
+    #[cfg(feature = "tiktoken")]
+    if let Some(tokenizer) = TiktokenTokenizer::for_model(model) {
+        return Arc::new(tokenizer);
+    }
+
+    // Claude's tokenizer produces more tokens than OpenAI's for the same text
+    let chars_per_token = if model.starts_with("claude") {
+        3.5
+    } else {
+        4.0
+    };
+    Arc::new(HeuristicTokenizer::new(chars_per_token))
+}
+
+/// Context windows of known models, by model name prefix. More specific prefixes must come first.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    // OpenAI
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-0125", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo-instruct", 4_096),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1-mini", 128_000),
+    ("o1", 200_000),
+    // Anthropic
+    ("claude-", 200_000),
+    // Gemini
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-1.5-flash", 1_048_576),
+    ("gemini-2.0-flash", 1_048_576),
+    ("gemini-1.0-pro", 32_760),
+    // Cohere
+    ("command-r", 128_000),
+    ("command-light", 4_096),
+    ("command", 4_096),
+    // xAI
+    ("grok-beta", 131_072),
+    ("grok-", 131_072),
+    // Perplexity
+    ("llama-3.1-sonar", 127_072),
+];
+
+/// Returns the context window (in tokens, including the completion) of the given model, if known.
+pub fn context_window(model: &str) -> Option<usize> {
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{context_window, HeuristicTokenizer, Tokenizer};
+
+    #[test]
+    fn test_heuristic_tokenizer() {
+        let tokenizer = HeuristicTokenizer::default();
+        assert_eq!(tokenizer.count_tokens("abcdefghi"), 3);
+        assert_eq!(tokenizer.truncate("abcdefghi", 2), "abcdefgh");
+    }
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(context_window("gpt-4o-mini"), Some(128_000));
+        assert_eq!(context_window("gpt-4"), Some(8_192));
+        assert_eq!(context_window("claude-3-5-sonnet-latest"), Some(200_000));
+        assert_eq!(context_window("my-local-model"), None);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_tiktoken_tokenizer() {
+        let tokenizer = super::for_model("gpt-4o");
+        assert_eq!(tokenizer.count_tokens("hello world"), 2);
+        assert_eq!(tokenizer.truncate("hello world", 1), "hello");
+    }
+}
@@ -5,6 +5,7 @@ use serde_json::Value;
 use crate::embeddings::EmbeddingError;
 
 pub mod in_memory_store;
+pub mod scoring;
 
 #[derive(Debug, thiserror::Error)]
 pub enum VectorStoreError {
@@ -0,0 +1,228 @@
+//! Utilities to post-process the results of a [VectorStoreIndex](super::VectorStoreIndex) search.
+//!
+//! Backends do not agree on what the score of a result means: the in-memory store and Qdrant
+//! return a similarity (higher is better), while `rig-sqlite` returns a distance (lower is
+//! better). [ScoreKind] converts raw scores into a similarity so that thresholds can be compared
+//! across backends.
+//!
+//! [SearchOptions] bundles the per-index settings used by the dynamic context of an
+//! [Agent](crate::agent::Agent): a minimum score and an optional maximal marginal relevance
+//! (MMR) selection, which trades off relevance against diversity so that near-duplicate
+//! documents do not crowd out everything else.
+use std::collections::HashSet;
+
+use crate::completion::Document;
+
+/// Describes how the raw scores returned by a vector store index should be interpreted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScoreKind {
+    /// Scores are similarities where higher is better (e.g.: cosine similarity). Scores are used
+    /// as is.
+    #[default]
+    Similarity,
+    /// Scores are cosine distances (`1 - cosine similarity`). Normalized as `1 - distance`.
+    CosineDistance,
+    /// Scores are unbounded distances where lower is better (e.g.: euclidean distance).
+    /// Normalized as `1 / (1 + distance)`.
+    Distance,
+}
+
+impl ScoreKind {
+    /// Converts a raw score into a similarity where higher is better.
+    pub fn normalize(&self, score: f64) -> f64 {
+        match self {
+            ScoreKind::Similarity => score,
+            ScoreKind::CosineDistance => 1.0 - score,
+            ScoreKind::Distance => 1.0 / (1.0 + score.max(0.0)),
+        }
+    }
+}
+
+/// Per-index options of the dynamic context of an [Agent](crate::agent::Agent).
+///
+/// # Example
+/// ```rust
+/// use rig::vector_store::scoring::{ScoreKind, SearchOptions};
+///
+/// // rig-sqlite returns euclidean distances: only keep documents with a normalized similarity
+/// // of at least 0.5, and pick the sampled documents among the 20 best matches using MMR.
+/// let options = SearchOptions::default()
+///     .score_kind(ScoreKind::Distance)
+///     .min_score(0.5)
+///     .mmr(20, 0.7);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchOptions {
+    /// How raw scores returned by the index should be normalized
+    pub score_kind: ScoreKind,
+    /// Minimum normalized score of a document to be included
+    pub min_score: Option<f64>,
+    /// Number of candidates to fetch and trade-off between relevance (1.0) and diversity (0.0)
+    /// used to select the documents with maximal marginal relevance
+    pub mmr: Option<(usize, f64)>,
+}
+
+impl SearchOptions {
+    /// Set how raw scores returned by the index should be normalized
+    pub fn score_kind(mut self, score_kind: ScoreKind) -> Self {
+        self.score_kind = score_kind;
+        self
+    }
+
+    /// Only keep documents whose normalized score is at least `min_score`
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Fetch `fetch` candidates and select the sampled documents among them using maximal
+    /// marginal relevance, with `lambda` between 0.0 (only diversity) and 1.0 (only relevance).
+    pub fn mmr(mut self, fetch: usize, lambda: f64) -> Self {
+        self.mmr = Some((fetch, lambda.clamp(0.0, 1.0)));
+        self
+    }
+
+    /// Number of candidates to request from the index to end up with `sample` documents.
+    pub fn fetch_size(&self, sample: usize) -> usize {
+        match self.mmr {
+            Some((fetch, _)) => fetch.max(sample),
+            None => sample,
+        }
+    }
+
+    /// Normalizes, filters and selects `sample` documents among `results`, which are expected
+    /// to be sorted from best to worst match. The returned scores are normalized.
+    pub fn apply(&self, results: Vec<(f64, Document)>, sample: usize) -> Vec<(f64, Document)> {
+        let results = results
+            .into_iter()
+            .map(|(score, doc)| (self.score_kind.normalize(score), doc))
+            .filter(|(score, _)| self.min_score.is_none_or(|min| *score >= min))
+            .collect::<Vec<_>>();
+
+        match self.mmr {
+            Some((_, lambda)) => maximal_marginal_relevance(results, sample, lambda),
+            None => results.into_iter().take(sample).collect(),
+        }
+    }
+}
+
+/// Selects `k` documents among `candidates` using maximal marginal relevance. Documents are
+///  picked one at a time, maximizing `lambda * relevance - (1 - lambda) * redundancy`, where
+///  `relevance` is the (normalized) score of the candidate and `redundancy` is its highest
+///  [text_similarity] with the documents already selected.
+///
+/// The selected documents are returned in the order they were picked.
+pub fn maximal_marginal_relevance(
+    candidates: Vec<(f64, Document)>,
+    k: usize,
+    lambda: f64,
+) -> Vec<(f64, Document)> {
+    let mut remaining = candidates
+        .into_iter()
+        .map(|(score, doc)| {
+            let words = words(&doc.text);
+            (score, doc, words)
+        })
+        .collect::<Vec<_>>();
+    let mut selected: Vec<(f64, Document, HashSet<String>)> = vec![];
+
+    while selected.len() < k && !remaining.is_empty() {
+        let (best, _) = remaining
+            .iter()
+            .enumerate()
+            .map(|(i, (score, _, words))| {
+                let redundancy = selected
+                    .iter()
+                    .map(|(_, _, other)| jaccard(words, other))
+                    .fold(0.0, f64::max);
+                (i, lambda * score - (1.0 - lambda) * redundancy)
+            })
+            .fold((0, f64::NEG_INFINITY), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+        selected.push(remaining.remove(best));
+    }
+
+    selected
+        .into_iter()
+        .map(|(score, doc, _)| (score, doc))
+        .collect()
+}
+
+/// Lexical similarity between two texts, computed as the Jaccard index of their sets of
+///  lowercase words. Returns a value between 0.0 (no common words) and 1.0 (same words).
+pub fn text_similarity(a: &str, b: &str) -> f64 {
+    jaccard(&words(a), &words(b))
+}
+
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '$')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{ScoreKind, SearchOptions};
+    use crate::completion::Document;
+
+    fn doc(id: &str, text: &str) -> Document {
+        Document {
+            id: id.to_string(),
+            text: text.to_string(),
+            additional_props: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_score_normalization() {
+        assert_eq!(ScoreKind::Similarity.normalize(0.8), 0.8);
+        assert_eq!(ScoreKind::CosineDistance.normalize(0.25), 0.75);
+        assert_eq!(ScoreKind::Distance.normalize(1.0), 0.5);
+    }
+
+    #[test]
+    fn test_min_score() {
+        let options = SearchOptions::default()
+            .score_kind(ScoreKind::Distance)
+            .min_score(0.5);
+        let results = options.apply(vec![(0.5, doc("a", "close")), (3.0, doc("b", "far"))], 10);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1.id, "a");
+    }
+
+    #[test]
+    fn test_mmr_skips_near_duplicates() {
+        let options = SearchOptions::default().mmr(3, 0.5);
+        let results = options.apply(
+            vec![
+                (0.95, doc("a", "Bought 10 SOL at $140 on breakout")),
+                (0.94, doc("b", "Bought 10 SOL at $141 on breakout")),
+                (0.80, doc("c", "Sold BONK after funding turned negative")),
+            ],
+            2,
+        );
+
+        let ids = results
+            .iter()
+            .map(|(_, d)| d.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "c"]);
+    }
+}
@@ -1,163 +1,905 @@
 use anyhow::Result;
-use rig::{
-    agent::Agent,
-    providers::openai::{Client as OpenAIClient, CompletionModel, GPT_4_TURBO, TEXT_EMBEDDING_ADA_002, EmbeddingModel},
-};
+use rig::providers::openai::{Client as OpenAIClient, GPT_4_TURBO, TEXT_EMBEDDING_ADA_002, EmbeddingModel};
 use qdrant_client::{
     qdrant::{CreateCollectionBuilder, Distance, QueryPointsBuilder, VectorParamsBuilder},
     Qdrant,
 };
+use async_trait::async_trait;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tokio::{
+    sync::{watch, RwLock},
+    task::JoinHandle,
+};
 use crate::{
-    trading::TradingEngine,
-    twitter::TwitterClient,
-    birdeye::BirdeyeClient,
+    character::{Character, CharacterAgent, ChatAgent, PostSettings, PostVoice, PostWriter},
+    trading::{TradeDecision, TradingEngine},
+    clients::{
+        twitter::{
+            mentions::cashtags, MentionHandler, MentionListener, MentionSettings, Tweet, TwitterAuth, TwitterClient,
+        },
+        SocialMediaClient,
+    },
+    content::{default_calendar, ContentHandler, ContentJob, ContentKind, ContentScheduler},
+    sentiment::{
+        ExtractorScorer, SentimentReading, SentimentSettings, SentimentTool, SentimentTracker,
+    },
+    notifications::{
+        AlertSettings, CharacterSink, Event, LogSink, NotificationBus, ReviewQueue, Route,
+        WebhookSink,
+    },
+    providers::birdeye::MarketDataProvider,
+    triggers::{default_triggers, Trigger, TriggerAction, TriggerEngine, TriggerRule},
+};
+use rig_birdeye::{
+    providers::WebSocketProvider, BirdeyeProvider, ResponseCache, Side, TimeInterval,
+    TokenListItem, TokenOverview,
 };
 use rig_qdrant::QdrantVectorStore;
+#[cfg(any(feature = "discord", feature = "telegram"))]
+use crate::clients::bot::BotHandler;
+#[cfg(feature = "discord")]
+use crate::clients::discord::{DiscordBot, DiscordClient, DiscordConfig};
+#[cfg(feature = "telegram")]
+use crate::clients::telegram::{TelegramBot, TelegramClient, TelegramConfig};
+
+mod runtime;
+
+pub use runtime::CharacterRuntime;
 
 const COLLECTION_NAME: &str = "trade_memories";
 const VECTOR_SIZE: u64 = 1536; // OpenAI embedding size
+const DEFAULT_AGENT_NAME: &str = "default";
+/// Trades whose estimated slippage is above this, in %, are rejected
+const MAX_SLIPPAGE: f64 = 2.0;
+/// Hourly closes of the token given to the market analysis
+const ANALYSIS_HISTORY_HOURS: usize = 24;
+/// Directory of the mention cursors of the agents
+const MENTIONS_DIR: &str = "data/mentions";
+/// Directory of the OAuth 2.0 tokens of the Twitter accounts of the agents
+const TWITTER_DIR: &str = "data/twitter";
+/// Directory of the content calendar states of the agents
+const CALENDAR_DIR: &str = "data/calendar";
+/// Directory of the chats subscribed to the Telegram alerts of the agents
+#[cfg(feature = "telegram")]
+const TELEGRAM_DIR: &str = "data/telegram";
 
 #[derive(Debug, Clone)]
 pub struct AgentConfig {
     pub openai_api_key: String,
     pub birdeye_api_key: String,
-    pub twitter_email: String,
-    pub twitter_username: String,
-    pub twitter_password: String,
+    /// Credentials of the Twitter account of the default agent. Without credentials, trade
+    /// updates are not posted.
+    pub twitter_auth: Option<TwitterAuth>,
 }
 
 pub struct TradingAgent {
-    agent: Agent<CompletionModel>,
+    name: String,
+    namespace: String,
+    agent: Arc<RwLock<Box<dyn ChatAgent>>>,
     trading_engine: TradingEngine,
-    twitter_client: TwitterClient,
+    twitter_client: Option<TwitterClient>,
+    /// Users the agent never replies to on Twitter
+    blocked_users: Vec<String>,
+    mention_settings: MentionSettings,
+    #[cfg(feature = "discord")]
+    discord_client: Option<DiscordClient>,
+    #[cfg(feature = "telegram")]
+    telegram_client: Option<TelegramClient>,
+    notifications: NotificationBus,
+    /// Writes the posts of the agent in the voice of its character
+    posts: PostWriter,
+    /// Posts waiting for approval, when posts are reviewed
+    review_queue: Option<Arc<ReviewQueue>>,
+    /// Scheduled posts, published on Twitter
+    calendar: Vec<ContentJob>,
+    /// Social sentiment of the tokens, when Twitter is enabled
+    sentiment: Option<Arc<SentimentTracker>>,
+    /// Rules of the trading loop
+    triggers: Vec<TriggerRule>,
     vector_store: QdrantVectorStore<EmbeddingModel>,
-    birdeye_client: BirdeyeClient,
+    market_data: Arc<dyn MarketDataProvider>,
     config: AgentConfig,
 }
 
 impl TradingAgent {
+    /// Creates the default agent, which uses GPT-4 Turbo with the default system prompt.
     pub async fn new(config: AgentConfig) -> Result<Self> {
-        // Initialize OpenAI client
+        let twitter_client = config
+            .twitter_auth
+            .clone()
+            .map(|auth| twitter_client(auth, DEFAULT_AGENT_NAME));
+        let sentiment =
+            sentiment_tracker(twitter_client.as_ref(), &SentimentSettings::default(), &config);
+
         let openai_client = OpenAIClient::new(&config.openai_api_key);
-        
-        // Create agent with GPT-4
-        let agent = openai_client
+        let mut builder = openai_client
             .agent(GPT_4_TURBO)
-            .preamble(include_str!("../prompts/system.txt"))
-            .build();
+            .preamble(include_str!("../prompts/system.txt"));
+        if let Some(tracker) = &sentiment {
+            builder = builder.tool(SentimentTool::new(tracker.clone()));
+        }
+        let agent = Box::new(CharacterAgent::new(builder.build(), vec![]));
 
-        // Initialize components
-        let trading_engine = TradingEngine::new(0.7, 1000.0);
-        
-        // Initialize Twitter client and login
-        let mut twitter_client = TwitterClient::new(
-            config.twitter_email.clone(),
-            config.twitter_username.clone(),
-            config.twitter_password.clone(),
-        );
-        twitter_client.login().await?;
-        
+        let mut agent =
+            Self::init(DEFAULT_AGENT_NAME, agent, twitter_client, COLLECTION_NAME, config).await?;
+        agent.sentiment = sentiment;
+        agent.blocked_users = blocked_users(std::env::var("TWITTER_BLOCKED_USERS").ok());
+        #[cfg(feature = "discord")]
+        if std::env::var("DISCORD_API_TOKEN").is_ok() {
+            let config = DiscordConfig::from_secrets(|name| std::env::var(name).ok())?;
+            agent.discord_client = Some(DiscordClient::new(config));
+        }
+        #[cfg(feature = "telegram")]
+        if std::env::var("TELEGRAM_BOT_TOKEN").is_ok() {
+            let config = TelegramConfig::from_secrets(|name| std::env::var(name).ok())?;
+            agent.telegram_client = Some(TelegramClient::new(config, agent.telegram_subscriptions()));
+        }
+        let settings = PostSettings {
+            review: std::env::var("REVIEW_POSTS").is_ok_and(|review| review == "true"),
+            ..Default::default()
+        };
+        agent.set_post_voice(PostVoice {
+            name: agent.name.clone(),
+            settings,
+            ..Default::default()
+        })
+        .await;
+        let routes = agent.notification_routes(&HashMap::new(), |name| std::env::var(name).ok());
+        agent.notifications = NotificationBus::new(routes);
+        Ok(agent)
+    }
+
+    /// Creates the agent of a character. The character gets its own Twitter client (if
+    /// `twitter` is one of its clients), authenticated with the `TWITTER_*` secrets of the
+    /// character file or environment variables, and its own trade memory collection.
+    pub async fn from_character(character: &Character, config: AgentConfig) -> Result<Self> {
+        let twitter_client = if character.has_client("twitter") {
+            let auth = TwitterAuth::from_secrets(|name| character.secret(name))?;
+            Some(twitter_client(auth, &character.namespace()))
+        } else {
+            None
+        };
+        let sentiment =
+            sentiment_tracker(twitter_client.as_ref(), &character.settings.sentiment, &config);
+        let agent = build_agent(character, sentiment.as_ref()).await?;
+
+        let collection = format!("{}_{}", COLLECTION_NAME, character.namespace());
+        let mut agent = Self::init(&character.name, agent, twitter_client, &collection, config).await?;
+        agent.sentiment = sentiment;
+        agent.namespace = character.namespace();
+        agent.blocked_users = blocked_users(character.secret("TWITTER_BLOCKED_USERS"));
+        agent.mention_settings = character.settings.mentions.clone();
+        #[cfg(feature = "discord")]
+        if character.has_client("discord") {
+            let config = DiscordConfig::from_secrets(|name| character.secret(name))?;
+            agent.discord_client = Some(DiscordClient::new(config));
+        }
+        #[cfg(feature = "telegram")]
+        if character.has_client("telegram") {
+            let config = TelegramConfig::from_secrets(|name| character.secret(name))?;
+            agent.telegram_client = Some(TelegramClient::new(config, agent.telegram_subscriptions()));
+        }
+        agent.set_post_voice(PostVoice::from(character)).await;
+        agent.calendar = character
+            .settings
+            .calendar
+            .clone()
+            .unwrap_or_else(default_calendar);
+        agent.triggers = character
+            .settings
+            .triggers
+            .clone()
+            .unwrap_or_else(default_triggers);
+        let routes = agent.notification_routes(&character.settings.alerts, |name| character.secret(name));
+        agent.notifications = NotificationBus::new(routes);
+        Ok(agent)
+    }
+
+    async fn init(
+        name: &str,
+        agent: Box<dyn ChatAgent>,
+        twitter_client: Option<TwitterClient>,
+        collection: &str,
+        config: AgentConfig,
+    ) -> Result<Self> {
         // Initialize vector store
         let qdrant = Qdrant::from_url("http://localhost:6334").build()?;
         
         // Create collection if it doesn't exist
-        if !qdrant.collection_exists(COLLECTION_NAME).await? {
+        if !qdrant.collection_exists(collection).await? {
             qdrant
                 .create_collection(
-                    CreateCollectionBuilder::new(COLLECTION_NAME)
+                    CreateCollectionBuilder::new(collection)
                         .vectors_config(VectorParamsBuilder::new(VECTOR_SIZE, Distance::Cosine)),
                 )
                 .await?;
         }
 
         // Create vector store with OpenAI embeddings
+        let openai_client = OpenAIClient::new(&config.openai_api_key);
         let embedding_model = openai_client.embedding_model(TEXT_EMBEDDING_ADA_002);
-        let query_params = QueryPointsBuilder::new(COLLECTION_NAME).with_payload(true).build();
+        let query_params = QueryPointsBuilder::new(collection).with_payload(true).build();
         let vector_store = QdrantVectorStore::new(qdrant, embedding_model, query_params);
 
-        // Initialize Birdeye client
-        let birdeye_client = BirdeyeClient::new(config.birdeye_api_key.clone());
+        // Initialize Birdeye client, caching the market data polled by every loop
+        let market_data = Arc::new(
+            BirdeyeProvider::builder(&config.birdeye_api_key)
+                .cache(ResponseCache::default())
+                .build(),
+        );
+
+        Ok(Self::from_parts(name, agent, twitter_client, vector_store, market_data, config))
+    }
 
-        Ok(Self {
+    /// Creates an agent with the default settings from its services
+    fn from_parts(
+        name: &str,
+        agent: Box<dyn ChatAgent>,
+        twitter_client: Option<TwitterClient>,
+        vector_store: QdrantVectorStore<EmbeddingModel>,
+        market_data: Arc<dyn MarketDataProvider>,
+        config: AgentConfig,
+    ) -> Self {
+        let trading_engine = TradingEngine::new(0.7, 1000.0);
+        let agent = Arc::new(RwLock::new(agent));
+        let posts = PostWriter::new(agent.clone(), PostVoice::default());
+
+        Self {
+            name: name.to_string(),
+            namespace: DEFAULT_AGENT_NAME.to_string(),
             agent,
             trading_engine,
             twitter_client,
+            blocked_users: vec![],
+            mention_settings: MentionSettings::default(),
+            #[cfg(feature = "discord")]
+            discord_client: None,
+            #[cfg(feature = "telegram")]
+            telegram_client: None,
+            notifications: NotificationBus::new(vec![Route::new(LogSink, AlertSettings::default())]),
+            posts,
+            review_queue: None,
+            calendar: default_calendar(),
+            sentiment: None,
+            triggers: default_triggers(),
             vector_store,
-            birdeye_client,
+            market_data,
             config,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Rebuilds the chat agent and the voice of the posts every time the character is
+    /// reloaded. If the new version of the character cannot be built (e.g.: missing API key),
+    /// the previous agent is kept. Everything else derived from the character (name and memory
+    /// namespace, clients, alerts, mentions, sentiment, calendar and triggers) is only read when
+    /// the agent is created: changes to it are reported as needing a restart.
+    pub fn watch(&self, mut updates: watch::Receiver<Character>) -> JoinHandle<()> {
+        let agent = self.agent.clone();
+        let posts = self.posts.clone();
+        let notifications = self.notifications.clone();
+        let sentiment = self.sentiment.clone();
+        let mut current = updates.borrow().clone();
+        tokio::spawn(async move {
+            while updates.changed().await.is_ok() {
+                let character = updates.borrow_and_update().clone();
+                let changed = restart_required(&current, &character);
+                if !changed.is_empty() {
+                    notifications.publish(Event::Error {
+                        source: format!("character {}", current.name),
+                        message: format!(
+                            "Restart required to apply the changes to: {}",
+                            changed.join(", ")
+                        ),
+                    });
+                }
+                match build_agent(&character, sentiment.as_ref()).await {
+                    Ok(new_agent) => {
+                        *agent.write().await = new_agent;
+                        posts.set_voice(PostVoice::from(&character)).await;
+                        tracing::info!("Rebuilt agent for {}", character.name);
+                        current = character;
+                    }
+                    Err(err) => notifications.publish(Event::Error {
+                        source: format!("character {}", character.name),
+                        message: format!("Failed to rebuild the agent: {:#}", err),
+                    }),
+                }
+            }
+        })
+    }
+
+    /// Chats with the agent of the character
+    pub async fn chat(&self, prompt: &str) -> Result<String> {
+        Ok(self.agent.read().await.chat(prompt, vec![]).await?)
+    }
+
+    /// Returns a listener replying to the Twitter mentions of the agent, if Twitter is enabled.
+    /// The cursor of the listener is stored per namespace, so that characters sharing an
+    /// account do not skip each other's mentions.
+    pub fn mention_listener(self: &Arc<Self>) -> Option<MentionListener<Arc<Self>>> {
+        let client = self.twitter_client.clone()?;
+        let cursor_path = PathBuf::from(MENTIONS_DIR).join(format!("{}.json", self.namespace));
+        let listener = MentionListener::new(client, self.clone(), cursor_path)
+            .settings(&self.mention_settings);
+        let listener = self
+            .blocked_users
+            .iter()
+            .fold(listener, |listener, user| listener.block(user));
+        Some(listener)
+    }
+
+    /// Returns the scheduler of the posts of the content calendar, if Twitter is enabled and
+    /// the calendar is not empty. Changes to the calendar apply on restart.
+    pub fn content_scheduler(self: &Arc<Self>) -> Option<ContentScheduler<Arc<Self>>> {
+        if self.twitter_client.is_none() || self.calendar.is_empty() {
+            return None;
+        }
+        let state_path = PathBuf::from(CALENDAR_DIR).join(format!("{}.json", self.namespace));
+        Some(ContentScheduler::new(self.clone(), self.calendar.clone(), state_path))
+    }
+
+    /// Returns the Discord bot of the agent, if Discord is enabled
+    #[cfg(feature = "discord")]
+    pub fn discord_bot(self: &Arc<Self>) -> Option<DiscordBot<Arc<Self>>> {
+        let bot = self.discord_client.as_ref()?.bot(self.clone());
+        Some(match &self.review_queue {
+            Some(queue) => bot.review_queue(queue.clone()),
+            None => bot,
         })
     }
 
-    pub async fn analyze_market(&self, symbol: &str) -> Result<()> {
+    /// Returns the Telegram bot of the agent, if Telegram is enabled
+    #[cfg(feature = "telegram")]
+    pub fn telegram_bot(self: &Arc<Self>) -> Option<TelegramBot<Arc<Self>>> {
+        Some(self.telegram_client.as_ref()?.bot(self.clone()))
+    }
+
+    #[cfg(feature = "telegram")]
+    fn telegram_subscriptions(&self) -> PathBuf {
+        PathBuf::from(TELEGRAM_DIR).join(format!("{}.json", self.namespace))
+    }
+
+    /// Routes of the notifications: the logs, the clients of the agent and the webhook at
+    /// `ALERT_WEBHOOK_URL`. Twitter only gets a few trade alerts unless configured otherwise,
+    /// since it is public.
+    fn notification_routes(
+        &self,
+        alerts: &HashMap<String, AlertSettings>,
+        secret: impl Fn(&str) -> Option<String>,
+    ) -> Vec<Route> {
+        let settings = |sink: &str| alerts.get(sink).cloned().unwrap_or_default();
+
+        let mut routes = vec![Route::new(LogSink, settings("log"))];
+        if let Some(twitter_client) = &self.twitter_client {
+            let settings = alerts.get("twitter").cloned().unwrap_or_else(AlertSettings::public);
+            let mut sink = CharacterSink::new("twitter", self.posts.clone(), twitter_client.clone());
+            if let Some(queue) = &self.review_queue {
+                sink = sink.review(queue.clone());
+            }
+            routes.push(Route::new(sink, settings));
+        }
+        #[cfg(feature = "discord")]
+        if let Some(discord_client) = &self.discord_client {
+            routes.push(Route::new(discord_client.clone(), settings("discord")));
+        }
+        #[cfg(feature = "telegram")]
+        if let Some(telegram_client) = &self.telegram_client {
+            let sink = crate::notifications::SocialSink::new("telegram", telegram_client.clone());
+            routes.push(Route::new(sink, settings("telegram")));
+        }
+        if let Some(url) = secret("ALERT_WEBHOOK_URL") {
+            routes.push(Route::new(WebhookSink::new(&url), settings("webhook")));
+        }
+        routes
+    }
+
+    /// Sets the voice of the posts, and creates the review queue if the posts are reviewed.
+    /// Turning the review on or off requires a restart, as the sinks and bots share the queue.
+    async fn set_post_voice(&mut self, voice: PostVoice) {
+        self.review_queue = match (&self.twitter_client, voice.settings.review) {
+            (Some(twitter_client), true) => Some(Arc::new(ReviewQueue::new(
+                twitter_client.clone(),
+                self.posts.clone(),
+            ))),
+            _ => None,
+        };
+        self.posts.set_voice(voice).await;
+    }
+
+    /// Twitter client of the agent, if it has one
+    pub fn twitter_client(&self) -> Option<&TwitterClient> {
+        self.twitter_client.as_ref()
+    }
+
+    /// Posts waiting for approval, if posts are reviewed
+    pub fn review_queue(&self) -> Option<&Arc<ReviewQueue>> {
+        self.review_queue.as_ref()
+    }
+
+    /// Publishes a notification to the configured sinks
+    pub fn notify(&self, event: Event) {
+        self.notifications.publish(event);
+    }
+
+    /// Live market data of the tokens mentioned with cashtags in `text`, with their social
+    /// sentiment when it is known. Unknown tokens are skipped.
+    async fn market_context(&self, text: &str) -> String {
+        let mut context = String::new();
+        for symbol in cashtags(text) {
+            match self.market_data.get_token_info_by_symbol(&symbol).await {
+                Ok(info) => context.push_str(&format!(
+                    "${}: price ${:.6}, 24h change {:.2}%, 24h volume ${:.0}, liquidity ${:.0}, 24h trades {}",
+                    symbol, info.price, info.price_change_24h, info.volume_24h, info.liquidity, info.trades_24h
+                )),
+                Err(err) => {
+                    tracing::debug!("No market data for ${}: {}", symbol, err);
+                    continue;
+                }
+            }
+            // Only the known sentiment, searching tweets for every message would be too slow
+            if let Some(reading) = self.sentiment.as_ref().and_then(|tracker| tracker.cached(&symbol)) {
+                context.push_str(&format!(
+                    ", social sentiment {:+.2} over {} tweets",
+                    reading.score, reading.tweets
+                ));
+            }
+            context.push('\n');
+        }
+        context
+    }
+
+    /// Social sentiment of the token, scoring the latest tweets about it. `None` without
+    /// Twitter, or if nobody tweeted about it.
+    pub async fn sentiment(&self, symbol: &str) -> Option<SentimentReading> {
+        let tracker = self.sentiment.as_ref()?;
+        match tracker.reading(symbol).await {
+            Ok(reading) => reading,
+            Err(err) => {
+                tracing::warn!("Failed to refresh the sentiment of {}: {:#}", symbol, err);
+                tracker.cached(symbol)
+            }
+        }
+    }
+
+    /// Analyzes the market of the token with the agent, and returns its decision: BUY, SELL or
+    /// HOLD. The decision is not executed.
+    pub async fn analyze_market(&self, symbol: &str) -> Result<TradeDecision> {
         println!("Starting market analysis for {}", symbol);
         println!("Fetching market data from Birdeye...");
         
-        let token_info = self.birdeye_client.get_token_info(symbol).await?;
+        let token_info = self.market_data.get_token_info_by_symbol(symbol).await?;
         
         println!("\nMarket Analysis for {}:", symbol);
         println!("Current Price: ${:.4}", token_info.price);
-        println!("24h Volume: ${:.2}", token_info.volume24h);
+        println!("24h Volume: ${:.2}", token_info.volume_24h);
         println!("24h Price Change: {:.2}%", token_info.price_change_24h);
         println!("Liquidity: ${:.2}", token_info.liquidity);
-        println!("24h Trades: {}", token_info.trade24h);
+        println!("24h Trades: {}", token_info.trades_24h);
+        if let Some(reading) = self.sentiment(symbol).await {
+            println!(
+                "Social Sentiment: {:+.2} over {} tweets",
+                reading.score, reading.tweets
+            );
+        }
         
+        let history = self
+            .market_data
+            .get_price_history(&token_info.address, TimeInterval::OneHour)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::warn!("No price history for {}: {:#}", symbol, err);
+                vec![]
+            });
+        let closes = history[history.len().saturating_sub(ANALYSIS_HISTORY_HOURS)..]
+            .iter()
+            .map(|point| format!("{:.6}", point.price))
+            .collect::<Vec<_>>();
+        if !closes.is_empty() {
+            println!("Hourly Closes: {}", closes.join(", "));
+        }
+
+        let prompt = format!(
+            "Market data of {} from Birdeye:\n\
+             Price: ${:.4}\n24h volume: ${:.2}\n24h price change: {:.2}%\n\
+             Liquidity: ${:.2}\n24h trades: {}\nHourly closes, oldest first: {}\n\n\
+             Decide whether to buy, sell or hold {}. Answer with a JSON object only: \
+             {{\"action\": \"BUY\" | \"SELL\" | \"HOLD\", \"amount\": <USD to trade>, \
+             \"confidence\": <0 to 1>, \"reason\": <one sentence>}}",
+            symbol,
+            token_info.price,
+            token_info.volume_24h,
+            token_info.price_change_24h,
+            token_info.liquidity,
+            token_info.trades_24h,
+            if closes.is_empty() { "unknown".to_string() } else { closes.join(", ") },
+            symbol
+        );
+        let decision = TradeDecision::from_analysis(symbol, &self.chat(&prompt).await?)?;
+        println!(
+            "Decision: {} {} (confidence {:.0}%): {}",
+            decision.action,
+            decision.symbol,
+            decision.confidence * 100.0,
+            decision.reason
+        );
+
         println!("\nStoring analysis in vector store...");
         // TODO: Store analysis in vector store
         
         println!("Analysis complete for {}", symbol);
-        Ok(())
+        Ok(decision)
+    }
+
+    /// Executes the trade, unless the trading engine rejects it or its estimated slippage is
+    /// above [MAX_SLIPPAGE]
+    pub async fn execute_trade(&self, decision: &TradeDecision) -> Result<bool> {
+        if self.trading_engine.rejection_reason(decision).is_none() {
+            if let Some(reason) = self.impact_rejection(decision).await {
+                tracing::warn!("Trade rejected: {}", reason);
+                self.notify(Event::TradeRejected {
+                    decision: decision.clone(),
+                    reason,
+                });
+                return Ok(false);
+            }
+        }
+        let executed = self.trading_engine.execute_trade(decision).await?;
+        if !executed {
+            self.notify_rejection(decision.clone());
+        }
+        Ok(executed)
     }
 
-    pub async fn execute_trade(&self, symbol: &str, action: &str, amount: f64) -> Result<bool> {
-        let decision = crate::trading::TradeDecision {
-            action: action.to_string(),
-            symbol: symbol.to_string(),
-            amount,
-            reason: "Market analysis indicates favorable conditions".to_string(),
-            confidence: 0.8,
+    /// Returns why the trade would be rejected for its market impact, if it would. Trades whose
+    /// impact cannot be estimated are rejected.
+    async fn impact_rejection(&self, decision: &TradeDecision) -> Option<String> {
+        let side = if decision.action.eq_ignore_ascii_case("SELL") {
+            Side::Sell
+        } else {
+            Side::Buy
         };
+        let impact = async {
+            let address = self.market_data.token_address(&decision.symbol).await?;
+            self.market_data
+                .get_market_impact(&address, side, decision.amount)
+                .await
+        }
+        .await;
+        match impact {
+            Ok(impact) if impact.slippage > MAX_SLIPPAGE => Some(format!(
+                "slippage {:.2}% above maximum {:.2}%",
+                impact.slippage, MAX_SLIPPAGE
+            )),
+            Ok(_) => None,
+            Err(err) => Some(format!("market impact unknown: {:#}", err)),
+        }
+    }
 
-        self.trading_engine.execute_trade(&decision).await
+    /// Open positions, by symbol
+    pub fn positions(&self) -> Vec<(String, f64)> {
+        self.trading_engine.positions()
     }
 
-    pub async fn post_trade_update(&self, symbol: &str, action: &str, amount: f64) -> Result<()> {
-        let tweet = format!(
-            "🤖 Trade Alert!\n{} {} {}\nReason: Market analysis indicates favorable conditions",
-            action, amount, symbol
-        );
+    /// Announces the trade on the clients of the agent, with its rationale
+    pub async fn post_trade_update(&self, decision: &TradeDecision) -> Result<()> {
+        self.notify(Event::TradeExecuted {
+            decision: decision.clone(),
+        });
+        Ok(())
+    }
+
+    fn notify_rejection(&self, decision: TradeDecision) {
+        let reason = self
+            .trading_engine
+            .rejection_reason(&decision)
+            .unwrap_or_else(|| "rejected by the trading engine".to_string());
+        self.notify(Event::TradeRejected { decision, reason });
+    }
 
-        self.twitter_client.post_tweet(&tweet).await
+    /// Whether the agent has a trading loop, see [TradingAgent::run]
+    pub fn has_triggers(&self) -> bool {
+        !self.triggers.is_empty()
     }
 
+    /// Trading loop: follows the tokens of the trigger rules on the Birdeye WebSocket and runs
+    /// the action of every rule that fires. Changes to the rules apply on restart.
     pub async fn run(&self) -> Result<()> {
-        loop {
-            // Perform market analysis
-            self.analyze_market("SOL").await?;
+        let stream = WebSocketProvider::new(&self.config.birdeye_api_key);
+        let mut triggers = TriggerEngine::new(self.triggers.clone()).watch(&stream);
+        stream.start();
 
-            // Execute a trade
-            self.execute_trade("SOL", "BUY", 100.0).await?;
+        while let Some(trigger) = triggers.recv().await {
+            if let Err(err) = self.handle_trigger(&trigger).await {
+                self.notify(Event::Error {
+                    source: format!("trigger on {}", trigger.rule.symbol),
+                    message: format!("Failed to handle {}: {:#}", trigger.message, err),
+                });
+            }
+        }
+        Ok(())
+    }
 
-            // Post trade update
-            self.post_trade_update("SOL", "BUY", 100.0).await?;
+    async fn handle_trigger(&self, trigger: &Trigger) -> Result<()> {
+        let symbol = &trigger.rule.symbol;
+        match trigger.rule.action {
+            TriggerAction::Analyze => {
+                let mut decision = self.analyze_market(symbol).await?;
+                if decision.is_hold() {
+                    tracing::info!("Holding {} {}: {}", symbol, trigger.message, decision.reason);
+                    return Ok(());
+                }
 
-            // Sleep for a specified interval before the next iteration
-            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+                // Execute the decision, more or less confidently depending on the social sentiment
+                decision.reason = format!("{} {}: {}", symbol, trigger.message, decision.reason);
+                if let Some(reading) = self.sentiment(symbol).await {
+                    reading.adjust(&mut decision);
+                }
+                if self.execute_trade(&decision).await? {
+                    self.post_trade_update(&decision).await?;
+                }
+            }
+            TriggerAction::StopLoss => {
+                if self.trading_engine.stop_loss(symbol, trigger.price).is_some() {
+                    self.notify(Event::StopLossHit {
+                        symbol: symbol.clone(),
+                        price: trigger.price,
+                        stop_price: trigger.rule.condition.level().unwrap_or(trigger.price),
+                    });
+                }
+            }
+            TriggerAction::Notify => self.notify(Event::PriceAlert {
+                symbol: symbol.clone(),
+                price: trigger.price,
+                message: trigger.message.clone(),
+            }),
         }
+        Ok(())
     }
 }
 
+#[async_trait]
+impl MentionHandler for TradingAgent {
+    async fn handle_mention(&self, mention: &Tweet, thread: &[Tweet]) -> Result<Option<String>> {
+        let mut prompt = String::new();
+
+        if !thread.is_empty() {
+            prompt.push_str("Conversation so far:\n");
+            for tweet in thread {
+                let author = tweet.author_username.as_deref().unwrap_or("unknown");
+                prompt.push_str(&format!("@{}: {}\n", author, tweet.text));
+            }
+            prompt.push('\n');
+        }
+
+        let market_context = self.market_context(&mention.text).await;
+        if !market_context.is_empty() {
+            prompt.push_str("Live market data from Birdeye:\n");
+            prompt.push_str(&market_context);
+            prompt.push('\n');
+        }
+
+        let author = mention.author_username.as_deref().unwrap_or("unknown");
+        prompt.push_str(&format!(
+            "Reply to this tweet from @{}: {}\n\nUse the market data above when relevant. \
+             Reply with the text of the tweet only, in less than 280 characters.",
+            author, mention.text
+        ));
+
+        let reply = self.chat(&prompt).await?;
+        Ok(Some(reply))
+    }
+}
+
+#[async_trait]
+impl ContentHandler for TradingAgent {
+    async fn token(&self, symbol: &str) -> Result<TokenOverview> {
+        self.market_data.get_token_info_by_symbol(symbol).await
+    }
+
+    async fn top_movers(&self, limit: usize, min_liquidity: f64) -> Result<Vec<TokenListItem>> {
+        self.market_data.top_movers(limit, min_liquidity).await
+    }
+
+    fn positions(&self) -> Vec<(String, f64)> {
+        TradingAgent::positions(self)
+    }
+
+    async fn topics(&self) -> Vec<String> {
+        self.posts.topics().await
+    }
+
+    async fn write_post(&self, task: &str) -> Result<String> {
+        self.posts.write_post(task).await
+    }
+
+    async fn publish(&self, kind: ContentKind, post: &str) -> Result<()> {
+        if let Some(queue) = &self.review_queue {
+            queue.submit(post, &kind.to_string());
+            return Ok(());
+        }
+        let twitter_client = self
+            .twitter_client
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Twitter is not enabled for {}", self.name))?;
+        twitter_client.post(post).await?;
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "discord", feature = "telegram"))]
+#[async_trait]
+impl BotHandler for TradingAgent {
+    async fn analyze(&self, symbol: &str) -> Result<String> {
+        let market_context = self.market_context(&format!("${}", symbol)).await;
+        if market_context.is_empty() {
+            return Err(anyhow::anyhow!("No market data for {}", symbol));
+        }
+
+        self.chat(&format!(
+            "Live market data from Birdeye:\n{}\nGive a short analysis of {} for a chat of \
+             traders: trend, liquidity and risks.",
+            market_context, symbol
+        ))
+        .await
+    }
+
+    async fn portfolio(&self) -> Result<Vec<(String, f64)>> {
+        Ok(self.positions())
+    }
+
+    async fn trade(&self, decision: &TradeDecision) -> Result<bool> {
+        let executed = TradingAgent::execute_trade(self, decision).await?;
+        if executed {
+            self.notify(Event::TradeExecuted {
+                decision: decision.clone(),
+            });
+        }
+        Ok(executed)
+    }
+
+    async fn chat(&self, author: &str, message: &str) -> Result<String> {
+        let mut prompt = String::new();
+
+        let market_context = self.market_context(message).await;
+        if !market_context.is_empty() {
+            prompt.push_str("Live market data from Birdeye:\n");
+            prompt.push_str(&market_context);
+            prompt.push('\n');
+        }
+
+        prompt.push_str(&format!(
+            "Reply to this chat message from {}: {}\n\nUse the market data above when relevant.",
+            author, message
+        ));
+        TradingAgent::chat(self, &prompt).await
+    }
+}
+
+/// Creates the Twitter client of an agent. The OAuth 2.0 token is saved every time it is
+/// refreshed, and the saved token is used instead of the one of the secrets on the next start.
+fn twitter_client(auth: TwitterAuth, namespace: &str) -> TwitterClient {
+    let path = PathBuf::from(TWITTER_DIR).join(format!("{}.json", namespace));
+    TwitterClient::new(auth.with_saved_token(&path)).on_token_refresh(move |token| {
+        if let Err(err) = token.save(&path) {
+            tracing::error!("Failed to save the X API token to {}: {:#}", path.display(), err);
+        }
+    })
+}
+
+/// Tracks the social sentiment with the Twitter client of the agent, if it has one
+fn sentiment_tracker(
+    twitter_client: Option<&TwitterClient>,
+    settings: &SentimentSettings,
+    config: &AgentConfig,
+) -> Option<Arc<SentimentTracker>> {
+    let scorer = ExtractorScorer::openai(&config.openai_api_key);
+    let tracker = SentimentTracker::new(twitter_client?.clone(), scorer).settings(settings);
+    Some(Arc::new(tracker))
+}
+
+/// Builds the agent of the character, with the sentiment tool if sentiment is tracked
+async fn build_agent(
+    character: &Character,
+    sentiment: Option<&Arc<SentimentTracker>>,
+) -> Result<Box<dyn ChatAgent>> {
+    let mut builder = character.agent_builder();
+    if let Some(tracker) = sentiment {
+        builder = builder.sentiment_tool(SentimentTool::new(tracker.clone()));
+    }
+    builder.build().await
+}
+
+/// Settings of the character that changed between `old` and `new`, among the ones only read
+/// when its agent is created
+fn restart_required(old: &Character, new: &Character) -> Vec<&'static str> {
+    let same = |old: serde_json::Result<serde_json::Value>, new: serde_json::Result<serde_json::Value>| {
+        matches!((old, new), (Ok(old), Ok(new)) if old == new)
+    };
+
+    let mut changed = Vec::new();
+    if old.name != new.name || old.namespace() != new.namespace() {
+        changed.push("name and username");
+    }
+    if old.clients != new.clients || old.settings.secrets != new.settings.secrets {
+        changed.push("clients and secrets");
+    }
+    if !same(serde_json::to_value(&old.settings.alerts), serde_json::to_value(&new.settings.alerts)) {
+        changed.push("alerts");
+    }
+    if old.settings.mentions != new.settings.mentions {
+        changed.push("mentions");
+    }
+    if old.settings.posts.review != new.settings.posts.review {
+        changed.push("posts review");
+    }
+    if old.settings.sentiment != new.settings.sentiment {
+        changed.push("sentiment");
+    }
+    if old.settings.calendar != new.settings.calendar {
+        changed.push("calendar");
+    }
+    if old.settings.triggers != new.settings.triggers {
+        changed.push("triggers");
+    }
+    changed
+}
+
+fn blocked_users(list: Option<String>) -> Vec<String> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|user| !user.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_restart_required() -> Result<()> {
+        let character = |name: &str, temperature: f32, triggers: serde_json::Value| {
+            Character::from_json(
+                &serde_json::json!({
+                    "name": name,
+                    "modelProvider": "openai",
+                    "bio": ["Trader"],
+                    "style": { "all": ["be concise"] },
+                    "settings": {
+                        "modelConfig": { "temperature": temperature },
+                        "triggers": triggers,
+                        "posts": { "review": temperature > 0.8 }
+                    }
+                })
+                .to_string(),
+            )
+        };
+        let old = character("Vergen", 0.7, serde_json::json!([]))?;
+
+        // The model and the prompt are reloaded
+        assert!(restart_required(&old, &character("Vergen", 0.8, serde_json::json!([]))?).is_empty());
+        assert_eq!(
+            restart_required(&old, &character("Vergen", 0.9, serde_json::json!([]))?),
+            ["posts review"]
+        );
+        assert_eq!(
+            restart_required(&old, &character("Vergen v2", 0.7, serde_json::Value::Null)?),
+            ["name and username", "triggers"]
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_trading_agent_creation() -> Result<()> {
         let config = AgentConfig {
             openai_api_key: "test_key".to_string(),
             birdeye_api_key: "test_key".to_string(),
-            twitter_email: "test@example.com".to_string(),
-            twitter_username: "test_user".to_string(),
-            twitter_password: "test_pass".to_string(),
+            twitter_auth: None,
         };
 
         let agent = TradingAgent::new(config).await?;
@@ -170,9 +912,7 @@ mod tests {
         let config = AgentConfig {
             openai_api_key: "test_key".to_string(),
             birdeye_api_key: "test_key".to_string(),
-            twitter_email: "test@example.com".to_string(),
-            twitter_username: "test_user".to_string(),
-            twitter_password: "test_pass".to_string(),
+            twitter_auth: None,
         };
 
         let agent = TradingAgent::new(config).await?;
@@ -185,14 +925,123 @@ mod tests {
         let config = AgentConfig {
             openai_api_key: "test_key".to_string(),
             birdeye_api_key: "test_key".to_string(),
-            twitter_email: "test@example.com".to_string(),
-            twitter_username: "test_user".to_string(),
-            twitter_password: "test_pass".to_string(),
+            twitter_auth: None,
         };
 
         let agent = TradingAgent::new(config).await?;
-        let result = agent.execute_trade("SOL", "BUY", 100.0).await?;
+        let result = agent
+            .execute_trade(&TradeDecision::manual("SOL", "BUY", 100.0, "the tests"))
+            .await?;
         assert!(result);
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Market of tokens whose trades slip by `slippage` %
+    #[cfg(any(feature = "discord", feature = "telegram"))]
+    struct SlippingMarket {
+        slippage: f64,
+    }
+
+    #[cfg(any(feature = "discord", feature = "telegram"))]
+    #[async_trait]
+    impl MarketDataProvider for SlippingMarket {
+        async fn token_address(&self, symbol: &str) -> Result<String> {
+            Ok(symbol.to_string())
+        }
+
+        async fn get_token_info(&self, address: &str) -> Result<TokenOverview> {
+            Ok(TokenOverview {
+                address: address.to_string(),
+                symbol: address.to_string(),
+                price: 1.0,
+                ..Default::default()
+            })
+        }
+
+        async fn get_price_history(
+            &self,
+            _address: &str,
+            _interval: TimeInterval,
+        ) -> Result<Vec<rig_birdeye::PricePoint>> {
+            Ok(vec![])
+        }
+
+        async fn top_movers(
+            &self,
+            _limit: usize,
+            _min_liquidity: f64,
+        ) -> Result<Vec<TokenListItem>> {
+            Ok(vec![])
+        }
+
+        async fn get_market_impact(
+            &self,
+            _address: &str,
+            side: Side,
+            size_usd: f64,
+        ) -> Result<rig_birdeye::MarketImpact> {
+            Ok(rig_birdeye::MarketImpact {
+                side,
+                price_impact: 2.0 * self.slippage,
+                executed_price: 1.0 + self.slippage / 100.0,
+                slippage: self.slippage,
+                size_usd,
+                size_tokens: size_usd,
+                filled_tokens: size_usd,
+                source: rig_birdeye::ImpactSource::Amm,
+            })
+        }
+    }
+
+    #[cfg(any(feature = "discord", feature = "telegram"))]
+    struct Silent;
+
+    #[cfg(any(feature = "discord", feature = "telegram"))]
+    #[async_trait]
+    impl ChatAgent for Silent {
+        async fn chat(
+            &self,
+            _: &str,
+            _: Vec<rig::completion::Message>,
+        ) -> Result<String, rig::completion::PromptError> {
+            Ok(String::new())
+        }
+    }
+
+    /// Agent trading on `market`, without a trade memory (Qdrant is never queried)
+    #[cfg(any(feature = "discord", feature = "telegram"))]
+    fn test_agent(market: SlippingMarket) -> Result<TradingAgent> {
+        let config = AgentConfig {
+            openai_api_key: "test_key".to_string(),
+            birdeye_api_key: "test_key".to_string(),
+            twitter_auth: None,
+        };
+        let qdrant = Qdrant::from_url("http://localhost:6334").build()?;
+        let embedding_model = OpenAIClient::new("test_key").embedding_model(TEXT_EMBEDDING_ADA_002);
+        let query_params = QueryPointsBuilder::new(COLLECTION_NAME).build();
+        let vector_store = QdrantVectorStore::new(qdrant, embedding_model, query_params);
+        Ok(TradingAgent::from_parts(
+            "test",
+            Box::new(Silent),
+            None,
+            vector_store,
+            Arc::new(market),
+            config,
+        ))
+    }
+
+    #[cfg(any(feature = "discord", feature = "telegram"))]
+    #[tokio::test]
+    async fn test_bot_trade_checks_slippage() -> Result<()> {
+        let decision = TradeDecision::manual("SOL", "BUY", 100.0, "the tests");
+
+        let agent = test_agent(SlippingMarket { slippage: 5.0 })?;
+        assert!(!BotHandler::trade(&agent, &decision).await?);
+        assert!(agent.positions().is_empty());
+
+        let agent = test_agent(SlippingMarket { slippage: 0.5 })?;
+        assert!(BotHandler::trade(&agent, &decision).await?);
+        assert_eq!(agent.positions(), vec![("SOL".to_string(), 100.0)]);
+        Ok(())
+    }
+}
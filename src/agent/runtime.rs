@@ -0,0 +1,153 @@
+//! Runs several characters in the same process.
+//!
+//! Each character gets its own [TradingAgent], with its own clients and trade memory
+//! collection, and is hot-reloaded when its character file changes. Agents with a Twitter
+//! client reply to their mentions and post on their content calendar, and agents with a
+//! Discord or Telegram client run a bot. Agents with trigger rules run their trading loop.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use tokio::task::JoinHandle;
+
+use super::{AgentConfig, TradingAgent};
+use crate::{character::CharacterWatcher, notifications::Event};
+
+#[derive(Default)]
+pub struct CharacterRuntime {
+    agents: Vec<Arc<TradingAgent>>,
+    // Kept alive to keep watching the character files
+    watchers: Vec<CharacterWatcher>,
+    // Character reloaders, mention listeners, content schedulers, bots and trading loops,
+    // stopped with the runtime
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl CharacterRuntime {
+    /// Runtime with a single agent that is not backed by a character file.
+    pub fn single(agent: TradingAgent) -> Self {
+        let mut runtime = Self::default();
+        runtime.add_agent(Arc::new(agent));
+        runtime
+    }
+
+    /// Loads the given character files (or all `.json` files of the given directories) and
+    /// starts an agent for each of them. Fails if two characters share the same namespace.
+    pub async fn start(paths: &[PathBuf], config: &AgentConfig) -> Result<Self> {
+        let mut runtime = Self::default();
+        let mut namespaces = HashMap::new();
+
+        for path in character_files(paths)? {
+            let watcher = CharacterWatcher::new(&path)?;
+            let character = watcher.current();
+
+            if let Some(other) = namespaces.insert(character.namespace(), path.clone()) {
+                return Err(anyhow!(
+                    "Characters {} and {} share the namespace {}, set a different `username`",
+                    other.display(),
+                    path.display(),
+                    character.namespace()
+                ));
+            }
+
+            let agent = TradingAgent::from_character(&character, config.clone()).await?;
+            runtime.tasks.push(agent.watch(watcher.subscribe()));
+            runtime.add_agent(Arc::new(agent));
+            runtime.watchers.push(watcher);
+            tracing::info!("Started character {} from {}", character.name, path.display());
+        }
+
+        if runtime.agents.is_empty() {
+            return Err(anyhow!("No character files found"));
+        }
+        Ok(runtime)
+    }
+
+    /// Adds an agent to the runtime and starts replying to its Twitter mentions and chat bot
+    /// messages, posting on its content calendar and trading on its triggers
+    fn add_agent(&mut self, agent: Arc<TradingAgent>) {
+        if agent.has_triggers() {
+            let agent = agent.clone();
+            self.tasks.push(tokio::spawn(async move {
+                if let Err(err) = agent.run().await {
+                    agent.notify(Event::Error {
+                        source: "trading loop".to_string(),
+                        message: format!("Trading loop of {} stopped: {:#}", agent.name(), err),
+                    });
+                }
+            }));
+        }
+        if let Some(listener) = agent.mention_listener() {
+            self.tasks.push(tokio::spawn(listener.run()));
+        }
+        if let Some(scheduler) = agent.content_scheduler() {
+            self.tasks.push(tokio::spawn(scheduler.run()));
+        }
+        #[cfg(feature = "discord")]
+        if let Some(bot) = agent.discord_bot() {
+            let agent = agent.clone();
+            self.tasks.push(tokio::spawn(async move {
+                if let Err(err) = bot.run().await {
+                    agent.notify(Event::Error {
+                        source: "discord".to_string(),
+                        message: format!("Discord bot of {} stopped: {:#}", agent.name(), err),
+                    });
+                }
+            }));
+        }
+        #[cfg(feature = "telegram")]
+        if let Some(bot) = agent.telegram_bot() {
+            self.tasks.push(tokio::spawn(bot.run()));
+        }
+        self.agents.push(agent);
+    }
+
+    /// Returns the agent of the character with the given name (case insensitive)
+    pub fn get(&self, name: &str) -> Option<Arc<TradingAgent>> {
+        self.agents
+            .iter()
+            .find(|agent| agent.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    pub fn agents(&self) -> &[Arc<TradingAgent>] {
+        &self.agents
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.agents.iter().map(|agent| agent.name()).collect()
+    }
+}
+
+impl Drop for CharacterRuntime {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Expands directories into the `.json` files they contain, sorted by name.
+fn character_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            entries.retain(|entry| is_json(entry));
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
@@ -0,0 +1,553 @@
+//! Construction of rig agents from a [Character].
+//!
+//! [CharacterAgentBuilder] turns a character file into an agent:
+//! - the completion model is selected from `model_provider` (and `model_config.model`),
+//! - temperature, top_p, max_tokens and penalties are taken from `model_config`,
+//! - a sample of the bio and lore is injected in the preamble, along with the style guidelines,
+//! - `message_examples` are prepended to the chat history as few-shot turns,
+//! - `knowledge` is indexed in an in-memory vector store and used as dynamic context when
+//!   `rag_knowledge` is set (otherwise it is appended to the preamble),
+//! - the social sentiment tool is added when the agent has one.
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rand::seq::SliceRandom;
+use rig::{
+    agent::{Agent, AgentBuilder},
+    completion::{Chat, CompletionModel, Message, Prompt, PromptError},
+    embeddings::{EmbeddingModel, EmbeddingsBuilder},
+    providers::{anthropic, cohere, gemini, openai, perplexity, xai},
+    vector_store::in_memory_store::InMemoryVectorStore,
+};
+use serde_json::{json, Map, Value};
+
+use super::{Character, ModelConfig};
+use crate::sentiment::SentimentTool;
+
+pub const DEFAULT_BIO_SAMPLE: usize = 3;
+pub const DEFAULT_LORE_SAMPLE: usize = 3;
+pub const DEFAULT_KNOWLEDGE_SAMPLE: usize = 3;
+pub const DEFAULT_EXAMPLE_SAMPLE: usize = 3;
+
+/// Completion model providers supported in character files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelProvider {
+    OpenAI,
+    Anthropic,
+    Cohere,
+    Gemini,
+    Xai,
+    Perplexity,
+}
+
+impl FromStr for ModelProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(Self::OpenAI),
+            "anthropic" | "claude" => Ok(Self::Anthropic),
+            "cohere" => Ok(Self::Cohere),
+            "gemini" | "google" => Ok(Self::Gemini),
+            "xai" | "grok" => Ok(Self::Xai),
+            "perplexity" => Ok(Self::Perplexity),
+            other => Err(anyhow!("Unknown model provider: {}", other)),
+        }
+    }
+}
+
+impl ModelProvider {
+    /// Names accepted in the `modelProvider` field of character files
+    pub const NAMES: &'static [&'static str] =
+        &["openai", "anthropic", "cohere", "gemini", "xai", "perplexity"];
+
+    /// Model used when the character file does not specify one
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            Self::OpenAI => openai::GPT_4O,
+            Self::Anthropic => anthropic::CLAUDE_3_5_SONNET,
+            Self::Cohere => cohere::COMMAND_R,
+            Self::Gemini => gemini::completion::GEMINI_1_5_PRO,
+            Self::Xai => xai::GROK_BETA,
+            Self::Perplexity => perplexity::LLAMA_3_1_SONAR_LARGE_CHAT,
+        }
+    }
+
+    /// Environment variable (or character secret) holding the API key of the provider
+    pub fn api_key_var(&self) -> &'static str {
+        match self {
+            Self::OpenAI => "OPENAI_API_KEY",
+            Self::Anthropic => "ANTHROPIC_API_KEY",
+            Self::Cohere => "COHERE_API_KEY",
+            Self::Gemini => "GEMINI_API_KEY",
+            Self::Xai => "XAI_API_KEY",
+            Self::Perplexity => "PERPLEXITY_API_KEY",
+        }
+    }
+
+    /// Sampling parameters not covered by the agent builder, in the format expected by the
+    /// provider. Parameters left at their neutral value are omitted.
+    pub fn sampling_params(&self, config: &ModelConfig) -> Value {
+        let mut params = Map::new();
+        let (top_p, frequency_penalty, presence_penalty) = match self {
+            Self::Cohere => ("p", "frequency_penalty", "presence_penalty"),
+            Self::Gemini => ("topP", "frequencyPenalty", "presencePenalty"),
+            _ => ("top_p", "frequency_penalty", "presence_penalty"),
+        };
+
+        if config.top_p < 1.0 {
+            params.insert(top_p.into(), json!(config.top_p));
+        }
+        // Anthropic does not support penalties
+        if *self != Self::Anthropic {
+            if config.frequency_penalty != 0.0 {
+                params.insert(frequency_penalty.into(), json!(config.frequency_penalty));
+            }
+            if config.presence_penalty != 0.0 {
+                params.insert(presence_penalty.into(), json!(config.presence_penalty));
+            }
+        }
+
+        Value::Object(params)
+    }
+}
+
+/// Chat interface shared by the agents of every provider, so that the agent of a character can
+/// be stored without knowing its completion model.
+#[async_trait]
+pub trait ChatAgent: Send + Sync {
+    async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError>;
+}
+
+#[async_trait]
+impl<T: Chat> ChatAgent for T {
+    async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
+        Chat::chat(self, prompt, chat_history).await
+    }
+}
+
+/// Agent of a character: a rig [Agent] whose chat history always starts with the few-shot
+/// message examples of the character.
+pub struct CharacterAgent<M: CompletionModel> {
+    agent: Agent<M>,
+    examples: Vec<Message>,
+}
+
+impl<M: CompletionModel> CharacterAgent<M> {
+    pub fn new(agent: Agent<M>, examples: Vec<Message>) -> Self {
+        Self { agent, examples }
+    }
+}
+
+impl<M: CompletionModel> Chat for CharacterAgent<M> {
+    async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
+        let history = self
+            .examples
+            .iter()
+            .cloned()
+            .chain(chat_history)
+            .collect();
+        Chat::chat(&self.agent, prompt, history).await
+    }
+}
+
+impl<M: CompletionModel> Prompt for CharacterAgent<M> {
+    async fn prompt(&self, prompt: &str) -> Result<String, PromptError> {
+        Chat::chat(self, prompt, vec![]).await
+    }
+}
+
+/// Builds the agent of a [Character].
+///
+/// Bio, lore and message examples are sampled once, when the agent is built, so that a
+/// character with a long bio does not flood the context window.
+///
+/// # Example
+/// ```rust
+/// let character = Character::load("characters/vergen.json")?;
+/// let agent = character.agent_builder().bio_sample(5).build().await?;
+/// let response = agent.chat("What do you think of SOL?", vec![]).await?;
+/// ```
+pub struct CharacterAgentBuilder<'a> {
+    character: &'a Character,
+    bio_sample: usize,
+    lore_sample: usize,
+    knowledge_sample: usize,
+    example_sample: usize,
+    sentiment_tool: Option<SentimentTool>,
+}
+
+impl<'a> CharacterAgentBuilder<'a> {
+    pub fn new(character: &'a Character) -> Self {
+        Self {
+            character,
+            bio_sample: DEFAULT_BIO_SAMPLE,
+            lore_sample: DEFAULT_LORE_SAMPLE,
+            knowledge_sample: DEFAULT_KNOWLEDGE_SAMPLE,
+            example_sample: DEFAULT_EXAMPLE_SAMPLE,
+            sentiment_tool: None,
+        }
+    }
+
+    /// Set the number of bio entries included in the preamble
+    pub fn bio_sample(mut self, sample: usize) -> Self {
+        self.bio_sample = sample;
+        self
+    }
+
+    /// Set the number of lore entries included in the preamble
+    pub fn lore_sample(mut self, sample: usize) -> Self {
+        self.lore_sample = sample;
+        self
+    }
+
+    /// Set the number of knowledge entries retrieved on each prompt when `rag_knowledge` is set
+    pub fn knowledge_sample(mut self, sample: usize) -> Self {
+        self.knowledge_sample = sample;
+        self
+    }
+
+    /// Set the number of message example conversations used as few-shot turns
+    pub fn example_sample(mut self, sample: usize) -> Self {
+        self.example_sample = sample;
+        self
+    }
+
+    /// Give the agent the social sentiment of the tokens
+    pub fn sentiment_tool(mut self, tool: SentimentTool) -> Self {
+        self.sentiment_tool = Some(tool);
+        self
+    }
+
+    /// Provider selected by the `model_provider` of the character
+    pub fn provider(&self) -> Result<ModelProvider> {
+        self.character.model_provider.parse()
+    }
+
+    /// Returns the preamble of the character, with a fresh sample of its bio and lore.
+    pub fn preamble(&self) -> String {
+        let character = self.character;
+        let mut rng = rand::thread_rng();
+        let mut preamble = String::new();
+
+        if !character.system.is_empty() {
+            preamble.push_str(&character.system);
+            preamble.push_str("\n\n");
+        }
+
+        let sections = [
+            (
+                format!("About {}", character.name),
+                character
+                    .bio
+                    .choose_multiple(&mut rng, self.bio_sample)
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Lore".to_string(),
+                character
+                    .lore
+                    .choose_multiple(&mut rng, self.lore_sample)
+                    .cloned()
+                    .collect(),
+            ),
+            ("Topics of interest".to_string(), character.topics.clone()),
+            (
+                "Style guidelines".to_string(),
+                character
+                    .style
+                    .all
+                    .iter()
+                    .chain(&character.style.chat)
+                    .cloned()
+                    .collect(),
+            ),
+        ];
+        for (title, entries) in sections.iter().filter(|(_, entries)| !entries.is_empty()) {
+            preamble.push_str(&format!("{}:\n", title));
+            for entry in entries {
+                preamble.push_str(&format!("- {}\n", entry));
+            }
+            preamble.push('\n');
+        }
+
+        if !character.adjectives.is_empty() {
+            preamble.push_str(&format!(
+                "{} is {}.\n",
+                character.name,
+                character.adjectives.join(", ")
+            ));
+        }
+
+        preamble.trim_end().to_string()
+    }
+
+    /// Returns a sample of the message examples of the character as chat messages. Messages
+    /// sent by the character become assistant turns, everything else becomes user turns.
+    pub fn examples(&self) -> Vec<Message> {
+        let character = self.character;
+        let mut rng = rand::thread_rng();
+
+        character
+            .message_examples
+            .choose_multiple(&mut rng, self.example_sample)
+            .flatten()
+            .map(|example| {
+                let role = if example.user == character.name || example.user == character.username
+                {
+                    "assistant"
+                } else {
+                    "user"
+                };
+                Message {
+                    role: role.into(),
+                    content: example.content.text.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Applies the character to an agent builder of any provider. The knowledge of the character
+    /// is embedded with `embedding_model` and used as dynamic context if `rag_knowledge` is set;
+    /// without an embedding model, it is appended to the preamble instead.
+    pub async fn configure<M, E>(
+        &self,
+        builder: AgentBuilder<M>,
+        provider: ModelProvider,
+        embedding_model: Option<E>,
+    ) -> Result<AgentBuilder<M>>
+    where
+        M: CompletionModel,
+        E: EmbeddingModel + Sync + 'static,
+    {
+        let character = self.character;
+        let config = &character.settings.model_config;
+
+        let mut builder = builder
+            .preamble(&self.preamble())
+            .temperature(config.temperature as f64)
+            .max_tokens(config.max_tokens as u64);
+
+        let params = provider.sampling_params(config);
+        if params.as_object().is_some_and(|params| !params.is_empty()) {
+            builder = builder.additional_params(params);
+        }
+        if let Some(tool) = &self.sentiment_tool {
+            builder = builder.tool(tool.clone());
+        }
+
+        if character.knowledge.is_empty() {
+            return Ok(builder);
+        }
+
+        match embedding_model {
+            Some(model) if character.settings.rag_knowledge => {
+                let embeddings = EmbeddingsBuilder::new(model.clone())
+                    .documents(character.knowledge.clone())?
+                    .build()
+                    .await?;
+                let index = InMemoryVectorStore::from_documents(embeddings).index(model);
+                Ok(builder.dynamic_context(self.knowledge_sample, index))
+            }
+            _ => {
+                if character.settings.rag_knowledge {
+                    tracing::warn!(
+                        "No embedding model available for {}, adding knowledge to the preamble",
+                        character.name
+                    );
+                }
+                let knowledge = character
+                    .knowledge
+                    .iter()
+                    .map(|entry| format!("- {}", entry))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                Ok(builder.append_preamble(&format!("\nKnowledge:\n{}", knowledge)))
+            }
+        }
+    }
+
+    /// Builds the agent of the character with the provider selected by `model_provider`.
+    ///
+    /// API keys are read from the `secrets` of the character, then from the environment.
+    /// Providers without an embedding model (Anthropic, Perplexity) embed knowledge with
+    /// OpenAI when `OPENAI_API_KEY` is available.
+    pub async fn build(&self) -> Result<Box<dyn ChatAgent>> {
+        let provider = self.provider()?;
+        let model = self
+            .character
+            .settings
+            .model_config
+            .model
+            .clone()
+            .unwrap_or_else(|| provider.default_model().to_string());
+        let api_key = self
+            .character
+            .secret(provider.api_key_var())
+            .ok_or_else(|| anyhow!("{} must be set", provider.api_key_var()))?;
+        let openai_embeddings = self.character.secret("OPENAI_API_KEY").map(|key| {
+            openai::Client::new(&key).embedding_model(openai::TEXT_EMBEDDING_3_SMALL)
+        });
+
+        tracing::info!(
+            "Building agent for {} with {:?} model {}",
+            self.character.name,
+            provider,
+            model
+        );
+
+        Ok(match provider {
+            ModelProvider::OpenAI => {
+                let client = openai::Client::new(&api_key);
+                let embeddings = client.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+                self.finish(client.agent(&model), provider, Some(embeddings))
+                    .await?
+            }
+            ModelProvider::Anthropic => {
+                let client = anthropic::ClientBuilder::new(&api_key).build();
+                self.finish(client.agent(&model), provider, openai_embeddings)
+                    .await?
+            }
+            ModelProvider::Cohere => {
+                let client = cohere::Client::new(&api_key);
+                let embeddings =
+                    client.embedding_model(cohere::EMBED_ENGLISH_V3, "search_document");
+                self.finish(client.agent(&model), provider, Some(embeddings))
+                    .await?
+            }
+            ModelProvider::Gemini => {
+                let client = gemini::Client::new(&api_key);
+                let embeddings = client.embedding_model(gemini::embedding::EMBEDDING_004);
+                self.finish(client.agent(&model), provider, Some(embeddings))
+                    .await?
+            }
+            ModelProvider::Xai => {
+                let client = xai::Client::new(&api_key);
+                let embeddings = client.embedding_model(xai::embedding::EMBEDDING_V1);
+                self.finish(client.agent(&model), provider, Some(embeddings))
+                    .await?
+            }
+            ModelProvider::Perplexity => {
+                let client = perplexity::Client::new(&api_key);
+                self.finish(client.agent(&model), provider, openai_embeddings)
+                    .await?
+            }
+        })
+    }
+
+    async fn finish<M, E>(
+        &self,
+        builder: AgentBuilder<M>,
+        provider: ModelProvider,
+        embedding_model: Option<E>,
+    ) -> Result<Box<dyn ChatAgent>>
+    where
+        M: CompletionModel + 'static,
+        E: EmbeddingModel + Sync + 'static,
+    {
+        let agent = self
+            .configure(builder, provider, embedding_model)
+            .await?
+            .build();
+        Ok(Box::new(CharacterAgent::new(agent, self.examples())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character() -> Character {
+        serde_json::from_value(json!({
+            "name": "Vergen",
+            "username": "vergen",
+            "clients": [],
+            "modelProvider": "anthropic",
+            "imageModelProvider": "openai",
+            "plugins": [],
+            "settings": {
+                "secrets": {},
+                "voice": { "model": "en_US-hfc_male-medium" },
+                "ragKnowledge": false,
+                "modelConfig": {
+                    "temperature": 0.7,
+                    "maxTokens": 2048,
+                    "frequencyPenalty": 0.5,
+                    "presencePenalty": 0.0,
+                    "topP": 0.9
+                }
+            },
+            "system": "You are Vergen, a Solana trader.",
+            "bio": ["bio 1", "bio 2", "bio 3", "bio 4"],
+            "lore": ["lore 1"],
+            "knowledge": ["SOL has 9 decimals"],
+            "messageExamples": [[
+                { "user": "{{user1}}", "content": { "text": "gm" } },
+                { "user": "Vergen", "content": { "text": "gm. charts look spicy." } }
+            ]],
+            "postExamples": [],
+            "topics": ["memecoins"],
+            "style": {
+                "tone": "confident",
+                "writing": "terse",
+                "personality": "degen",
+                "quirks": [],
+                "all": ["lowercase only"],
+                "chat": [],
+                "post": []
+            },
+            "adjectives": ["analytical", "blunt"]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_preamble_samples_bio() {
+        let character = character();
+        let preamble = CharacterAgentBuilder::new(&character)
+            .bio_sample(2)
+            .preamble();
+
+        assert!(preamble.starts_with("You are Vergen, a Solana trader."));
+        assert_eq!(preamble.matches("- bio").count(), 2);
+        assert!(preamble.contains("- lore 1"));
+        assert!(preamble.contains("- lowercase only"));
+        assert!(preamble.ends_with("Vergen is analytical, blunt."));
+    }
+
+    #[test]
+    fn test_samples_from_settings() {
+        let mut character = character();
+        character.settings.samples.bio = 1;
+        let preamble = character.agent_builder().preamble();
+
+        assert_eq!(preamble.matches("- bio").count(), 1);
+    }
+
+    #[test]
+    fn test_examples_roles() {
+        let character = character();
+        let examples = CharacterAgentBuilder::new(&character).examples();
+
+        let roles = examples.iter().map(|m| m.role.as_str()).collect::<Vec<_>>();
+        assert_eq!(roles, vec!["user", "assistant"]);
+    }
+
+    #[test]
+    fn test_sampling_params() {
+        let config = character().settings.model_config;
+
+        assert_eq!(
+            ModelProvider::OpenAI.sampling_params(&config),
+            json!({ "top_p": 0.9f32, "frequency_penalty": 0.5 })
+        );
+        assert_eq!(
+            ModelProvider::Anthropic.sampling_params(&config),
+            json!({ "top_p": 0.9f32 })
+        );
+        assert_eq!("Claude".parse::<ModelProvider>().unwrap(), ModelProvider::Anthropic);
+        assert!("llama-local".parse::<ModelProvider>().is_err());
+    }
+}
@@ -1,41 +1,117 @@
+use anyhow::{anyhow, Context};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{
+    clients::twitter::MentionSettings,
+    content::ContentJob,
+    notifications::{self, AlertSettings},
+    sentiment::SentimentSettings,
+    triggers::TriggerRule,
+};
+
+mod builder;
+mod posts;
+mod watcher;
+
+pub use builder::{CharacterAgent, CharacterAgentBuilder, ChatAgent, ModelProvider};
+pub use posts::{PostSettings, PostVoice, PostWriter};
+pub use watcher::CharacterWatcher;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Character {
     pub name: String,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub clients: Vec<String>,
     pub model_provider: String,
+    #[serde(default)]
     pub image_model_provider: String,
+    #[serde(default)]
     pub plugins: Vec<String>,
+    #[serde(default)]
     pub settings: Settings,
+    #[serde(default)]
     pub system: String,
     pub bio: Vec<String>,
+    #[serde(default)]
     pub lore: Vec<String>,
+    #[serde(default)]
     pub knowledge: Vec<String>,
+    #[serde(default)]
     pub message_examples: Vec<Vec<MessageExample>>,
+    #[serde(default)]
     pub post_examples: Vec<String>,
+    #[serde(default)]
     pub topics: Vec<String>,
     pub style: Style,
+    #[serde(default)]
     pub adjectives: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Settings {
     pub secrets: HashMap<String, String>,
     pub voice: VoiceSettings,
     pub rag_knowledge: bool,
     pub model_config: ModelConfig,
+    /// Number of entries of the character sampled in the prompts of the agent
+    pub samples: SampleSettings,
+    /// Notification settings, by sink
+    pub alerts: HashMap<String, AlertSettings>,
+    /// Rules of the posts written by the agent
+    pub posts: PostSettings,
+    /// Replies to the Twitter mentions of the agent
+    pub mentions: MentionSettings,
+    /// Social sentiment of the tokens, tracked when Twitter is enabled
+    pub sentiment: SentimentSettings,
+    /// Scheduled posts of the agent. Uses the default calendar when not set, `[]` disables
+    /// the scheduled posts.
+    pub calendar: Option<Vec<ContentJob>>,
+    /// Rules acting on the real-time market data. Uses the default rules when not set, `[]`
+    /// disables the trading loop.
+    pub triggers: Option<Vec<TriggerRule>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct VoiceSettings {
     pub model: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SampleSettings {
+    /// Bio entries included in the preamble
+    pub bio: usize,
+    /// Lore entries included in the preamble
+    pub lore: usize,
+    /// Knowledge entries retrieved on each prompt when `rag_knowledge` is set
+    pub knowledge: usize,
+    /// Message example conversations used as few-shot turns
+    pub examples: usize,
+}
+
+impl Default for SampleSettings {
+    fn default() -> Self {
+        Self {
+            bio: builder::DEFAULT_BIO_SAMPLE,
+            lore: builder::DEFAULT_LORE_SAMPLE,
+            knowledge: builder::DEFAULT_KNOWLEDGE_SAMPLE,
+            examples: builder::DEFAULT_EXAMPLE_SAMPLE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct ModelConfig {
+    /// Model to use with `model_provider`. Defaults to the provider's flagship model.
+    pub model: Option<String>,
     pub temperature: f32,
     pub max_tokens: u32,
     pub frequency_penalty: f32,
@@ -43,20 +119,36 @@ pub struct ModelConfig {
     pub top_p: f32,
 }
 
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            model: None,
+            temperature: 0.7,
+            max_tokens: 2048,
+            frequency_penalty: 0.0,
+            presence_penalty: 0.0,
+            top_p: 1.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MessageExample {
     pub user: String,
     pub content: MessageContent,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MessageContent {
     pub text: String,
     pub action: Option<String>,
     pub content: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Style {
     pub tone: String,
     pub writing: String,
@@ -68,12 +160,120 @@ pub struct Style {
 }
 
 impl Character {
-    pub fn load(path: &str) -> anyhow::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let character = serde_json::from_str(&content)?;
+    /// Loads and validates a character file.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read character file {}", path.display()))?;
+        Self::from_json(&content)
+            .with_context(|| format!("Invalid character file {}", path.display()))
+    }
+
+    /// Parses and validates a character from its JSON definition.
+    pub fn from_json(content: &str) -> anyhow::Result<Self> {
+        let character: Character = serde_json::from_str(content)?;
+        character.validate()?;
         Ok(character)
     }
 
+    /// Checks that the character can be used to build an agent. All problems are reported at
+    /// once, one per line.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut problems = Vec::new();
+
+        if self.name.trim().is_empty() {
+            problems.push("`name` must not be empty".to_string());
+        }
+        if let Err(err) = self.model_provider.parse::<ModelProvider>() {
+            problems.push(format!(
+                "{} (expected one of: {})",
+                err,
+                ModelProvider::NAMES.join(", ")
+            ));
+        }
+        if self.bio.is_empty() {
+            problems.push("`bio` must have at least one entry".to_string());
+        }
+        if self.style.all.is_empty() && self.style.chat.is_empty() && self.style.post.is_empty() {
+            problems.push("`style` must have at least one guideline in `all`, `chat` or `post`".to_string());
+        }
+        for (i, conversation) in self.message_examples.iter().enumerate() {
+            if conversation.iter().any(|example| example.content.text.trim().is_empty()) {
+                problems.push(format!("`messageExamples[{}]` has a message without text", i));
+            }
+        }
+
+        let config = &self.settings.model_config;
+        let ranges = [
+            ("temperature", config.temperature, 0.0, 2.0),
+            ("topP", config.top_p, 0.0, 1.0),
+            ("frequencyPenalty", config.frequency_penalty, -2.0, 2.0),
+            ("presencePenalty", config.presence_penalty, -2.0, 2.0),
+        ];
+        for (field, value, min, max) in ranges {
+            if !(min..=max).contains(&value) {
+                problems.push(format!(
+                    "`settings.modelConfig.{}` must be between {} and {}, got {}",
+                    field, min, max, value
+                ));
+            }
+        }
+        if config.max_tokens == 0 {
+            problems.push("`settings.modelConfig.maxTokens` must be greater than 0".to_string());
+        }
+        if config.model.as_deref().is_some_and(|model| model.trim().is_empty()) {
+            problems.push("`settings.modelConfig.model` must not be empty when set".to_string());
+        }
+
+        for (sink, settings) in &self.settings.alerts {
+            if !notifications::SINKS.contains(&sink.as_str()) {
+                problems.push(format!(
+                    "`settings.alerts.{}` is not a sink (expected one of: {})",
+                    sink,
+                    notifications::SINKS.join(", ")
+                ));
+            }
+            if let Err(err) = settings.validate() {
+                problems.push(format!("`settings.alerts.{}`: {}", sink, err));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("{}", problems.join("\n")))
+        }
+    }
+
+    /// Namespace isolating the memories and sessions of this character from the other
+    /// characters running in the same process.
+    pub fn namespace(&self) -> String {
+        let base = if self.username.is_empty() {
+            &self.name
+        } else {
+            &self.username
+        };
+        base.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect()
+    }
+
+    /// Returns a secret of the character, falling back to the environment variable of the
+    /// same name.
+    pub fn secret(&self, name: &str) -> Option<String> {
+        self.settings
+            .secrets
+            .get(name)
+            .filter(|value| !value.is_empty())
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    }
+
+    /// Whether the character enables the given client (e.g.: "twitter")
+    pub fn has_client(&self, client: &str) -> bool {
+        self.clients.iter().any(|c| c.eq_ignore_ascii_case(client))
+    }
+
     pub fn get_system_prompt(&self) -> String {
         let mut prompt = String::new();
         
@@ -97,6 +297,17 @@ impl Character {
         prompt
     }
 
+    /// Returns a builder for the agent of this character, sampling the character as set in its
+    /// settings
+    pub fn agent_builder(&self) -> CharacterAgentBuilder<'_> {
+        let samples = &self.settings.samples;
+        CharacterAgentBuilder::new(self)
+            .bio_sample(samples.bio)
+            .lore_sample(samples.lore)
+            .knowledge_sample(samples.knowledge)
+            .example_sample(samples.examples)
+    }
+
     pub fn get_post_style(&self) -> Vec<String> {
         self.style.post.clone()
     }
@@ -155,5 +366,32 @@ mod tests {
         let character: Character = serde_json::from_str(json).unwrap();
         assert_eq!(character.name, "Vergen");
         assert_eq!(character.username, "vergen");
+        assert_eq!(character.settings.model_config.max_tokens, 2048);
+        assert!(character.validate().is_ok());
+    }
+
+    #[test]
+    fn test_character_validation() {
+        let json = r#"{
+            "name": "Vergen",
+            "modelProvider": "llama-local",
+            "bio": ["Test bio"],
+            "style": {},
+            "settings": {
+                "modelConfig": { "temperature": 3.5, "topP": 0.9 },
+                "alerts": {
+                    "twitter": { "throttle": { "max": 0, "periodSecs": 3600 } },
+                    "slack": {}
+                }
+            }
+        }"#;
+
+        let err = Character::from_json(json).unwrap_err().to_string();
+        assert!(err.contains("Unknown model provider: llama-local (expected one of: openai,"));
+        assert!(err.contains("`style` must have at least one guideline"));
+        assert!(err.contains("`settings.modelConfig.temperature` must be between 0 and 2, got 3.5"));
+        assert!(!err.contains("topP"));
+        assert!(err.contains("`settings.alerts.slack` is not a sink"));
+        assert!(err.contains("`settings.alerts.twitter`: `throttle.max`"));
     }
 } 
\ No newline at end of file
@@ -0,0 +1,373 @@
+//! Posts written by the agent in the voice of its character.
+//!
+//! [PostWriter] asks the agent of the character to write a post, e.g. about a trade from its
+//! real rationale, with the post style, post examples and adjectives of the character.
+//! Posts are checked against [PostSettings] (length, hashtags and moderation) and rewritten
+//! when they break a rule.
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{Character, ChatAgent};
+use crate::{clients::twitter::mentions::MAX_TWEET_CHARS, trading::TradeDecision};
+
+/// Post examples shown to the agent
+const DEFAULT_POST_SAMPLE: usize = 5;
+/// Attempts at writing a post following the rules
+const MAX_ATTEMPTS: usize = 3;
+/// Phrases no post may contain, on top of the blocked words of the settings
+const BLOCKED_PHRASES: &[&str] = &[
+    "guaranteed",
+    "risk-free",
+    "risk free",
+    "can't lose",
+    "cannot lose",
+    "free money",
+    "get rich",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PostSettings {
+    /// Queue the posts for human approval instead of posting them
+    pub review: bool,
+    pub max_hashtags: usize,
+    /// Hashtags every post must contain
+    pub hashtags: Vec<String>,
+    /// Words no post may contain (case insensitive)
+    pub blocked_words: Vec<String>,
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            review: false,
+            max_hashtags: 2,
+            hashtags: vec![],
+            blocked_words: vec![],
+        }
+    }
+}
+
+impl PostSettings {
+    /// Returns the rules the post breaks, if any
+    pub fn check(&self, post: &str) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        let lowercase = post.to_lowercase();
+
+        let length = post.chars().count();
+        if length > MAX_TWEET_CHARS {
+            problems.push(format!("it is {} characters long, the maximum is {}", length, MAX_TWEET_CHARS));
+        }
+        if post.trim().is_empty() {
+            problems.push("it is empty".to_string());
+        }
+
+        let hashtags: Vec<&str> = words(post).filter(|word| word.starts_with('#')).collect();
+        if hashtags.len() > self.max_hashtags {
+            problems.push(format!(
+                "it has {} hashtags, the maximum is {}",
+                hashtags.len(),
+                self.max_hashtags
+            ));
+        }
+        for hashtag in &self.hashtags {
+            if !hashtags.iter().any(|tag| tag.eq_ignore_ascii_case(hashtag)) {
+                problems.push(format!("it must contain {}", hashtag));
+            }
+        }
+
+        if words(post).any(|word| word.starts_with('@') && word.len() > 1) {
+            problems.push("it must not mention other accounts".to_string());
+        }
+        let blocked = BLOCKED_PHRASES
+            .iter()
+            .copied()
+            .chain(self.blocked_words.iter().map(String::as_str))
+            .filter(|phrase| lowercase.contains(&phrase.to_lowercase()));
+        for phrase in blocked {
+            problems.push(format!("it must not contain \"{}\"", phrase));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+/// Words of a post, without the trailing punctuation
+fn words(post: &str) -> impl Iterator<Item = &str> {
+    post.split_whitespace()
+        .map(|word| word.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_'))
+}
+
+/// What the writer needs from the character
+#[derive(Debug, Clone, Default)]
+pub struct PostVoice {
+    pub name: String,
+    pub post_style: Vec<String>,
+    pub examples: Vec<String>,
+    pub adjectives: Vec<String>,
+    pub topics: Vec<String>,
+    pub settings: PostSettings,
+}
+
+impl From<&Character> for PostVoice {
+    fn from(character: &Character) -> Self {
+        Self {
+            name: character.name.clone(),
+            post_style: character
+                .style
+                .all
+                .iter()
+                .chain(&character.style.post)
+                .cloned()
+                .collect(),
+            examples: character.post_examples.clone(),
+            adjectives: character.adjectives.clone(),
+            topics: character.topics.clone(),
+            settings: character.settings.posts.clone(),
+        }
+    }
+}
+
+impl PostVoice {
+    /// Prompt asking for a post about the trade
+    pub fn trade_prompt(&self, decision: &TradeDecision) -> String {
+        self.prompt(&format!(
+            "Write a tweet announcing a trade you just made.\n\n\
+             Trade: {} {} {}\nRationale: {}\nConfidence: {:.0}%\n",
+            decision.action,
+            decision.amount,
+            decision.symbol,
+            decision.reason,
+            decision.confidence * 100.0
+        ))
+    }
+
+    /// Prompt asking for the post described by `task`, in the voice of the character and
+    /// following the post rules
+    pub fn prompt(&self, task: &str) -> String {
+        let mut prompt = task.to_string();
+
+        if !self.adjectives.is_empty() {
+            prompt.push_str(&format!(
+                "\nYou are {}, {}.\n",
+                self.name,
+                self.adjectives.join(", ")
+            ));
+        }
+        if !self.post_style.is_empty() {
+            prompt.push_str("\nPost style:\n");
+            for guideline in &self.post_style {
+                prompt.push_str(&format!("- {}\n", guideline));
+            }
+        }
+        let examples: Vec<_> = self
+            .examples
+            .choose_multiple(&mut rand::thread_rng(), DEFAULT_POST_SAMPLE)
+            .collect();
+        if !examples.is_empty() {
+            prompt.push_str("\nExamples of your posts:\n");
+            for example in examples {
+                prompt.push_str(&format!("- {}\n", example));
+            }
+        }
+
+        prompt.push_str(&format!(
+            "\nRules:\n- At most {} characters\n- At most {} hashtags\n",
+            MAX_TWEET_CHARS, self.settings.max_hashtags
+        ));
+        if !self.settings.hashtags.is_empty() {
+            prompt.push_str(&format!("- Include {}\n", self.settings.hashtags.join(" ")));
+        }
+        prompt.push_str(
+            "- Only use the facts above, and never promise returns\n\
+             - Do not mention other accounts\n\n\
+             Reply with the text of the tweet only.",
+        );
+        prompt
+    }
+}
+
+/// Writes posts with the agent of the character
+#[derive(Clone)]
+pub struct PostWriter {
+    agent: Arc<RwLock<Box<dyn ChatAgent>>>,
+    voice: Arc<RwLock<PostVoice>>,
+}
+
+impl PostWriter {
+    pub fn new(agent: Arc<RwLock<Box<dyn ChatAgent>>>, voice: PostVoice) -> Self {
+        Self {
+            agent,
+            voice: Arc::new(RwLock::new(voice)),
+        }
+    }
+
+    /// Updates the voice, when the character is reloaded
+    pub async fn set_voice(&self, voice: PostVoice) {
+        *self.voice.write().await = voice;
+    }
+
+    pub async fn settings(&self) -> PostSettings {
+        self.voice.read().await.settings.clone()
+    }
+
+    /// Topics of interest of the character
+    pub async fn topics(&self) -> Vec<String> {
+        self.voice.read().await.topics.clone()
+    }
+
+    /// Writes a post about the trade. The agent is asked to rewrite posts breaking the rules,
+    /// and an error is returned if it keeps breaking them.
+    pub async fn write_trade_post(&self, decision: &TradeDecision) -> Result<String> {
+        let voice = self.voice.read().await.clone();
+        self.write(&voice, voice.trade_prompt(decision)).await
+    }
+
+    /// Writes the post described by `task`, following the rules like
+    /// [write_trade_post](Self::write_trade_post)
+    pub async fn write_post(&self, task: &str) -> Result<String> {
+        let voice = self.voice.read().await.clone();
+        self.write(&voice, voice.prompt(task)).await
+    }
+
+    async fn write(&self, voice: &PostVoice, prompt: String) -> Result<String> {
+        let mut problems = Vec::new();
+        for _ in 0..MAX_ATTEMPTS {
+            let prompt = if problems.is_empty() {
+                prompt.clone()
+            } else {
+                format!(
+                    "{}\n\nYour previous attempt was rejected because {}. Fix it.",
+                    prompt,
+                    problems.join(", ")
+                )
+            };
+
+            let post = clean(&self.agent.read().await.chat(&prompt, vec![]).await?);
+            match voice.settings.check(&post) {
+                Ok(()) => return Ok(post),
+                Err(rejections) => {
+                    tracing::debug!("Rejected post {:?}: {}", post, rejections.join(", "));
+                    problems = rejections;
+                }
+            }
+        }
+        Err(anyhow!(
+            "Could not write a post following the rules: {}",
+            problems.join(", ")
+        ))
+    }
+}
+
+/// Removes the quotes models like to wrap posts in
+fn clean(post: &str) -> String {
+    let post = post.trim();
+    post.strip_prefix('"')
+        .and_then(|post| post.strip_suffix('"'))
+        .unwrap_or(post)
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use rig::completion::{Message, PromptError};
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Agent answering with canned replies, and recording the prompts
+    struct ScriptedAgent {
+        replies: Mutex<Vec<&'static str>>,
+        prompts: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl ChatAgent for ScriptedAgent {
+        async fn chat(&self, prompt: &str, _: Vec<Message>) -> Result<String, PromptError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(self.replies.lock().unwrap().remove(0).to_string())
+        }
+    }
+
+    fn decision() -> TradeDecision {
+        TradeDecision {
+            action: "BUY".to_string(),
+            symbol: "BONK".to_string(),
+            amount: 100.0,
+            reason: "Volume up 300% with rising liquidity".to_string(),
+            confidence: 0.8,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let settings = PostSettings {
+            hashtags: vec!["#Solana".to_string()],
+            blocked_words: vec!["moon".to_string()],
+            ..Default::default()
+        };
+        assert!(settings.check("Aped into $BONK, volume is wild. #solana").is_ok());
+
+        let problems = settings
+            .check("Guaranteed MOON, thanks @someone #a #b #c")
+            .unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                "it has 3 hashtags, the maximum is 2",
+                "it must contain #Solana",
+                "it must not mention other accounts",
+                "it must not contain \"guaranteed\"",
+                "it must not contain \"moon\"",
+            ]
+        );
+        assert!(settings.check(&format!("{} #Solana", "a".repeat(280))).is_err());
+    }
+
+    #[test]
+    fn test_trade_prompt() {
+        let voice = PostVoice {
+            name: "Vergen".to_string(),
+            post_style: vec!["lowercase only".to_string()],
+            examples: vec!["gm degens".to_string()],
+            adjectives: vec!["witty".to_string(), "bold".to_string()],
+            topics: vec![],
+            settings: PostSettings::default(),
+        };
+        let prompt = voice.trade_prompt(&decision());
+        assert!(prompt.contains("Trade: BUY 100 BONK\nRationale: Volume up 300% with rising liquidity"));
+        assert!(prompt.contains("You are Vergen, witty, bold."));
+        assert!(prompt.contains("Post style:\n- lowercase only"));
+        assert!(prompt.contains("Examples of your posts:\n- gm degens"));
+        assert!(prompt.contains("- At most 2 hashtags"));
+    }
+
+    #[tokio::test]
+    async fn test_write_trade_post() -> Result<()> {
+        let prompts = Arc::new(Mutex::new(vec![]));
+        let agent: Box<dyn ChatAgent> = Box::new(ScriptedAgent {
+            replies: Mutex::new(vec![
+                "Guaranteed 10x on $BONK",
+                "\"bought $BONK, volume up 300%\"",
+            ]),
+            prompts: prompts.clone(),
+        });
+        let writer = PostWriter::new(Arc::new(RwLock::new(agent)), PostVoice::default());
+
+        let post = writer.write_trade_post(&decision()).await?;
+        assert_eq!(post, "bought $BONK, volume up 300%");
+        assert!(prompts.lock().unwrap()[1]
+            .contains("Your previous attempt was rejected because it must not contain \"guaranteed\""));
+        Ok(())
+    }
+}
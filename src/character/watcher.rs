@@ -0,0 +1,129 @@
+//! Hot reload of character files.
+//!
+//! [CharacterWatcher] watches a character file and publishes a new [Character] every time the
+//! file is saved with valid contents. Invalid edits are logged and ignored, so that a typo in a
+//! character file does not take down a running agent.
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::watch;
+
+use super::Character;
+
+pub struct CharacterWatcher {
+    receiver: watch::Receiver<Character>,
+    // Dropping the watcher stops watching the file
+    _watcher: RecommendedWatcher,
+}
+
+impl CharacterWatcher {
+    /// Loads the character file at `path` and starts watching it for changes.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path
+            .as_ref()
+            .canonicalize()
+            .with_context(|| format!("Character file {} not found", path.as_ref().display()))?;
+        let character = Character::load(&path)?;
+        let mut contents = std::fs::read_to_string(&path)?;
+        let (sender, receiver) = watch::channel(character);
+
+        let file = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            match event {
+                Ok(event) if is_write(&event, &file) => {
+                    // Editors emit several events per save: only reload when the contents change
+                    let Ok(new_contents) = std::fs::read_to_string(&file) else {
+                        return;
+                    };
+                    if new_contents == contents {
+                        return;
+                    }
+                    match Character::from_json(&new_contents) {
+                        Ok(character) => {
+                            tracing::info!("Reloaded character {} from {}", character.name, file.display());
+                            sender.send_replace(character);
+                        }
+                        Err(err) => tracing::error!(
+                            "Ignoring invalid character file {}: {:#}",
+                            file.display(),
+                            err
+                        ),
+                    }
+                    contents = new_contents;
+                }
+                Ok(_) => {}
+                Err(err) => tracing::error!("Error watching {}: {}", file.display(), err),
+            }
+        })?;
+
+        // Watch the directory rather than the file, since editors often save by replacing it
+        let dir = path
+            .parent()
+            .ok_or_else(|| anyhow!("Invalid character file path {}", path.display()))?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            receiver,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns the latest valid version of the character
+    pub fn current(&self) -> Character {
+        self.receiver.borrow().clone()
+    }
+
+    /// Returns a receiver notified every time the character is reloaded
+    pub fn subscribe(&self) -> watch::Receiver<Character> {
+        self.receiver.clone()
+    }
+}
+
+fn is_write(event: &Event, file: &Path) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event.paths.iter().any(|path| path == file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn character_json(name: &str, temperature: f32) -> String {
+        serde_json::json!({
+            "name": name,
+            "modelProvider": "openai",
+            "bio": ["Trader"],
+            "style": { "all": ["be concise"] },
+            "settings": { "modelConfig": { "temperature": temperature } }
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_hot_reload() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("character-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("vergen.json");
+        std::fs::write(&path, character_json("Vergen", 0.7))?;
+
+        let watcher = CharacterWatcher::new(&path)?;
+        let mut updates = watcher.subscribe();
+        assert_eq!(watcher.current().name, "Vergen");
+
+        // Invalid edits are ignored
+        std::fs::write(&path, character_json("Vergen", 5.0))?;
+        let update = tokio::time::timeout(Duration::from_millis(500), updates.changed()).await;
+        assert!(update.is_err(), "invalid character published");
+        assert_eq!(watcher.current().settings.model_config.temperature, 0.7);
+
+        std::fs::write(&path, character_json("Vergen v2", 0.7))?;
+        tokio::time::timeout(Duration::from_secs(5), updates.changed()).await??;
+        assert_eq!(watcher.current().name, "Vergen v2");
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! Parts shared by the chat bots (Discord and Telegram).
+//!
+//! The bots answer commands with a [BotHandler], usually the [TradingAgent] of the character,
+//! and only execute trades once the user who requested them confirms them with a button.
+//!
+//! [TradingAgent]: crate::agent::TradingAgent
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::trading::TradeDecision;
+
+/// Time users have to confirm a trade
+pub const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What the bots need from the agent
+#[async_trait]
+pub trait BotHandler: Send + Sync + 'static {
+    /// Analysis of a token with live market data
+    async fn analyze(&self, symbol: &str) -> Result<String>;
+    /// Open positions, by symbol
+    async fn portfolio(&self) -> Result<Vec<(String, f64)>>;
+    /// Executes a confirmed trade, returns false if the trade was rejected
+    async fn trade(&self, decision: &TradeDecision) -> Result<bool>;
+    /// Answer to a message sent to the bot
+    async fn chat(&self, author: &str, message: &str) -> Result<String>;
+}
+
+#[async_trait]
+impl<T: BotHandler> BotHandler for Arc<T> {
+    async fn analyze(&self, symbol: &str) -> Result<String> {
+        (**self).analyze(symbol).await
+    }
+
+    async fn portfolio(&self) -> Result<Vec<(String, f64)>> {
+        (**self).portfolio().await
+    }
+
+    async fn trade(&self, decision: &TradeDecision) -> Result<bool> {
+        (**self).trade(decision).await
+    }
+
+    async fn chat(&self, author: &str, message: &str) -> Result<String> {
+        (**self).chat(author, message).await
+    }
+}
+
+struct PendingTrade {
+    user: u64,
+    decision: TradeDecision,
+    requested_at: Instant,
+}
+
+/// Trades waiting for the confirmation of the user who requested them
+pub struct PendingTrades {
+    timeout: Duration,
+    trades: Mutex<HashMap<String, PendingTrade>>,
+}
+
+impl Default for PendingTrades {
+    fn default() -> Self {
+        Self::new(CONFIRMATION_TIMEOUT)
+    }
+}
+
+impl PendingTrades {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            trades: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a trade requested by `user`, and forgets the expired ones
+    pub fn insert(&self, id: &str, user: u64, decision: TradeDecision) {
+        let mut trades = self.trades.lock().unwrap();
+        trades.retain(|_, trade| trade.requested_at.elapsed() < self.timeout);
+        trades.insert(
+            id.to_string(),
+            PendingTrade {
+                user,
+                decision,
+                requested_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Removes the trade if it was requested by `user` and has not expired
+    pub fn take(&self, id: &str, user: u64) -> Option<TradeDecision> {
+        let mut trades = self.trades.lock().unwrap();
+        match trades.get(id) {
+            Some(trade) if trade.user == user => {
+                let trade = trades.remove(id)?;
+                (trade.requested_at.elapsed() < self.timeout).then_some(trade.decision)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Data of the confirmation buttons of a trade
+pub fn trade_button(confirm: bool, id: &str) -> String {
+    let action = if confirm { "confirm" } else { "cancel" };
+    format!("trade:{}:{}", action, id)
+}
+
+/// Parses the data of a confirmation button into (confirmed, trade id)
+pub fn parse_trade_button(data: &str) -> Option<(bool, &str)> {
+    let (action, id) = data.strip_prefix("trade:")?.split_once(':')?;
+    match action {
+        "confirm" => Some((true, id)),
+        "cancel" => Some((false, id)),
+        _ => None,
+    }
+}
+
+/// Executes a confirmed trade and describes the outcome to the user
+pub async fn execute_trade(handler: &impl BotHandler, decision: &TradeDecision) -> String {
+    match handler.trade(decision).await {
+        Ok(true) => format!(
+            "Executed {} {} {}",
+            decision.action, decision.amount, decision.symbol
+        ),
+        Ok(false) => "Trade rejected by the risk limits".to_string(),
+        Err(err) => format!("Trade failed: {}", err),
+    }
+}
+
+/// Message of the positions
+pub fn format_positions(positions: &[(String, f64)]) -> String {
+    if positions.is_empty() {
+        return "No open positions".to_string();
+    }
+    positions
+        .iter()
+        .map(|(symbol, amount)| format!("{}: {}", symbol, amount))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Truncates `content` to `max_chars` characters
+pub fn truncate(content: &str, max_chars: usize) -> String {
+    if content.chars().count() <= max_chars {
+        return content.to_string();
+    }
+    let mut truncated: String = content.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decision() -> TradeDecision {
+        TradeDecision {
+            action: "BUY".to_string(),
+            symbol: "SOL".to_string(),
+            amount: 10.0,
+            reason: "Test".to_string(),
+            confidence: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_pending_trades() {
+        let trades = PendingTrades::default();
+        trades.insert("1", 42, decision());
+
+        // Only the user who requested the trade can confirm it, once
+        assert!(trades.take("1", 7).is_none());
+        assert_eq!(trades.take("1", 42).map(|d| d.symbol), Some("SOL".to_string()));
+        assert!(trades.take("1", 42).is_none());
+
+        let trades = PendingTrades::new(Duration::ZERO);
+        trades.insert("2", 42, decision());
+        assert!(trades.take("2", 42).is_none());
+    }
+
+    #[test]
+    fn test_trade_buttons() {
+        assert_eq!(parse_trade_button(&trade_button(true, "42")), Some((true, "42")));
+        assert_eq!(parse_trade_button(&trade_button(false, "42")), Some((false, "42")));
+        assert_eq!(parse_trade_button("trade:execute:42"), None);
+        assert_eq!(parse_trade_button("other:confirm:42"), None);
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_positions(&[]), "No open positions");
+        assert_eq!(
+            format_positions(&[("BONK".to_string(), 1e6), ("SOL".to_string(), 2.5)]),
+            "BONK: 1000000\nSOL: 2.5"
+        );
+        assert_eq!(truncate("gm", 10), "gm");
+        assert_eq!(truncate(&"a".repeat(30), 10).chars().count(), 10);
+    }
+}
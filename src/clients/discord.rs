@@ -0,0 +1,648 @@
+//! Discord bot of the agent, enabled with the `discord` feature.
+//!
+//! The bot registers the `/analyze`, `/portfolio` and `/trade` slash commands and answers the
+//! messages that mention it. Trades requested with `/trade` are only executed after the user
+//! confirms them, and only for members with the trader role. [DiscordClient] posts trade
+//! alerts as embeds in the alert channel.
+//!
+//! Message ids are `<channel id>/<message id>`, since Discord messages are only addressable
+//! within their channel.
+use anyhow::{anyhow, Context as _, Result};
+use async_trait::async_trait;
+use serenity::{
+    all::{
+        ButtonStyle, ChannelId, Colour, Command, CommandInteraction, CommandOptionType,
+        ComponentInteraction, Context, CreateActionRow, CreateButton, CreateCommand,
+        CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse, EventHandler,
+        GatewayIntents, GuildId, Http, Interaction, Member, Message, MessageId, Ready,
+        ResolvedValue, RoleId, Timestamp, UserId,
+    },
+    Client,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, OnceLock,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{
+    bot::{self, parse_trade_button, trade_button, BotHandler, PendingTrades},
+    SocialMediaClient,
+};
+use crate::{notifications::ReviewQueue, trading::TradeDecision};
+
+/// Longest message accepted by Discord
+pub const MAX_MESSAGE_CHARS: usize = 2000;
+
+#[derive(Debug, Clone)]
+pub struct DiscordConfig {
+    pub token: String,
+    /// Channel of the trade alerts
+    pub alert_channel: Option<ChannelId>,
+    /// Role required to trade. Without it, `/trade` is disabled.
+    pub trader_role: Option<RoleId>,
+    /// Guild to register the commands in. Guild commands are available immediately, while
+    /// global commands can take an hour to show up.
+    pub guild: Option<GuildId>,
+    /// Channel where the posts waiting for review are sent, the alert channel by default.
+    /// Members with the trader role approve them.
+    pub review_channel: Option<ChannelId>,
+}
+
+impl DiscordConfig {
+    /// Reads the configuration from `DISCORD_API_TOKEN`, and the optional
+    /// `DISCORD_ALERT_CHANNEL_ID`, `DISCORD_TRADER_ROLE_ID`, `DISCORD_GUILD_ID` and
+    /// `DISCORD_REVIEW_CHANNEL_ID`.
+    pub fn from_secrets(secret: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let id = |name: &str| -> Result<Option<u64>> {
+            secret(name)
+                .map(|id| {
+                    id.trim()
+                        .parse()
+                        .with_context(|| format!("Invalid {}", name))
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            token: secret("DISCORD_API_TOKEN")
+                .ok_or_else(|| anyhow!("DISCORD_API_TOKEN is not set"))?,
+            alert_channel: id("DISCORD_ALERT_CHANNEL_ID")?.map(ChannelId::new),
+            trader_role: id("DISCORD_TRADER_ROLE_ID")?.map(RoleId::new),
+            guild: id("DISCORD_GUILD_ID")?.map(GuildId::new),
+            review_channel: id("DISCORD_REVIEW_CHANNEL_ID")?.map(ChannelId::new),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct DiscordClient {
+    http: Arc<Http>,
+    config: DiscordConfig,
+}
+
+impl DiscordClient {
+    pub fn new(config: DiscordConfig) -> Self {
+        Self {
+            http: Arc::new(Http::new(&config.token)),
+            config,
+        }
+    }
+
+    /// Posts the trade as an embed in the alert channel, with `description` above its fields
+    pub async fn send_trade_alert(
+        &self,
+        decision: &TradeDecision,
+        description: &str,
+    ) -> Result<String> {
+        let channel = self.alert_channel()?;
+        let embed = trade_embed(decision).description(description);
+        let message = channel
+            .send_message(&self.http, CreateMessage::new().embed(embed))
+            .await?;
+        Ok(message_ref(channel, message.id))
+    }
+
+    /// Returns a bot answering the commands and mentions with `handler`
+    pub fn bot<H: BotHandler>(&self, handler: H) -> DiscordBot<H> {
+        DiscordBot {
+            config: self.config.clone(),
+            handler,
+            bot_id: OnceLock::new(),
+            pending_trades: PendingTrades::default(),
+            review_queue: None,
+            forwarding_reviews: AtomicBool::new(false),
+        }
+    }
+
+    fn alert_channel(&self) -> Result<ChannelId> {
+        self.config
+            .alert_channel
+            .ok_or_else(|| anyhow!("DISCORD_ALERT_CHANNEL_ID is not set"))
+    }
+}
+
+#[async_trait]
+impl SocialMediaClient for DiscordClient {
+    async fn post(&self, content: &str) -> Result<String> {
+        let channel = self.alert_channel()?;
+        let message = channel
+            .send_message(&self.http, CreateMessage::new().content(truncate(content)))
+            .await?;
+        Ok(message_ref(channel, message.id))
+    }
+
+    async fn reply(&self, parent_id: &str, content: &str) -> Result<String> {
+        let (channel, parent) = parse_message_ref(parent_id)?;
+        let message = channel
+            .send_message(
+                &self.http,
+                CreateMessage::new()
+                    .content(truncate(content))
+                    .reference_message((channel, parent)),
+            )
+            .await?;
+        Ok(message_ref(channel, message.id))
+    }
+
+    async fn delete(&self, post_id: &str) -> Result<()> {
+        let (channel, message) = parse_message_ref(post_id)?;
+        channel.delete_message(&self.http, message).await?;
+        Ok(())
+    }
+}
+
+pub struct DiscordBot<H> {
+    config: DiscordConfig,
+    handler: H,
+    // Set when connected, to recognize the mentions of the bot
+    bot_id: OnceLock<UserId>,
+    pending_trades: PendingTrades,
+    review_queue: Option<Arc<ReviewQueue>>,
+    // Set once the new drafts are forwarded to the review channel, since `ready` is called
+    // again on reconnections
+    forwarding_reviews: AtomicBool,
+}
+
+impl<H: BotHandler> DiscordBot<H> {
+    /// Sends the posts of the queue to the review channel, to be approved with buttons
+    pub fn review_queue(mut self, queue: Arc<ReviewQueue>) -> Self {
+        self.review_queue = Some(queue);
+        self
+    }
+
+    /// Connects to Discord and handles events until the connection is closed
+    pub async fn run(self) -> Result<()> {
+        let intents = GatewayIntents::GUILDS
+            | GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT;
+        let token = self.config.token.clone();
+        let mut client = Client::builder(token, intents).event_handler(self).await?;
+        client.start().await?;
+        Ok(())
+    }
+
+    async fn on_command(&self, ctx: &Context, command: &CommandInteraction) -> Result<()> {
+        match command.data.name.as_str() {
+            "analyze" => {
+                let symbol = string_option(command, "symbol")?.to_uppercase();
+                command.defer(&ctx.http).await?;
+                let content = match self.handler.analyze(&symbol).await {
+                    Ok(analysis) => truncate(&analysis),
+                    Err(err) => format!("Could not analyze {}: {}", symbol, err),
+                };
+                command
+                    .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+                    .await?;
+            }
+            "portfolio" => {
+                let positions = self.handler.portfolio().await?;
+                let embed = portfolio_embed(&positions);
+                respond(
+                    ctx,
+                    command,
+                    CreateInteractionResponseMessage::new().embed(embed),
+                )
+                .await?;
+            }
+            "trade" => self.on_trade_command(ctx, command).await?,
+            name => tracing::warn!("Unknown Discord command {}", name),
+        }
+        Ok(())
+    }
+
+    /// Asks the user to confirm the trade
+    async fn on_trade_command(&self, ctx: &Context, command: &CommandInteraction) -> Result<()> {
+        if !self.is_trader(command.member.as_deref()) {
+            let message = CreateInteractionResponseMessage::new()
+                .content("You are not allowed to trade")
+                .ephemeral(true);
+            return respond(ctx, command, message).await;
+        }
+
+        let amount = number_option(command, "amount")?;
+        if amount <= 0.0 {
+            let message = CreateInteractionResponseMessage::new()
+                .content("The amount must be positive")
+                .ephemeral(true);
+            return respond(ctx, command, message).await;
+        }
+
+        let decision = TradeDecision {
+            action: string_option(command, "action")?.to_uppercase(),
+            symbol: string_option(command, "symbol")?.to_uppercase(),
+            amount,
+            reason: format!("Requested on Discord by {}", command.user.name),
+            confidence: 1.0,
+        };
+
+        let id = command.id.to_string();
+        let message = CreateInteractionResponseMessage::new()
+            .content("Confirm this trade?")
+            .embed(trade_embed(&decision))
+            .components(vec![CreateActionRow::Buttons(vec![
+                CreateButton::new(trade_button(true, &id))
+                    .label("Confirm")
+                    .style(ButtonStyle::Success),
+                CreateButton::new(trade_button(false, &id))
+                    .label("Cancel")
+                    .style(ButtonStyle::Secondary),
+            ])])
+            .ephemeral(true);
+
+        self.pending_trades
+            .insert(&id, command.user.id.get(), decision);
+        respond(ctx, command, message).await
+    }
+
+    /// Executes or cancels a trade when the user clicks a confirmation button
+    async fn on_component(&self, ctx: &Context, component: &ComponentInteraction) -> Result<()> {
+        if let Some((approved, id)) = parse_review_button(&component.data.custom_id) {
+            return self.on_review(ctx, component, approved, id).await;
+        }
+        let Some((confirmed, id)) = parse_trade_button(&component.data.custom_id) else {
+            return Ok(());
+        };
+
+        let content = match self.pending_trades.take(id, component.user.id.get()) {
+            None => "This trade expired or is no longer pending".to_string(),
+            Some(_) if !confirmed => "Trade cancelled".to_string(),
+            // The roles of the user may have changed since the request
+            Some(_) if !self.is_trader(component.member.as_ref()) => {
+                "You are not allowed to trade".to_string()
+            }
+            Some(decision) => bot::execute_trade(&self.handler, &decision).await,
+        };
+
+        component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Publishes or discards a post when a reviewer clicks a review button
+    async fn on_review(
+        &self,
+        ctx: &Context,
+        component: &ComponentInteraction,
+        approved: bool,
+        id: u64,
+    ) -> Result<()> {
+        let Some(queue) = &self.review_queue else {
+            return Ok(());
+        };
+        if !self.is_trader(component.member.as_ref()) {
+            let message = CreateInteractionResponseMessage::new()
+                .content("You are not allowed to review posts")
+                .ephemeral(true);
+            component
+                .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+                .await?;
+            return Ok(());
+        }
+
+        let reviewer = &component.user.name;
+        let outcome = if approved {
+            queue.approve(id).await.map(|_| format!("✅ Posted, approved by {}", reviewer))
+        } else {
+            queue.reject(id).map(|_| format!("❌ Rejected by {}", reviewer))
+        };
+        // Keep the quoted post below the outcome
+        let post = component
+            .message
+            .content
+            .split_once('\n')
+            .map_or("", |(_, post)| post);
+        let content = match outcome {
+            Ok(outcome) => format!("{}\n{}", outcome, post),
+            Err(err) => format!("{:#}\n{}", err, post),
+        };
+
+        component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(truncate(&content))
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Forwards the new drafts of the review queue to the review channel
+    fn forward_reviews(&self, http: Arc<Http>) {
+        let Some(queue) = &self.review_queue else {
+            return;
+        };
+        let Some(channel) = self.config.review_channel.or(self.config.alert_channel) else {
+            tracing::warn!("No Discord review channel, set DISCORD_REVIEW_CHANNEL_ID");
+            return;
+        };
+        if self.forwarding_reviews.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let mut drafts = queue.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let draft = match drafts.recv().await {
+                    Ok(draft) => draft,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("{} posts were not sent for review, list them in the REPL", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let message = CreateMessage::new()
+                    .content(truncate(&format!("Post waiting for review:\n>>> {}", draft.text)))
+                    .components(vec![CreateActionRow::Buttons(vec![
+                        CreateButton::new(review_button(true, draft.id))
+                            .label("Approve")
+                            .style(ButtonStyle::Success),
+                        CreateButton::new(review_button(false, draft.id))
+                            .label("Reject")
+                            .style(ButtonStyle::Danger),
+                    ])]);
+                if let Err(err) = channel.send_message(&http, message).await {
+                    tracing::error!("Could not send post {} for review: {}", draft.id, err);
+                }
+            }
+        });
+    }
+
+    /// Answers the messages mentioning the bot
+    async fn on_message(&self, ctx: &Context, message: &Message) -> Result<()> {
+        let Some(bot_id) = self.bot_id.get() else {
+            return Ok(());
+        };
+        if message.author.bot || !message.mentions_user_id(*bot_id) {
+            return Ok(());
+        }
+
+        let text = strip_mention(&message.content, *bot_id);
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let _ = message.channel_id.broadcast_typing(&ctx.http).await;
+        let answer = self.handler.chat(&message.author.name, &text).await?;
+        message.reply(&ctx.http, truncate(&answer)).await?;
+        Ok(())
+    }
+
+    fn is_trader(&self, member: Option<&Member>) -> bool {
+        match (self.config.trader_role, member) {
+            (Some(role), Some(member)) => member.roles.contains(&role),
+            _ => false,
+        }
+    }
+}
+
+#[async_trait]
+impl<H: BotHandler> EventHandler for DiscordBot<H> {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        let _ = self.bot_id.set(ready.user.id);
+        self.forward_reviews(ctx.http.clone());
+
+        let registered = match self.config.guild {
+            Some(guild) => guild.set_commands(&ctx.http, commands()).await,
+            None => Command::set_global_commands(&ctx.http, commands()).await,
+        };
+        match registered {
+            Ok(_) => tracing::info!("Discord bot {} is ready", ready.user.name),
+            Err(err) => tracing::error!("Could not register the Discord commands: {}", err),
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let result = match &interaction {
+            Interaction::Command(command) => self.on_command(&ctx, command).await,
+            Interaction::Component(component) => self.on_component(&ctx, component).await,
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            tracing::error!("Error handling Discord interaction: {:#}", err);
+        }
+    }
+
+    async fn message(&self, ctx: Context, message: Message) {
+        if let Err(err) = self.on_message(&ctx, &message).await {
+            tracing::error!("Error answering Discord message {}: {:#}", message.id, err);
+        }
+    }
+}
+
+/// Slash commands of the bot
+pub fn commands() -> Vec<CreateCommand> {
+    let symbol = || {
+        CreateCommandOption::new(CommandOptionType::String, "symbol", "Token symbol, e.g. SOL")
+            .required(true)
+    };
+
+    vec![
+        CreateCommand::new("analyze")
+            .description("Analyze a token with live market data")
+            .add_option(symbol()),
+        CreateCommand::new("portfolio").description("Show the open positions"),
+        CreateCommand::new("trade")
+            .description("Buy or sell a token, after confirmation")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "action", "Buy or sell")
+                    .required(true)
+                    .add_string_choice("Buy", "BUY")
+                    .add_string_choice("Sell", "SELL"),
+            )
+            .add_option(symbol())
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Number, "amount", "Amount to trade")
+                    .required(true)
+                    .min_number_value(0.0),
+            ),
+    ]
+}
+
+/// Embed of a trade alert
+pub fn trade_embed(decision: &TradeDecision) -> CreateEmbed {
+    let colour = if decision.action.eq_ignore_ascii_case("SELL") {
+        Colour::RED
+    } else {
+        Colour::DARK_GREEN
+    };
+
+    CreateEmbed::new()
+        .title(format!("🤖 Trade Alert: {} {}", decision.action, decision.symbol))
+        .colour(colour)
+        .field("Amount", decision.amount.to_string(), true)
+        .field("Confidence", format!("{:.0}%", decision.confidence * 100.0), true)
+        .field("Reason", &decision.reason, false)
+        .timestamp(Timestamp::now())
+}
+
+fn portfolio_embed(positions: &[(String, f64)]) -> CreateEmbed {
+    let embed = CreateEmbed::new()
+        .title("Portfolio")
+        .colour(Colour::BLUE)
+        .footer(CreateEmbedFooter::new(format!("{} open positions", positions.len())));
+
+    if positions.is_empty() {
+        return embed.description("No open positions");
+    }
+    positions
+        .iter()
+        .fold(embed, |embed, (symbol, amount)| embed.field(symbol, amount.to_string(), true))
+}
+
+async fn respond(
+    ctx: &Context,
+    command: &CommandInteraction,
+    message: CreateInteractionResponseMessage,
+) -> Result<()> {
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+fn string_option<'a>(command: &'a CommandInteraction, name: &str) -> Result<&'a str> {
+    command
+        .data
+        .options()
+        .into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::String(value) if option.name == name => Some(value),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("Missing option {}", name))
+}
+
+fn number_option(command: &CommandInteraction, name: &str) -> Result<f64> {
+    command
+        .data
+        .options()
+        .into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Number(value) if option.name == name => Some(value),
+            ResolvedValue::Integer(value) if option.name == name => Some(value as f64),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("Missing option {}", name))
+}
+
+fn review_button(approve: bool, id: u64) -> String {
+    let action = if approve { "approve" } else { "reject" };
+    format!("review:{}:{}", action, id)
+}
+
+/// Parses the id of a review button into (approved, draft id)
+fn parse_review_button(custom_id: &str) -> Option<(bool, u64)> {
+    let (action, id) = custom_id.strip_prefix("review:")?.split_once(':')?;
+    let id = id.parse().ok()?;
+    match action {
+        "approve" => Some((true, id)),
+        "reject" => Some((false, id)),
+        _ => None,
+    }
+}
+
+/// Removes the mentions of the bot from a message
+fn strip_mention(content: &str, bot_id: UserId) -> String {
+    content
+        .replace(&format!("<@{}>", bot_id), "")
+        .replace(&format!("<@!{}>", bot_id), "")
+        .trim()
+        .to_string()
+}
+
+fn message_ref(channel: ChannelId, message: MessageId) -> String {
+    format!("{}/{}", channel, message)
+}
+
+fn parse_message_ref(id: &str) -> Result<(ChannelId, MessageId)> {
+    let invalid = || anyhow!("Invalid Discord message id {}, expected <channel>/<message>", id);
+    let (channel, message) = id.split_once('/').ok_or_else(invalid)?;
+    let channel: u64 = channel.parse().map_err(|_| invalid())?;
+    let message: u64 = message.parse().map_err(|_| invalid())?;
+    if channel == 0 || message == 0 {
+        return Err(invalid());
+    }
+    Ok((ChannelId::new(channel), MessageId::new(message)))
+}
+
+fn truncate(content: &str) -> String {
+    bot::truncate(content, MAX_MESSAGE_CHARS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_config_from_secrets() -> Result<()> {
+        let secrets = HashMap::from([
+            ("DISCORD_API_TOKEN", "token"),
+            ("DISCORD_ALERT_CHANNEL_ID", "1234"),
+            ("DISCORD_TRADER_ROLE_ID", " 5678 "),
+        ]);
+        let config = DiscordConfig::from_secrets(|name| secrets.get(name).map(|s| s.to_string()))?;
+        assert_eq!(config.token, "token");
+        assert_eq!(config.alert_channel, Some(ChannelId::new(1234)));
+        assert_eq!(config.trader_role, Some(RoleId::new(5678)));
+        assert_eq!(config.guild, None);
+
+        assert!(DiscordConfig::from_secrets(|_| None).is_err());
+        assert!(DiscordConfig::from_secrets(|name| match name {
+            "DISCORD_API_TOKEN" => Some("token".to_string()),
+            "DISCORD_GUILD_ID" => Some("general".to_string()),
+            _ => None,
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_refs() -> Result<()> {
+        let id = message_ref(ChannelId::new(12), MessageId::new(34));
+        assert_eq!(id, "12/34");
+        assert_eq!(parse_message_ref(&id)?, (ChannelId::new(12), MessageId::new(34)));
+        assert!(parse_message_ref("34").is_err());
+        assert!(parse_message_ref("0/34").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_buttons() {
+        assert_eq!(parse_review_button(&review_button(true, 7)), Some((true, 7)));
+        assert_eq!(parse_review_button(&review_button(false, 7)), Some((false, 7)));
+        assert_eq!(parse_review_button("review:approve:abc"), None);
+        assert_eq!(parse_review_button("trade:confirm:7"), None);
+    }
+
+    #[test]
+    fn test_strip_mention() {
+        let bot_id = UserId::new(99);
+        assert_eq!(strip_mention("<@99> what about $BONK?", bot_id), "what about $BONK?");
+        assert_eq!(strip_mention("hey <@!99>", bot_id), "hey");
+    }
+
+    #[test]
+    fn test_commands() {
+        let names: Vec<_> = commands()
+            .into_iter()
+            .map(|command| serde_json::to_value(command).unwrap()["name"].clone())
+            .collect();
+        assert_eq!(names, vec!["analyze", "portfolio", "trade"]);
+    }
+}
@@ -0,0 +1,23 @@
+//! Clients of the social platforms the agents talk on.
+//!
+//! Every client implements [SocialMediaClient], so that the agent can post updates without
+//! knowing which platforms are enabled.
+use anyhow::Result;
+use async_trait::async_trait;
+
+#[cfg(any(feature = "discord", feature = "telegram"))]
+pub mod bot;
+#[cfg(feature = "discord")]
+pub mod discord;
+#[cfg(feature = "telegram")]
+pub mod telegram;
+pub mod twitter;
+
+#[async_trait]
+pub trait SocialMediaClient: Send + Sync {
+    /// Publishes `content` and returns the id of the new post
+    async fn post(&self, content: &str) -> Result<String>;
+    /// Replies to the post `parent_id` and returns the id of the reply
+    async fn reply(&self, parent_id: &str, content: &str) -> Result<String>;
+    async fn delete(&self, post_id: &str) -> Result<()>;
+}
@@ -0,0 +1,538 @@
+//! Telegram bot of the agent, enabled with the `telegram` feature.
+//!
+//! The commands of the bot mirror the REPL: `/analyze`, `/trade` and `/positions`. Trades are
+//! only executed after the user confirms them with an inline keyboard, and only for the users
+//! listed in `TELEGRAM_TRADER_IDS`. Chats subscribe to the trade alerts with `/subscribe`, and
+//! the subscriptions are persisted so that they survive restarts.
+//!
+//! Message ids are `<chat id>/<message id>`, since Telegram messages are only addressable
+//! within their chat.
+use anyhow::{anyhow, Context as _, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use teloxide::{
+    dispatching::{HandlerExt, UpdateFilterExt},
+    dptree,
+    prelude::{Dispatcher, Requester},
+    payloads::{AnswerCallbackQuerySetters, EditMessageTextSetters, SendMessageSetters},
+    types::{
+        CallbackQuery, ChatAction, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Me,
+        Message, MessageId, Update, UserId,
+    },
+    utils::command::BotCommands,
+    Bot,
+};
+
+use super::{
+    bot::{self, parse_trade_button, trade_button, BotHandler, PendingTrades},
+    SocialMediaClient,
+};
+use crate::trading::TradeDecision;
+
+/// Longest message accepted by Telegram
+pub const MAX_MESSAGE_CHARS: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct TelegramConfig {
+    pub token: String,
+    /// Users allowed to trade. Without traders, `/trade` is disabled.
+    pub traders: Vec<UserId>,
+}
+
+impl TelegramConfig {
+    /// Reads the configuration from `TELEGRAM_BOT_TOKEN` and the optional comma separated
+    /// user ids of `TELEGRAM_TRADER_IDS`.
+    pub fn from_secrets(secret: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let traders = secret("TELEGRAM_TRADER_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse()
+                    .map(UserId)
+                    .with_context(|| format!("Invalid TELEGRAM_TRADER_IDS user id {}", id))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            token: secret("TELEGRAM_BOT_TOKEN")
+                .ok_or_else(|| anyhow!("TELEGRAM_BOT_TOKEN is not set"))?,
+            traders,
+        })
+    }
+}
+
+#[derive(BotCommands, Clone, Debug, PartialEq)]
+#[command(rename_rule = "lowercase", description = "Available commands:")]
+pub enum Command {
+    #[command(description = "show this message")]
+    Help,
+    #[command(description = "analyze the market for a symbol: /analyze <symbol>")]
+    Analyze(String),
+    #[command(
+        description = "execute a trade: /trade <symbol> <buy|sell> <amount>",
+        parse_with = "split"
+    )]
+    Trade {
+        symbol: String,
+        action: String,
+        amount: f64,
+    },
+    #[command(description = "show the open positions")]
+    Positions,
+    #[command(description = "receive the trade alerts in this chat")]
+    Subscribe,
+    #[command(description = "stop receiving the trade alerts")]
+    Unsubscribe,
+}
+
+/// Chats subscribed to the alerts, persisted in a JSON file
+pub struct Subscriptions {
+    path: PathBuf,
+    chats: Mutex<BTreeSet<i64>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SubscriptionsFile {
+    chats: BTreeSet<i64>,
+}
+
+impl Subscriptions {
+    /// Loads the subscriptions from `path` if it exists
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let file: SubscriptionsFile = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            chats: Mutex::new(file.chats),
+        }
+    }
+
+    pub fn chats(&self) -> Vec<ChatId> {
+        self.chats.lock().unwrap().iter().copied().map(ChatId).collect()
+    }
+
+    /// Subscribes the chat, returns false if it already was
+    pub fn subscribe(&self, chat: ChatId) -> Result<bool> {
+        self.update(|chats| chats.insert(chat.0))
+    }
+
+    /// Unsubscribes the chat, returns false if it was not subscribed
+    pub fn unsubscribe(&self, chat: ChatId) -> Result<bool> {
+        self.update(|chats| chats.remove(&chat.0))
+    }
+
+    fn update(&self, change: impl FnOnce(&mut BTreeSet<i64>) -> bool) -> Result<bool> {
+        let mut chats = self.chats.lock().unwrap();
+        if !change(&mut chats) {
+            return Ok(false);
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = SubscriptionsFile {
+            chats: chats.clone(),
+        };
+        std::fs::write(&self.path, serde_json::to_string(&file)?)?;
+        Ok(true)
+    }
+}
+
+#[derive(Clone)]
+pub struct TelegramClient {
+    bot: Bot,
+    config: TelegramConfig,
+    subscriptions: Arc<Subscriptions>,
+}
+
+impl TelegramClient {
+    /// Creates a client persisting the subscribed chats at `subscriptions_path`
+    pub fn new(config: TelegramConfig, subscriptions_path: impl Into<PathBuf>) -> Self {
+        Self {
+            bot: Bot::new(&config.token),
+            config,
+            subscriptions: Arc::new(Subscriptions::load(subscriptions_path)),
+        }
+    }
+
+    /// Returns a bot answering the commands and messages with `handler`
+    pub fn bot<H: BotHandler>(&self, handler: H) -> TelegramBot<H> {
+        TelegramBot {
+            bot: self.bot.clone(),
+            config: self.config.clone(),
+            subscriptions: self.subscriptions.clone(),
+            handler,
+            pending_trades: PendingTrades::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl SocialMediaClient for TelegramClient {
+    /// Sends `content` to every subscribed chat, and returns the comma separated ids of the
+    /// messages
+    async fn post(&self, content: &str) -> Result<String> {
+        let mut ids = Vec::new();
+        for chat in self.subscriptions.chats() {
+            match self.bot.send_message(chat, truncate(content)).await {
+                Ok(message) => ids.push(message_ref(chat, message.id)),
+                Err(err) => tracing::warn!("Could not send a Telegram alert to {}: {}", chat, err),
+            }
+        }
+        Ok(ids.join(","))
+    }
+
+    async fn reply(&self, parent_id: &str, content: &str) -> Result<String> {
+        let (chat, parent) = parse_message_ref(parent_id)?;
+        let message = self
+            .bot
+            .send_message(chat, truncate(content))
+            .reply_to_message_id(parent)
+            .await?;
+        Ok(message_ref(chat, message.id))
+    }
+
+    /// Deletes the messages of a post, `post_id` being one or more comma separated ids
+    async fn delete(&self, post_id: &str) -> Result<()> {
+        for id in post_id.split(',').filter(|id| !id.is_empty()) {
+            let (chat, message) = parse_message_ref(id)?;
+            self.bot.delete_message(chat, message).await?;
+        }
+        Ok(())
+    }
+}
+
+pub struct TelegramBot<H> {
+    bot: Bot,
+    config: TelegramConfig,
+    subscriptions: Arc<Subscriptions>,
+    handler: H,
+    pending_trades: PendingTrades,
+}
+
+impl<H: BotHandler> TelegramBot<H> {
+    /// Polls the updates of the bot and handles them until the bot is stopped
+    pub async fn run(self) {
+        let this = Arc::new(self);
+        let bot = this.bot.clone();
+
+        let commands = this.clone();
+        let messages = this.clone();
+        let callbacks = this;
+        let handler = dptree::entry()
+            .branch(
+                Update::filter_message()
+                    .filter_command::<Command>()
+                    .endpoint(move |message: Message, command: Command| {
+                        let this = commands.clone();
+                        async move { this.on_command(&message, command).await }
+                    }),
+            )
+            .branch(Update::filter_message().endpoint(move |message: Message, me: Me| {
+                let this = messages.clone();
+                async move { this.on_message(&message, &me).await }
+            }))
+            .branch(Update::filter_callback_query().endpoint(move |query: CallbackQuery| {
+                let this = callbacks.clone();
+                async move { this.on_callback(&query).await }
+            }));
+
+        if let Err(err) = bot.set_my_commands(Command::bot_commands()).await {
+            tracing::error!("Could not register the Telegram commands: {}", err);
+        }
+        Dispatcher::builder(bot, handler).build().dispatch().await;
+    }
+
+    async fn on_command(&self, message: &Message, command: Command) -> Result<()> {
+        let chat = message.chat.id;
+        let reply = match command {
+            Command::Help => Command::descriptions().to_string(),
+            Command::Analyze(symbol) => {
+                let symbol = symbol.trim().to_uppercase();
+                if symbol.is_empty() {
+                    "Usage: /analyze <symbol>".to_string()
+                } else {
+                    let _ = self.bot.send_chat_action(chat, ChatAction::Typing).await;
+                    match self.handler.analyze(&symbol).await {
+                        Ok(analysis) => analysis,
+                        Err(err) => format!("Could not analyze {}: {}", symbol, err),
+                    }
+                }
+            }
+            Command::Trade {
+                symbol,
+                action,
+                amount,
+            } => return self.on_trade_command(message, symbol, action, amount).await,
+            Command::Positions => bot::format_positions(&self.handler.portfolio().await?),
+            Command::Subscribe => match self.subscriptions.subscribe(chat)? {
+                true => "Subscribed to the trade alerts".to_string(),
+                false => "This chat is already subscribed".to_string(),
+            },
+            Command::Unsubscribe => match self.subscriptions.unsubscribe(chat)? {
+                true => "Unsubscribed from the trade alerts".to_string(),
+                false => "This chat is not subscribed".to_string(),
+            },
+        };
+
+        self.bot.send_message(chat, truncate(&reply)).await?;
+        Ok(())
+    }
+
+    /// Asks the user to confirm the trade
+    async fn on_trade_command(
+        &self,
+        message: &Message,
+        symbol: String,
+        action: String,
+        amount: f64,
+    ) -> Result<()> {
+        let chat = message.chat.id;
+        let Some(user) = message.from() else {
+            return Ok(());
+        };
+        if !self.config.traders.contains(&user.id) {
+            self.bot.send_message(chat, "You are not allowed to trade").await?;
+            return Ok(());
+        }
+
+        let action = action.to_uppercase();
+        if !matches!(action.as_str(), "BUY" | "SELL") || amount <= 0.0 {
+            self.bot
+                .send_message(chat, "Usage: /trade <symbol> <buy|sell> <amount>")
+                .await?;
+            return Ok(());
+        }
+
+        let decision = TradeDecision {
+            action,
+            symbol: symbol.to_uppercase(),
+            amount,
+            reason: format!("Requested on Telegram by {}", user.full_name()),
+            confidence: 1.0,
+        };
+
+        let id = format!("{}.{}", chat, message.id);
+        let keyboard = InlineKeyboardMarkup::new(vec![vec![
+            InlineKeyboardButton::callback("Confirm", trade_button(true, &id)),
+            InlineKeyboardButton::callback("Cancel", trade_button(false, &id)),
+        ]]);
+        let text = format!("Confirm this trade?\n\n{}", format_trade(&decision));
+
+        self.pending_trades.insert(&id, user.id.0, decision);
+        self.bot
+            .send_message(chat, text)
+            .reply_markup(keyboard)
+            .await?;
+        Ok(())
+    }
+
+    /// Executes or cancels a trade when the user presses a confirmation button
+    async fn on_callback(&self, query: &CallbackQuery) -> Result<()> {
+        let Some((confirmed, id)) = query.data.as_deref().and_then(parse_trade_button) else {
+            self.bot.answer_callback_query(query.id.clone()).await?;
+            return Ok(());
+        };
+        // The traders may have changed since the trade was requested
+        if !self.config.traders.contains(&query.from.id) {
+            self.bot
+                .answer_callback_query(query.id.clone())
+                .text("You are not allowed to trade")
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
+        self.bot.answer_callback_query(query.id.clone()).await?;
+
+        let text = match self.pending_trades.take(id, query.from.id.0) {
+            None => "This trade expired or is no longer pending".to_string(),
+            Some(_) if !confirmed => "Trade cancelled".to_string(),
+            Some(decision) => bot::execute_trade(&self.handler, &decision).await,
+        };
+
+        // Editing the message without a keyboard removes the buttons
+        match &query.message {
+            Some(message) => {
+                self.bot
+                    .edit_message_text(message.chat.id, message.id, text)
+                    .reply_markup(InlineKeyboardMarkup::default())
+                    .await?;
+            }
+            None => {
+                self.bot.send_message(query.from.id, text).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers the private messages, and the group messages mentioning the bot or replying
+    /// to it
+    async fn on_message(&self, message: &Message, me: &Me) -> Result<()> {
+        let (Some(text), Some(user)) = (message.text(), message.from()) else {
+            return Ok(());
+        };
+        if user.is_bot {
+            return Ok(());
+        }
+        if text.starts_with('/') {
+            // Unknown command, or a command with invalid arguments
+            if is_addressed_to(text, me.username(), message.chat.is_private()) {
+                self.bot
+                    .send_message(message.chat.id, Command::descriptions().to_string())
+                    .await?;
+            }
+            return Ok(());
+        }
+
+        let mention = format!("@{}", me.username());
+        let replies_to_bot = message
+            .reply_to_message()
+            .and_then(|parent| parent.from())
+            .is_some_and(|author| author.id == me.id);
+        if !message.chat.is_private() && !text.contains(&mention) && !replies_to_bot {
+            return Ok(());
+        }
+
+        let text = text.replace(&mention, "");
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let _ = self.bot.send_chat_action(message.chat.id, ChatAction::Typing).await;
+        let author = user.username.clone().unwrap_or_else(|| user.full_name());
+        let answer = self.handler.chat(&author, text).await?;
+        self.bot
+            .send_message(message.chat.id, truncate(&answer))
+            .reply_to_message_id(message.id)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Whether a command is addressed to the bot: commands of private chats always are, commands
+/// of groups must be suffixed with the username of the bot (`/help@bot`), as other bots of the
+/// group may handle them.
+fn is_addressed_to(command: &str, username: &str, private: bool) -> bool {
+    let name = command.split_whitespace().next().unwrap_or_default();
+    match name.split_once('@') {
+        Some((_, recipient)) => recipient.eq_ignore_ascii_case(username),
+        None => private,
+    }
+}
+
+/// Text of a trade to confirm
+fn format_trade(decision: &TradeDecision) -> String {
+    format!(
+        "{} {}\nAmount: {}\nConfidence: {:.0}%\nReason: {}",
+        decision.action,
+        decision.symbol,
+        decision.amount,
+        decision.confidence * 100.0,
+        decision.reason
+    )
+}
+
+fn message_ref(chat: ChatId, message: MessageId) -> String {
+    format!("{}/{}", chat, message.0)
+}
+
+fn parse_message_ref(id: &str) -> Result<(ChatId, MessageId)> {
+    let invalid = || anyhow!("Invalid Telegram message id {}, expected <chat>/<message>", id);
+    let (chat, message) = id.split_once('/').ok_or_else(invalid)?;
+    let chat = chat.parse().map_err(|_| invalid())?;
+    let message = message.parse().map_err(|_| invalid())?;
+    Ok((ChatId(chat), MessageId(message)))
+}
+
+fn truncate(content: &str) -> String {
+    bot::truncate(content, MAX_MESSAGE_CHARS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_secrets() -> Result<()> {
+        let config = TelegramConfig::from_secrets(|name| match name {
+            "TELEGRAM_BOT_TOKEN" => Some("token".to_string()),
+            "TELEGRAM_TRADER_IDS" => Some("12, 34".to_string()),
+            _ => None,
+        })?;
+        assert_eq!(config.token, "token");
+        assert_eq!(config.traders, vec![UserId(12), UserId(34)]);
+
+        assert!(TelegramConfig::from_secrets(|_| None).is_err());
+        assert!(TelegramConfig::from_secrets(|name| match name {
+            "TELEGRAM_BOT_TOKEN" => Some("token".to_string()),
+            "TELEGRAM_TRADER_IDS" => Some("@trader".to_string()),
+            _ => None,
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(
+            Command::parse("/trade sol buy 1.5", "bot").unwrap(),
+            Command::Trade {
+                symbol: "sol".to_string(),
+                action: "buy".to_string(),
+                amount: 1.5,
+            }
+        );
+        assert_eq!(
+            Command::parse("/analyze BONK", "bot").unwrap(),
+            Command::Analyze("BONK".to_string())
+        );
+        assert_eq!(Command::parse("/positions", "bot").unwrap(), Command::Positions);
+        assert!(Command::parse("/trade sol buy", "bot").is_err());
+    }
+
+    #[test]
+    fn test_is_addressed_to() {
+        assert!(is_addressed_to("/price sol", "trading_bot", true));
+        assert!(!is_addressed_to("/price sol", "trading_bot", false));
+        assert!(is_addressed_to("/price@Trading_Bot sol", "trading_bot", false));
+        assert!(!is_addressed_to("/price@other_bot sol", "trading_bot", true));
+    }
+
+    #[test]
+    fn test_subscriptions() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("telegram-{}.json", uuid::Uuid::new_v4()));
+
+        let subscriptions = Subscriptions::load(&path);
+        assert!(subscriptions.subscribe(ChatId(-100))?);
+        assert!(!subscriptions.subscribe(ChatId(-100))?);
+        assert!(subscriptions.subscribe(ChatId(42))?);
+        assert!(subscriptions.unsubscribe(ChatId(42))?);
+        assert!(!subscriptions.unsubscribe(ChatId(42))?);
+
+        // Subscriptions survive restarts
+        assert_eq!(Subscriptions::load(&path).chats(), vec![ChatId(-100)]);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_refs() -> Result<()> {
+        let id = message_ref(ChatId(-100), MessageId(7));
+        assert_eq!(id, "-100/7");
+        assert_eq!(parse_message_ref(&id)?, (ChatId(-100), MessageId(7)));
+        assert!(parse_message_ref("7").is_err());
+        Ok(())
+    }
+}
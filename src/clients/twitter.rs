@@ -1,207 +0,0 @@
-use anyhow::{Result, anyhow};
-use async_trait::async_trait;
-use reqwest::{Client, cookie::Jar};
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use url::Url;
-
-const TWITTER_API_URL: &str = "https://api.twitter.com";
-const TWITTER_LOGIN_URL: &str = "https://twitter.com/i/flow/login";
-
-#[derive(Debug, Clone)]
-pub struct TwitterClient {
-    client: Arc<Client>,
-    session: Arc<RwLock<Option<TwitterSession>>>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct TwitterSession {
-    auth_token: String,
-    csrf_token: String,
-    cookies: Vec<(String, String)>,
-}
-
-#[async_trait]
-pub trait SocialMediaClient: Send + Sync {
-    async fn post(&self, content: &str) -> Result<String>;
-    async fn reply(&self, parent_id: &str, content: &str) -> Result<String>;
-    async fn delete(&self, post_id: &str) -> Result<()>;
-}
-
-impl TwitterClient {
-    pub fn new() -> Self {
-        let cookie_store = Arc::new(Jar::default());
-        let client = Client::builder()
-            .cookie_provider(cookie_store.clone())
-            .build()
-            .unwrap();
-
-        Self {
-            client: Arc::new(client),
-            session: Arc::new(RwLock::new(None)),
-        }
-    }
-
-    pub async fn login(&self, email: &str, username: &str, password: &str) -> Result<()> {
-        // First, get the guest token and initial cookies
-        let guest_token = self.get_guest_token().await?;
-        
-        // Start login flow
-        let flow_token = self.start_login_flow(&guest_token).await?;
-        
-        // Submit username/email
-        let account_flow_token = self.submit_username(&flow_token, username, email).await?;
-        
-        // Submit password
-        let auth_token = self.submit_password(&account_flow_token, password).await?;
-        
-        // Store session
-        let session = TwitterSession {
-            auth_token,
-            csrf_token: self.get_csrf_token().await?,
-            cookies: self.extract_cookies(),
-        };
-
-        *self.session.write().await = Some(session);
-        Ok(())
-    }
-
-    async fn get_guest_token(&self) -> Result<String> {
-        let response = self.client
-            .post(&format!("{}/1.1/guest/activate.json", TWITTER_API_URL))
-            .send()
-            .await?;
-
-        #[derive(Deserialize)]
-        struct GuestToken {
-            guest_token: String,
-        }
-
-        let token: GuestToken = response.json().await?;
-        Ok(token.guest_token)
-    }
-
-    async fn start_login_flow(&self, guest_token: &str) -> Result<String> {
-        let response = self.client
-            .get(TWITTER_LOGIN_URL)
-            .header("x-guest-token", guest_token)
-            .send()
-            .await?;
-
-        // Extract flow_token from response
-        // This is a placeholder - actual implementation would need to parse the HTML/JS
-        Ok("flow_token".to_string())
-    }
-
-    async fn submit_username(&self, flow_token: &str, username: &str, email: &str) -> Result<String> {
-        // Submit username/email to the login flow
-        // This is a placeholder - actual implementation would need to handle the specific endpoints
-        Ok("account_flow_token".to_string())
-    }
-
-    async fn submit_password(&self, flow_token: &str, password: &str) -> Result<String> {
-        // Submit password and get auth token
-        // This is a placeholder - actual implementation would need to handle the specific endpoints
-        Ok("auth_token".to_string())
-    }
-
-    async fn get_csrf_token(&self) -> Result<String> {
-        // Get CSRF token from cookies or make a request to get it
-        Ok("csrf_token".to_string())
-    }
-
-    fn extract_cookies(&self) -> Vec<(String, String)> {
-        // Extract relevant cookies from the cookie store
-        vec![]
-    }
-
-    async fn ensure_authenticated(&self) -> Result<()> {
-        if self.session.read().await.is_none() {
-            return Err(anyhow!("Not authenticated"));
-        }
-        Ok(())
-    }
-}
-
-#[async_trait]
-impl SocialMediaClient for TwitterClient {
-    async fn post(&self, content: &str) -> Result<String> {
-        self.ensure_authenticated().await?;
-        
-        let session = self.session.read().await;
-        let session = session.as_ref().unwrap();
-
-        let response = self.client
-            .post(&format!("{}/2/tweets", TWITTER_API_URL))
-            .header("authorization", &format!("Bearer {}", session.auth_token))
-            .header("x-csrf-token", &session.csrf_token)
-            .json(&serde_json::json!({
-                "text": content
-            }))
-            .send()
-            .await?;
-
-        #[derive(Deserialize)]
-        struct TweetResponse {
-            data: TweetData,
-        }
-
-        #[derive(Deserialize)]
-        struct TweetData {
-            id: String,
-        }
-
-        let tweet: TweetResponse = response.json().await?;
-        Ok(tweet.data.id)
-    }
-
-    async fn reply(&self, parent_id: &str, content: &str) -> Result<String> {
-        self.ensure_authenticated().await?;
-        
-        let session = self.session.read().await;
-        let session = session.as_ref().unwrap();
-
-        let response = self.client
-            .post(&format!("{}/2/tweets", TWITTER_API_URL))
-            .header("authorization", &format!("Bearer {}", session.auth_token))
-            .header("x-csrf-token", &session.csrf_token)
-            .json(&serde_json::json!({
-                "text": content,
-                "reply": {
-                    "in_reply_to_tweet_id": parent_id
-                }
-            }))
-            .send()
-            .await?;
-
-        #[derive(Deserialize)]
-        struct TweetResponse {
-            data: TweetData,
-        }
-
-        #[derive(Deserialize)]
-        struct TweetData {
-            id: String,
-        }
-
-        let tweet: TweetResponse = response.json().await?;
-        Ok(tweet.data.id)
-    }
-
-    async fn delete(&self, post_id: &str) -> Result<()> {
-        self.ensure_authenticated().await?;
-        
-        let session = self.session.read().await;
-        let session = session.as_ref().unwrap();
-
-        self.client
-            .delete(&format!("{}/2/tweets/{}", TWITTER_API_URL, post_id))
-            .header("authorization", &format!("Bearer {}", session.auth_token))
-            .header("x-csrf-token", &session.csrf_token)
-            .send()
-            .await?;
-
-        Ok(())
-    }
-} 
\ No newline at end of file
@@ -0,0 +1,495 @@
+//! Authentication for the X (Twitter) API v2.
+//!
+//! Two flows are supported:
+//! - OAuth 1.0a user context: requests are signed with the consumer key/secret and the access
+//!   token/secret of the account (from the developer portal). Tokens do not expire.
+//! - OAuth 2.0 authorization code with PKCE: the account owner authorizes the app once (see
+//!   [OAuth2App::authorize_url] and [OAuth2App::exchange_code]), then the access token is
+//!   refreshed with the refresh token whenever it expires (requires the `offline.access` scope).
+use anyhow::{anyhow, Result};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rand::{distributions::Alphanumeric, Rng};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::{io::Write, path::Path};
+
+const AUTHORIZE_URL: &str = "https://x.com/i/oauth2/authorize";
+
+/// Scopes needed to post, read mentions and upload media on behalf of a user
+pub const DEFAULT_SCOPES: &[&str] = &[
+    "tweet.read",
+    "tweet.write",
+    "users.read",
+    "media.write",
+    "offline.access",
+];
+
+/// Tokens are refreshed this long before they expire
+const REFRESH_MARGIN_SECS: i64 = 60;
+
+/// Characters left unencoded by OAuth 1.0a (RFC 3986 unreserved characters)
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    utf8_percent_encode(value, UNRESERVED).to_string()
+}
+
+/// Credentials used to authenticate requests to the X API
+#[derive(Debug, Clone)]
+pub enum TwitterAuth {
+    OAuth1(OAuth1Credentials),
+    OAuth2 { app: OAuth2App, token: OAuth2Token },
+}
+
+impl TwitterAuth {
+    /// Reads the credentials from the given secrets (e.g.: environment variables or the secrets
+    /// of a character file). OAuth 1.0a is used when `TWITTER_API_KEY`, `TWITTER_API_SECRET`,
+    /// `TWITTER_ACCESS_TOKEN` and `TWITTER_ACCESS_TOKEN_SECRET` are set, otherwise OAuth 2.0 is
+    /// used with `TWITTER_CLIENT_ID`, `TWITTER_REFRESH_TOKEN` and optionally
+    /// `TWITTER_CLIENT_SECRET`, `TWITTER_REDIRECT_URI` and `TWITTER_OAUTH2_ACCESS_TOKEN`.
+    pub fn from_secrets(secret: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        if let (Some(consumer_key), Some(consumer_secret), Some(access_token), Some(token_secret)) = (
+            secret("TWITTER_API_KEY"),
+            secret("TWITTER_API_SECRET"),
+            secret("TWITTER_ACCESS_TOKEN"),
+            secret("TWITTER_ACCESS_TOKEN_SECRET"),
+        ) {
+            return Ok(Self::OAuth1(OAuth1Credentials::new(
+                &consumer_key,
+                &consumer_secret,
+                &access_token,
+                &token_secret,
+            )));
+        }
+
+        match (secret("TWITTER_CLIENT_ID"), secret("TWITTER_REFRESH_TOKEN")) {
+            (Some(client_id), Some(refresh_token)) => {
+                let redirect_uri = secret("TWITTER_REDIRECT_URI").unwrap_or_default();
+                let mut app = OAuth2App::new(&client_id, &redirect_uri);
+                if let Some(client_secret) = secret("TWITTER_CLIENT_SECRET") {
+                    app = app.client_secret(&client_secret);
+                }
+                // Without an access token, the first request refreshes the token
+                let token = OAuth2Token {
+                    access_token: secret("TWITTER_OAUTH2_ACCESS_TOKEN").unwrap_or_default(),
+                    refresh_token: Some(refresh_token),
+                    expires_at: None,
+                    scope: None,
+                };
+                Ok(Self::OAuth2 { app, token })
+            }
+            _ => Err(anyhow!(
+                "Twitter credentials not set: set TWITTER_API_KEY, TWITTER_API_SECRET, \
+                 TWITTER_ACCESS_TOKEN and TWITTER_ACCESS_TOKEN_SECRET (OAuth 1.0a), or \
+                 TWITTER_CLIENT_ID and TWITTER_REFRESH_TOKEN (OAuth 2.0)"
+            )),
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        Self::from_secrets(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    }
+
+    /// Replaces the OAuth 2.0 token with the token saved at `path`, if any. Refresh tokens are
+    /// single use, so once the token has been refreshed the one of the secrets is stale.
+    pub fn with_saved_token(self, path: &Path) -> Self {
+        match (self, OAuth2Token::load(path)) {
+            (Self::OAuth2 { app, .. }, Some(token)) => Self::OAuth2 { app, token },
+            (auth, _) => auth,
+        }
+    }
+}
+
+// ================================================================
+// OAuth 1.0a
+// ================================================================
+
+#[derive(Debug, Clone)]
+pub struct OAuth1Credentials {
+    consumer_key: String,
+    consumer_secret: String,
+    access_token: String,
+    access_token_secret: String,
+}
+
+impl OAuth1Credentials {
+    pub fn new(
+        consumer_key: &str,
+        consumer_secret: &str,
+        access_token: &str,
+        access_token_secret: &str,
+    ) -> Self {
+        Self {
+            consumer_key: consumer_key.to_string(),
+            consumer_secret: consumer_secret.to_string(),
+            access_token: access_token.to_string(),
+            access_token_secret: access_token_secret.to_string(),
+        }
+    }
+
+    /// Returns the `Authorization` header of a request. `url` must not contain a query string:
+    /// query and form parameters are passed in `params` (JSON and multipart bodies are not
+    /// signed).
+    pub fn authorization(&self, method: &str, url: &str, params: &[(String, String)]) -> String {
+        let nonce: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        self.authorization_with(method, url, params, &nonce, Utc::now().timestamp())
+    }
+
+    fn authorization_with(
+        &self,
+        method: &str,
+        url: &str,
+        params: &[(String, String)],
+        nonce: &str,
+        timestamp: i64,
+    ) -> String {
+        let mut oauth_params = vec![
+            ("oauth_consumer_key", self.consumer_key.clone()),
+            ("oauth_nonce", nonce.to_string()),
+            ("oauth_signature_method", "HMAC-SHA1".to_string()),
+            ("oauth_timestamp", timestamp.to_string()),
+            ("oauth_token", self.access_token.clone()),
+            ("oauth_version", "1.0".to_string()),
+        ];
+
+        let signature = self.signature(method, url, params, &oauth_params);
+        oauth_params.push(("oauth_signature", signature));
+
+        let header = oauth_params
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("OAuth {}", header)
+    }
+
+    fn signature(
+        &self,
+        method: &str,
+        url: &str,
+        params: &[(String, String)],
+        oauth_params: &[(&str, String)],
+    ) -> String {
+        let mut encoded = params
+            .iter()
+            .map(|(key, value)| (percent_encode(key), percent_encode(value)))
+            .chain(
+                oauth_params
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), percent_encode(value))),
+            )
+            .collect::<Vec<_>>();
+        encoded.sort();
+        let parameter_string = encoded
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let base_string = format!(
+            "{}&{}&{}",
+            method.to_uppercase(),
+            percent_encode(url),
+            percent_encode(&parameter_string)
+        );
+        let signing_key = format!(
+            "{}&{}",
+            percent_encode(&self.consumer_secret),
+            percent_encode(&self.access_token_secret)
+        );
+
+        let mut mac = Hmac::<Sha1>::new_from_slice(signing_key.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(base_string.as_bytes());
+        STANDARD.encode(mac.finalize().into_bytes())
+    }
+}
+
+// ================================================================
+// OAuth 2.0 with PKCE
+// ================================================================
+
+/// OAuth 2.0 app registered in the X developer portal. Public clients (native apps) have no
+/// client secret, confidential clients (web apps) authenticate with their client secret.
+#[derive(Debug, Clone)]
+pub struct OAuth2App {
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+}
+
+/// PKCE verifier and state of an authorization request, needed to exchange the code
+#[derive(Debug, Clone)]
+pub struct PkceChallenge {
+    pub verifier: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Token {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub scope: Option<String>,
+}
+
+impl OAuth2Token {
+    /// Whether the token is missing, expired or about to expire
+    pub fn needs_refresh(&self) -> bool {
+        self.access_token.is_empty()
+            || self.expires_at.is_some_and(|expires_at| {
+                expires_at - Duration::seconds(REFRESH_MARGIN_SECS) <= Utc::now()
+            })
+    }
+
+    /// Reads a token saved with [OAuth2Token::save]
+    pub fn load(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+    }
+
+    /// Saves the token to `path`, readable by its owner only. The token is written to a
+    /// temporary file renamed to `path`, so a crash while saving cannot lose the refresh token.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        // A leftover of a previous crash may have other permissions
+        let _ = std::fs::remove_file(&temp_path);
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&temp_path)?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+    scope: Option<String>,
+}
+
+impl OAuth2App {
+    pub fn new(client_id: &str, redirect_uri: &str) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            client_secret: None,
+            redirect_uri: redirect_uri.to_string(),
+        }
+    }
+
+    /// Set the client secret of a confidential client
+    pub fn client_secret(mut self, client_secret: &str) -> Self {
+        self.client_secret = Some(client_secret.to_string());
+        self
+    }
+
+    /// Returns the URL the account owner must visit to authorize the app, along with the PKCE
+    /// challenge to keep until the owner is redirected back with a code.
+    pub fn authorize_url(&self, scopes: &[&str]) -> (String, PkceChallenge) {
+        let challenge = PkceChallenge {
+            verifier: random_string(64),
+            state: random_string(32),
+        };
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(challenge.verifier.as_bytes()));
+
+        let url = format!(
+            "{}?response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&code_challenge={}&code_challenge_method=S256",
+            AUTHORIZE_URL,
+            percent_encode(&self.client_id),
+            percent_encode(&self.redirect_uri),
+            percent_encode(&scopes.join(" ")),
+            challenge.state,
+            code_challenge,
+        );
+        (url, challenge)
+    }
+
+    /// Exchanges the authorization code received on the redirect URI for a token
+    pub async fn exchange_code(
+        &self,
+        http: &Client,
+        api_url: &str,
+        code: &str,
+        challenge: &PkceChallenge,
+    ) -> Result<OAuth2Token> {
+        self.token_request(
+            http,
+            api_url,
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri),
+                ("code_verifier", &challenge.verifier),
+            ],
+        )
+        .await
+    }
+
+    /// Exchanges a refresh token for a new token. X rotates refresh tokens: the refresh token
+    /// of the returned token replaces the one passed in.
+    pub async fn refresh(&self, http: &Client, api_url: &str, refresh_token: &str) -> Result<OAuth2Token> {
+        self.token_request(
+            http,
+            api_url,
+            &[("grant_type", "refresh_token"), ("refresh_token", refresh_token)],
+        )
+        .await
+    }
+
+    async fn token_request(&self, http: &Client, api_url: &str, params: &[(&str, &str)]) -> Result<OAuth2Token> {
+        let mut form = params.to_vec();
+        form.push(("client_id", &self.client_id));
+
+        let mut request = http.post(format!("{}/2/oauth2/token", api_url)).form(&form);
+        if let Some(client_secret) = &self.client_secret {
+            request = request.basic_auth(&self.client_id, Some(client_secret));
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("OAuth 2.0 token request failed ({}): {}", status, body));
+        }
+
+        let token: TokenResponse = response.json().await?;
+        Ok(OAuth2Token {
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+            expires_at: token
+                .expires_in
+                .map(|expires_in| Utc::now() + Duration::seconds(expires_in)),
+            scope: token.scope,
+        })
+    }
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oauth1_signature() {
+        // Example from the X developer documentation ("Creating a signature")
+        let credentials = OAuth1Credentials::new(
+            "xvz1evFS4wEEPTGEFPHBog",
+            "kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw",
+            "370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb",
+            "LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE",
+        );
+        let header = credentials.authorization_with(
+            "POST",
+            "https://api.twitter.com/1.1/statuses/update.json",
+            &[
+                ("include_entities".to_string(), "true".to_string()),
+                (
+                    "status".to_string(),
+                    "Hello Ladies + Gentlemen, a signed OAuth request!".to_string(),
+                ),
+            ],
+            "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg",
+            1318622958,
+        );
+
+        assert!(header.starts_with("OAuth oauth_consumer_key=\"xvz1evFS4wEEPTGEFPHBog\""));
+        assert!(header.contains("oauth_signature=\"hCtSmYh%2BiHYCEqBWrE7C7hYmtUk%3D\""));
+    }
+
+    #[test]
+    fn test_authorize_url() {
+        let app = OAuth2App::new("client-id", "http://127.0.0.1:3000/callback");
+        let (url, challenge) = app.authorize_url(DEFAULT_SCOPES);
+
+        assert!(url.starts_with("https://x.com/i/oauth2/authorize?response_type=code&client_id=client-id"));
+        assert!(url.contains("redirect_uri=http%3A%2F%2F127.0.0.1%3A3000%2Fcallback"));
+        assert!(url.contains("scope=tweet.read%20tweet.write"));
+        assert!(url.contains(&format!("state={}", challenge.state)));
+        assert!(url.ends_with("&code_challenge_method=S256"));
+        assert_eq!(challenge.verifier.len(), 64);
+    }
+
+    #[test]
+    fn test_token_needs_refresh() {
+        let mut token = OAuth2Token {
+            access_token: "token".to_string(),
+            refresh_token: None,
+            expires_at: Some(Utc::now() + Duration::hours(2)),
+            scope: None,
+        };
+        assert!(!token.needs_refresh());
+
+        token.expires_at = Some(Utc::now() + Duration::seconds(30));
+        assert!(token.needs_refresh());
+    }
+
+    #[test]
+    fn test_saved_token_replaces_secrets() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("twitter-{}.json", uuid::Uuid::new_v4()));
+        let secrets = |name: &str| match name {
+            "TWITTER_CLIENT_ID" => Some("client-id".to_string()),
+            "TWITTER_REFRESH_TOKEN" => Some("refresh-1".to_string()),
+            _ => None,
+        };
+        let refresh_token = |auth: TwitterAuth| match auth {
+            TwitterAuth::OAuth2 { token, .. } => token.refresh_token,
+            TwitterAuth::OAuth1(_) => None,
+        };
+
+        let auth = TwitterAuth::from_secrets(secrets)?.with_saved_token(&path);
+        assert_eq!(refresh_token(auth).as_deref(), Some("refresh-1"));
+
+        OAuth2Token {
+            access_token: "access-2".to_string(),
+            refresh_token: Some("refresh-2".to_string()),
+            expires_at: None,
+            scope: None,
+        }
+        .save(&path)?;
+        let auth = TwitterAuth::from_secrets(secrets)?.with_saved_token(&path);
+        assert_eq!(refresh_token(auth).as_deref(), Some("refresh-2"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        }
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,519 @@
+//! Polls the mentions of the authenticated account and replies to them.
+//!
+//! [MentionListener] fetches new mentions on every poll, skips the ones it already handled,
+//! the ones from blocked users and the ones from users who exceeded their rate limit, and passes
+//! the others to a [MentionHandler] along with the tweets they reply to. The id of the last
+//! handled mention is persisted in a cursor file, so that mentions are not answered twice across
+//! restarts. Without a cursor, the first poll only records the newest mention: mentions sent
+//! before the listener first started are never answered.
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::{Tweet, TwitterClient};
+use crate::clients::SocialMediaClient;
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Number of tweets the mention replies to that are passed to the handler
+const DEFAULT_THREAD_DEPTH: usize = 5;
+const DEFAULT_USER_RATE_LIMIT: (usize, Duration) = (5, Duration::from_secs(60 * 60));
+/// Number of polls a mention is retried on when replying fails, before it is skipped
+const MAX_REPLY_ATTEMPTS: usize = 3;
+/// Number of handled mention ids remembered to dedupe mentions
+const SEEN_CAPACITY: usize = 10_000;
+/// Maximum length of a tweet, in characters
+pub const MAX_TWEET_CHARS: usize = 280;
+
+/// Settings of the mention listener of a character
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MentionSettings {
+    /// Time between two polls of the mentions
+    pub poll_interval_secs: u64,
+    /// Number of tweets the mention replies to that are passed to the agent
+    pub max_thread_depth: usize,
+    /// Replies to the same user at most `max_replies_per_user` times every `reply_period_secs`
+    pub max_replies_per_user: usize,
+    pub reply_period_secs: u64,
+}
+
+impl Default for MentionSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: DEFAULT_POLL_INTERVAL.as_secs(),
+            max_thread_depth: DEFAULT_THREAD_DEPTH,
+            max_replies_per_user: DEFAULT_USER_RATE_LIMIT.0,
+            reply_period_secs: DEFAULT_USER_RATE_LIMIT.1.as_secs(),
+        }
+    }
+}
+
+#[async_trait]
+pub trait MentionHandler: Send + Sync {
+    /// Returns the reply to `mention`, or `None` to leave it unanswered. `thread` contains the
+    /// tweets the mention replies to, oldest first.
+    async fn handle_mention(&self, mention: &Tweet, thread: &[Tweet]) -> Result<Option<String>>;
+}
+
+#[async_trait]
+impl<T: MentionHandler> MentionHandler for Arc<T> {
+    async fn handle_mention(&self, mention: &Tweet, thread: &[Tweet]) -> Result<Option<String>> {
+        (**self).handle_mention(mention, thread).await
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cursor {
+    since_id: Option<String>,
+}
+
+pub struct MentionListener<H: MentionHandler> {
+    client: TwitterClient,
+    handler: H,
+    cursor_path: PathBuf,
+    poll_interval: Duration,
+    max_thread_depth: usize,
+    user_rate_limit: (usize, Duration),
+    blocked_users: HashSet<String>,
+    user_id: Option<String>,
+    since_id: Option<String>,
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>,
+    replies: HashMap<String, VecDeque<Instant>>,
+    /// Number of failed replies of the mentions being retried
+    failures: HashMap<String, usize>,
+}
+
+impl<H: MentionHandler> MentionListener<H> {
+    /// Creates a listener persisting its cursor at `cursor_path`. The cursor is loaded from
+    /// the file if it exists.
+    pub fn new(client: TwitterClient, handler: H, cursor_path: impl Into<PathBuf>) -> Self {
+        let cursor_path = cursor_path.into();
+        let cursor: Cursor = std::fs::read_to_string(&cursor_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            client,
+            handler,
+            cursor_path,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_thread_depth: DEFAULT_THREAD_DEPTH,
+            user_rate_limit: DEFAULT_USER_RATE_LIMIT,
+            blocked_users: HashSet::new(),
+            user_id: None,
+            since_id: cursor.since_id,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+            replies: HashMap::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Set the interval between two polls of the mentions
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Set the number of tweets the mention replies to that are passed to the handler
+    pub fn max_thread_depth(mut self, max_thread_depth: usize) -> Self {
+        self.max_thread_depth = max_thread_depth;
+        self
+    }
+
+    /// Reply at most `max_replies` times to the same user per `period`
+    pub fn user_rate_limit(mut self, max_replies: usize, period: Duration) -> Self {
+        self.user_rate_limit = (max_replies, period);
+        self
+    }
+
+    /// Applies the settings of a character
+    pub fn settings(self, settings: &MentionSettings) -> Self {
+        self.poll_interval(Duration::from_secs(settings.poll_interval_secs))
+            .max_thread_depth(settings.max_thread_depth)
+            .user_rate_limit(
+                settings.max_replies_per_user,
+                Duration::from_secs(settings.reply_period_secs),
+            )
+    }
+
+    /// Never reply to the given user (username, with or without `@`, or user id)
+    pub fn block(mut self, user: &str) -> Self {
+        self.blocked_users
+            .insert(user.trim_start_matches('@').to_lowercase());
+        self
+    }
+
+    /// Polls the mentions forever. Errors are logged and the next poll happens as usual.
+    pub async fn run(mut self) {
+        loop {
+            match self.poll().await {
+                Ok(replies) if replies > 0 => tracing::info!("Replied to {} mentions", replies),
+                Ok(_) => {}
+                Err(err) => tracing::error!("Failed to poll mentions: {:#}", err),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Handles the new mentions once and returns the number of replies sent. When replying to a
+    /// mention fails, the mentions from this one on are left for the next poll, and the mention
+    /// is skipped after [MAX_REPLY_ATTEMPTS] failures.
+    pub async fn poll(&mut self) -> Result<usize> {
+        let user_id = match &self.user_id {
+            Some(user_id) => user_id.clone(),
+            None => {
+                let user_id = self.client.me().await?.id;
+                self.user_id = Some(user_id.clone());
+                user_id
+            }
+        };
+
+        let mentions = self
+            .client
+            .mentions(&user_id, self.since_id.as_deref())
+            .await?;
+
+        if self.since_id.is_none() {
+            if let Some(newest) = mentions.last() {
+                tracing::info!("Starting to reply to the mentions newer than {}", newest.id);
+                self.mark_handled(&newest.id)?;
+            }
+            return Ok(0);
+        }
+
+        let mut replies = 0;
+        for mention in mentions {
+            if self.should_reply(&mention, &user_id) {
+                match self.reply(&mention).await {
+                    Ok(true) => replies += 1,
+                    Ok(false) => {}
+                    Err(err) => {
+                        let attempts = self.failures.entry(mention.id.clone()).or_default();
+                        *attempts += 1;
+                        tracing::error!(
+                            "Failed to reply to mention {} (attempt {}/{}): {:#}",
+                            mention.id,
+                            attempts,
+                            MAX_REPLY_ATTEMPTS,
+                            err
+                        );
+                        if *attempts < MAX_REPLY_ATTEMPTS {
+                            break;
+                        }
+                    }
+                }
+            }
+            self.failures.remove(&mention.id);
+            self.mark_handled(&mention.id)?;
+        }
+
+        Ok(replies)
+    }
+
+    fn should_reply(&mut self, mention: &Tweet, user_id: &str) -> bool {
+        if self.seen.contains(&mention.id) || mention.author_id.as_deref() == Some(user_id) {
+            return false;
+        }
+
+        let author_id = mention.author_id.clone().unwrap_or_default();
+        let blocked = self.blocked_users.contains(&author_id.to_lowercase())
+            || mention
+                .author_username
+                .as_ref()
+                .is_some_and(|username| self.blocked_users.contains(&username.to_lowercase()));
+        if blocked {
+            tracing::debug!("Ignoring mention {} from blocked user {}", mention.id, author_id);
+            return false;
+        }
+
+        let (max_replies, period) = self.user_rate_limit;
+        let now = Instant::now();
+        let replies = self.replies.entry(author_id.clone()).or_default();
+        while replies
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= period)
+        {
+            replies.pop_front();
+        }
+        if replies.len() >= max_replies {
+            tracing::info!("Rate limiting mention {} from user {}", mention.id, author_id);
+            return false;
+        }
+
+        true
+    }
+
+    async fn reply(&mut self, mention: &Tweet) -> Result<bool> {
+        let thread = self
+            .client
+            .thread_context(mention, self.max_thread_depth)
+            .await?;
+        let Some(reply) = self.handler.handle_mention(mention, &thread).await? else {
+            return Ok(false);
+        };
+
+        self.client
+            .reply(&mention.id, &truncate_tweet(&reply))
+            .await?;
+        self.replies
+            .entry(mention.author_id.clone().unwrap_or_default())
+            .or_default()
+            .push_back(Instant::now());
+        Ok(true)
+    }
+
+    /// Remembers that the mention was handled and advances the persisted cursor.
+    fn mark_handled(&mut self, id: &str) -> Result<()> {
+        if self.seen.insert(id.to_string()) {
+            self.seen_order.push_back(id.to_string());
+            if self.seen_order.len() > SEEN_CAPACITY {
+                if let Some(oldest) = self.seen_order.pop_front() {
+                    self.seen.remove(&oldest);
+                }
+            }
+        }
+
+        let newer = self
+            .since_id
+            .as_deref()
+            .is_none_or(|since_id| compare_ids(id, since_id) == Ordering::Greater);
+        if newer {
+            self.since_id = Some(id.to_string());
+            if let Some(dir) = self.cursor_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let cursor = Cursor {
+                since_id: self.since_id.clone(),
+            };
+            std::fs::write(&self.cursor_path, serde_json::to_string(&cursor)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares tweet ids, which are increasing integers that may not fit in a `u64` one day
+fn compare_ids(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Truncates `text` to the maximum length of a tweet
+pub fn truncate_tweet(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= MAX_TWEET_CHARS {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(MAX_TWEET_CHARS - 1).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+/// Returns the cashtags of a tweet (e.g.: `$BONK`), uppercase and without the `$`
+pub fn cashtags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in text.split(|c: char| c.is_whitespace() || c == ',' || c == '?' || c == '!') {
+        let Some(tag) = word.strip_prefix('$') else {
+            continue;
+        };
+        let tag = tag
+            .trim_end_matches(|c: char| !c.is_ascii_alphanumeric())
+            .to_uppercase();
+        let valid = !tag.is_empty()
+            && tag.chars().all(|c| c.is_ascii_alphanumeric())
+            && !tag.chars().all(|c| c.is_ascii_digit());
+        if valid && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::twitter::{OAuth2App, OAuth2Token, TwitterAuth};
+    use serde_json::json;
+    use std::sync::Mutex;
+    use wiremock::{
+        matchers::{body_partial_json, method, path, query_param, query_param_is_missing},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    struct Echo {
+        threads: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl MentionHandler for Echo {
+        async fn handle_mention(&self, mention: &Tweet, thread: &[Tweet]) -> Result<Option<String>> {
+            self.threads.lock().unwrap().push(thread.len());
+            Ok(Some(format!("re: {}", mention.text)))
+        }
+    }
+
+    fn mention(id: &str, author: &str, text: &str) -> serde_json::Value {
+        json!({ "id": id, "author_id": author, "text": text })
+    }
+
+    async fn mock_me(server: &MockServer) {
+        Mock::given(path("/2/users/me"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "id": "1", "name": "Vergen", "username": "vergen" }
+            })))
+            .mount(server)
+            .await;
+    }
+
+    fn client(server: &MockServer) -> TwitterClient {
+        TwitterClient::new(TwitterAuth::OAuth2 {
+            app: OAuth2App::new("client-id", ""),
+            token: OAuth2Token {
+                access_token: "access".to_string(),
+                refresh_token: None,
+                expires_at: None,
+                scope: None,
+            },
+        })
+        .api_url(&server.uri())
+    }
+
+    fn cursor_path() -> PathBuf {
+        std::env::temp_dir().join(format!("mentions-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_poll_mentions() -> Result<()> {
+        let server = MockServer::start().await;
+        mock_me(&server).await;
+        Mock::given(path("/2/users/1/mentions"))
+            .and(query_param_is_missing("since_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [mention("9", "2", "gm"), mention("8", "2", "gm")],
+                "meta": { "result_count": 2 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/2/users/1/mentions"))
+            .and(query_param("since_id", "9"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [
+                    mention("13", "spammer", "$SCAM to the moon"),
+                    mention("12", "2", "what about $BONK?"),
+                    mention("11", "2", "what do you think of $BONK"),
+                    mention("10", "1", "my own tweet"),
+                ],
+                "includes": { "users": [{ "id": "spammer", "name": "Spam", "username": "Spammer" }] },
+                "meta": { "result_count": 4 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/2/users/1/mentions"))
+            .and(query_param("since_id", "13"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "meta": { "result_count": 0 } })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .and(body_partial_json(json!({
+                "text": "re: what do you think of $BONK",
+                "reply": { "in_reply_to_tweet_id": "11" }
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "data": { "id": "20" } })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        let cursor_path = cursor_path();
+        let handler = Arc::new(Echo {
+            threads: Mutex::new(vec![]),
+        });
+
+        let mut listener = MentionListener::new(client.clone(), handler.clone(), &cursor_path)
+            .user_rate_limit(1, Duration::from_secs(60))
+            .block("@spammer");
+        // Without a cursor, the mentions sent before the first poll are not answered
+        assert_eq!(listener.poll().await?, 0);
+        // Own tweet and blocked user are skipped, second mention of user 2 is rate limited
+        assert_eq!(listener.poll().await?, 1);
+        assert_eq!(*handler.threads.lock().unwrap(), vec![0]);
+
+        // The cursor is persisted and used by a new listener
+        let mut listener = MentionListener::new(client, handler, &cursor_path);
+        assert_eq!(listener.poll().await?, 0);
+
+        std::fs::remove_file(cursor_path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_reply_is_retried() -> Result<()> {
+        let server = MockServer::start().await;
+        mock_me(&server).await;
+        Mock::given(path("/2/users/1/mentions"))
+            .and(query_param("since_id", "9"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [mention("11", "3", "gm"), mention("10", "2", "wen $BONK")],
+                "meta": { "result_count": 2 }
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .and(body_partial_json(json!({ "reply": { "in_reply_to_tweet_id": "10" } })))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "data": { "id": "20" } })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let cursor_path = cursor_path();
+        std::fs::write(&cursor_path, r#"{"since_id":"9"}"#)?;
+        let handler = Arc::new(Echo {
+            threads: Mutex::new(vec![]),
+        });
+        let mut listener = MentionListener::new(client(&server), handler, &cursor_path);
+        // The failed mention and the ones after it are left for the next poll
+        assert_eq!(listener.poll().await?, 0);
+        assert_eq!(listener.since_id.as_deref(), Some("9"));
+        assert_eq!(listener.poll().await?, 2);
+        assert_eq!(listener.since_id.as_deref(), Some("11"));
+
+        std::fs::remove_file(cursor_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_cashtags() {
+        assert_eq!(
+            cashtags("what do you think of $bonk? and $SOL, $SOL or $5"),
+            vec!["BONK", "SOL"]
+        );
+    }
+
+    #[test]
+    fn test_truncate_tweet() {
+        let text = "a".repeat(300);
+        let truncated = truncate_tweet(&text);
+        assert_eq!(truncated.chars().count(), MAX_TWEET_CHARS);
+        assert!(truncated.ends_with('…'));
+    }
+}
@@ -0,0 +1,845 @@
+//! Client for the X (Twitter) API v2.
+//!
+//! Requests are authenticated with OAuth 1.0a user context or OAuth 2.0 (see [auth]). OAuth 2.0
+//! access tokens are refreshed before they expire, and once more if the API rejects a token.
+//!
+//! # Example
+//! ```rust
+//! let client = TwitterClient::new(TwitterAuth::from_env()?);
+//! let ids = client.thread(&["gm 1/2", "charts look spicy 2/2"]).await?;
+//! client.quote(&ids[0], "bumping this").await?;
+//! ```
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header::AUTHORIZATION,
+    multipart::{Form, Part},
+    Client, Method, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+
+use super::SocialMediaClient;
+
+pub mod auth;
+pub mod mentions;
+
+pub use mentions::{MentionHandler, MentionListener, MentionSettings};
+pub use auth::{OAuth1Credentials, OAuth2App, OAuth2Token, TwitterAuth, DEFAULT_SCOPES};
+
+const API_URL: &str = "https://api.x.com";
+/// Largest media uploaded in a single request, larger media are uploaded in chunks
+const SIMPLE_UPLOAD_LIMIT: usize = 5 * 1024 * 1024;
+const UPLOAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+enum Credentials {
+    OAuth1(OAuth1Credentials),
+    OAuth2 {
+        app: OAuth2App,
+        token: RwLock<OAuth2Token>,
+    },
+}
+
+type TokenCallback = Arc<dyn Fn(&OAuth2Token) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct TwitterClient {
+    http: Client,
+    api_url: String,
+    credentials: Arc<Credentials>,
+    on_token_refresh: Option<TokenCallback>,
+}
+
+/// Tweet to create with [TwitterClient::create_tweet]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NewTweet {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<TweetReply>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote_tweet_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media: Option<TweetMedia>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TweetReply {
+    pub in_reply_to_tweet_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TweetMedia {
+    pub media_ids: Vec<String>,
+}
+
+impl NewTweet {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn reply_to(mut self, tweet_id: &str) -> Self {
+        self.reply = Some(TweetReply {
+            in_reply_to_tweet_id: tweet_id.to_string(),
+        });
+        self
+    }
+
+    pub fn quote(mut self, tweet_id: &str) -> Self {
+        self.quote_tweet_id = Some(tweet_id.to_string());
+        self
+    }
+
+    /// Attach media uploaded with [TwitterClient::upload_media]
+    pub fn media(mut self, media_ids: Vec<String>) -> Self {
+        self.media = Some(TweetMedia { media_ids });
+        self
+    }
+}
+
+/// Tweet returned by the API, with the fields requested by [TwitterClient]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tweet {
+    pub id: String,
+    pub text: String,
+    pub author_id: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub referenced_tweets: Vec<ReferencedTweet>,
+    /// Username of the author, filled from the expanded users of the response
+    #[serde(skip)]
+    pub author_username: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReferencedTweet {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+impl Tweet {
+    /// Id of the tweet this tweet replies to
+    pub fn replied_to(&self) -> Option<&str> {
+        self.referenced_tweets
+            .iter()
+            .find(|tweet| tweet.kind == "replied_to")
+            .map(|tweet| tweet.id.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+}
+
+const TWEET_FIELDS: &str = "tweet.fields=author_id,created_at,referenced_tweets&expansions=author_id&user.fields=username";
+
+#[derive(Deserialize)]
+struct Data<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct TweetsPage {
+    #[serde(default)]
+    data: Vec<Tweet>,
+    includes: Option<Includes>,
+    meta: Option<PageMeta>,
+}
+
+#[derive(Deserialize)]
+struct TweetResponse {
+    data: Tweet,
+    includes: Option<Includes>,
+}
+
+#[derive(Default, Deserialize)]
+struct Includes {
+    #[serde(default)]
+    users: Vec<User>,
+}
+
+impl Includes {
+    fn fill_usernames(&self, tweets: &mut [Tweet]) {
+        for tweet in tweets {
+            tweet.author_username = self
+                .users
+                .iter()
+                .find(|user| Some(&user.id) == tweet.author_id.as_ref())
+                .map(|user| user.username.clone());
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PageMeta {
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CreatedTweet {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct DeletedTweet {
+    deleted: bool,
+}
+
+#[derive(Deserialize)]
+struct UploadedMedia {
+    id: String,
+    processing_info: Option<ProcessingInfo>,
+}
+
+#[derive(Deserialize)]
+struct ProcessingInfo {
+    state: String,
+    check_after_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    title: Option<String>,
+    detail: Option<String>,
+    #[serde(default)]
+    errors: Vec<ApiErrorItem>,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorItem {
+    message: Option<String>,
+}
+
+impl TwitterClient {
+    pub fn new(auth: TwitterAuth) -> Self {
+        let credentials = match auth {
+            TwitterAuth::OAuth1(credentials) => Credentials::OAuth1(credentials),
+            TwitterAuth::OAuth2 { app, token } => Credentials::OAuth2 {
+                app,
+                token: RwLock::new(token),
+            },
+        };
+
+        Self {
+            http: Client::new(),
+            api_url: API_URL.to_string(),
+            credentials: Arc::new(credentials),
+            on_token_refresh: None,
+        }
+    }
+
+    /// Set the base URL of the API, to use a mock server
+    #[cfg(test)]
+    pub fn api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set a callback called with the new token every time the OAuth 2.0 token is refreshed.
+    /// Refresh tokens are single use, so the new token should be persisted.
+    pub fn on_token_refresh(mut self, callback: impl Fn(&OAuth2Token) + Send + Sync + 'static) -> Self {
+        self.on_token_refresh = Some(Arc::new(callback));
+        self
+    }
+
+    pub async fn create_tweet(&self, tweet: &NewTweet) -> Result<String> {
+        let body = serde_json::to_value(tweet)?;
+        let response = self
+            .request(Method::POST, "/2/tweets", |request| request.json(&body))
+            .await?;
+        let tweet: Data<CreatedTweet> = response.json().await?;
+        Ok(tweet.data.id)
+    }
+
+    pub async fn delete_tweet(&self, tweet_id: &str) -> Result<()> {
+        let response = self
+            .request(Method::DELETE, &format!("/2/tweets/{}", tweet_id), |request| request)
+            .await?;
+        let deleted: Data<DeletedTweet> = response.json().await?;
+        if !deleted.data.deleted {
+            return Err(anyhow!("Tweet {} was not deleted", tweet_id));
+        }
+        Ok(())
+    }
+
+    /// Returns the authenticated user
+    pub async fn me(&self) -> Result<User> {
+        let response = self.request(Method::GET, "/2/users/me", |request| request).await?;
+        let user: Data<User> = response.json().await?;
+        Ok(user.data)
+    }
+
+    pub async fn tweet(&self, tweet_id: &str) -> Result<Tweet> {
+        let response = self
+            .request(
+                Method::GET,
+                &format!("/2/tweets/{}?{}", tweet_id, TWEET_FIELDS),
+                |request| request,
+            )
+            .await?;
+        let response: TweetResponse = response.json().await?;
+        let mut tweets = [response.data];
+        response.includes.unwrap_or_default().fill_usernames(&mut tweets);
+        let [tweet] = tweets;
+        Ok(tweet)
+    }
+
+    /// Returns the tweets mentioning the user that are newer than `since_id` (all of the last
+    /// 800 mentions without `since_id`), oldest first.
+    pub async fn mentions(&self, user_id: &str, since_id: Option<&str>) -> Result<Vec<Tweet>> {
+        let mut mentions = Vec::new();
+        let mut next_token: Option<String> = None;
+
+        loop {
+            let mut path = format!("/2/users/{}/mentions?max_results=100&{}", user_id, TWEET_FIELDS);
+            if let Some(since_id) = since_id {
+                path.push_str(&format!("&since_id={}", since_id));
+            }
+            if let Some(token) = &next_token {
+                path.push_str(&format!("&pagination_token={}", token));
+            }
+
+            let response = self.request(Method::GET, &path, |request| request).await?;
+            let mut page: TweetsPage = response.json().await?;
+            page.includes.unwrap_or_default().fill_usernames(&mut page.data);
+            mentions.extend(page.data);
+
+            next_token = page.meta.and_then(|meta| meta.next_token);
+            if next_token.is_none() {
+                break;
+            }
+        }
+
+        // Pages are sorted newest first
+        mentions.reverse();
+        Ok(mentions)
+    }
+
+    /// Returns the tweets of the last 7 days matching `query` (X search syntax, e.g.
+    /// `$SOL -is:retweet`) that are newer than `since_id`, at most `max_results` (10 to 100),
+    /// oldest first.
+    pub async fn search_recent(
+        &self,
+        query: &str,
+        since_id: Option<&str>,
+        max_results: usize,
+    ) -> Result<Vec<Tweet>> {
+        let mut path = format!(
+            "/2/tweets/search/recent?query={}&max_results={}&{}",
+            auth::percent_encode(query),
+            max_results.clamp(10, 100),
+            TWEET_FIELDS
+        );
+        if let Some(since_id) = since_id {
+            path.push_str(&format!("&since_id={}", since_id));
+        }
+
+        let response = self.request(Method::GET, &path, |request| request).await?;
+        let mut page: TweetsPage = response.json().await?;
+        page.includes.unwrap_or_default().fill_usernames(&mut page.data);
+
+        // Results are sorted newest first
+        page.data.reverse();
+        Ok(page.data)
+    }
+
+    /// Returns the tweets `tweet` replies to, up to `max_depth` levels up, oldest first.
+    /// Deleted or protected tweets end the thread.
+    pub async fn thread_context(&self, tweet: &Tweet, max_depth: usize) -> Result<Vec<Tweet>> {
+        let mut thread = Vec::new();
+        let mut parent_id = tweet.replied_to().map(str::to_string);
+
+        while let Some(id) = parent_id.take() {
+            if thread.len() >= max_depth {
+                break;
+            }
+            match self.tweet(&id).await {
+                Ok(parent) => {
+                    parent_id = parent.replied_to().map(str::to_string);
+                    thread.push(parent);
+                }
+                Err(err) => {
+                    tracing::debug!("Could not fetch tweet {} of thread: {}", id, err);
+                    break;
+                }
+            }
+        }
+
+        thread.reverse();
+        Ok(thread)
+    }
+
+    pub async fn quote(&self, tweet_id: &str, text: &str) -> Result<String> {
+        self.create_tweet(&NewTweet::new(text).quote(tweet_id)).await
+    }
+
+    /// Posts a thread, each tweet replying to the previous one, and returns the ids of the
+    /// tweets. If a tweet fails, the tweets already posted are left in place.
+    pub async fn thread(&self, texts: &[&str]) -> Result<Vec<String>> {
+        let mut ids: Vec<String> = Vec::with_capacity(texts.len());
+        for text in texts {
+            let tweet = match ids.last() {
+                Some(parent_id) => NewTweet::new(text).reply_to(parent_id),
+                None => NewTweet::new(text),
+            };
+            match self.create_tweet(&tweet).await {
+                Ok(id) => ids.push(id),
+                Err(err) => {
+                    return Err(err.context(format!(
+                        "Failed to post tweet {} of thread (posted: {:?})",
+                        ids.len() + 1,
+                        ids
+                    )))
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Uploads an image, GIF or video and returns its media id, to attach to a tweet with
+    /// [NewTweet::media]. Media up to 5 MB are uploaded in a single request, larger media are
+    /// uploaded in chunks and waited for until processed.
+    pub async fn upload_media(&self, data: Vec<u8>, mime_type: &str) -> Result<String> {
+        Part::bytes(Vec::new())
+            .mime_str(mime_type)
+            .map_err(|_| anyhow!("Invalid media type {}", mime_type))?;
+        let category = if mime_type == "image/gif" {
+            "tweet_gif"
+        } else if mime_type.starts_with("video/") {
+            "tweet_video"
+        } else {
+            "tweet_image"
+        };
+
+        if data.len() <= SIMPLE_UPLOAD_LIMIT && category == "tweet_image" {
+            let response = self
+                .request(Method::POST, "/2/media/upload", |request| {
+                    request.multipart(
+                        Form::new()
+                            .text("media_category", category)
+                            .part("media", media_part(data.clone(), mime_type)),
+                    )
+                })
+                .await?;
+            let media: Data<UploadedMedia> = response.json().await?;
+            return Ok(media.data.id);
+        }
+
+        let init = json!({
+            "media_type": mime_type,
+            "total_bytes": data.len(),
+            "media_category": category,
+        });
+        let response = self
+            .request(Method::POST, "/2/media/upload/initialize", |request| request.json(&init))
+            .await?;
+        let media: Data<UploadedMedia> = response.json().await?;
+        let media_id = media.data.id;
+
+        for (index, chunk) in data.chunks(UPLOAD_CHUNK_SIZE).enumerate() {
+            self.request(
+                Method::POST,
+                &format!("/2/media/upload/{}/append", media_id),
+                |request| {
+                    request.multipart(
+                        Form::new()
+                            .text("segment_index", index.to_string())
+                            .part("media", media_part(chunk.to_vec(), mime_type)),
+                    )
+                },
+            )
+            .await?;
+        }
+
+        let response = self
+            .request(
+                Method::POST,
+                &format!("/2/media/upload/{}/finalize", media_id),
+                |request| request,
+            )
+            .await?;
+        let mut media: Data<UploadedMedia> = response.json().await?;
+
+        while let Some(processing) = media.data.processing_info {
+            match processing.state.as_str() {
+                "succeeded" => break,
+                "failed" => return Err(anyhow!("Processing of media {} failed", media_id)),
+                _ => {
+                    let wait = processing.check_after_secs.unwrap_or(1);
+                    tokio::time::sleep(Duration::from_secs(wait)).await;
+                    let response = self
+                        .request(
+                            Method::GET,
+                            &format!("/2/media/upload?command=STATUS&media_id={}", media_id),
+                            |request| request,
+                        )
+                        .await?;
+                    media = response.json().await?;
+                }
+            }
+        }
+
+        Ok(media_id)
+    }
+
+    /// Sends an authenticated request. `build` adds the body to the request, and may be called
+    /// twice if the OAuth 2.0 token has to be refreshed.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response> {
+        let url = format!("{}{}", self.api_url, path);
+        let mut refreshed = false;
+
+        loop {
+            let authorization = self.authorization(&method, &url).await?;
+            let response = build(self.http.request(method.clone(), &url))
+                .header(AUTHORIZATION, authorization)
+                .send()
+                .await?;
+
+            if response.status() == StatusCode::UNAUTHORIZED
+                && !refreshed
+                && matches!(*self.credentials, Credentials::OAuth2 { .. })
+            {
+                tracing::warn!("X API rejected the OAuth 2.0 token, refreshing it");
+                self.refresh_token(true).await?;
+                refreshed = true;
+                continue;
+            }
+
+            return check_response(response).await;
+        }
+    }
+
+    async fn authorization(&self, method: &Method, url: &str) -> Result<String> {
+        match &*self.credentials {
+            Credentials::OAuth1(credentials) => {
+                let mut url = Url::parse(url)?;
+                let params = url
+                    .query_pairs()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect::<Vec<_>>();
+                url.set_query(None);
+                Ok(credentials.authorization(method.as_str(), url.as_str(), &params))
+            }
+            Credentials::OAuth2 { token, .. } => {
+                if token.read().await.needs_refresh() {
+                    self.refresh_token(false).await?;
+                }
+                Ok(format!("Bearer {}", token.read().await.access_token))
+            }
+        }
+    }
+
+    /// Refreshes the OAuth 2.0 token if it needs to be refreshed (or unconditionally if `force`
+    /// is set).
+    async fn refresh_token(&self, force: bool) -> Result<()> {
+        let Credentials::OAuth2 { app, token } = &*self.credentials else {
+            return Ok(());
+        };
+
+        let mut token = token.write().await;
+        // Another request may have refreshed the token while waiting for the lock
+        if !force && !token.needs_refresh() {
+            return Ok(());
+        }
+        let refresh_token = token
+            .refresh_token
+            .clone()
+            .ok_or_else(|| anyhow!("OAuth 2.0 token expired and no refresh token is available"))?;
+
+        let mut new_token = app.refresh(&self.http, &self.api_url, &refresh_token).await?;
+        // The refresh token is only rotated when the `offline.access` scope is granted
+        if new_token.refresh_token.is_none() {
+            new_token.refresh_token = Some(refresh_token);
+        }
+        tracing::info!("Refreshed X API OAuth 2.0 token");
+
+        if let Some(callback) = &self.on_token_refresh {
+            callback(&new_token);
+        }
+        *token = new_token;
+        Ok(())
+    }
+}
+
+fn media_part(data: Vec<u8>, mime_type: &str) -> Part {
+    Part::bytes(data)
+        .file_name("media")
+        .mime_str(mime_type)
+        .expect("media type is validated by upload_media")
+}
+
+async fn check_response(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let reset = response
+            .headers()
+            .get("x-rate-limit-reset")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<i64>().ok())
+            .and_then(|reset| DateTime::from_timestamp(reset, 0));
+        return Err(match reset {
+            Some(reset) => anyhow!("Rate limited by the X API until {}", reset),
+            None => anyhow!("Rate limited by the X API"),
+        });
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ApiError>(&body)
+        .ok()
+        .and_then(|error| {
+            error
+                .detail
+                .or(error.title)
+                .or_else(|| error.errors.into_iter().find_map(|item| item.message))
+        })
+        .unwrap_or(body);
+    Err(anyhow!("X API error ({}): {}", status, message))
+}
+
+#[async_trait]
+impl SocialMediaClient for TwitterClient {
+    async fn post(&self, content: &str) -> Result<String> {
+        self.create_tweet(&NewTweet::new(content)).await
+    }
+
+    async fn reply(&self, parent_id: &str, content: &str) -> Result<String> {
+        self.create_tweet(&NewTweet::new(content).reply_to(parent_id))
+            .await
+    }
+
+    async fn delete(&self, post_id: &str) -> Result<()> {
+        self.delete_tweet(post_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use wiremock::{
+        matchers::{
+            body_partial_json, body_string_contains, header, header_regex, method, path,
+            query_param,
+        },
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn oauth2_client(server: &MockServer, access_token: &str) -> TwitterClient {
+        TwitterClient::new(TwitterAuth::OAuth2 {
+            app: OAuth2App::new("client-id", "http://127.0.0.1/callback"),
+            token: OAuth2Token {
+                access_token: access_token.to_string(),
+                refresh_token: Some("refresh-1".to_string()),
+                expires_at: None,
+                scope: None,
+            },
+        })
+        .api_url(&server.uri())
+    }
+
+    fn created(id: &str) -> ResponseTemplate {
+        ResponseTemplate::new(201).set_body_json(json!({ "data": { "id": id, "text": "" } }))
+    }
+
+    #[tokio::test]
+    async fn test_post_and_delete() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .and(header("authorization", "Bearer access-1"))
+            .and(body_partial_json(json!({ "text": "gm" })))
+            .respond_with(created("100"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/2/tweets/100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": { "deleted": true } })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = oauth2_client(&server, "access-1");
+        let id = client.post("gm").await?;
+        assert_eq!(id, "100");
+        client.delete(&id).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oauth1_signed_request() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .and(header_regex("authorization", r#"^OAuth oauth_consumer_key="key".*oauth_signature=""#))
+            .and(body_partial_json(json!({ "text": "quoting", "quote_tweet_id": "42" })))
+            .respond_with(created("101"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = TwitterClient::new(TwitterAuth::OAuth1(OAuth1Credentials::new(
+            "key", "secret", "token", "token-secret",
+        )))
+        .api_url(&server.uri());
+        assert_eq!(client.quote("42", "quoting").await?, "101");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_thread_replies_to_previous_tweet() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(body_partial_json(json!({ "text": "1/3" })))
+            .respond_with(created("1"))
+            .mount(&server)
+            .await;
+        Mock::given(body_partial_json(json!({ "text": "2/3", "reply": { "in_reply_to_tweet_id": "1" } })))
+            .respond_with(created("2"))
+            .mount(&server)
+            .await;
+        Mock::given(body_partial_json(json!({ "text": "3/3", "reply": { "in_reply_to_tweet_id": "2" } })))
+            .respond_with(created("3"))
+            .mount(&server)
+            .await;
+
+        let client = oauth2_client(&server, "access-1");
+        let ids = client.thread(&["1/3", "2/3", "3/3"]).await?;
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_refresh() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/2/oauth2/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=refresh-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "token_type": "bearer",
+                "access_token": "access-2",
+                "refresh_token": "refresh-2",
+                "expires_in": 7200,
+                "scope": "tweet.write offline.access"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/2/tweets"))
+            .and(header("authorization", "Bearer access-2"))
+            .respond_with(created("102"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let refreshed = Arc::new(Mutex::new(None));
+        let store = refreshed.clone();
+        // No access token: the first request refreshes the token
+        let client = oauth2_client(&server, "").on_token_refresh(move |token| {
+            *store.lock().unwrap() = token.refresh_token.clone();
+        });
+
+        assert_eq!(client.reply("99", "wagmi").await?, "102");
+        assert_eq!(refreshed.lock().unwrap().as_deref(), Some("refresh-2"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_media() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/2/media/upload"))
+            .and(body_string_contains("tweet_image"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "id": "555", "media_key": "3_555" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/2/tweets"))
+            .and(body_partial_json(json!({ "media": { "media_ids": ["555"] } })))
+            .respond_with(created("103"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = oauth2_client(&server, "access-1");
+        let media_id = client.upload_media(b"PNG".to_vec(), "image/png").await?;
+        let id = client
+            .create_tweet(&NewTweet::new("chart").media(vec![media_id]))
+            .await?;
+        assert_eq!(id, "103");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_recent() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/2/tweets/search/recent"))
+            .and(query_param("query", "$SOL -is:retweet"))
+            .and(query_param("since_id", "7"))
+            .and(query_param("max_results", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [
+                    { "id": "9", "text": "$SOL ripping", "author_id": "1" },
+                    { "id": "8", "text": "$SOL dead", "author_id": "2" }
+                ],
+                "includes": { "users": [{ "id": "1", "name": "A", "username": "alice" }] },
+                "meta": { "result_count": 2 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = oauth2_client(&server, "access-1");
+        let tweets = client.search_recent("$SOL -is:retweet", Some("7"), 500).await?;
+        assert_eq!(tweets.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), vec!["8", "9"]);
+        assert_eq!(tweets[1].author_username.as_deref(), Some("alice"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_errors() {
+        let server = MockServer::start().await;
+        Mock::given(path("/2/tweets"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "title": "Forbidden",
+                "detail": "You are not allowed to create a Tweet with duplicate content.",
+                "type": "about:blank",
+                "status": 403
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(path("/2/tweets/1"))
+            .respond_with(ResponseTemplate::new(429).insert_header("x-rate-limit-reset", "1700000000"))
+            .mount(&server)
+            .await;
+
+        let client = oauth2_client(&server, "access-1");
+        let err = client.post("gm").await.unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "X API error (403 Forbidden): You are not allowed to create a Tweet with duplicate content."
+        );
+        let err = client.delete("1").await.unwrap_err().to_string();
+        assert_eq!(err, "Rate limited by the X API until 2023-11-14 22:13:20 UTC");
+    }
+}
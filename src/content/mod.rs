@@ -0,0 +1,588 @@
+//! Scheduled market commentary.
+//!
+//! Besides the trade alerts, characters post on a content calendar: a daily SOL recap, the
+//! top movers of the day and a weekly PnL. Each [ContentJob] runs on a cron [Schedule] and
+//! builds its post from Birdeye data, written by the agent in the voice of the character and
+//! tied to one of its `topics`.
+//!
+//! The [ContentScheduler] persists the last run of every job and the recent posts, so that
+//! restarts neither skip nor repeat jobs (a job missed by less than an hour runs on start) and
+//! new posts can be compared to the previous ones. Posts too close to a recent one are
+//! rewritten, then dropped.
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use rig_birdeye::{TokenListItem, TokenOverview};
+use serde::{Deserialize, Serialize};
+
+mod schedule;
+
+pub use schedule::Schedule;
+
+/// Jobs missed by more than this while the agent was down are skipped
+const MISSED_RUN_GRACE: Duration = Duration::hours(1);
+/// Posts remembered to avoid repeating them
+const HISTORY_CAPACITY: usize = 50;
+/// Recent posts of the same kind shown to the agent
+const RECENT_POSTS_IN_PROMPT: usize = 5;
+/// Word overlap above which a post repeats a previous one
+const REPETITION_THRESHOLD: f64 = 0.6;
+/// Attempts at writing a post that does not repeat a previous one
+const MAX_ATTEMPTS: usize = 3;
+const TOP_MOVERS: usize = 5;
+/// Minimum liquidity of the top movers, in USD
+const TOP_MOVERS_MIN_LIQUIDITY: f64 = 100_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentKind {
+    /// Daily recap of the SOL market
+    SolRecap,
+    /// Largest 24h price changes among the liquid tokens
+    TopMovers,
+    /// Profit and loss of the open positions since the last weekly PnL
+    WeeklyPnl,
+}
+
+impl ContentKind {
+    fn task(&self) -> &'static str {
+        match self {
+            Self::SolRecap => "Write your daily recap of the SOL market for your followers.",
+            Self::TopMovers => "Write a tweet about today's top movers on Solana.",
+            Self::WeeklyPnl => {
+                "Write a tweet sharing the profit and loss of your positions this week. Be \
+                 honest about losses."
+            }
+        }
+    }
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SolRecap => "solRecap",
+            Self::TopMovers => "topMovers",
+            Self::WeeklyPnl => "weeklyPnl",
+        })
+    }
+}
+
+/// Entry of the content calendar, e.g. `{"kind": "solRecap", "schedule": "0 14 * * *"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentJob {
+    pub kind: ContentKind,
+    pub schedule: Schedule,
+}
+
+impl ContentJob {
+    pub fn new(kind: ContentKind, schedule: &str) -> Result<Self> {
+        Ok(Self {
+            kind,
+            schedule: Schedule::parse(schedule)?,
+        })
+    }
+
+    /// Key of the job in the state, the same kind can be scheduled several times
+    fn key(&self) -> String {
+        format!("{} {}", self.kind, self.schedule)
+    }
+}
+
+/// Calendar of the characters that do not configure one: the SOL recap at 14:00 UTC, the top
+/// movers at 18:00 UTC and the weekly PnL on Sundays at 20:00 UTC
+pub fn default_calendar() -> Vec<ContentJob> {
+    [
+        (ContentKind::SolRecap, "0 14 * * *"),
+        (ContentKind::TopMovers, "0 18 * * *"),
+        (ContentKind::WeeklyPnl, "0 20 * * 0"),
+    ]
+    .into_iter()
+    .map(|(kind, schedule)| ContentJob::new(kind, schedule).expect("valid default schedule"))
+    .collect()
+}
+
+#[async_trait]
+pub trait ContentHandler: Send + Sync {
+    /// Market data of a token, by symbol
+    async fn token(&self, symbol: &str) -> Result<TokenOverview>;
+    async fn top_movers(&self, limit: usize, min_liquidity: f64) -> Result<Vec<TokenListItem>>;
+    /// Open positions, by symbol
+    fn positions(&self) -> Vec<(String, f64)>;
+    /// Topics of interest of the character
+    async fn topics(&self) -> Vec<String>;
+    /// Writes the post described by `task`, in the voice of the character and following its
+    /// post rules
+    async fn write_post(&self, task: &str) -> Result<String>;
+    /// Publishes the post, or queues it for review
+    async fn publish(&self, kind: ContentKind, post: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl<T: ContentHandler> ContentHandler for Arc<T> {
+    async fn token(&self, symbol: &str) -> Result<TokenOverview> {
+        (**self).token(symbol).await
+    }
+
+    async fn top_movers(&self, limit: usize, min_liquidity: f64) -> Result<Vec<TokenListItem>> {
+        (**self).top_movers(limit, min_liquidity).await
+    }
+
+    fn positions(&self) -> Vec<(String, f64)> {
+        (**self).positions()
+    }
+
+    async fn topics(&self) -> Vec<String> {
+        (**self).topics().await
+    }
+
+    async fn write_post(&self, task: &str) -> Result<String> {
+        (**self).write_post(task).await
+    }
+
+    async fn publish(&self, kind: ContentKind, post: &str) -> Result<()> {
+        (**self).publish(kind, post).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostedContent {
+    kind: ContentKind,
+    text: String,
+    topic: Option<String>,
+    posted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ContentState {
+    /// Last scheduled run of every job, by job key
+    last_runs: HashMap<String, DateTime<Utc>>,
+    /// Recent posts, oldest first
+    history: VecDeque<PostedContent>,
+    /// Prices of the positions at the last weekly PnL, by symbol
+    pnl_prices: HashMap<String, f64>,
+}
+
+pub struct ContentScheduler<H: ContentHandler> {
+    handler: H,
+    jobs: Vec<ContentJob>,
+    state_path: PathBuf,
+    state: ContentState,
+}
+
+impl<H: ContentHandler> ContentScheduler<H> {
+    /// Creates a scheduler persisting its state at `state_path`. The state is loaded from the
+    /// file if it exists.
+    pub fn new(handler: H, jobs: Vec<ContentJob>, state_path: impl Into<PathBuf>) -> Self {
+        let state_path = state_path.into();
+        let state = std::fs::read_to_string(&state_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            handler,
+            jobs,
+            state_path,
+            state,
+        }
+    }
+
+    /// Runs the jobs forever. Failed jobs are logged and run again at their next occurrence.
+    pub async fn run(mut self) {
+        loop {
+            let now = Utc::now();
+            let Some((index, at)) = self
+                .jobs
+                .iter()
+                .enumerate()
+                .filter_map(|(index, job)| Some((index, self.next_run(job, now)?)))
+                .min_by_key(|(_, at)| *at)
+            else {
+                tracing::warn!("No content job will ever run, stopping the scheduler");
+                return;
+            };
+
+            tokio::time::sleep((at - now).to_std().unwrap_or_default()).await;
+
+            let job = self.jobs[index].clone();
+            match self.run_job(job.kind).await {
+                Ok(Some(post)) => tracing::info!("Posted {}: {}", job.kind, post),
+                Ok(None) => tracing::info!("Nothing to post for {}", job.kind),
+                Err(err) => tracing::error!("Failed to post {}: {:#}", job.kind, err),
+            }
+            self.state.last_runs.insert(job.key(), at);
+            if let Err(err) = self.save() {
+                tracing::error!("Failed to save the content calendar state: {:#}", err);
+            }
+        }
+    }
+
+    /// Next run of the job: the occurrence following its last run if it was missed by less
+    /// than [MISSED_RUN_GRACE], the next occurrence otherwise
+    fn next_run(&self, job: &ContentJob, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let missed = self
+            .state
+            .last_runs
+            .get(&job.key())
+            .and_then(|last_run| job.schedule.next_after(*last_run))
+            .filter(|missed| *missed >= now - MISSED_RUN_GRACE);
+        missed.or_else(|| job.schedule.next_after(now))
+    }
+
+    /// Writes and publishes the post of the job. Returns `None` when there is nothing to post.
+    pub async fn run_job(&mut self, kind: ContentKind) -> Result<Option<String>> {
+        let Some(data) = self.market_data(kind).await? else {
+            return Ok(None);
+        };
+        let topic = self.next_topic().await;
+
+        let mut task = format!("{}\n\nMarket data from Birdeye:\n{}", kind.task(), data);
+        if let Some(topic) = &topic {
+            task.push_str(&format!("\nIf it fits, relate it to {}.\n", topic));
+        }
+        let recent: Vec<_> = self
+            .state
+            .history
+            .iter()
+            .rev()
+            .filter(|posted| posted.kind == kind)
+            .take(RECENT_POSTS_IN_PROMPT)
+            .collect();
+        if !recent.is_empty() {
+            task.push_str("\nYour recent posts, do not repeat them:\n");
+            for posted in recent {
+                task.push_str(&format!("- {}\n", posted.text));
+            }
+        }
+
+        let mut feedback = None;
+        for _ in 0..MAX_ATTEMPTS {
+            let prompt = match &feedback {
+                Some(previous) => format!(
+                    "{}\nYour previous attempt was too close to your post {:?}. Write something \
+                     different.\n",
+                    task, previous
+                ),
+                None => task.clone(),
+            };
+            let post = self.handler.write_post(&prompt).await?;
+
+            if let Some(previous) = self.repeated_post(&post) {
+                tracing::debug!("Rejected post {:?} repeating {:?}", post, previous);
+                feedback = Some(previous);
+                continue;
+            }
+
+            self.handler.publish(kind, &post).await?;
+            if kind == ContentKind::WeeklyPnl {
+                self.state.pnl_prices = self.prices().await;
+            }
+            self.state.history.push_back(PostedContent {
+                kind,
+                text: post.clone(),
+                topic,
+                posted_at: Utc::now(),
+            });
+            while self.state.history.len() > HISTORY_CAPACITY {
+                self.state.history.pop_front();
+            }
+            self.save()?;
+            return Ok(Some(post));
+        }
+        Err(anyhow!("Could not write a {} post that does not repeat a previous one", kind))
+    }
+
+    /// Birdeye data of the post, or `None` if there is nothing to post about
+    async fn market_data(&self, kind: ContentKind) -> Result<Option<String>> {
+        match kind {
+            ContentKind::SolRecap => {
+                let sol = self.handler.token("SOL").await?;
+                Ok(Some(format!(
+                    "SOL: price ${:.2}, 24h change {:+.2}%, 24h volume ${:.0}, liquidity ${:.0}, \
+                     24h trades {}\n",
+                    sol.price, sol.price_change_24h, sol.volume_24h, sol.liquidity, sol.trades_24h
+                )))
+            }
+            ContentKind::TopMovers => {
+                let movers = self
+                    .handler
+                    .top_movers(TOP_MOVERS, TOP_MOVERS_MIN_LIQUIDITY)
+                    .await?;
+                if movers.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(
+                    movers
+                        .iter()
+                        .map(|token| {
+                            format!(
+                                "${}: price ${:.6}, 24h change {:+.2}%, 24h volume ${:.0}\n",
+                                token.symbol, token.price, token.price_change_24h, token.volume_24h
+                            )
+                        })
+                        .collect(),
+                ))
+            }
+            ContentKind::WeeklyPnl => {
+                let positions = self.handler.positions();
+                if positions.is_empty() {
+                    return Ok(None);
+                }
+
+                let prices = self.prices().await;
+                let mut data = String::new();
+                let mut total = 0.0;
+                for (symbol, amount) in positions {
+                    let Some(price) = prices.get(&symbol) else {
+                        data.push_str(&format!("{} {}: no price\n", amount, symbol));
+                        continue;
+                    };
+                    match self.state.pnl_prices.get(&symbol) {
+                        Some(previous) => {
+                            let pnl = amount * (price - previous);
+                            total += pnl;
+                            data.push_str(&format!(
+                                "{} {}: ${:.2} -> ${:.2}, PnL {:+.2} USD\n",
+                                amount, symbol, previous, price, pnl
+                            ));
+                        }
+                        None => data.push_str(&format!(
+                            "{} {}: ${:.2}, opened this week\n",
+                            amount, symbol, price
+                        )),
+                    }
+                }
+                data.push_str(&format!("Total PnL: {:+.2} USD\n", total));
+                Ok(Some(data))
+            }
+        }
+    }
+
+    /// Current prices of the open positions. Tokens without market data are skipped.
+    async fn prices(&self) -> HashMap<String, f64> {
+        let mut prices = HashMap::new();
+        for (symbol, _) in self.handler.positions() {
+            match self.handler.token(&symbol).await {
+                Ok(info) => {
+                    prices.insert(symbol, info.price);
+                }
+                Err(err) => tracing::debug!("No price for {}: {}", symbol, err),
+            }
+        }
+        prices
+    }
+
+    /// Topic of the character that was used the least recently
+    async fn next_topic(&self) -> Option<String> {
+        let topics = self.handler.topics().await;
+        topics
+            .into_iter()
+            .min_by_key(|topic| {
+                self.state
+                    .history
+                    .iter()
+                    .rposition(|posted| posted.topic.as_ref() == Some(topic))
+                    .map_or(0, |position| position + 1)
+            })
+    }
+
+    /// Recent post the new post is too close to, if any
+    fn repeated_post(&self, post: &str) -> Option<String> {
+        let words = word_set(post);
+        self.state
+            .history
+            .iter()
+            .find(|posted| similarity(&words, &word_set(&posted.text)) >= REPETITION_THRESHOLD)
+            .map(|posted| posted.text.clone())
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.state_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.state_path, serde_json::to_string(&self.state)?)?;
+        Ok(())
+    }
+}
+
+fn word_set(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '$' && c != '#')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Jaccard index of the two sets of words
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeHandler {
+        replies: Mutex<Vec<&'static str>>,
+        prompts: Mutex<Vec<String>>,
+        published: Mutex<Vec<String>>,
+        positions: Vec<(String, f64)>,
+        sol_price: Mutex<f64>,
+    }
+
+    #[async_trait]
+    impl ContentHandler for FakeHandler {
+        async fn token(&self, symbol: &str) -> Result<TokenOverview> {
+            if symbol != "SOL" {
+                return Err(anyhow!("Unknown token symbol: {}", symbol));
+            }
+            Ok(TokenOverview {
+                price: *self.sol_price.lock().unwrap(),
+                volume_24h: 1_500_000_000.0,
+                price_change_24h: 4.2,
+                liquidity: 30_000_000.0,
+                trades_24h: 1_000_000,
+                ..Default::default()
+            })
+        }
+
+        async fn top_movers(
+            &self,
+            _limit: usize,
+            _min_liquidity: f64,
+        ) -> Result<Vec<TokenListItem>> {
+            Ok(vec![])
+        }
+
+        fn positions(&self) -> Vec<(String, f64)> {
+            self.positions.clone()
+        }
+
+        async fn topics(&self) -> Vec<String> {
+            vec!["memecoins".to_string(), "DeFi".to_string()]
+        }
+
+        async fn write_post(&self, task: &str) -> Result<String> {
+            self.prompts.lock().unwrap().push(task.to_string());
+            Ok(self.replies.lock().unwrap().remove(0).to_string())
+        }
+
+        async fn publish(&self, _kind: ContentKind, post: &str) -> Result<()> {
+            self.published.lock().unwrap().push(post.to_string());
+            Ok(())
+        }
+    }
+
+    fn state_path() -> PathBuf {
+        std::env::temp_dir().join(format!("calendar-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_run_job() -> Result<()> {
+        let handler = Arc::new(FakeHandler {
+            replies: Mutex::new(vec![
+                "SOL up 4.2% today, volume strong",
+                "sol up 4.2% today! volume strong",
+                "Quiet top for SOL at $150, memecoins looking thirsty",
+            ]),
+            sol_price: Mutex::new(150.0),
+            ..Default::default()
+        });
+        let path = state_path();
+        let mut scheduler = ContentScheduler::new(handler.clone(), default_calendar(), &path);
+
+        let post = scheduler.run_job(ContentKind::SolRecap).await?;
+        assert_eq!(post.as_deref(), Some("SOL up 4.2% today, volume strong"));
+        let prompt = handler.prompts.lock().unwrap()[0].clone();
+        assert!(prompt.contains("SOL: price $150.00, 24h change +4.20%"));
+        assert!(prompt.contains("relate it to memecoins"));
+
+        // Repeated posts are rewritten, with the least recently used topic
+        let post = scheduler.run_job(ContentKind::SolRecap).await?;
+        assert_eq!(post.as_deref(), Some("Quiet top for SOL at $150, memecoins looking thirsty"));
+        let prompts = handler.prompts.lock().unwrap().clone();
+        assert!(prompts[1].contains("relate it to DeFi"));
+        assert!(prompts[1].contains("do not repeat them:\n- SOL up 4.2% today, volume strong"));
+        assert!(prompts[2].contains("too close to your post \"SOL up 4.2% today, volume strong\""));
+        assert_eq!(handler.published.lock().unwrap().len(), 2);
+
+        // The history survives restarts
+        let scheduler = ContentScheduler::new(handler.clone(), default_calendar(), &path);
+        assert_eq!(scheduler.state.history.len(), 2);
+        assert!(scheduler.repeated_post("sol UP 4.2% today, volume strong").is_some());
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_weekly_pnl() -> Result<()> {
+        let handler = Arc::new(FakeHandler {
+            replies: Mutex::new(vec!["first week", "second week, SOL paid"]),
+            positions: vec![("BONK".to_string(), 1000.0), ("SOL".to_string(), 10.0)],
+            sol_price: Mutex::new(150.0),
+            ..Default::default()
+        });
+        let path = state_path();
+        let mut scheduler = ContentScheduler::new(handler.clone(), vec![], &path);
+
+        scheduler.run_job(ContentKind::WeeklyPnl).await?;
+        *handler.sol_price.lock().unwrap() = 160.0;
+        scheduler.run_job(ContentKind::WeeklyPnl).await?;
+
+        let prompts = handler.prompts.lock().unwrap().clone();
+        assert!(prompts[0].contains("10 SOL: $150.00, opened this week"));
+        assert!(prompts[1].contains("1000 BONK: no price\n10 SOL: $150.00 -> $160.00, PnL +100.00 USD"));
+        assert!(prompts[1].contains("Total PnL: +100.00 USD"));
+
+        // Nothing to post without data
+        assert_eq!(scheduler.run_job(ContentKind::TopMovers).await?, None);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_run() {
+        let mut scheduler =
+            ContentScheduler::new(FakeHandler::default(), default_calendar(), state_path());
+        let job = &default_calendar()[0];
+        let time = |value: &str| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc);
+        let now = time("2024-06-15T14:30:00Z");
+
+        assert_eq!(scheduler.next_run(job, now), Some(time("2024-06-16T14:00:00Z")));
+
+        // Missed by 30 minutes: runs now
+        scheduler
+            .state
+            .last_runs
+            .insert(job.key(), time("2024-06-14T14:00:00Z"));
+        assert_eq!(scheduler.next_run(job, now), Some(time("2024-06-15T14:00:00Z")));
+
+        // Missed for too long: skipped
+        let later = time("2024-06-15T16:00:00Z");
+        assert_eq!(scheduler.next_run(job, later), Some(time("2024-06-16T14:00:00Z")));
+    }
+
+    #[test]
+    fn test_calendar_json() {
+        let jobs: Vec<ContentJob> =
+            serde_json::from_str(r#"[{"kind": "topMovers", "schedule": "30 9 * * 1-5"}]"#).unwrap();
+        assert_eq!(jobs, vec![ContentJob::new(ContentKind::TopMovers, "30 9 * * 1-5").unwrap()]);
+        assert!(serde_json::from_str::<ContentJob>(r#"{"kind": "solRecap", "schedule": "daily"}"#).is_err());
+    }
+}
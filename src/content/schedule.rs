@@ -0,0 +1,208 @@
+//! Cron expressions of the content calendar.
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Occurrences are searched this far ahead, which covers every valid expression (e.g.
+/// February 29th on a Monday)
+const MAX_SEARCH_DAYS: i64 = 366 * 8;
+
+/// When a job runs, as a cron expression in UTC: `"minute hour day-of-month month
+/// day-of-week"`. Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps
+/// (`*/15`). Sunday is 0 or 7. `@hourly`, `@daily` and `@weekly` are shorthands.
+///
+/// As with cron, a day matches if it matches either the day of month or the day of week
+/// when both are restricted. A field starting with `*` (e.g. `*/2`) is not restricted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(anyhow!(
+                "Invalid schedule {:?}, expected 5 fields: minute hour day-of-month month day-of-week",
+                expression
+            ));
+        };
+
+        let field = |value: &str, name: &str, min: u32, max: u32| {
+            parse_field(value, min, max)
+                .map_err(|err| anyhow!("Invalid {} in schedule {:?}: {}", name, expression, err))
+        };
+        let mut weekdays = field(weekday, "day of week", 0, 7)?;
+        // Sunday is both 0 and 7
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: field(minute, "minute", 0, 59)?,
+            hours: field(hour, "hour", 0, 23)?,
+            days: field(day, "day of month", 1, 31)?,
+            months: field(month, "month", 1, 12)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// First occurrence strictly after `time`
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = time.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        let limit = start + Duration::days(MAX_SEARCH_DAYS);
+
+        let mut next = start;
+        while next < limit {
+            if !matches(self.months, next.month()) || !self.matches_day(next) {
+                next = next.date_naive().succ_opt()?.and_time(NaiveTime::MIN).and_utc();
+            } else if !matches(self.hours, next.hour()) {
+                next = next.duration_trunc(Duration::hours(1)).ok()? + Duration::hours(1);
+            } else if !matches(self.minutes, next.minute()) {
+                next += Duration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, time: DateTime<Utc>) -> bool {
+        let day = matches(self.days, time.day());
+        let weekday = matches(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn matches(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parses a field into the set of its values, as a bit set
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| anyhow!("invalid step {:?}", step))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow!("the step must be greater than 0"));
+        }
+
+        let number = |value: &str| -> Result<u32> {
+            let number = value.parse().map_err(|_| anyhow!("invalid value {:?}", value))?;
+            if number < min || number > max {
+                return Err(anyhow!("{} is not between {} and {}", number, min, max));
+            }
+            Ok(number)
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `5/10` starts at 5 and runs to the end of the range
+                None if part.contains('/') => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start > end {
+            return Err(anyhow!("the range {:?} is empty", range));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expression: &str, after: &str) -> String {
+        Schedule::parse(expression)
+            .unwrap()
+            .next_after(time(after))
+            .unwrap()
+            .to_rfc3339()
+    }
+
+    #[test]
+    fn test_next_after() {
+        // Saturday 2024-06-15
+        let now = "2024-06-15T14:30:10Z";
+        assert_eq!(next("0 14 * * *", now), "2024-06-16T14:00:00+00:00");
+        assert_eq!(next("45 14 * * *", now), "2024-06-15T14:45:00+00:00");
+        assert_eq!(next("*/20 * * * *", now), "2024-06-15T14:40:00+00:00");
+        assert_eq!(next("0 20 * * 0", now), "2024-06-16T20:00:00+00:00");
+        assert_eq!(next("0 20 * * 7", now), "2024-06-16T20:00:00+00:00");
+        assert_eq!(next("0 9 * * 1-5", now), "2024-06-17T09:00:00+00:00");
+        assert_eq!(next("0 0 1 1,7 *", now), "2024-07-01T00:00:00+00:00");
+        assert_eq!(next("@hourly", now), "2024-06-15T15:00:00+00:00");
+        // Either the day of month or the day of week when both are restricted
+        assert_eq!(next("0 12 20 * 1", now), "2024-06-17T12:00:00+00:00");
+        // Both when one of them starts with `*`: the first odd Tuesday
+        assert_eq!(next("0 12 */2 * 2", now), "2024-06-25T12:00:00+00:00");
+        // Strictly after
+        assert_eq!(next("30 14 * * *", "2024-06-15T14:30:00Z"), "2024-06-16T14:30:00+00:00");
+        assert_eq!(next("0 0 29 2 *", now), "2028-02-29T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expression in ["0 14 * *", "60 * * * *", "0 14 * * mon", "*/0 * * * *", "5-1 * * * *"] {
+            assert!(Schedule::parse(expression).is_err(), "{}", expression);
+        }
+        assert!(Schedule::parse("0 0 31 2 *").unwrap().next_after(Utc::now()).is_none());
+
+        let schedule: Schedule = serde_json::from_str("\"0 14 * * *\"").unwrap();
+        assert_eq!(serde_json::to_string(&schedule).unwrap(), "\"0 14 * * *\"");
+    }
+}
@@ -1,13 +1,22 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use dotenv;
 use std::io::{self, Write};
 use tokio;
-use crate::agent::{AgentConfig, TradingAgent};
+use std::path::{Path, PathBuf};
+use crate::agent::{AgentConfig, CharacterRuntime, TradingAgent};
+use crate::clients::twitter::{NewTweet, OAuth2App, TwitterAuth, TwitterClient, DEFAULT_SCOPES};
+use crate::clients::SocialMediaClient;
+use crate::trading::TradeDecision;
 
 mod agent;
+mod character;
+mod clients;
+mod content;
+mod notifications;
+mod providers;
+mod sentiment;
 mod trading;
-mod twitter;
-mod birdeye;
+mod triggers;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -19,30 +28,60 @@ async fn main() -> Result<()> {
     // Load environment variables
     dotenv::dotenv().ok();
 
+    if std::env::args().nth(1).as_deref() == Some("twitter-auth") {
+        return authorize_twitter().await;
+    }
+
     let config = AgentConfig {
         openai_api_key: std::env::var("OPENAI_API_KEY")
             .expect("OPENAI_API_KEY must be set"),
         birdeye_api_key: std::env::var("BIRDEYE_API_KEY")
             .expect("BIRDEYE_API_KEY must be set"),
-        twitter_email: std::env::var("TWITTER_EMAIL")
-            .expect("TWITTER_EMAIL must be set"),
-        twitter_username: std::env::var("TWITTER_USERNAME")
-            .expect("TWITTER_USERNAME must be set"),
-        twitter_password: std::env::var("TWITTER_PASSWORD")
-            .expect("TWITTER_PASSWORD must be set"),
+        twitter_auth: match TwitterAuth::from_env() {
+            Ok(auth) => Some(auth),
+            Err(err) => {
+                tracing::warn!("{}", err);
+                None
+            }
+        },
     };
 
-    // Initialize trading agent
-    let agent = TradingAgent::new(config).await?;
+    // Start one agent per character file (comma separated files or directories), or the
+    // default agent without characters
+    let character_paths: Vec<PathBuf> = std::env::var("CHARACTERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    let runtime = if character_paths.is_empty() {
+        CharacterRuntime::single(TradingAgent::new(config).await?)
+    } else {
+        CharacterRuntime::start(&character_paths, &config).await?
+    };
+    let mut agent = runtime.agents()[0].clone();
 
     println!("Trading Agent initialized! Available commands:");
     println!("  analyze <symbol>           - Analyze market for a symbol");
     println!("  trade <symbol> <buy|sell> <amount>  - Execute a trade");
+    println!("  positions                  - Show the open positions");
+    println!("  drafts                     - List the posts waiting for review");
+    println!("  approve <id>               - Publish a post waiting for review");
+    println!("  edit <id> <text>           - Rewrite a post waiting for review");
+    println!("  reject <id>                - Discard a post waiting for review");
+    println!("  thread <text> | <text>...  - Post a thread on Twitter");
+    println!("  quote <tweet id> <text>    - Quote a tweet");
+    println!("  media <file> <text>        - Post a tweet with an image or a video");
+    println!("  delete <tweet id>          - Delete a tweet");
+    println!("  chat <message>             - Chat with the current character");
+    println!("  characters                 - List the running characters");
+    println!("  use <character>            - Switch to another character");
     println!("  exit                       - Exit the program");
 
     let mut input = String::new();
     loop {
-        print!("> ");
+        print!("{}> ", agent.name());
         io::stdout().flush()?;
         input.clear();
         io::stdin().read_line(&mut input)?;
@@ -66,9 +105,118 @@ async fn main() -> Result<()> {
                     continue;
                 }
                 let amount = parts[3].parse::<f64>()?;
-                let success = agent.execute_trade(parts[1], parts[2], amount).await?;
-                if success {
-                    agent.post_trade_update(parts[1], parts[2], amount).await?;
+                let decision = TradeDecision::manual(parts[1], parts[2], amount, "the REPL");
+                if agent.execute_trade(&decision).await? {
+                    agent.post_trade_update(&decision).await?;
+                }
+            }
+            "positions" => {
+                let positions = agent.positions();
+                if positions.is_empty() {
+                    println!("No open positions");
+                }
+                for (symbol, amount) in positions {
+                    println!("{}: {}", symbol, amount);
+                }
+            }
+            "drafts" | "approve" | "edit" | "reject" => {
+                let Some(queue) = agent.review_queue() else {
+                    println!("Posts of {} are not reviewed, set `posts.review`", agent.name());
+                    continue;
+                };
+                if parts[0] == "drafts" {
+                    let drafts = queue.pending();
+                    if drafts.is_empty() {
+                        println!("No posts waiting for review");
+                    }
+                    for draft in drafts {
+                        println!(
+                            "[{}] {} at {}: {}",
+                            draft.id,
+                            draft.source,
+                            draft.created_at.format("%H:%M"),
+                            draft.text
+                        );
+                    }
+                    continue;
+                }
+
+                let Some(id) = parts.get(1).and_then(|id| id.parse::<u64>().ok()) else {
+                    println!("Usage: {} <id>", parts[0]);
+                    continue;
+                };
+                let result = match parts[0] {
+                    "approve" => queue.approve(id).await.map(|post_id| format!("Posted {}", post_id)),
+                    "reject" => queue.reject(id).map(|_| format!("Discarded post {}", id)),
+                    _ => {
+                        let text = input.trim().splitn(3, ' ').nth(2).unwrap_or("").trim();
+                        queue.edit(id, text).await.map(|_| format!("Updated post {}", id))
+                    }
+                };
+                match result {
+                    Ok(message) => println!("{}", message),
+                    Err(err) => println!("Error: {:#}", err),
+                }
+            }
+            "thread" | "quote" | "media" | "delete" => {
+                let Some(twitter) = agent.twitter_client() else {
+                    println!("{} has no Twitter client", agent.name());
+                    continue;
+                };
+                let args = input.trim()[parts[0].len()..].trim();
+                let result = match (parts[0], args.split_once(' ')) {
+                    ("thread", _) if !args.is_empty() => {
+                        let texts: Vec<&str> = args.split('|').map(str::trim).collect();
+                        let ids = twitter.thread(&texts).await;
+                        ids.map(|ids| format!("Posted {}", ids.join(", ")))
+                    }
+                    ("quote", Some((tweet_id, text))) => {
+                        let id = twitter.quote(tweet_id, text.trim()).await;
+                        id.map(|id| format!("Posted {}", id))
+                    }
+                    ("media", Some((file, text))) => {
+                        let id = post_media(twitter, Path::new(file), text.trim()).await;
+                        id.map(|id| format!("Posted {}", id))
+                    }
+                    ("delete", None) if !args.is_empty() => {
+                        twitter.delete(args).await.map(|_| format!("Deleted {}", args))
+                    }
+                    (command, _) => {
+                        let usage = match command {
+                            "thread" => "thread <text> | <text>...",
+                            "quote" => "quote <tweet id> <text>",
+                            "media" => "media <file> <text>",
+                            _ => "delete <tweet id>",
+                        };
+                        println!("Usage: {}", usage);
+                        continue;
+                    }
+                };
+                match result {
+                    Ok(message) => println!("{}", message),
+                    Err(err) => println!("Error: {:#}", err),
+                }
+            }
+            "chat" => {
+                let message = input.trim()["chat".len()..].trim();
+                if message.is_empty() {
+                    println!("Usage: chat <message>");
+                    continue;
+                }
+                match agent.chat(message).await {
+                    Ok(response) => println!("{}", response),
+                    Err(err) => println!("Error: {:#}", err),
+                }
+            }
+            "characters" => println!("{}", runtime.names().join(", ")),
+            "use" => {
+                if parts.len() != 2 {
+                    println!("Usage: use <character>");
+                    continue;
+                }
+                match runtime.get(parts[1]) {
+                    Some(other) => agent = other,
+                    None => println!("Unknown character. Running: {}", runtime.names().join(", ")),
                 }
             }
             "exit" => break,
@@ -77,4 +225,59 @@ async fn main() -> Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Posts a tweet with the image or video at `path`
+async fn post_media(twitter: &TwitterClient, path: &Path, text: &str) -> Result<String> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    let mime_type = match extension.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        _ => return Err(anyhow!("Unsupported media file {}", path.display())),
+    };
+    let media_id = twitter.upload_media(tokio::fs::read(path).await?, mime_type).await?;
+    twitter.create_tweet(&NewTweet::new(text).media(vec![media_id])).await
+}
+
+/// Authorizes the app to post on behalf of a Twitter account with OAuth 2.0 and prints the
+/// refresh token to set in `TWITTER_REFRESH_TOKEN`.
+async fn authorize_twitter() -> Result<()> {
+    let client_id = std::env::var("TWITTER_CLIENT_ID").expect("TWITTER_CLIENT_ID must be set");
+    let redirect_uri =
+        std::env::var("TWITTER_REDIRECT_URI").expect("TWITTER_REDIRECT_URI must be set");
+    let mut app = OAuth2App::new(&client_id, &redirect_uri);
+    if let Ok(client_secret) = std::env::var("TWITTER_CLIENT_SECRET") {
+        app = app.client_secret(&client_secret);
+    }
+
+    let (url, challenge) = app.authorize_url(DEFAULT_SCOPES);
+    println!("Open this URL, authorize the app and paste the URL you are redirected to:\n{}", url);
+    print!("> ");
+    io::stdout().flush()?;
+    let mut redirect = String::new();
+    io::stdin().read_line(&mut redirect)?;
+
+    let redirect = reqwest::Url::parse(redirect.trim())?;
+    let param = |name: &str| {
+        redirect
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    if param("state").as_deref() != Some(challenge.state.as_str()) {
+        anyhow::bail!("State mismatch, restart the authorization");
+    }
+    let code = param("code").ok_or_else(|| anyhow::anyhow!("No code in the redirect URL"))?;
+
+    let token = app
+        .exchange_code(&reqwest::Client::new(), "https://api.x.com", &code, &challenge)
+        .await?;
+    println!("TWITTER_OAUTH2_ACCESS_TOKEN={}", token.access_token);
+    println!("TWITTER_REFRESH_TOKEN={}", token.refresh_token.unwrap_or_default());
+    println!("Delete the tokens saved in data/twitter of the agents using this account");
+    Ok(())
+}
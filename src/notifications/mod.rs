@@ -0,0 +1,505 @@
+//! Outbound notifications.
+//!
+//! Components publish [Event]s on a [NotificationBus], which delivers them in the background
+//! to its [Route]s. A route sends the events to a [Sink] (Twitter, Discord, Telegram, a
+//! webhook or the logs), rendered with its own templates, and applies its own [AlertSettings]:
+//! the events it accepts, a throttle and quiet hours. This keeps the public Twitter account to
+//! a few trade alerts while operators get everything in their chats.
+//!
+//! Posts to public accounts can be written by the agent of the character and held in a
+//! [ReviewQueue] until a human approves them (see [CharacterSink]).
+//!
+//! Templates reference the fields of the event between braces, e.g.
+//! `"{action} {amount} {symbol}: {reason}"`. The fields are listed in [Event::fields].
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::trading::TradeDecision;
+
+mod review;
+mod sinks;
+
+pub use review::ReviewQueue;
+#[cfg(feature = "telegram")]
+pub use sinks::SocialSink;
+pub use sinks::{CharacterSink, LogSink, Sink, WebhookSink};
+
+/// Names of the sinks that can be configured in the `alerts` settings of a character
+pub const SINKS: &[&str] = &["log", "twitter", "discord", "telegram", "webhook"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Event {
+    TradeExecuted {
+        decision: TradeDecision,
+    },
+    TradeRejected {
+        decision: TradeDecision,
+        reason: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    StopLossHit {
+        symbol: String,
+        price: f64,
+        stop_price: f64,
+    },
+    PriceAlert {
+        symbol: String,
+        price: f64,
+        message: String,
+    },
+    Error {
+        source: String,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventKind {
+    TradeExecuted,
+    TradeRejected,
+    StopLossHit,
+    PriceAlert,
+    Error,
+}
+
+impl EventKind {
+    fn default_template(&self) -> &'static str {
+        match self {
+            EventKind::TradeExecuted => {
+                "🤖 Trade Alert!\n{action} {amount} {symbol}\nReason: {reason}"
+            }
+            EventKind::TradeRejected => {
+                "Trade rejected: {action} {amount} {symbol}\nReason: {reason}"
+            }
+            EventKind::StopLossHit => "🛑 Stop-loss hit on {symbol}: ${price} (stop ${stop_price})",
+            EventKind::PriceAlert => "🔔 {symbol} at ${price}: {message}",
+            EventKind::Error => "⚠️ Error in {source}: {message}",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EventKind::TradeExecuted => "tradeExecuted",
+            EventKind::TradeRejected => "tradeRejected",
+            EventKind::StopLossHit => "stopLossHit",
+            EventKind::PriceAlert => "priceAlert",
+            EventKind::Error => "error",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::TradeExecuted { .. } => EventKind::TradeExecuted,
+            Event::TradeRejected { .. } => EventKind::TradeRejected,
+            Event::StopLossHit { .. } => EventKind::StopLossHit,
+            Event::PriceAlert { .. } => EventKind::PriceAlert,
+            Event::Error { .. } => EventKind::Error,
+        }
+    }
+
+    /// Fields available in the templates. Every event has an `event` field with its kind.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("event", self.kind().to_string())];
+        match self {
+            Event::TradeExecuted { decision } | Event::TradeRejected { decision, .. } => {
+                fields.extend([
+                    ("action", decision.action.clone()),
+                    ("symbol", decision.symbol.clone()),
+                    ("amount", decision.amount.to_string()),
+                    ("confidence", format!("{:.0}%", decision.confidence * 100.0)),
+                ]);
+                match self {
+                    Event::TradeRejected { reason, .. } => fields.push(("reason", reason.clone())),
+                    _ => fields.push(("reason", decision.reason.clone())),
+                }
+            }
+            Event::StopLossHit {
+                symbol,
+                price,
+                stop_price,
+            } => fields.extend([
+                ("symbol", symbol.clone()),
+                ("price", price.to_string()),
+                ("stop_price", stop_price.to_string()),
+            ]),
+            Event::PriceAlert {
+                symbol,
+                price,
+                message,
+            } => fields.extend([
+                ("symbol", symbol.clone()),
+                ("price", price.to_string()),
+                ("message", message.clone()),
+            ]),
+            Event::Error { source, message } => {
+                fields.extend([("source", source.clone()), ("message", message.clone())])
+            }
+        }
+        fields
+    }
+
+    /// Replaces the `{field}` placeholders of the template. Unknown placeholders are kept.
+    pub fn render(&self, template: &str) -> String {
+        self.fields()
+            .into_iter()
+            .fold(template.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), &value)
+            })
+    }
+}
+
+/// Delivery settings of a sink, set per sink in the `alerts` settings of a character:
+///
+/// ```json
+/// "alerts": {
+///     "twitter": {
+///         "events": ["tradeExecuted"],
+///         "templates": { "tradeExecuted": "Just bought {amount} ${symbol} 🚀" },
+///         "throttle": { "max": 5, "periodSecs": 3600 },
+///         "quietHours": "22:00-07:00"
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlertSettings {
+    /// Events sent to the sink, all of them when unset
+    pub events: Option<Vec<EventKind>>,
+    /// Templates replacing the default ones, by event
+    pub templates: HashMap<EventKind, String>,
+    pub throttle: Option<Throttle>,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl AlertSettings {
+    /// Settings of public accounts: only the executed trades, at most 5 per hour
+    pub fn public() -> Self {
+        Self {
+            events: Some(vec![EventKind::TradeExecuted]),
+            throttle: Some(Throttle {
+                max: 5,
+                period_secs: 3600,
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(throttle) = &self.throttle {
+            if throttle.max == 0 || throttle.period_secs == 0 {
+                return Err(anyhow!("`throttle.max` and `throttle.periodSecs` must be greater than 0"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// At most `max` events every `period_secs` seconds. Events over the limit are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Throttle {
+    pub max: usize,
+    pub period_secs: u64,
+}
+
+/// Daily time range, in UTC, during which the events are dropped. Written `"HH:MM-HH:MM"`,
+/// and may span midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let invalid = || anyhow!("Invalid quiet hours {:?}, expected \"HH:MM-HH:MM\"", value);
+        let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+        let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid());
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl From<QuietHours> for String {
+    fn from(hours: QuietHours) -> Self {
+        format!("{}-{}", hours.start.format("%H:%M"), hours.end.format("%H:%M"))
+    }
+}
+
+/// A sink with its delivery settings
+pub struct Route {
+    sink: Box<dyn Sink>,
+    settings: AlertSettings,
+    sent: VecDeque<Instant>,
+}
+
+impl Route {
+    pub fn new(sink: impl Sink + 'static, settings: AlertSettings) -> Self {
+        Self {
+            sink: Box::new(sink),
+            settings,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Returns whether the event should be sent now, and counts it in the throttle
+    fn accepts(&mut self, kind: EventKind, now: DateTime<Utc>) -> bool {
+        if let Some(events) = &self.settings.events {
+            if !events.contains(&kind) {
+                return false;
+            }
+        }
+        if let Some(quiet_hours) = &self.settings.quiet_hours {
+            if quiet_hours.contains(now.time()) {
+                tracing::debug!("Dropping {} for {}: quiet hours", kind, self.sink.name());
+                return false;
+            }
+        }
+        if let Some(throttle) = &self.settings.throttle {
+            let period = Duration::from_secs(throttle.period_secs);
+            while self.sent.front().is_some_and(|sent| sent.elapsed() >= period) {
+                self.sent.pop_front();
+            }
+            if self.sent.len() >= throttle.max {
+                tracing::debug!("Dropping {} for {}: throttled", kind, self.sink.name());
+                return false;
+            }
+            self.sent.push_back(Instant::now());
+        }
+        true
+    }
+
+    fn render(&self, event: &Event) -> String {
+        let kind = event.kind();
+        let template = self
+            .settings
+            .templates
+            .get(&kind)
+            .map(String::as_str)
+            .unwrap_or_else(|| kind.default_template());
+        event.render(template)
+    }
+
+    async fn deliver(&mut self, event: &Event, now: DateTime<Utc>) {
+        if !self.accepts(event.kind(), now) {
+            return;
+        }
+        let message = self.render(event);
+        if let Err(err) = self.sink.send(event, &message).await {
+            tracing::warn!("Could not send {} to {}: {:#}", event.kind(), self.sink.name(), err);
+        }
+    }
+}
+
+/// Publishes events to routes. Every route delivers its events in its own background task, so
+/// that publishing never blocks trading, and a slow sink never delays the other ones.
+#[derive(Clone)]
+pub struct NotificationBus {
+    senders: Vec<mpsc::UnboundedSender<Event>>,
+}
+
+impl NotificationBus {
+    pub fn new(routes: Vec<Route>) -> Self {
+        let senders = routes
+            .into_iter()
+            .map(|mut route| {
+                let (sender, mut receiver) = mpsc::unbounded_channel::<Event>();
+                tokio::spawn(async move {
+                    while let Some(event) = receiver.recv().await {
+                        route.deliver(&event, Utc::now()).await;
+                    }
+                });
+                sender
+            })
+            .collect();
+        Self { senders }
+    }
+
+    pub fn publish(&self, event: Event) {
+        for sender in &self.senders {
+            if sender.send(event.clone()).is_err() {
+                tracing::warn!("Notification route is closed, dropping event");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct RecordingSink {
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Sink for RecordingSink {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn send(&self, _event: &Event, message: &str) -> Result<()> {
+            self.messages.lock().unwrap().push(message.to_string());
+            Ok(())
+        }
+    }
+
+    /// Sink that never finishes sending
+    struct StuckSink;
+
+    #[async_trait]
+    impl Sink for StuckSink {
+        fn name(&self) -> &str {
+            "stuck"
+        }
+
+        async fn send(&self, _event: &Event, _message: &str) -> Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    fn trade(symbol: &str) -> Event {
+        Event::TradeExecuted {
+            decision: TradeDecision {
+                action: "BUY".to_string(),
+                symbol: symbol.to_string(),
+                amount: 100.0,
+                reason: "Momentum".to_string(),
+                confidence: 0.8,
+            },
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        let time = NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+        Utc::now().date_naive().and_time(time).and_utc()
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            trade("SOL").render("{action} {amount} {symbol} ({confidence}): {reason} {unknown}"),
+            "BUY 100 SOL (80%): Momentum {unknown}"
+        );
+        assert_eq!(
+            Event::Error {
+                source: "birdeye".to_string(),
+                message: "timeout".to_string()
+            }
+            .render(EventKind::Error.default_template()),
+            "⚠️ Error in birdeye: timeout"
+        );
+    }
+
+    #[test]
+    fn test_settings() -> Result<()> {
+        let settings: AlertSettings = serde_json::from_value(serde_json::json!({
+            "events": ["tradeExecuted", "stopLossHit"],
+            "templates": { "tradeExecuted": "{symbol}" },
+            "throttle": { "max": 5, "periodSecs": 3600 },
+            "quietHours": "22:00-07:00"
+        }))?;
+        assert_eq!(settings.events, Some(vec![EventKind::TradeExecuted, EventKind::StopLossHit]));
+        assert_eq!(settings.templates[&EventKind::TradeExecuted], "{symbol}");
+
+        let quiet_hours = settings.quiet_hours.unwrap();
+        assert!(quiet_hours.contains(at("23:30").time()));
+        assert!(quiet_hours.contains(at("06:59").time()));
+        assert!(!quiet_hours.contains(at("07:00").time()));
+        assert!(!quiet_hours.contains(at("12:00").time()));
+        assert_eq!(String::from(quiet_hours), "22:00-07:00");
+
+        assert!(QuietHours::try_from("22h-7h".to_string()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_route() {
+        let sink = RecordingSink::default();
+        let mut route = Route::new(
+            sink.clone(),
+            AlertSettings {
+                templates: HashMap::from([(EventKind::TradeExecuted, "{symbol}".to_string())]),
+                quiet_hours: Some(QuietHours::try_from("22:00-07:00".to_string()).unwrap()),
+                ..AlertSettings::public()
+            },
+        );
+
+        let rejected = Event::TradeRejected {
+            decision: TradeDecision {
+                action: "BUY".to_string(),
+                symbol: "SOL".to_string(),
+                amount: 5000.0,
+                reason: "Momentum".to_string(),
+                confidence: 0.8,
+            },
+            reason: "too large".to_string(),
+        };
+        route.deliver(&rejected, at("12:00")).await;
+        route.deliver(&trade("BONK"), at("23:00")).await;
+        for i in 0..10 {
+            route.deliver(&trade(&format!("T{}", i)), at("12:00")).await;
+        }
+
+        // Only the executed trades outside quiet hours, up to the throttle
+        assert_eq!(*sink.messages.lock().unwrap(), vec!["T0", "T1", "T2", "T3", "T4"]);
+    }
+
+    #[tokio::test]
+    async fn test_bus() -> Result<()> {
+        let sink = RecordingSink::default();
+        let bus = NotificationBus::new(vec![
+            Route::new(StuckSink, AlertSettings::default()),
+            Route::new(sink.clone(), AlertSettings::default()),
+        ]);
+        // The stuck sink does not delay the other one
+        bus.publish(trade("SOL"));
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sink.messages.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        assert_eq!(
+            *sink.messages.lock().unwrap(),
+            vec!["🤖 Trade Alert!\nBUY 100 SOL\nReason: Momentum"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! Human approval of the posts before they are published.
+//!
+//! Drafts wait in the [ReviewQueue] until someone approves them, from the REPL or Discord, and
+//! are then posted with the client of the queue. New drafts are broadcast so that reviewers
+//! can be notified.
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
+
+use crate::{character::PostWriter, clients::SocialMediaClient};
+
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub id: u64,
+    pub text: String,
+    /// What the draft was written for, e.g. the kind of its event
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct ReviewQueue {
+    client: Arc<dyn SocialMediaClient>,
+    /// Writer of the character, whose rules the edits must follow
+    writer: PostWriter,
+    drafts: Mutex<BTreeMap<u64, Draft>>,
+    next_id: AtomicU64,
+    sender: broadcast::Sender<Draft>,
+}
+
+impl ReviewQueue {
+    /// Creates a queue publishing the approved drafts with `client`, and checking the edits
+    /// against the rules of `writer`
+    pub fn new(client: impl SocialMediaClient + 'static, writer: PostWriter) -> Self {
+        let (sender, _) = broadcast::channel(64);
+        Self {
+            client: Arc::new(client),
+            writer,
+            drafts: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            sender,
+        }
+    }
+
+    /// Adds a draft to the queue and notifies the reviewers
+    pub fn submit(&self, text: &str, source: &str) -> Draft {
+        let draft = Draft {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            text: text.to_string(),
+            source: source.to_string(),
+            created_at: Utc::now(),
+        };
+        self.drafts.lock().unwrap().insert(draft.id, draft.clone());
+        tracing::info!("Post {} is waiting for review: {}", draft.id, draft.text);
+        // Nobody may be listening, reviewers can still list the pending drafts
+        let _ = self.sender.send(draft.clone());
+        draft
+    }
+
+    /// Drafts waiting for review, oldest first
+    pub fn pending(&self) -> Vec<Draft> {
+        self.drafts.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Receiver notified of every new draft
+    #[cfg(feature = "discord")]
+    pub fn subscribe(&self) -> broadcast::Receiver<Draft> {
+        self.sender.subscribe()
+    }
+
+    /// Replaces the text of a pending draft, if the new text follows the rules of the posts
+    pub async fn edit(&self, id: u64, text: &str) -> Result<()> {
+        if let Err(problems) = self.writer.settings().await.check(text) {
+            return Err(anyhow!("Refusing to post {:?}: {}", text, problems.join(", ")));
+        }
+        let mut drafts = self.drafts.lock().unwrap();
+        let draft = drafts.get_mut(&id).ok_or_else(|| not_pending(id))?;
+        draft.text = text.to_string();
+        Ok(())
+    }
+
+    /// Publishes the draft and returns the id of the post. The draft stays pending if
+    /// publishing fails.
+    pub async fn approve(&self, id: u64) -> Result<String> {
+        let draft = self.take(id)?;
+        match self.client.post(&draft.text).await {
+            Ok(post_id) => Ok(post_id),
+            Err(err) => {
+                self.drafts.lock().unwrap().insert(draft.id, draft);
+                Err(err)
+            }
+        }
+    }
+
+    /// Discards the draft
+    pub fn reject(&self, id: u64) -> Result<Draft> {
+        self.take(id)
+    }
+
+    fn take(&self, id: u64) -> Result<Draft> {
+        self.drafts
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .ok_or_else(|| not_pending(id))
+    }
+}
+
+fn not_pending(id: u64) -> anyhow::Error {
+    anyhow!("Post {} is not waiting for review", id)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use rig::completion::{Message, PromptError};
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::character::{ChatAgent, PostSettings, PostVoice};
+
+    struct Silent;
+
+    #[async_trait]
+    impl ChatAgent for Silent {
+        async fn chat(&self, _: &str, _: Vec<Message>) -> Result<String, PromptError> {
+            Ok(String::new())
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingClient {
+        posts: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl SocialMediaClient for RecordingClient {
+        async fn post(&self, content: &str) -> Result<String> {
+            let mut posts = self.posts.lock().unwrap();
+            posts.push(content.to_string());
+            Ok(posts.len().to_string())
+        }
+
+        async fn reply(&self, _parent_id: &str, content: &str) -> Result<String> {
+            self.post(content).await
+        }
+
+        async fn delete(&self, _post_id: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_review_queue() -> Result<()> {
+        let client = RecordingClient::default();
+        let voice = PostVoice {
+            settings: PostSettings {
+                blocked_words: vec!["moon".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let writer = PostWriter::new(Arc::new(RwLock::new(Box::new(Silent))), voice);
+        let queue = ReviewQueue::new(client.clone(), writer);
+        #[cfg(feature = "discord")]
+        let mut drafts = queue.subscribe();
+
+        let first = queue.submit("gm", "test");
+        let second = queue.submit("bought $BONK", "tradeExecuted");
+        #[cfg(feature = "discord")]
+        assert_eq!(drafts.recv().await?.id, first.id);
+        assert_eq!(
+            queue.pending().iter().map(|d| d.id).collect::<Vec<_>>(),
+            vec![first.id, second.id]
+        );
+
+        // Edits follow the rules of the posts
+        assert!(queue.edit(second.id, "bought $BONK, moon soon").await.is_err());
+        assert!(queue.edit(second.id, " ").await.is_err());
+        queue.edit(second.id, "bought some $BONK").await?;
+        assert_eq!(queue.approve(second.id).await?, "1");
+        queue.reject(first.id)?;
+
+        // Only the approved draft is posted, with its edits
+        assert_eq!(*client.posts.lock().unwrap(), vec!["bought some $BONK"]);
+        assert!(queue.pending().is_empty());
+        assert!(queue.approve(first.id).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+//! Destinations of the notifications.
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{Event, ReviewQueue};
+use crate::{character::PostWriter, clients::SocialMediaClient};
+#[cfg(feature = "discord")]
+use crate::clients::discord::DiscordClient;
+
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Name of the sink in the logs
+    fn name(&self) -> &str;
+    /// Sends the event, `message` being the event rendered with the template of the sink
+    async fn send(&self, event: &Event, message: &str) -> Result<()>;
+}
+
+/// Posts the notifications with a [SocialMediaClient], e.g. in the subscribed Telegram chats
+#[cfg(feature = "telegram")]
+pub struct SocialSink {
+    name: String,
+    client: Arc<dyn SocialMediaClient>,
+}
+
+#[cfg(feature = "telegram")]
+impl SocialSink {
+    pub fn new(name: &str, client: impl SocialMediaClient + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            client: Arc::new(client),
+        }
+    }
+}
+
+#[cfg(feature = "telegram")]
+#[async_trait]
+impl Sink for SocialSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, _event: &Event, message: &str) -> Result<()> {
+        self.client.post(message).await?;
+        Ok(())
+    }
+}
+
+/// Posts in the voice of the character: trades are announced with a post written by the
+/// agent, falling back to the template when the agent cannot write one following the rules.
+/// With a review queue, posts wait for approval instead of being published.
+pub struct CharacterSink {
+    name: String,
+    writer: PostWriter,
+    client: Arc<dyn SocialMediaClient>,
+    review: Option<Arc<ReviewQueue>>,
+}
+
+impl CharacterSink {
+    pub fn new(name: &str, writer: PostWriter, client: impl SocialMediaClient + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            writer,
+            client: Arc::new(client),
+            review: None,
+        }
+    }
+
+    pub fn review(mut self, queue: Arc<ReviewQueue>) -> Self {
+        self.review = Some(queue);
+        self
+    }
+}
+
+#[async_trait]
+impl Sink for CharacterSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, event: &Event, message: &str) -> Result<()> {
+        let post = match event {
+            Event::TradeExecuted { decision } => {
+                match self.writer.write_trade_post(decision).await {
+                    Ok(post) => post,
+                    Err(err) => {
+                        tracing::warn!("Using the template for the trade post: {:#}", err);
+                        message.to_string()
+                    }
+                }
+            }
+            _ => message.to_string(),
+        };
+
+        // Templates are written by operators, but are checked all the same
+        if let Err(problems) = self.writer.settings().await.check(&post) {
+            return Err(anyhow!("Refusing to post {:?}: {}", post, problems.join(", ")));
+        }
+
+        match &self.review {
+            Some(queue) => {
+                queue.submit(&post, &event.kind().to_string());
+            }
+            None => {
+                self.client.post(&post).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Posts the trade alerts as embeds, and the other notifications as messages
+#[cfg(feature = "discord")]
+#[async_trait]
+impl Sink for DiscordClient {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn send(&self, event: &Event, message: &str) -> Result<()> {
+        match event {
+            Event::TradeExecuted { decision } => self.send_trade_alert(decision, message).await?,
+            _ => self.post(message).await?,
+        };
+        Ok(())
+    }
+}
+
+/// POSTs the notifications as JSON: `{"event": ..., "message": ..., "data": {...}}`
+pub struct WebhookSink {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, event: &Event, message: &str) -> Result<()> {
+        self.http
+            .post(&self.url)
+            .json(&json!({
+                "event": event.kind(),
+                "message": message,
+                "data": event,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Logs the notifications, errors and rejected trades as warnings
+pub struct LogSink;
+
+#[async_trait]
+impl Sink for LogSink {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    async fn send(&self, event: &Event, message: &str) -> Result<()> {
+        match event {
+            Event::Error { .. } => tracing::error!(event = %event.kind(), "{}", message),
+            Event::TradeRejected { .. } | Event::StopLossHit { .. } => {
+                tracing::warn!(event = %event.kind(), "{}", message)
+            }
+            _ => tracing::info!(event = %event.kind(), "{}", message),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_partial_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_webhook_sink() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/alerts"))
+            .and(body_partial_json(json!({
+                "event": "stopLossHit",
+                "message": "SOL stopped",
+                "data": { "event": "stopLossHit", "symbol": "SOL", "stopPrice": 95.0 }
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let event = Event::StopLossHit {
+            symbol: "SOL".to_string(),
+            price: 94.5,
+            stop_price: 95.0,
+        };
+        WebhookSink::new(&format!("{}/alerts", server.uri()))
+            .send(&event, "SOL stopped")
+            .await?;
+
+        // Failed deliveries are errors
+        let sink = WebhookSink::new(&format!("{}/missing", server.uri()));
+        assert!(sink.send(&event, "SOL stopped").await.is_err());
+        Ok(())
+    }
+}
@@ -1,181 +1,183 @@
-use anyhow::Result;
+//! Market data of the agents.
+//!
+//! [MarketDataProvider] is what the agents need from a source of market data. The Birdeye API
+//! is the source, through the [BirdeyeProvider] of rig-birdeye: this is the only Birdeye
+//! client of the workspace, its types are the types of the market data.
+//!
+//! Solana tokens trade in AMM pools, and Birdeye reports the liquidity of the pools but no
+//! order books: the market impact of a trade is estimated with the constant-product formula
+//! only. The order book analysis of rig-birdeye ([MarketDepth](rig_birdeye::MarketDepth)) is
+//! not used, for lack of a source of order books.
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-
-const BIRDEYE_API_URL: &str = "https://public-api.birdeye.so";
-
-#[derive(Debug, Clone)]
-pub struct BirdeyeProvider {
-    client: Arc<Client>,
-    api_key: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TokenInfo {
-    pub address: String,
-    pub symbol: String,
-    pub name: String,
-    pub decimals: u8,
-    pub price_usd: f64,
-    pub volume_24h: f64,
-    pub market_cap: f64,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MarketDepth {
-    pub bids: Vec<OrderBookEntry>,
-    pub asks: Vec<OrderBookEntry>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct OrderBookEntry {
-    pub price: f64,
-    pub size: f64,
-}
+use rig_birdeye::{
+    BirdeyeProvider, MarketImpact, PricePoint, Side, SortType, TimeInterval, TokenListItem,
+    TokenListParams, TokenListSortBy, TokenOverview,
+};
+
+/// Tokens found without searching for their symbol
+const KNOWN_TOKENS: &[(&str, &str)] = &[
+    ("SOL", "So11111111111111111111111111111111111111112"),
+    ("USDC", "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+    ("BONK", "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263"),
+];
+/// Tokens of the token list among which the top movers are picked
+const TOP_MOVERS_CANDIDATES: u32 = 50;
 
 #[async_trait]
 pub trait MarketDataProvider: Send + Sync {
-    async fn get_token_info(&self, address: &str) -> Result<TokenInfo>;
-    async fn get_market_depth(&self, address: &str) -> Result<MarketDepth>;
-    async fn get_price_history(&self, address: &str, interval: &str) -> Result<Vec<PricePoint>>;
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PricePoint {
-    pub timestamp: i64,
-    pub price: f64,
-    pub volume: f64,
-}
-
-impl BirdeyeProvider {
-    pub fn new(api_key: &str) -> Self {
-        Self {
-            client: Arc::new(Client::new()),
-            api_key: api_key.to_string(),
-        }
-    }
-
-    async fn make_request<T: for<'de> Deserialize<'de>>(
+    /// Address of the token with the symbol
+    async fn token_address(&self, symbol: &str) -> Result<String>;
+    async fn get_token_info(&self, address: &str) -> Result<TokenOverview>;
+    async fn get_price_history(&self, address: &str, interval: TimeInterval) -> Result<Vec<PricePoint>>;
+    /// Tokens with the largest 24h change, among the tokens with at least `min_liquidity` of
+    /// liquidity (low liquidity tokens are mostly noise)
+    async fn top_movers(&self, limit: usize, min_liquidity: f64) -> Result<Vec<TokenListItem>>;
+    /// Estimates the execution of an order of `size_usd` as a swap in constant-product pools
+    /// holding the liquidity of the token, fees excluded
+    async fn get_market_impact(
         &self,
-        endpoint: &str,
-        params: &[(&str, &str)],
-    ) -> Result<T> {
-        let url = format!("{}{}", BIRDEYE_API_URL, endpoint);
-        
-        let response = self.client
-            .get(&url)
-            .header("X-API-KEY", &self.api_key)
-            .query(params)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        let data = response.json::<T>().await?;
-        Ok(data)
+        address: &str,
+        side: Side,
+        size_usd: f64,
+    ) -> Result<MarketImpact>;
+
+    /// Market data of the token with the symbol
+    async fn get_token_info_by_symbol(&self, symbol: &str) -> Result<TokenOverview> {
+        let address = self.token_address(symbol).await?;
+        self.get_token_info(&address).await
     }
 }
 
 #[async_trait]
 impl MarketDataProvider for BirdeyeProvider {
-    async fn get_token_info(&self, address: &str) -> Result<TokenInfo> {
-        self.make_request(
-            "/public/token",
-            &[("address", address)],
-        ).await
+    async fn token_address(&self, symbol: &str) -> Result<String> {
+        if let Some((_, address)) = KNOWN_TOKENS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(symbol))
+        {
+            return Ok(address.to_string());
+        }
+        let token = self
+            .find_token(symbol)
+            .await?
+            .ok_or_else(|| anyhow!("Unknown token symbol: {}", symbol.to_uppercase()))?;
+        Ok(token.address)
     }
 
-    async fn get_market_depth(&self, address: &str) -> Result<MarketDepth> {
-        self.make_request(
-            "/public/orderbook",
-            &[("address", address)],
-        ).await
+    async fn get_token_info(&self, address: &str) -> Result<TokenOverview> {
+        Ok(self.get_token_overview(address).await?)
     }
 
-    async fn get_price_history(&self, address: &str, interval: &str) -> Result<Vec<PricePoint>> {
-        self.make_request(
-            "/public/price_history",
-            &[
-                ("address", address),
-                ("interval", interval),
-            ],
-        ).await
+    async fn get_price_history(&self, address: &str, interval: TimeInterval) -> Result<Vec<PricePoint>> {
+        Ok(BirdeyeProvider::get_price_history(self, address, interval).await?)
     }
-}
 
-// Additional helper functions for market analysis
-impl BirdeyeProvider {
-    pub async fn analyze_liquidity(&self, address: &str) -> Result<LiquidityAnalysis> {
-        let depth = self.get_market_depth(address).await?;
-        
-        let total_bid_liquidity: f64 = depth.bids
-            .iter()
-            .map(|entry| entry.price * entry.size)
-            .sum();
-
-        let total_ask_liquidity: f64 = depth.asks
-            .iter()
-            .map(|entry| entry.price * entry.size)
-            .sum();
-
-        Ok(LiquidityAnalysis {
-            total_bid_liquidity,
-            total_ask_liquidity,
-            bid_ask_ratio: total_bid_liquidity / total_ask_liquidity,
-            depth_quality: calculate_depth_quality(&depth),
-        })
+    async fn top_movers(&self, limit: usize, min_liquidity: f64) -> Result<Vec<TokenListItem>> {
+        let params = TokenListParams::new(TokenListSortBy::Change, SortType::Descending)
+            .with_limit(TOP_MOVERS_CANDIDATES)
+            .with_min_liquidity(min_liquidity);
+        Ok(self
+            .get_token_list(params)
+            .await?
+            .into_iter()
+            .filter(|token| !token.symbol.is_empty() && token.liquidity >= min_liquidity)
+            .take(limit)
+            .collect())
     }
 
-    pub async fn get_market_impact(&self, address: &str, size_usd: f64) -> Result<MarketImpact> {
-        let depth = self.get_market_depth(address).await?;
-        let token_info = self.get_token_info(address).await?;
-
-        let size_tokens = size_usd / token_info.price_usd;
-        let (price_impact, executed_price) = calculate_price_impact(&depth, size_tokens, token_info.price_usd);
-
-        Ok(MarketImpact {
-            price_impact,
-            executed_price,
-            size_usd,
-            size_tokens,
-        })
+    /// Estimated across the markets of the token
+    async fn get_market_impact(
+        &self,
+        address: &str,
+        side: Side,
+        size_usd: f64,
+    ) -> Result<MarketImpact> {
+        Ok(BirdeyeProvider::get_market_impact(self, address, side, size_usd).await?)
     }
 }
 
-#[derive(Debug)]
-pub struct LiquidityAnalysis {
-    pub total_bid_liquidity: f64,
-    pub total_ask_liquidity: f64,
-    pub bid_ask_ratio: f64,
-    pub depth_quality: f64,
-}
-
-#[derive(Debug)]
-pub struct MarketImpact {
-    pub price_impact: f64,
-    pub executed_price: f64,
-    pub size_usd: f64,
-    pub size_tokens: f64,
-}
-
-fn calculate_depth_quality(depth: &MarketDepth) -> f64 {
-    // Implement depth quality calculation
-    // This could consider factors like:
-    // - Spread
-    // - Depth distribution
-    // - Number of price levels
-    0.0 // Placeholder
+#[cfg(test)]
+mod tests {
+    use rig_birdeye::ImpactSource;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    const WIF: &str = "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm";
+
+    #[tokio::test]
+    async fn test_birdeye_provider() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/defi/v3/search"))
+            .and(query_param("keyword", "WIF"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "success": true,
+                "data": { "items": [{ "type": "token", "result": [
+                    { "address": "wifi", "symbol": "WIFI", "name": "Not WIF", "decimals": 6 },
+                    { "address": WIF, "symbol": "WIF", "name": "dogwifhat", "decimals": 6 }
+                ]}]}
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/defi/tokenlist"))
+            .and(query_param("sort_by", "v24hChangePercent"))
+            .and(query_param("min_liquidity", "100000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "success": true,
+                "data": { "tokens": [
+                    { "address": WIF, "symbol": "WIF", "price": 2.5, "v24hChangePercent": 42.0, "liquidity": 2e7 },
+                    { "address": "unnamed", "price": 1.0, "v24hChangePercent": 30.0, "liquidity": 1e7 },
+                    { "address": "shallow", "symbol": "LOW", "price": 1.0, "v24hChangePercent": 25.0, "liquidity": 1000.0 },
+                    { "address": "popcat", "symbol": "POPCAT", "price": 1.2, "v24hChangePercent": 20.0, "liquidity": 5e6 },
+                    { "address": "bome", "symbol": "BOME", "price": 0.01, "v24hChangePercent": 10.0, "liquidity": 4e6 }
+                ]}
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/defi/token_overview"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "success": true,
+                "data": { "address": WIF, "symbol": "WIF", "name": "dogwifhat", "decimals": 6, "price": 2.5, "liquidity": 2e7 }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/defi/v2/markets"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "success": true,
+                "data": { "items": [{ "address": "pool", "source": "Raydium", "liquidity": 2e6 }], "total": 1 }
+            })))
+            .mount(&server)
+            .await;
+        let provider = BirdeyeProvider::builder("test_key").base_url(&server.uri()).build();
+
+        // Well known tokens are not searched
+        assert_eq!(
+            provider.token_address("sol").await?,
+            "So11111111111111111111111111111111111111112"
+        );
+        assert_eq!(provider.token_address("WIF").await?, WIF);
+        assert!(provider.token_address("NOPE").await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        let movers = provider.top_movers(2, 100_000.0).await?;
+        let symbols: Vec<_> = movers.iter().map(|token| token.symbol.as_str()).collect();
+        assert_eq!(symbols, ["WIF", "POPCAT"]);
+
+        // Solana tokens trade in pools
+        let impact =
+            MarketDataProvider::get_market_impact(&provider, WIF, Side::Buy, 1000.0).await?;
+        assert_eq!(impact.source, ImpactSource::Amm);
+        assert_eq!(impact.size_tokens, 400.0);
+
+        Ok(())
+    }
 }
-
-fn calculate_price_impact(
-    depth: &MarketDepth,
-    size_tokens: f64,
-    current_price: f64,
-) -> (f64, f64) {
-    // Implement price impact calculation
-    // This should walk the order book to determine:
-    // - Average execution price
-    // - Price impact percentage
-    (0.0, current_price) // Placeholder
-} 
\ No newline at end of file
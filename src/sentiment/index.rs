@@ -0,0 +1,166 @@
+//! Time-decayed sentiment index of the tokens.
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::trading::TradeDecision;
+
+/// Observations older than this many half-lives weigh less than 0.4% and are dropped
+const MAX_AGE_HALF_LIVES: i32 = 8;
+/// Decayed weight of tweets at which a reading is fully trusted
+const FULL_STRENGTH_WEIGHT: f64 = 20.0;
+/// Largest change of the confidence of a trade made by the sentiment
+const SENTIMENT_WEIGHT: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy)]
+struct Observation {
+    score: f64,
+    weight: f64,
+    at: DateTime<Utc>,
+}
+
+/// Sentiment scores of the tweets about each token. The weight of a score halves every
+/// `half_life`, so that the index follows the current mood rather than last week's.
+#[derive(Debug)]
+pub struct SentimentIndex {
+    half_life: Duration,
+    observations: HashMap<String, Vec<Observation>>,
+}
+
+/// Sentiment of a token at a point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SentimentReading {
+    pub symbol: String,
+    /// Decay weighted average of the scores, from -1 (bearish) to 1 (bullish)
+    pub score: f64,
+    /// Decayed weight of the tweets behind the score
+    pub weight: f64,
+    /// Tweets behind the score
+    pub tweets: usize,
+    pub at: DateTime<Utc>,
+}
+
+impl SentimentIndex {
+    pub fn new(half_life: Duration) -> Self {
+        Self {
+            half_life,
+            observations: HashMap::new(),
+        }
+    }
+
+    /// Adds the score (from -1 to 1) of a tweet posted at `at`. `weight` is the confidence of
+    /// the score.
+    pub fn add(&mut self, symbol: &str, score: f64, weight: f64, at: DateTime<Utc>) {
+        let oldest = at - self.half_life * MAX_AGE_HALF_LIVES;
+        let observations = self.observations.entry(symbol.to_uppercase()).or_default();
+        observations.retain(|observation| observation.at >= oldest);
+        observations.push(Observation {
+            score: score.clamp(-1.0, 1.0),
+            weight: weight.clamp(0.0, 1.0),
+            at,
+        });
+    }
+
+    /// Sentiment of the token at `now`, if any tweet about it was scored
+    pub fn reading(&self, symbol: &str, now: DateTime<Utc>) -> Option<SentimentReading> {
+        let symbol = symbol.to_uppercase();
+        let oldest = now - self.half_life * MAX_AGE_HALF_LIVES;
+        let observations: Vec<_> = self
+            .observations
+            .get(&symbol)?
+            .iter()
+            .filter(|observation| observation.at >= oldest)
+            .collect();
+
+        let half_life = self.half_life.num_seconds().max(1) as f64;
+        let (weighted, weight) = observations.iter().fold((0.0, 0.0), |(weighted, weight), observation| {
+            // Tweets from the future (clock skew) count as fresh
+            let age = (now - observation.at).num_seconds().max(0) as f64;
+            let decayed = observation.weight * 0.5f64.powf(age / half_life);
+            (weighted + observation.score * decayed, weight + decayed)
+        });
+        if weight <= 0.0 {
+            return None;
+        }
+
+        Some(SentimentReading {
+            symbol,
+            score: weighted / weight,
+            weight,
+            tweets: observations.len(),
+            at: now,
+        })
+    }
+}
+
+impl SentimentReading {
+    /// How much the reading can be trusted, from 0 to 1, given the tweets behind it
+    pub fn strength(&self) -> f64 {
+        (self.weight / FULL_STRENGTH_WEIGHT).min(1.0)
+    }
+
+    /// Feeds the sentiment into the decision: the confidence of the trade goes up when the
+    /// sentiment agrees with it and down when it does not, by at most [SENTIMENT_WEIGHT]
+    pub fn adjust(&self, decision: &mut TradeDecision) {
+        let alignment = if decision.action.eq_ignore_ascii_case("SELL") {
+            -self.score
+        } else {
+            self.score
+        };
+        decision.confidence =
+            (decision.confidence + SENTIMENT_WEIGHT * alignment * self.strength()).clamp(0.0, 1.0);
+        decision.reason.push_str(&format!(
+            " Social sentiment {:+.2} over {} tweets.",
+            self.score, self.tweets
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decay() {
+        let now = Utc::now();
+        let mut index = SentimentIndex::new(Duration::hours(6));
+        index.add("sol", 1.0, 1.0, now - Duration::hours(6));
+        index.add("SOL", -1.0, 1.0, now);
+
+        // The bullish tweet is one half-life old, so it weighs half as much
+        let reading = index.reading("SOL", now).unwrap();
+        assert!((reading.score + 1.0 / 3.0).abs() < 1e-9);
+        assert!((reading.weight - 1.5).abs() < 1e-9);
+        assert_eq!(reading.tweets, 2);
+
+        // Old tweets are forgotten
+        let later = now + Duration::hours(6 * 8);
+        let reading = index.reading("SOL", later).unwrap();
+        assert_eq!(reading.score, -1.0);
+        assert!(index.reading("SOL", later + Duration::hours(1)).is_none());
+        assert!(index.reading("BONK", now).is_none());
+    }
+
+    #[test]
+    fn test_adjust() {
+        let reading = SentimentReading {
+            symbol: "SOL".to_string(),
+            score: -0.5,
+            weight: 10.0,
+            tweets: 12,
+            at: Utc::now(),
+        };
+
+        let mut buy = TradeDecision::manual("SOL", "BUY", 100.0, "the tests");
+        buy.confidence = 0.8;
+        reading.adjust(&mut buy);
+        assert!((buy.confidence - 0.75).abs() < 1e-9);
+        assert!(buy.reason.ends_with("Social sentiment -0.50 over 12 tweets."));
+
+        let mut sell = TradeDecision::manual("SOL", "SELL", 100.0, "the tests");
+        reading.adjust(&mut sell);
+        assert_eq!(sell.confidence, 1.0);
+    }
+}
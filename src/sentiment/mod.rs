@@ -0,0 +1,451 @@
+//! Social sentiment of the tokens, as a trading signal.
+//!
+//! [SentimentTracker] searches the recent tweets about a token's cashtag with the Twitter
+//! client, has them scored by a [TweetScorer] (an rig [Extractor] by default) and adds the
+//! scores to a time-decayed [SentimentIndex]. The index is a feature of the decision loop (see
+//! [SentimentReading::adjust]), and the agents query it with the [SentimentTool].
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use rig::{
+    completion::{CompletionModel, ToolDefinition},
+    extractor::Extractor,
+    providers::openai,
+    tool::Tool,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::clients::twitter::{Tweet, TwitterClient};
+
+mod index;
+
+pub use index::{SentimentIndex, SentimentReading};
+
+/// Time after which the weight of a score is halved
+const DEFAULT_HALF_LIFE: chrono::Duration = chrono::Duration::hours(6);
+/// Tweets of a token are searched at most once per interval
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Tweets scored per search
+const MAX_TWEETS: usize = 50;
+/// Model scoring the tweets: the task is simple and there are many tweets
+const SCORER_MODEL: &str = openai::GPT_4O_MINI;
+
+/// Settings of the sentiment tracker of a character
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SentimentSettings {
+    /// Minimum time between two searches of the tweets of a token
+    pub refresh_interval_secs: u64,
+    /// Time after which the weight of a score is halved
+    pub half_life_secs: u64,
+}
+
+impl Default for SentimentSettings {
+    fn default() -> Self {
+        Self {
+            refresh_interval_secs: DEFAULT_REFRESH_INTERVAL.as_secs(),
+            half_life_secs: DEFAULT_HALF_LIFE.num_seconds() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema, Serialize)]
+/// Sentiment of a tweet towards a token
+pub enum Sentiment {
+    Bullish,
+    Bearish,
+    Neutral,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+pub struct TweetSentiment {
+    /// Number of the tweet in the list
+    pub tweet: usize,
+    /// Sentiment of the tweet towards the token
+    pub sentiment: Sentiment,
+    /// How sure you are of the sentiment, between 0 and 1
+    pub confidence: f64,
+    /// Whether the tweet looks like spam, a bot or a paid promotion
+    pub spam: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+pub struct TweetSentiments {
+    /// Sentiment of every tweet of the list
+    pub tweets: Vec<TweetSentiment>,
+}
+
+/// Score of a tweet, from -1 (bearish) to 1 (bullish), with its confidence from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct TweetScore {
+    pub tweet_id: String,
+    pub score: f64,
+    pub confidence: f64,
+}
+
+#[async_trait]
+pub trait TweetScorer: Send + Sync {
+    /// Scores the tweets about the token. Tweets that cannot be scored (e.g. spam) are left
+    /// out.
+    async fn score(&self, symbol: &str, tweets: &[Tweet]) -> Result<Vec<TweetScore>>;
+}
+
+/// Scores the tweets with an [Extractor], in a single request per search
+pub struct ExtractorScorer<M: CompletionModel> {
+    extractor: Extractor<M, TweetSentiments>,
+}
+
+impl<M: CompletionModel> ExtractorScorer<M> {
+    pub fn new(extractor: Extractor<M, TweetSentiments>) -> Self {
+        Self { extractor }
+    }
+}
+
+impl ExtractorScorer<openai::CompletionModel> {
+    pub fn openai(api_key: &str) -> Self {
+        let extractor = openai::Client::new(api_key)
+            .extractor::<TweetSentiments>(SCORER_MODEL)
+            .preamble(
+                "You rate the sentiment of crypto traders' tweets towards a token. Bullish \
+                 tweets expect the price to go up, bearish tweets expect it to go down. Flag \
+                 giveaways, bots and paid promotions as spam.",
+            )
+            .build();
+        Self::new(extractor)
+    }
+}
+
+#[async_trait]
+impl<M: CompletionModel + Sync> TweetScorer for ExtractorScorer<M> {
+    async fn score(&self, symbol: &str, tweets: &[Tweet]) -> Result<Vec<TweetScore>> {
+        let mut text = format!("Token: ${}\n\nTweets:\n", symbol);
+        for (number, tweet) in tweets.iter().enumerate() {
+            text.push_str(&format!("{}. {}\n", number + 1, tweet.text.replace('\n', " ")));
+        }
+        let sentiments = self.extractor.extract(&text).await?;
+        Ok(scores(tweets, sentiments))
+    }
+}
+
+/// Matches the extracted sentiments with the tweets, without the spam
+fn scores(tweets: &[Tweet], sentiments: TweetSentiments) -> Vec<TweetScore> {
+    let mut scores: HashMap<usize, TweetScore> = HashMap::new();
+    for sentiment in sentiments.tweets {
+        let Some(tweet) = sentiment.tweet.checked_sub(1).and_then(|index| tweets.get(index)) else {
+            continue;
+        };
+        if sentiment.spam {
+            continue;
+        }
+        let score = match sentiment.sentiment {
+            Sentiment::Bullish => 1.0,
+            Sentiment::Bearish => -1.0,
+            Sentiment::Neutral => 0.0,
+        };
+        scores.entry(sentiment.tweet).or_insert(TweetScore {
+            tweet_id: tweet.id.clone(),
+            score,
+            confidence: sentiment.confidence.clamp(0.0, 1.0),
+        });
+    }
+    let mut scores: Vec<_> = scores.into_iter().collect();
+    scores.sort_by_key(|(number, _)| *number);
+    scores.into_iter().map(|(_, score)| score).collect()
+}
+
+#[derive(Default)]
+struct SearchCursor {
+    since_id: Option<String>,
+    searched_at: Option<Instant>,
+}
+
+pub struct SentimentTracker {
+    twitter: TwitterClient,
+    scorer: Box<dyn TweetScorer>,
+    refresh_interval: Duration,
+    index: Mutex<SentimentIndex>,
+    // Held during the searches, so that a token is not searched twice at once
+    cursors: tokio::sync::Mutex<HashMap<String, SearchCursor>>,
+}
+
+impl SentimentTracker {
+    pub fn new(twitter: TwitterClient, scorer: impl TweetScorer + 'static) -> Self {
+        Self {
+            twitter,
+            scorer: Box::new(scorer),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            index: Mutex::new(SentimentIndex::new(DEFAULT_HALF_LIFE)),
+            cursors: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set the minimum interval between two searches of the tweets of a token
+    pub fn refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    /// Set the time after which the weight of a score is halved
+    pub fn half_life(mut self, half_life: chrono::Duration) -> Self {
+        self.index = Mutex::new(SentimentIndex::new(half_life));
+        self
+    }
+
+    /// Applies the settings of a character
+    pub fn settings(self, settings: &SentimentSettings) -> Self {
+        self.refresh_interval(Duration::from_secs(settings.refresh_interval_secs))
+            .half_life(chrono::Duration::seconds(settings.half_life_secs as i64))
+    }
+
+    /// Current sentiment of the token, after scoring the new tweets about it if it was not
+    /// searched recently. `None` if nobody tweeted about it.
+    pub async fn reading(&self, symbol: &str) -> Result<Option<SentimentReading>> {
+        self.refresh(symbol).await?;
+        Ok(self.cached(symbol))
+    }
+
+    /// Current sentiment of the token, without searching for new tweets
+    pub fn cached(&self, symbol: &str) -> Option<SentimentReading> {
+        self.index.lock().unwrap().reading(&normalize(symbol), Utc::now())
+    }
+
+    /// Scores the tweets about the token posted since the last search
+    pub async fn refresh(&self, symbol: &str) -> Result<()> {
+        let symbol = normalize(symbol);
+        let mut cursors = self.cursors.lock().await;
+        let cursor = cursors.entry(symbol.clone()).or_default();
+        if cursor
+            .searched_at
+            .is_some_and(|searched_at| searched_at.elapsed() < self.refresh_interval)
+        {
+            return Ok(());
+        }
+
+        let query = format!("${} -is:retweet", symbol);
+        let tweets = self
+            .twitter
+            .search_recent(&query, cursor.since_id.as_deref(), MAX_TWEETS)
+            .await?;
+        cursor.searched_at = Some(Instant::now());
+        let Some(newest) = tweets.last() else {
+            return Ok(());
+        };
+        cursor.since_id = Some(newest.id.clone());
+
+        let scores = self.scorer.score(&symbol, &tweets).await?;
+        tracing::debug!("Scored {} of {} tweets about ${}", scores.len(), tweets.len(), symbol);
+        let now = Utc::now();
+        let mut index = self.index.lock().unwrap();
+        for score in scores {
+            let posted_at = tweets
+                .iter()
+                .find(|tweet| tweet.id == score.tweet_id)
+                .and_then(|tweet| tweet.created_at)
+                .unwrap_or(now);
+            index.add(&symbol, score.score, score.confidence, posted_at);
+        }
+        Ok(())
+    }
+}
+
+fn normalize(symbol: &str) -> String {
+    symbol.trim().trim_start_matches('$').to_uppercase()
+}
+
+/// Tool giving the agents the social sentiment of a token
+#[derive(Clone)]
+pub struct SentimentTool {
+    tracker: Arc<SentimentTracker>,
+}
+
+impl SentimentTool {
+    pub fn new(tracker: Arc<SentimentTracker>) -> Self {
+        Self { tracker }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SentimentArgs {
+    symbol: String,
+}
+
+#[derive(Debug)]
+pub struct SentimentError(String);
+
+impl fmt::Display for SentimentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to get the sentiment: {}", self.0)
+    }
+}
+
+impl std::error::Error for SentimentError {}
+
+impl Tool for SentimentTool {
+    const NAME: &'static str = "token_sentiment";
+    type Error = SentimentError;
+    type Args = SentimentArgs;
+    type Output = Option<SentimentReading>;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Social sentiment of a token on Twitter, from -1 (bearish) to 1 \
+                          (bullish), weighted towards the latest tweets. Returns null if \
+                          nobody tweeted about the token."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "symbol": {
+                        "type": "string",
+                        "description": "Symbol of the token, e.g. SOL"
+                    }
+                },
+                "required": ["symbol"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        // The futures of the tools must be Sync, which the requests are not
+        let tracker = self.tracker.clone();
+        tokio::spawn(async move { tracker.reading(&args.symbol).await })
+            .await
+            .map_err(|err| SentimentError(err.to_string()))?
+            .map_err(|err| SentimentError(format!("{:#}", err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::clients::twitter::{OAuth2App, OAuth2Token, TwitterAuth};
+
+    /// Scores tweets containing "moon" as bullish and the others as bearish
+    struct KeywordScorer;
+
+    #[async_trait]
+    impl TweetScorer for KeywordScorer {
+        async fn score(&self, _symbol: &str, tweets: &[Tweet]) -> Result<Vec<TweetScore>> {
+            Ok(tweets
+                .iter()
+                .map(|tweet| TweetScore {
+                    tweet_id: tweet.id.clone(),
+                    score: if tweet.text.contains("moon") { 1.0 } else { -1.0 },
+                    confidence: 1.0,
+                })
+                .collect())
+        }
+    }
+
+    fn tweet(id: &str, text: &str) -> Tweet {
+        serde_json::from_value(json!({ "id": id, "text": text })).unwrap()
+    }
+
+    #[test]
+    fn test_scores() {
+        let tweets = [
+            tweet("1", "$SOL to the moon"),
+            tweet("2", "free $SOL airdrop"),
+            tweet("3", "$SOL dumping"),
+        ];
+        let sentiments = TweetSentiments {
+            tweets: vec![
+                TweetSentiment {
+                    tweet: 3,
+                    sentiment: Sentiment::Bearish,
+                    confidence: 0.9,
+                    spam: false,
+                },
+                TweetSentiment {
+                    tweet: 1,
+                    sentiment: Sentiment::Bullish,
+                    confidence: 1.5,
+                    spam: false,
+                },
+                TweetSentiment {
+                    tweet: 2,
+                    sentiment: Sentiment::Bullish,
+                    confidence: 0.8,
+                    spam: true,
+                },
+                TweetSentiment {
+                    tweet: 9,
+                    sentiment: Sentiment::Neutral,
+                    confidence: 0.5,
+                    spam: false,
+                },
+            ],
+        };
+
+        let scores = scores(&tweets, sentiments);
+        assert_eq!(
+            scores,
+            vec![
+                TweetScore {
+                    tweet_id: "1".to_string(),
+                    score: 1.0,
+                    confidence: 1.0
+                },
+                TweetScore {
+                    tweet_id: "3".to_string(),
+                    score: -1.0,
+                    confidence: 0.9
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tracker() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/2/tweets/search/recent"))
+            .and(query_param("query", "$BONK -is:retweet"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [
+                    { "id": "3", "text": "$BONK to the moon" },
+                    { "id": "2", "text": "$BONK moon soon" },
+                    { "id": "1", "text": "$BONK is dead" }
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let twitter = TwitterClient::new(TwitterAuth::OAuth2 {
+            app: OAuth2App::new("client-id", "http://127.0.0.1/callback"),
+            token: OAuth2Token {
+                access_token: "access".to_string(),
+                refresh_token: None,
+                expires_at: None,
+                scope: None,
+            },
+        })
+        .api_url(&server.uri());
+        let tracker = SentimentTracker::new(twitter, KeywordScorer);
+
+        assert!(tracker.cached("BONK").is_none());
+        let reading = tracker.reading("$bonk").await?.unwrap();
+        assert_eq!(reading.tweets, 3);
+        assert!((reading.score - 1.0 / 3.0).abs() < 1e-6);
+
+        // Searched once per refresh interval
+        assert_eq!(tracker.reading("BONK").await?.unwrap().tweets, 3);
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Mutex};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeDecision {
@@ -10,9 +11,72 @@ pub struct TradeDecision {
     pub confidence: f64,
 }
 
+impl TradeDecision {
+    /// Trade requested by a user rather than decided by the agent
+    pub fn manual(symbol: &str, action: &str, amount: f64, source: &str) -> Self {
+        Self {
+            action: action.to_uppercase(),
+            symbol: symbol.to_uppercase(),
+            amount,
+            reason: format!("Manual trade from {}", source),
+            confidence: 1.0,
+        }
+    }
+
+    /// Parses the decision of a market analysis, answered by the agent as a JSON object with
+    /// an `action` (BUY, SELL or HOLD), an `amount` in USD, a `confidence` between 0 and 1 and
+    /// a `reason`. Text around the object is ignored.
+    pub fn from_analysis(symbol: &str, answer: &str) -> Result<Self> {
+        let json = answer
+            .find('{')
+            .zip(answer.rfind('}'))
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| &answer[start..=end])
+            .ok_or_else(|| anyhow!("No decision in the analysis: {:?}", answer))?;
+        let analysis: Analysis = serde_json::from_str(json)?;
+
+        let action = analysis.action.trim().to_uppercase();
+        if !matches!(action.as_str(), "BUY" | "SELL" | "HOLD") {
+            return Err(anyhow!("Invalid action {:?}, expected BUY, SELL or HOLD", action));
+        }
+        if !(0.0..=1.0).contains(&analysis.confidence) {
+            return Err(anyhow!("Invalid confidence {}, expected 0 to 1", analysis.confidence));
+        }
+        if action != "HOLD" && analysis.amount <= 0.0 {
+            return Err(anyhow!("Invalid amount {} to {}", analysis.amount, action));
+        }
+
+        Ok(Self {
+            action,
+            symbol: symbol.to_uppercase(),
+            amount: analysis.amount,
+            reason: analysis.reason,
+            confidence: analysis.confidence,
+        })
+    }
+
+    /// Whether the decision is to not trade
+    pub fn is_hold(&self) -> bool {
+        self.action.eq_ignore_ascii_case("HOLD")
+    }
+}
+
+/// Decision answered by the agent, see [TradeDecision::from_analysis]
+#[derive(Deserialize)]
+struct Analysis {
+    action: String,
+    #[serde(default)]
+    amount: f64,
+    confidence: f64,
+    #[serde(default)]
+    reason: String,
+}
+
 pub struct TradingEngine {
     min_confidence: f64,
     max_trade_size: f64,
+    /// Net amount bought per symbol
+    positions: Mutex<HashMap<String, f64>>,
 }
 
 impl TradingEngine {
@@ -20,26 +84,46 @@ impl TradingEngine {
         Self {
             min_confidence,
             max_trade_size,
+            positions: Mutex::new(HashMap::new()),
         }
     }
 
-    pub async fn execute_trade(&self, decision: &TradeDecision) -> Result<bool> {
-        // Validate trade parameters
+    /// Open positions, sorted by symbol
+    pub fn positions(&self) -> Vec<(String, f64)> {
+        let positions = self.positions.lock().unwrap();
+        let mut positions: Vec<_> = positions
+            .iter()
+            .filter(|(_, amount)| **amount != 0.0)
+            .map(|(symbol, amount)| (symbol.clone(), *amount))
+            .collect();
+        positions.sort_by(|a, b| a.0.cmp(&b.0));
+        positions
+    }
+
+    /// Returns why the trade would be rejected by the risk limits, if it would
+    pub fn rejection_reason(&self, decision: &TradeDecision) -> Option<String> {
+        if !["BUY", "SELL"].iter().any(|action| decision.action.eq_ignore_ascii_case(action)) {
+            return Some(format!("{} is not a trade", decision.action));
+        }
         if decision.confidence < self.min_confidence {
-            tracing::warn!(
-                "Trade rejected: confidence {:.2} below minimum {:.2}",
-                decision.confidence,
-                self.min_confidence
-            );
-            return Ok(false);
+            return Some(format!(
+                "confidence {:.2} below minimum {:.2}",
+                decision.confidence, self.min_confidence
+            ));
         }
-
         if decision.amount > self.max_trade_size {
-            tracing::warn!(
-                "Trade rejected: size {} above maximum {}",
-                decision.amount,
-                self.max_trade_size
-            );
+            return Some(format!(
+                "size {} above maximum {}",
+                decision.amount, self.max_trade_size
+            ));
+        }
+        None
+    }
+
+    pub async fn execute_trade(&self, decision: &TradeDecision) -> Result<bool> {
+        // Validate trade parameters
+        if let Some(reason) = self.rejection_reason(decision) {
+            tracing::warn!("Trade rejected: {}", reason);
             return Ok(false);
         }
 
@@ -53,8 +137,37 @@ impl TradingEngine {
         );
 
         // TODO: Implement actual trade execution logic
+        let amount = if decision.action.eq_ignore_ascii_case("SELL") {
+            -decision.amount
+        } else {
+            decision.amount
+        };
+        *self
+            .positions
+            .lock()
+            .unwrap()
+            .entry(decision.symbol.to_uppercase())
+            .or_default() += amount;
         Ok(true)
     }
+
+    /// Sells the whole long position in the symbol at a stop-loss, whatever the trade size
+    /// limit. Returns the sale, or None without a position.
+    pub fn stop_loss(&self, symbol: &str, price: f64) -> Option<TradeDecision> {
+        let symbol = symbol.to_uppercase();
+        let mut positions = self.positions.lock().unwrap();
+        let position = positions.get_mut(&symbol).filter(|amount| **amount > 0.0)?;
+        let decision = TradeDecision {
+            action: "SELL".to_string(),
+            symbol,
+            amount: *position,
+            reason: format!("Stop-loss at ${}", price),
+            confidence: 1.0,
+        };
+        *position = 0.0;
+        tracing::info!("Stop-loss: selling {} {} at ${}", decision.amount, decision.symbol, price);
+        Some(decision)
+    }
 }
 
 #[cfg(test)]
@@ -84,6 +197,10 @@ mod tests {
             confidence: 0.5,
         };
         assert!(!engine.execute_trade(&low_confidence_trade).await?);
+        assert_eq!(
+            engine.rejection_reason(&low_confidence_trade).as_deref(),
+            Some("confidence 0.50 below minimum 0.70")
+        );
 
         // Test large trade
         let large_trade = TradeDecision {
@@ -95,6 +212,52 @@ mod tests {
         };
         assert!(!engine.execute_trade(&large_trade).await?);
 
+        // Only executed trades are in the positions
+        assert_eq!(engine.positions(), vec![("SOL".to_string(), 100.0)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_analysis_decision() -> Result<()> {
+        let engine = TradingEngine::new(0.7, 1000.0);
+
+        let answer = "Volume is picking up.\n{\"action\": \"buy\", \"amount\": 200, \"confidence\": 0.8, \"reason\": \"Breakout\"}";
+        let buy = TradeDecision::from_analysis("sol", answer)?;
+        assert_eq!((buy.action.as_str(), buy.symbol.as_str()), ("BUY", "SOL"));
+        assert_eq!((buy.amount, buy.confidence), (200.0, 0.8));
+
+        // A HOLD places no trade
+        let hold = TradeDecision::from_analysis(
+            "SOL",
+            r#"{"action": "HOLD", "confidence": 0.9, "reason": "Choppy market"}"#,
+        )?;
+        assert!(hold.is_hold());
+        assert!(!engine.execute_trade(&hold).await?);
+        assert!(engine.positions().is_empty());
+
+        assert!(TradeDecision::from_analysis("SOL", "I would buy some").is_err());
+        assert!(TradeDecision::from_analysis("SOL", r#"{"action": "APE", "confidence": 1}"#).is_err());
+        assert!(TradeDecision::from_analysis("SOL", r#"{"action": "BUY", "confidence": 0.9}"#).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stop_loss() -> Result<()> {
+        let engine = TradingEngine::new(0.7, 1000.0);
+        assert!(engine.stop_loss("SOL", 140.0).is_none());
+
+        // The whole position is sold, even above the maximum trade size
+        for _ in 0..3 {
+            engine.execute_trade(&TradeDecision::manual("sol", "BUY", 800.0, "the tests")).await?;
+        }
+        let sale = engine.stop_loss("sol", 139.5).unwrap();
+        assert_eq!((sale.action.as_str(), sale.symbol.as_str()), ("SELL", "SOL"));
+        assert_eq!(sale.amount, 2400.0);
+        assert_eq!(sale.reason, "Stop-loss at $139.5");
+        assert!(engine.positions().is_empty());
+        assert!(engine.stop_loss("SOL", 139.0).is_none());
+
         Ok(())
     }
 } 
\ No newline at end of file
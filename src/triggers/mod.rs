@@ -0,0 +1,579 @@
+//! Agent actions triggered by the real-time market data.
+//!
+//! The [TriggerEngine] follows the prices and trades of the watched tokens on the Birdeye
+//! WebSocket and evaluates the [TriggerRule]s of the agent on every update: price crosses,
+//! moves within a window, volume spikes and large trades. A matching rule fires a [Trigger]
+//! with its [TriggerAction], which the agent runs: a market analysis, a stop-loss or a
+//! notification.
+//!
+//! Windows and cooldowns are measured with the times of the updates, not the local clock.
+use std::collections::{HashMap, VecDeque};
+
+use rig_birdeye::providers::{
+    MarketUpdate, Subscription, TradeSide, TradeUpdate, WebSocketProvider,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+/// Mint of wrapped SOL, watched by the default rules
+pub const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+const DEFAULT_COOLDOWN_SECS: u64 = 15 * 60;
+/// Triggers waiting for the agent, the stream is not read while the buffer is full
+const TRIGGER_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerRule {
+    /// Symbol of the token, used by the actions
+    pub symbol: String,
+    /// Mint address of the token, watched on the stream
+    pub mint: String,
+    pub condition: Condition,
+    pub action: TriggerAction,
+    /// Minimum time between two firings of the rule
+    #[serde(default = "default_cooldown")]
+    pub cooldown_secs: u64,
+}
+
+fn default_cooldown() -> u64 {
+    DEFAULT_COOLDOWN_SECS
+}
+
+impl TriggerRule {
+    pub fn new(symbol: &str, mint: &str, condition: Condition, action: TriggerAction) -> Self {
+        Self {
+            symbol: symbol.to_uppercase(),
+            mint: mint.to_string(),
+            condition,
+            action,
+            cooldown_secs: DEFAULT_COOLDOWN_SECS,
+        }
+    }
+
+    pub fn cooldown(mut self, secs: u64) -> Self {
+        self.cooldown_secs = secs;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Condition {
+    /// The price crosses the level upwards
+    PriceAbove { price: f64 },
+    /// The price crosses the level downwards
+    PriceBelow { price: f64 },
+    /// The price moves by `percent` within `minutes`: positive for a rise, negative for a drop
+    PriceMove { percent: f64, minutes: u32 },
+    /// The volume of the current minute reaches `multiplier` times the average volume of the
+    /// previous `minutes`
+    VolumeSpike { multiplier: f64, minutes: u32 },
+    /// A single trade worth at least `min_usd`
+    LargeTrade { min_usd: f64 },
+}
+
+impl Condition {
+    /// Price level of the crosses
+    pub fn level(&self) -> Option<f64> {
+        match self {
+            Condition::PriceAbove { price } | Condition::PriceBelow { price } => Some(*price),
+            _ => None,
+        }
+    }
+
+    /// How far back the condition looks, in seconds
+    fn window(&self) -> i64 {
+        match self {
+            Condition::PriceMove { minutes, .. } | Condition::VolumeSpike { minutes, .. } => {
+                i64::from(*minutes) * 60
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TriggerAction {
+    /// Analyzes the market of the token and decides on a trade
+    Analyze,
+    /// Closes the position in the token
+    StopLoss,
+    /// Publishes a price alert
+    Notify,
+}
+
+/// Rules watching SOL, for the characters that do not configure any: an analysis when it moves
+/// 3% within 15 minutes or its volume spikes, and an alert on trades of $100k or more
+pub fn default_triggers() -> Vec<TriggerRule> {
+    vec![
+        TriggerRule::new(
+            "SOL",
+            SOL_MINT,
+            Condition::PriceMove {
+                percent: 3.0,
+                minutes: 15,
+            },
+            TriggerAction::Analyze,
+        ),
+        TriggerRule::new(
+            "SOL",
+            SOL_MINT,
+            Condition::PriceMove {
+                percent: -3.0,
+                minutes: 15,
+            },
+            TriggerAction::Analyze,
+        ),
+        TriggerRule::new(
+            "SOL",
+            SOL_MINT,
+            Condition::VolumeSpike {
+                multiplier: 3.0,
+                minutes: 15,
+            },
+            TriggerAction::Analyze,
+        ),
+        TriggerRule::new(
+            "SOL",
+            SOL_MINT,
+            Condition::LargeTrade { min_usd: 100_000.0 },
+            TriggerAction::Notify,
+        )
+        .cooldown(60),
+    ]
+}
+
+/// Rule matching an update
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub rule: TriggerRule,
+    /// Price of the token when the rule fired
+    pub price: f64,
+    pub timestamp: i64,
+    /// What happened, e.g. "up 3.20% in 15 minutes"
+    pub message: String,
+}
+
+/// Recent prices and volumes of a token
+#[derive(Debug, Default)]
+struct TokenState {
+    /// Time of the first update
+    since: Option<i64>,
+    /// Prices within the longest window of the rules, oldest first
+    prices: VecDeque<(i64, f64)>,
+    /// Volume per minute within the longest window of the rules, oldest first
+    volumes: VecDeque<(i64, f64)>,
+}
+
+impl TokenState {
+    fn record(&mut self, update: &MarketUpdate, window: i64) {
+        self.since.get_or_insert(update.timestamp);
+        self.prices.push_back((update.timestamp, update.price));
+
+        // Updates repeat the volume of the current minute so far
+        let minute = update.timestamp.div_euclid(60);
+        match self.volumes.back_mut() {
+            Some((last, volume)) if *last == minute => *volume = update.volume,
+            _ => self.volumes.push_back((minute, update.volume)),
+        }
+
+        let oldest = update.timestamp - window;
+        while self.prices.front().is_some_and(|(time, _)| *time < oldest) {
+            self.prices.pop_front();
+        }
+        while self
+            .volumes
+            .front()
+            .is_some_and(|(minute, _)| minute * 60 < oldest - 60)
+        {
+            self.volumes.pop_front();
+        }
+    }
+
+    /// Change of the price in % since the oldest price within the last `minutes`
+    fn change(&self, now: i64, minutes: u32) -> Option<f64> {
+        let from = now - i64::from(minutes) * 60;
+        let (_, reference) = self.prices.iter().find(|(time, _)| *time >= from)?;
+        let (_, price) = self.prices.back()?;
+        (*reference > 0.0).then(|| (price - reference) / reference * 100.0)
+    }
+
+    /// Volume of the current minute and average volume of the previous `minutes`, once the
+    /// token has been followed for that long. Minutes without updates had no volume.
+    fn volume_spike(&self, minutes: u32) -> Option<(f64, f64)> {
+        let &(current_minute, current) = self.volumes.back()?;
+        let from = current_minute - i64::from(minutes);
+        if minutes == 0 || self.since? > from * 60 {
+            return None;
+        }
+        let previous: f64 = self
+            .volumes
+            .iter()
+            .filter(|(minute, _)| *minute >= from && *minute < current_minute)
+            .map(|(_, volume)| volume)
+            .sum();
+        Some((current, previous / f64::from(minutes)))
+    }
+}
+
+/// Evaluates the rules on the updates of the stream
+pub struct TriggerEngine {
+    rules: Vec<TriggerRule>,
+    /// Time each rule last fired, by index
+    fired: Vec<Option<i64>>,
+    /// Tokens watched by the rules, by mint
+    tokens: HashMap<String, TokenState>,
+    /// Longest window of the rules, by mint
+    windows: HashMap<String, i64>,
+}
+
+impl TriggerEngine {
+    pub fn new(rules: Vec<TriggerRule>) -> Self {
+        let mut windows = HashMap::new();
+        for rule in &rules {
+            let window = windows.entry(rule.mint.clone()).or_insert(0);
+            *window = rule.condition.window().max(*window);
+        }
+        Self {
+            fired: vec![None; rules.len()],
+            tokens: HashMap::new(),
+            windows,
+            rules,
+        }
+    }
+
+    /// Mints watched by the rules, sorted
+    pub fn mints(&self) -> Vec<String> {
+        let mut mints: Vec<_> = self.windows.keys().cloned().collect();
+        mints.sort();
+        mints
+    }
+
+    /// Evaluates the price and volume rules of the token
+    pub fn on_market(&mut self, update: &MarketUpdate) -> Vec<Trigger> {
+        let Some(window) = self.windows.get(&update.address) else {
+            return vec![];
+        };
+        let state = self.tokens.entry(update.address.clone()).or_default();
+        let previous = state.prices.back().map(|(_, price)| *price);
+        state.record(update, *window);
+
+        let state = &self.tokens[&update.address];
+        let matches = self.matching(&update.address, |condition| match *condition {
+            Condition::PriceAbove { price: level } => {
+                (previous.is_some_and(|previous| previous < level) && update.price >= level)
+                    .then(|| format!("crossed above ${}", level))
+            }
+            Condition::PriceBelow { price: level } => {
+                (previous.is_some_and(|previous| previous > level) && update.price <= level)
+                    .then(|| format!("crossed below ${}", level))
+            }
+            Condition::PriceMove { percent, minutes } => {
+                let change = state.change(update.timestamp, minutes)?;
+                let moved = if percent >= 0.0 {
+                    change >= percent
+                } else {
+                    change <= percent
+                };
+                moved.then(|| {
+                    let direction = if change >= 0.0 { "up" } else { "down" };
+                    format!("{} {:.2}% in {} minutes", direction, change.abs(), minutes)
+                })
+            }
+            Condition::VolumeSpike {
+                multiplier,
+                minutes,
+            } => {
+                let (current, average) = state.volume_spike(minutes)?;
+                (average > 0.0 && current >= multiplier * average).then(|| {
+                    format!(
+                        "volume ${:.0} this minute, {:.1}x the average of the last {} minutes",
+                        current,
+                        current / average,
+                        minutes
+                    )
+                })
+            }
+            Condition::LargeTrade { .. } => None,
+        });
+        self.fire(matches, update.price, update.timestamp)
+    }
+
+    /// Evaluates the trade rules of the token
+    pub fn on_trade(&mut self, update: &TradeUpdate) -> Vec<Trigger> {
+        let matches = self.matching(&update.address, |condition| match *condition {
+            Condition::LargeTrade { min_usd } => (update.size >= min_usd).then(|| {
+                let side = match update.side {
+                    TradeSide::Buy => "buy",
+                    TradeSide::Sell => "sell",
+                };
+                format!("{} of ${:.0} by {}", side, update.size, update.owner)
+            }),
+            _ => None,
+        });
+        self.fire(matches, update.price, update.timestamp)
+    }
+
+    /// Indexes of the rules of the token whose condition matches, with the message
+    fn matching(
+        &self,
+        mint: &str,
+        matches: impl Fn(&Condition) -> Option<String>,
+    ) -> Vec<(usize, String)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.mint == mint)
+            .filter_map(|(index, rule)| Some((index, matches(&rule.condition)?)))
+            .collect()
+    }
+
+    /// Fires the matching rules that are not cooling down
+    fn fire(&mut self, matches: Vec<(usize, String)>, price: f64, timestamp: i64) -> Vec<Trigger> {
+        let mut triggers = vec![];
+        for (index, message) in matches {
+            let rule = &self.rules[index];
+            let cooldown = rule.cooldown_secs as i64;
+            if self.fired[index].is_some_and(|fired| timestamp - fired < cooldown) {
+                continue;
+            }
+            self.fired[index] = Some(timestamp);
+            triggers.push(Trigger {
+                rule: rule.clone(),
+                price,
+                timestamp,
+                message,
+            });
+        }
+        triggers
+    }
+
+    /// Subscribes the stream to the prices and trades of the watched tokens, and evaluates the
+    /// rules on its updates in the background. The triggers are received until the stream is
+    /// dropped.
+    pub fn watch(mut self, stream: &WebSocketProvider) -> mpsc::Receiver<Trigger> {
+        let mut market_updates = stream.subscribe_market_updates();
+        let mut trade_updates = stream.subscribe_trade_updates();
+        for mint in self.mints() {
+            stream.subscribe(Subscription::Price {
+                address: mint.clone(),
+            });
+            stream.subscribe(Subscription::Txs { address: mint });
+        }
+
+        let (sender, receiver) = mpsc::channel(TRIGGER_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let triggers = tokio::select! {
+                    Some(update) = market_updates.recv() => self.on_market(&update),
+                    Some(update) = trade_updates.recv() => self.on_trade(&update),
+                    else => break,
+                };
+                for trigger in triggers {
+                    tracing::info!(
+                        "{} triggered on {}: {}",
+                        trigger.rule.symbol,
+                        trigger.rule.mint,
+                        trigger.message
+                    );
+                    if sender.send(trigger).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: i64 = 1_729_256_400;
+
+    fn market(timestamp: i64, price: f64, volume: f64) -> MarketUpdate {
+        MarketUpdate {
+            address: SOL_MINT.to_string(),
+            price,
+            volume,
+            timestamp,
+        }
+    }
+
+    fn trade(size: f64, side: TradeSide) -> TradeUpdate {
+        TradeUpdate {
+            address: SOL_MINT.to_string(),
+            price: 150.0,
+            size,
+            side,
+            timestamp: START,
+            tx_hash: "tx".to_string(),
+            owner: "whale".to_string(),
+        }
+    }
+
+    fn engine(condition: Condition, action: TriggerAction) -> TriggerEngine {
+        TriggerEngine::new(vec![TriggerRule::new("sol", SOL_MINT, condition, action)])
+    }
+
+    fn messages(triggers: Vec<Trigger>) -> Vec<String> {
+        triggers
+            .into_iter()
+            .map(|trigger| trigger.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_price_cross() {
+        let mut engine = engine(
+            Condition::PriceBelow { price: 140.0 },
+            TriggerAction::StopLoss,
+        );
+
+        // Already below on the first update, which is not a cross
+        assert!(engine.on_market(&market(START, 139.0, 0.0)).is_empty());
+        assert!(engine.on_market(&market(START + 60, 141.0, 0.0)).is_empty());
+
+        let triggers = engine.on_market(&market(START + 120, 139.5, 0.0));
+        assert_eq!(triggers.len(), 1);
+        assert_eq!(triggers[0].rule.symbol, "SOL");
+        assert_eq!(triggers[0].rule.action, TriggerAction::StopLoss);
+        assert_eq!(triggers[0].price, 139.5);
+        assert_eq!(triggers[0].message, "crossed below $140");
+
+        // Crossing again within the cooldown does not fire
+        engine.on_market(&market(START + 180, 141.0, 0.0));
+        assert!(engine
+            .on_market(&market(START + 240, 139.0, 0.0))
+            .is_empty());
+        engine.on_market(&market(START + 1200, 141.0, 0.0));
+        assert_eq!(engine.on_market(&market(START + 1260, 139.0, 0.0)).len(), 1);
+    }
+
+    #[test]
+    fn test_price_move() {
+        let mut engine = TriggerEngine::new(vec![
+            TriggerRule::new(
+                "SOL",
+                SOL_MINT,
+                Condition::PriceMove {
+                    percent: 5.0,
+                    minutes: 10,
+                },
+                TriggerAction::Analyze,
+            ),
+            TriggerRule::new(
+                "SOL",
+                SOL_MINT,
+                Condition::PriceMove {
+                    percent: -5.0,
+                    minutes: 10,
+                },
+                TriggerAction::Notify,
+            ),
+        ]);
+
+        assert!(engine.on_market(&market(START, 100.0, 0.0)).is_empty());
+        assert!(engine
+            .on_market(&market(START + 300, 104.0, 0.0))
+            .is_empty());
+        // 100 left the window, 104 is the reference
+        assert!(engine
+            .on_market(&market(START + 660, 108.0, 0.0))
+            .is_empty());
+        assert_eq!(
+            messages(engine.on_market(&market(START + 720, 110.0, 0.0))),
+            ["up 5.77% in 10 minutes"]
+        );
+        // Drops fire the other rule
+        assert!(engine
+            .on_market(&market(START + 1200, 110.0, 0.0))
+            .is_empty());
+        let triggers = engine.on_market(&market(START + 1500, 104.0, 0.0));
+        assert_eq!(messages(triggers.clone()), ["down 5.45% in 10 minutes"]);
+        assert_eq!(triggers[0].rule.action, TriggerAction::Notify);
+    }
+
+    #[test]
+    fn test_volume_spike() {
+        let mut engine = engine(
+            Condition::VolumeSpike {
+                multiplier: 3.0,
+                minutes: 5,
+            },
+            TriggerAction::Analyze,
+        );
+
+        // Not enough history yet
+        assert!(engine.on_market(&market(START, 100.0, 5000.0)).is_empty());
+        for minute in 1..=5 {
+            // The volume of a minute grows with its updates, the last one counts
+            engine.on_market(&market(START + minute * 60, 100.0, 100.0));
+            assert!(engine
+                .on_market(&market(START + minute * 60, 100.0, 1000.0))
+                .is_empty());
+        }
+        // 3x the average of 1000
+        assert!(engine
+            .on_market(&market(START + 360, 100.0, 2500.0))
+            .is_empty());
+        assert_eq!(
+            messages(engine.on_market(&market(START + 360, 100.0, 3000.0))),
+            ["volume $3000 this minute, 3.0x the average of the last 5 minutes"]
+        );
+    }
+
+    #[test]
+    fn test_large_trade() {
+        let mut engine = TriggerEngine::new(vec![TriggerRule::new(
+            "SOL",
+            SOL_MINT,
+            Condition::LargeTrade { min_usd: 100_000.0 },
+            TriggerAction::Notify,
+        )
+        .cooldown(0)]);
+
+        assert!(engine.on_trade(&trade(99_999.0, TradeSide::Buy)).is_empty());
+        assert_eq!(
+            messages(engine.on_trade(&trade(250_000.0, TradeSide::Sell))),
+            ["sell of $250000 by whale"]
+        );
+        assert_eq!(engine.on_trade(&trade(250_000.0, TradeSide::Buy)).len(), 1);
+
+        // Price updates do not evaluate the trade rules, and other tokens are ignored
+        assert!(engine.on_market(&market(START, 150.0, 1e9)).is_empty());
+        let mut other = trade(1e9, TradeSide::Buy);
+        other.address = "other".to_string();
+        assert!(engine.on_trade(&other).is_empty());
+    }
+
+    #[test]
+    fn test_rules_from_settings() {
+        let rules: Vec<TriggerRule> = serde_json::from_value(serde_json::json!([
+            {
+                "symbol": "SOL",
+                "mint": SOL_MINT,
+                "condition": { "type": "priceBelow", "price": 120.0 },
+                "action": "stopLoss"
+            },
+            {
+                "symbol": "SOL",
+                "mint": SOL_MINT,
+                "condition": { "type": "volumeSpike", "multiplier": 4.0, "minutes": 30 },
+                "action": "analyze",
+                "cooldownSecs": 3600
+            }
+        ]))
+        .unwrap();
+        assert_eq!(rules[0].condition.level(), Some(120.0));
+        assert_eq!(rules[0].cooldown_secs, DEFAULT_COOLDOWN_SECS);
+        assert_eq!(rules[1].action, TriggerAction::Analyze);
+        assert_eq!(rules[1].cooldown_secs, 3600);
+
+        let engine = TriggerEngine::new(rules);
+        assert_eq!(engine.mints(), [SOL_MINT]);
+        assert_eq!(engine.windows[SOL_MINT], 30 * 60);
+    }
+}